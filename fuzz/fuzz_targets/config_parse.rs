@@ -0,0 +1,45 @@
+//! Fuzz the config parser with arbitrary input
+//!
+//! The daemon parses config text straight from IPC callers, so the parser must
+//! return `Ok` or a `ConfigError` for any input: no panics, no hangs.
+//!
+//! ```text
+//! cargo +nightly fuzz run config_parse -- -max_len=8192 -timeout=5
+//! ```
+
+#![no_main]
+
+use std::net::SocketAddr;
+
+use libfuzzer_sys::fuzz_target;
+use minnowvpn::config::{ConfigLimits, WireGuardConfig};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if has_hostname_endpoint(text) {
+        return;
+    }
+
+    let limits = ConfigLimits::default();
+    if let Ok(config) = WireGuardConfig::parse_with_limits(text, &limits) {
+        assert!(config.peers.len() <= limits.max_peers);
+        assert!(config
+            .peers
+            .iter()
+            .all(|peer| peer.allowed_ips.len() <= limits.max_allowed_ips_per_peer));
+        let _ = config.validate_client();
+        let _ = config.validate_server(false);
+    }
+});
+
+/// Hostname endpoints go to the system resolver, which is slow and not
+/// deterministic under a fuzzer; stick to literal addresses
+fn has_hostname_endpoint(text: &str) -> bool {
+    text.lines()
+        .filter_map(|line| line.split('#').next()?.split_once('='))
+        .any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("endpoint") && value.trim().parse::<SocketAddr>().is_err()
+        })
+}
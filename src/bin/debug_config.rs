@@ -18,7 +18,7 @@ fn main() {
     println!("\nPeer:");
     println!("  Public key: {}", BASE64.encode(&config.peers[0].public_key));
     println!("  Public key hex: {}", hex::encode(&config.peers[0].public_key));
-    if let Some(endpoint) = config.peers[0].endpoint {
+    if let Some(endpoint) = config.peers[0].endpoint_string() {
         println!("  Endpoint: {}", endpoint);
     }
     println!("  Persistent keepalive: {:?}", config.peers[0].persistent_keepalive);
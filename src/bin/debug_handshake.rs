@@ -5,7 +5,7 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use minnowvpn::config::WireGuardConfig;
 use minnowvpn::protocol::handshake::InitiatorHandshake;
-use std::net::UdpSocket;
+use std::net::{ToSocketAddrs, UdpSocket};
 use std::time::Duration;
 
 fn main() {
@@ -22,7 +22,10 @@ fn main() {
     let private_key = config.interface.private_key;
     let peer = &config.peers[0];
     let peer_public = peer.public_key;
-    let endpoint = peer.endpoint.expect("Peer must have endpoint");
+    let endpoint = peer
+        .endpoint_string()
+        .and_then(|e| e.to_socket_addrs().ok()?.next())
+        .expect("Peer must have a resolvable endpoint");
 
     println!("Private key: {}", hex::encode(&private_key));
     println!("Peer public key: {}", hex::encode(&peer_public));
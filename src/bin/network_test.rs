@@ -3,7 +3,7 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use minnowvpn::config::WireGuardConfig;
 use minnowvpn::protocol::handshake::InitiatorHandshake;
-use std::net::UdpSocket;
+use std::net::{ToSocketAddrs, UdpSocket};
 use std::time::Duration;
 
 fn main() {
@@ -13,7 +13,10 @@ fn main() {
     let private_key = config.interface.private_key;
     let peer = &config.peers[0];
     let peer_public = peer.public_key;
-    let endpoint = peer.endpoint.expect("Peer must have endpoint");
+    let endpoint = peer
+        .endpoint_string()
+        .and_then(|e| e.to_socket_addrs().ok()?.next())
+        .expect("Peer must have a resolvable endpoint");
 
     println!("=== Network Test ===\n");
     println!("Our public key: {}", BASE64.encode(&minnowvpn::crypto::x25519::public_key(&private_key)));
@@ -0,0 +1,87 @@
+//! Transport encryption benchmark: `Vec`-returning vs caller-buffer API
+//!
+//! Encrypts and decrypts WireGuard-sized packets with `TransportState::encrypt`
+//! / `decrypt` and with `encrypt_into` / `decrypt_into`, counting heap
+//! allocations with a wrapping global allocator.
+//!
+//! ```text
+//! cargo run --release --bin transport_bench
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use minnowvpn::protocol::transport::{TransportState, TRANSPORT_OVERHEAD};
+
+/// Packets per run
+const PACKETS: usize = 200_000;
+
+/// A full 1420-MTU inner packet
+const PACKET_SIZE: usize = 1420;
+
+/// System allocator that counts allocations
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn main() {
+    println!("=== Transport Encryption Benchmark ===");
+    println!("{} packets x {} bytes, encrypt + decrypt\n", PACKETS, PACKET_SIZE);
+
+    let packet = vec![0x45u8; PACKET_SIZE];
+
+    let vec_run = run(|sender, receiver| {
+        let message = sender.encrypt(1, &packet).expect("encrypt");
+        let plaintext = receiver.decrypt(&message).expect("decrypt");
+        assert_eq!(plaintext.len(), PACKET_SIZE);
+    });
+    report("Vec", vec_run);
+
+    let mut message = vec![0u8; PACKET_SIZE + TRANSPORT_OVERHEAD];
+    let mut plaintext = vec![0u8; PACKET_SIZE];
+    let into_run = run(|sender, receiver| {
+        let len = sender.encrypt_into(1, &packet, &mut message).expect("encrypt");
+        let n = receiver.decrypt_into(&message[..len], &mut plaintext).expect("decrypt");
+        assert_eq!(n, PACKET_SIZE);
+    });
+    report("caller buffer", into_run);
+
+    if vec_run.1 > 0.0 {
+        println!("\nSpeedup: {:.2}x", into_run.1 / vec_run.1);
+    }
+}
+
+/// One run of `step` per packet; returns (allocations per packet, packets per second)
+fn run(mut step: impl FnMut(&mut TransportState, &mut TransportState)) -> (f64, f64) {
+    let mut sender = TransportState::new([1u8; 32], [2u8; 32]);
+    let mut receiver = TransportState::new([2u8; 32], [1u8; 32]);
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for _ in 0..PACKETS {
+        step(&mut sender, &mut receiver);
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    let allocated = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    (allocated as f64 / PACKETS as f64, PACKETS as f64 / elapsed)
+}
+
+fn report(name: &str, (allocations, rate): (f64, f64)) {
+    println!("{:<14} {:>5.2} allocations/packet, {:>10.0} packets/s", name, allocations, rate);
+}
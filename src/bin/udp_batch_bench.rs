@@ -0,0 +1,124 @@
+//! UDP throughput benchmark: single-packet path vs batched I/O
+//!
+//! Blasts WireGuard-sized datagrams over loopback and reports how many the
+//! receiver got per second. Build with `--features udp-batch` on Linux to
+//! compare against recvmmsg/sendmmsg + GSO/GRO; without it both runs use the
+//! single-packet path.
+//!
+//! ```text
+//! cargo run --release --features udp-batch --bin udp_batch_bench
+//! ```
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use minnowvpn::tunnel::batch::{self, RecvBatch, BATCH_SIZE};
+use tokio::net::UdpSocket;
+
+/// Datagrams sent per run
+const PACKETS: usize = 200_000;
+
+/// Transport packet size for a full 1420-MTU inner packet (1420 + 32 overhead)
+const PACKET_SIZE: usize = 1452;
+
+/// Receiver gives up after this long without traffic
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[tokio::main]
+async fn main() {
+    println!("=== UDP Batch Benchmark ===");
+    println!("{} datagrams x {} bytes, batch size {}\n", PACKETS, PACKET_SIZE, BATCH_SIZE);
+
+    let single = run(false).await;
+    report("single-packet", single);
+
+    let batched = run(true).await;
+    report("batched", batched);
+
+    if single.1 > 0.0 {
+        println!("\nSpeedup: {:.2}x", batched.1 / single.1);
+    }
+}
+
+/// One sender/receiver run; returns (datagrams received, datagrams per second)
+async fn run(batched: bool) -> (usize, f64) {
+    let receiver = UdpSocket::bind("127.0.0.1:0").await.expect("bind receiver");
+    let sender = UdpSocket::bind("127.0.0.1:0").await.expect("bind sender");
+    let to = receiver.local_addr().expect("receiver address");
+
+    let mut offload = batch::configure_socket(&sender, false);
+    if batched {
+        let rx_offload = batch::configure_socket(&receiver, true);
+        println!("Offloads: GSO {}, GRO {}", offload.gso, rx_offload.gro);
+    }
+
+    let recv_task = tokio::spawn(async move {
+        let mut received = 0usize;
+        let mut started = None;
+        let mut last = Instant::now();
+        let mut batch = RecvBatch::new();
+        let mut buf = vec![0u8; 65535];
+
+        loop {
+            let result = if batched {
+                tokio::time::timeout(IDLE_TIMEOUT, batch.recv(&receiver)).await
+            } else {
+                tokio::time::timeout(IDLE_TIMEOUT, async {
+                    receiver.recv_from(&mut buf).await.map(|_| 1)
+                })
+                .await
+            };
+
+            match result {
+                Ok(Ok(count)) => {
+                    started.get_or_insert_with(Instant::now);
+                    received += count;
+                    last = Instant::now();
+                    if received >= PACKETS {
+                        break;
+                    }
+                }
+                Ok(Err(e)) => panic!("recv failed: {}", e),
+                Err(_) => break,
+            }
+        }
+
+        let elapsed = started.map(|s| last - s).unwrap_or_default();
+        (received, elapsed)
+    });
+
+    let packets: Vec<(Vec<u8>, SocketAddr)> =
+        (0..BATCH_SIZE).map(|_| (vec![0xAB; PACKET_SIZE], to)).collect();
+
+    let mut sent = 0;
+    while sent < PACKETS {
+        if batched {
+            let n = BATCH_SIZE.min(PACKETS - sent);
+            let _ = batch::send_batch(&sender, &mut offload, &packets[..n]).await;
+            sent += n;
+        } else {
+            let _ = sender.send_to(&packets[0].0, to).await;
+            sent += 1;
+        }
+        // Give the receiver a chance to drain so loopback drops stay low
+        if sent % (BATCH_SIZE * 8) == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    let (received, elapsed) = recv_task.await.expect("receiver task");
+    let rate = if elapsed.is_zero() {
+        0.0
+    } else {
+        received as f64 / elapsed.as_secs_f64()
+    };
+    (received, rate)
+}
+
+fn report(label: &str, (received, rate): (usize, f64)) {
+    let mbps = rate * PACKET_SIZE as f64 * 8.0 / 1_000_000.0;
+    println!(
+        "{:>14}: received {}/{} ({:.0} pkt/s, {:.0} Mbit/s)",
+        label, received, PACKETS, rate, mbps
+    );
+}
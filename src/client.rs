@@ -6,24 +6,38 @@
 //! - Handshake initiation and response processing
 //! - Keepalive timers
 //! - Automatic rekey
+//!
+//! Every `[Peer]` gets its own sessions, handshakes and timers. Packets from
+//! the TUN go to the peer with the longest matching AllowedIPs. The first peer
+//! is the primary one: `run` waits for its handshake, and its state is what
+//! [`ClientEvent`]s and the shared traffic stats report. Other peers come up
+//! in the background, on first use or at their first keepalive.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ipnet::IpNet;
 use tokio::net::UdpSocket;
-use tokio::time::{interval, Interval};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Instant};
 
-use crate::config::WireGuardConfig;
+use crate::config::{InterfaceConfig, PeerConfig, RouteTable, WireGuardConfig};
 use crate::daemon::TrafficStats;
 use crate::error::{NetworkError, ProtocolError, MinnowVpnError};
 use crate::protocol::{
     CookieReply, CookieState, HandshakeResponse, InitiatorHandshake,
-    MessageType, Session, SessionManager, TransportHeader,
+    MessageType, Session, SessionManager, SessionTable, TransportHeader,
 };
 use crate::protocol::messages::get_message_type;
-use crate::protocol::session::generate_sender_index;
-use crate::tunnel::{RouteManager, TunDevice};
+use crate::protocol::transport::TRANSPORT_OVERHEAD;
+use crate::protocol::session::{generate_sender_index, longest_match, DEFAULT_MAX_SESSIONS, KEEPALIVE_TIMEOUT};
+use crate::server::parse_ip_dest;
+use crate::tunnel::batch::{self, RecvBatch};
+use crate::tunnel::netsim::NetSim;
+use crate::tunnel::{describe_ip_packet, set_socket_fwmark, RouteManager, TunDevice};
 
 /// Initial retry delay for connection
 const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
@@ -31,12 +45,67 @@ const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
 /// Maximum retry delay
 const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
 
+/// Failed handshakes before the initial connection gives up (about five
+/// minutes with the backoff above)
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+
 /// Handshake timeout
 const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// How long a connection retry waits for the Endpoint hostname to resolve
+/// (a slower lookup finishes in the background)
+const ENDPOINT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Buffer size for packets
 const BUFFER_SIZE: usize = 65535;
 
+/// Keepalive periods are spread by up to this fraction either way
+const KEEPALIVE_JITTER: f64 = 0.1;
+
+/// Index of the primary peer (the first `[Peer]`) in `WireGuardClient::peers`
+const PRIMARY: usize = 0;
+
+/// Control commands from the daemon to a running client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientCommand {
+    /// Stop tunnel traffic and timers but keep the TUN and routes (traffic is blackholed)
+    Suspend,
+    /// Re-handshake and resume traffic on the existing TUN and routes
+    Resume,
+}
+
+/// Events emitted by the client for embedding applications
+///
+/// Delivered with `try_send`, so a slow receiver never stalls the tunnel; an
+/// event that doesn't fit in the channel is dropped. Events describe the
+/// primary peer only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// A handshake established a session where there was none
+    HandshakeCompleted { endpoint: SocketAddr },
+    /// A rekey handshake replaced the current session
+    Rekeyed,
+    /// The server's packets now arrive from a different address (roaming)
+    EndpointChanged { endpoint: SocketAddr },
+    /// The session was dropped
+    Disconnected { reason: String },
+    /// A step towards a handshake (connect, resume or rekey)
+    Progress(HandshakeProgress),
+}
+
+/// Steps of a handshake attempt, for diagnosing slow or stuck connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeProgress {
+    /// Resolving the hostname Endpoint again before a retry
+    ResolvingEndpoint { host: String },
+    /// Attempt `attempt` of the initial connection is starting
+    Attempt { attempt: u32 },
+    /// An initiation went out to `endpoint`
+    InitiationSent { endpoint: SocketAddr },
+    /// The server is under load and sent a cookie; the initiation is repeated with it
+    CookieReceived,
+}
+
 /// Result of processing a handshake packet
 enum HandshakeResult {
     /// Handshake completed successfully
@@ -45,6 +114,103 @@ enum HandshakeResult {
     NeedRetry,
 }
 
+/// What a keepalive tick does for a peer
+#[derive(Debug, PartialEq, Eq)]
+enum KeepaliveAction {
+    /// The session is up: send a keepalive if one is due
+    Send,
+    /// No usable session: handshake (`expired` if one just timed out)
+    Handshake { expired: bool },
+}
+
+/// A configured peer and the client's handshake and timer state for it
+struct ClientPeer {
+    /// The `[Peer]` section (keys, AllowedIPs, Endpoint)
+    config: PeerConfig,
+    /// Address packets to this peer are sent to
+    endpoint: SocketAddr,
+    /// Addresses the Endpoint resolved to, tried in order until one answers
+    endpoint_candidates: Vec<SocketAddr>,
+    /// Cookie state for DoS protection
+    cookie_state: CookieState,
+    /// Current handshake state (if in progress)
+    pending_handshake: Option<InitiatorHandshake>,
+    /// Last MAC1 we sent (needed for cookie processing)
+    last_mac1: [u8; 16],
+    /// Keepalive interval
+    keepalive_interval: Option<Duration>,
+    /// Forced handshake interval (keeps aggressive NAT mappings alive)
+    handshake_interval: Option<Duration>,
+    /// When the keepalive timer fires next
+    next_keepalive: Option<Instant>,
+    /// When the forced handshake timer fires next
+    next_handshake: Option<Instant>,
+    /// Lookup of the Endpoint hostname running in the background
+    endpoint_lookup: Option<JoinHandle<Result<Vec<SocketAddr>, NetworkError>>>,
+}
+
+impl ClientPeer {
+    /// Resolve the peer's Endpoint (fails without one)
+    async fn new(config: &PeerConfig) -> Result<Self, NetworkError> {
+        let endpoint_candidates = config.resolve_endpoint().await?;
+        if let Some(host) = &config.endpoint_host {
+            tracing::info!("Resolved {} to {:?}", host, endpoint_candidates);
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            endpoint: endpoint_candidates[0],
+            endpoint_candidates,
+            cookie_state: CookieState::new(),
+            pending_handshake: None,
+            last_mac1: [0u8; 16],
+            keepalive_interval: config.persistent_keepalive.map(|secs| Duration::from_secs(secs as u64)),
+            handshake_interval: config.handshake_interval.map(|secs| Duration::from_secs(secs as u64)),
+            next_keepalive: None,
+            next_handshake: None,
+            endpoint_lookup: None,
+        })
+    }
+
+    /// Short public key prefix for logs
+    fn label(&self) -> String {
+        BASE64.encode(&self.config.public_key[..8])
+    }
+
+    /// Resolve the Endpoint hostname again in a background task
+    ///
+    /// Returns false for a literal address or while a lookup is running.
+    /// [`ClientPeer::lookup_done`] picks up the result.
+    fn start_lookup(&mut self) -> bool {
+        if self.config.endpoint_host.is_none() || self.endpoint_lookup.is_some() {
+            return false;
+        }
+        let config = self.config.clone();
+        self.endpoint_lookup = Some(tokio::spawn(async move { config.resolve_endpoint().await }));
+        true
+    }
+
+    /// Wait for the background lookup and make its addresses the new candidates
+    ///
+    /// Never completes while no lookup is running. Cancel-safe: the lookup
+    /// keeps running if this is dropped.
+    async fn lookup_done(&mut self) {
+        let Some(task) = self.endpoint_lookup.as_mut() else {
+            return std::future::pending().await;
+        };
+        let result = task.await;
+        self.endpoint_lookup = None;
+        match result {
+            Ok(Ok(addrs)) => {
+                tracing::debug!("Endpoint resolved to {:?}", addrs);
+                self.endpoint_candidates = addrs;
+            }
+            Ok(Err(e)) => tracing::warn!("{}; keeping previous addresses", e),
+            Err(e) => tracing::warn!("Endpoint lookup did not finish: {}; keeping previous addresses", e),
+        }
+    }
+}
+
 /// WireGuard client
 pub struct WireGuardClient {
     /// Configuration
@@ -55,18 +221,34 @@ pub struct WireGuardClient {
     tun: TunDevice,
     /// Route manager
     routes: RouteManager,
-    /// Session manager
-    sessions: SessionManager,
-    /// Cookie state for DoS protection
-    cookie_state: CookieState,
-    /// Current handshake state (if in progress)
-    pending_handshake: Option<InitiatorHandshake>,
-    /// Last MAC1 we sent (needed for cookie processing)
-    last_mac1: [u8; 16],
-    /// Peer endpoint
-    peer_endpoint: SocketAddr,
-    /// Keepalive interval
-    keepalive_interval: Option<Duration>,
+    /// Per-peer sessions, capped by `MaxSessions`
+    sessions: SessionTable,
+    /// Peers we talk to, the primary one first
+    peers: Vec<ClientPeer>,
+    /// Randomize the keepalive period (on by default)
+    keepalive_jitter: bool,
+    /// Install the config's DNS servers as the system resolvers (on by default)
+    apply_dns: bool,
+    /// Overall bound on the initial connection (None = no time limit)
+    connect_timeout: Option<Duration>,
+    /// Most handshake attempts for the initial connection (None = unlimited)
+    max_attempts: Option<u32>,
+    /// Disconnect after this long without tunneled traffic (None = never)
+    idle_timeout: Option<Duration>,
+    /// Last time a real (non-keepalive) packet went through the tunnel
+    last_activity: Instant,
+    /// Control commands from the daemon (suspend/resume)
+    command_rx: Option<mpsc::Receiver<ClientCommand>>,
+    /// Session lifecycle events for an embedding application
+    event_tx: Option<mpsc::Sender<ClientEvent>>,
+    /// Suspended: TUN and routes stay up, but no traffic or timers run
+    suspended: bool,
+    /// Resumed but the re-handshake has not succeeded yet (retried on the rekey timer)
+    resume_pending: bool,
+    /// Server answered with ICMP port unreachable; re-handshaking until it is back
+    server_unreachable: bool,
+    /// Simulated lossy link (debug builds with `MINNOWVPN_NETSIM` set)
+    netsim: Option<NetSim>,
     /// Optional traffic statistics (shared with daemon)
     traffic_stats: Option<Arc<TrafficStats>>,
 }
@@ -80,76 +262,117 @@ impl WireGuardClient {
         config: WireGuardConfig,
         traffic_stats: Option<Arc<TrafficStats>>,
     ) -> Result<Self, MinnowVpnError> {
+        // Validate peers before touching the system
+        config.validate_client()?;
+
         // Clean up any stale routes from crashed previous sessions
         RouteManager::cleanup_stale_routes();
 
-        // Parse our interface address
-        let our_address = config.interface.address
-            .first()
-            .ok_or_else(|| MinnowVpnError::Config(crate::error::ConfigError::MissingField {
+        // At least one interface address is required
+        if config.interface.address.is_empty() {
+            return Err(MinnowVpnError::Config(crate::error::ConfigError::MissingField {
                 field: "Address".to_string(),
-            }))?;
+            }));
+        }
 
-        // Create TUN device
+        // Resolve every peer's endpoint. The primary one is required (it has an
+        // Endpoint, checked by validate_client); others are skipped if unreachable.
+        let mut peers = Vec::with_capacity(config.peers.len());
+        for (i, peer) in config.peers.iter().enumerate() {
+            match ClientPeer::new(peer).await {
+                Ok(peer) => peers.push(peer),
+                Err(e) if i == PRIMARY => return Err(e.into()),
+                Err(e) => tracing::warn!("Skipping peer {}: {}", BASE64.encode(&peer.public_key[..8]), e),
+            }
+        }
+
+        // Create TUN device with every configured address
         let tun = TunDevice::create(
-            our_address.addr(),
-            our_address.prefix_len(),
+            &config.interface.addresses(),
             config.interface.mtu.unwrap_or(1420),
+            config.interface.tun_name.as_deref(),
         ).await?;
 
         // Create route manager
-        let routes = RouteManager::new(tun.name().to_string());
-
-        // Get peer endpoint first to determine bind address
-        let peer = config.peers.first()
-            .ok_or_else(|| MinnowVpnError::Config(crate::error::ConfigError::MissingField {
-                field: "Peer".to_string(),
-            }))?;
-
-        let peer_endpoint = peer.endpoint
-            .ok_or_else(|| MinnowVpnError::Config(crate::error::ConfigError::MissingField {
-                field: "Endpoint".to_string(),
-            }))?;
+        let table = config.interface.table.unwrap_or(RouteTable::Auto);
+        let routes = RouteManager::with_table(tun.name().to_string(), table);
 
         // Bind UDP socket
-        // For localhost endpoints, bind to 127.0.0.1 to ensure correct source address
-        // For other endpoints, use 0.0.0.0 to let the OS choose
-        let bind_addr = if peer_endpoint.ip().is_loopback() {
-            "127.0.0.1:0"
-        } else {
-            "0.0.0.0:0"
-        };
-
-        let socket = UdpSocket::bind(bind_addr).await
-            .map_err(|e| NetworkError::BindFailed {
-                addr: bind_addr.to_string(),
-                reason: e.to_string(),
-            })?;
+        let socket = bind_socket(peers[PRIMARY].endpoint, &config.interface, peers.len() == 1).await?;
 
-        // Keepalive interval
-        let keepalive_interval = peer.persistent_keepalive
+        let idle_timeout = config.interface.idle_timeout
             .map(|secs| Duration::from_secs(secs as u64));
 
+        let sessions = SessionTable::new(config.interface.max_sessions.unwrap_or(DEFAULT_MAX_SESSIONS));
+
         Ok(Self {
             config,
             socket,
             tun,
             routes,
-            sessions: SessionManager::new(),
-            cookie_state: CookieState::new(),
-            pending_handshake: None,
-            last_mac1: [0u8; 16],
-            peer_endpoint,
-            keepalive_interval,
+            sessions,
+            peers,
+            keepalive_jitter: true,
+            apply_dns: true,
+            connect_timeout: None,
+            max_attempts: Some(DEFAULT_MAX_ATTEMPTS),
+            idle_timeout,
+            last_activity: Instant::now(),
+            command_rx: None,
+            event_tx: None,
+            suspended: false,
+            resume_pending: false,
+            server_unreachable: false,
+            netsim: NetSim::from_env(),
             traffic_stats,
         })
     }
 
+    /// Receive suspend/resume commands on this channel while running
+    pub fn set_command_channel(&mut self, command_rx: mpsc::Receiver<ClientCommand>) {
+        self.command_rx = Some(command_rx);
+    }
+
+    /// Send handshake, rekey, roaming and disconnect events on this channel
+    pub fn set_event_channel(&mut self, event_tx: mpsc::Sender<ClientEvent>) {
+        self.event_tx = Some(event_tx);
+    }
+
+    /// Bound the initial connection attempts by time (None = no time limit;
+    /// `max_attempts` still applies)
+    pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Give up on the initial connection after `max` failed handshakes
+    /// (default [`DEFAULT_MAX_ATTEMPTS`]; None = retry forever)
+    pub fn set_max_attempts(&mut self, max: Option<u32>) {
+        self.max_attempts = max;
+    }
+
+    /// Disconnect after `timeout` without tunneled traffic (None = never), overriding `IdleTimeout`
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Spread the keepalive period by up to ±10% (default on; turn off for deterministic timing)
+    pub fn set_keepalive_jitter(&mut self, enabled: bool) {
+        self.keepalive_jitter = enabled;
+    }
+
+    /// Point the system resolver at the `DNS` servers while connected (default on)
+    ///
+    /// Turn off when DNS is managed some other way; `DNS` is then ignored.
+    pub fn set_apply_dns(&mut self, enabled: bool) {
+        self.apply_dns = enabled;
+    }
+
     /// Run the client (main event loop)
     pub async fn run(&mut self) -> Result<(), MinnowVpnError> {
         // Connect with retry (handshake must complete BEFORE setting up routes,
         // otherwise the VPN endpoint gets routed through the non-existent tunnel)
-        self.connect_with_retry().await?;
+        let (max_attempts, connect_timeout) = (self.max_attempts, self.connect_timeout);
+        connect_with_retry(self, max_attempts, connect_timeout).await?;
 
         // Set up routes for allowed IPs AFTER handshake succeeds
         self.setup_routes().await?;
@@ -158,114 +381,214 @@ impl WireGuardClient {
         self.event_loop().await
     }
 
-    /// Set up routes for peer's allowed IPs
+    /// Set up routes for every peer's allowed IPs
     async fn setup_routes(&mut self) -> Result<(), MinnowVpnError> {
-        let peer = &self.config.peers[0];
-
-        // CRITICAL: First add a route for the VPN endpoint to bypass the tunnel
-        // This prevents a routing loop where encrypted packets get re-routed through the tunnel
-        // Skip this for loopback addresses - they don't need bypass routing
-        if let std::net::SocketAddr::V4(v4_addr) = self.peer_endpoint {
-            let endpoint_ip = *v4_addr.ip();
+        let endpoint_ip = self.peers[PRIMARY].endpoint.ip();
+
+        // CRITICAL: First make sure encrypted packets can't be re-routed through the tunnel.
+        // With FwMark set, our socket's packets are marked and policy rules keep them out
+        // of the table holding the tunnel routes; otherwise add a bypass route for the endpoint.
+        // Skip this for loopback addresses - they don't need bypass routing.
+        // The bypass covers the primary peer only; other peers' endpoints need
+        // FwMark (or an on-link route) if their AllowedIPs would capture them.
+        let mut fwmark_installed = false;
+        if let Some(mark) = self.config.interface.fwmark {
             if !endpoint_ip.is_loopback() {
-                if let Err(e) = self.routes.add_endpoint_bypass(endpoint_ip).await {
-                    tracing::warn!("Failed to add endpoint bypass route: {}", e);
+                match self.routes.add_fwmark_rule(mark).await {
+                    Ok(()) => fwmark_installed = true,
+                    Err(e) => {
+                        tracing::warn!("Failed to add fwmark rule, falling back to bypass route: {}", e);
+                    }
                 }
             }
         }
 
-        for network in &peer.allowed_ips {
-            // Convert IpNet to Ipv4Net (we only support IPv4 for now)
-            if let ipnet::IpNet::V4(v4net) = network {
-                if let Err(e) = self.routes.add_route(*v4net).await {
-                    tracing::warn!("Failed to add route for {}: {}", network, e);
-                    // Continue with other routes
-                }
+        if !fwmark_installed && !endpoint_ip.is_loopback() {
+            if let Err(e) = self.routes.add_endpoint_bypass(endpoint_ip).await {
+                tracing::warn!("Failed to add endpoint bypass route: {}", e);
+            }
+        }
+
+        let mut networks: Vec<IpNet> = Vec::new();
+        for network in self.peers.iter().flat_map(|peer| &peer.config.allowed_ips) {
+            if !networks.contains(network) {
+                networks.push(*network);
+            }
+        }
+        for network in networks {
+            if let Err(e) = self.routes.add_route(network).await {
+                tracing::warn!("Failed to add route for {}: {}", network, e);
+                // Continue with other routes
+            }
+        }
+
+        // DNS last: the servers are usually only reachable through the tunnel
+        if self.apply_dns && !self.config.interface.dns.is_empty() {
+            if let Err(e) = self.routes.set_dns(&self.config.interface.dns).await {
+                tracing::warn!("Failed to set DNS servers: {}", e);
             }
         }
 
         Ok(())
     }
 
-    /// Connect with automatic retry and exponential backoff
-    async fn connect_with_retry(&mut self) -> Result<(), MinnowVpnError> {
-        let mut delay = INITIAL_RETRY_DELAY;
-        let mut attempts = 0u32;
+    /// Start resolving the primary peer's hostname Endpoint again after a
+    /// failed handshake (the server may have moved)
+    ///
+    /// The lookup runs in the background, so the event loop keeps serving
+    /// traffic; a literal address needs none.
+    fn start_endpoint_lookup(&mut self) {
+        let primary = &mut self.peers[PRIMARY];
+        if primary.start_lookup() {
+            let host = primary.config.endpoint_host.clone().unwrap_or_default();
+            emit_event(&self.event_tx, ClientEvent::Progress(HandshakeProgress::ResolvingEndpoint { host }));
+        }
+    }
 
-        loop {
-            attempts += 1;
-            tracing::info!("Connection attempt {}...", attempts);
+    /// Move to the primary peer's next candidate address after a failed handshake
+    ///
+    /// A literal address is simply retried.
+    async fn try_next_endpoint(&mut self) -> Result<(), MinnowVpnError> {
+        let primary = &mut self.peers[PRIMARY];
+        let next = next_endpoint(&primary.endpoint_candidates, primary.endpoint);
+
+        if next != primary.endpoint {
+            tracing::info!("Trying endpoint address {}", next);
+            primary.endpoint = next;
+            // The socket is connected to (and may be bound for the family of) the old address
+            self.rebind_primary_socket().await?;
+        }
+        Ok(())
+    }
 
-            match self.perform_handshake().await {
-                Ok(_) => {
-                    tracing::info!("Handshake complete! Session established.");
-                    return Ok(());
-                }
-                Err(e) => {
-                    tracing::warn!("Handshake failed: {}. Retrying in {:?}...", e, delay);
-                    tokio::time::sleep(delay).await;
-                    delay = (delay * 2).min(MAX_RETRY_DELAY);
-                }
+    /// Handshake with the primary peer from the event loop
+    ///
+    /// On failure the next attempt goes to the peer's next endpoint address,
+    /// and a hostname is looked up again without waiting for the answer.
+    async fn retry_primary_handshake(&mut self) -> Result<(), MinnowVpnError> {
+        let result = self.perform_handshake().await;
+        if result.is_err() {
+            self.start_endpoint_lookup();
+            if let Err(e) = self.try_next_endpoint().await {
+                tracing::warn!("Could not switch endpoint address: {}", e);
             }
         }
+        result
     }
 
-    /// Perform the WireGuard handshake
+    /// Replace the socket with a fresh one for the primary peer's endpoint
+    ///
+    /// The socket is only connected with a single peer: a connected UDP socket
+    /// can't send to anyone else. A pinned `ListenPort` is never shared, so the
+    /// old socket is closed first, with an ephemeral one standing in meanwhile.
+    async fn rebind_primary_socket(&mut self) -> Result<(), MinnowVpnError> {
+        if self.config.interface.listen_port.is_some() {
+            let standin = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.map_err(|e| NetworkError::BindFailed {
+                addr: "0.0.0.0:0".to_string(),
+                reason: e.to_string(),
+            })?;
+            drop(std::mem::replace(&mut self.socket, standin));
+        }
+        self.socket = bind_socket(self.peers[PRIMARY].endpoint, &self.config.interface, self.peers.len() == 1).await?;
+        Ok(())
+    }
+
+    /// Perform the WireGuard handshake with the primary peer and wait for the response
+    ///
+    /// Packets that don't answer this handshake (traffic from other peers, late
+    /// transport data) are handled as usual while waiting.
     async fn perform_handshake(&mut self) -> Result<(), MinnowVpnError> {
         // Loop to handle cookie retry without recursion
         loop {
-            let peer = &self.config.peers[0];
-
-            // Create handshake initiator
-            let sender_index = generate_sender_index();
-            let mut handshake = InitiatorHandshake::new(
-                self.config.interface.private_key,
-                peer.public_key,
-                self.config.interface.preshared_key,
-                sender_index,
-            );
-
-            // Get cookie if available
-            let cookie = self.cookie_state.get_cookie();
-
-            // Create initiation message
-            let init_msg = handshake.create_initiation(cookie)?;
-            self.last_mac1 = init_msg.mac1;
-
-            // Store handshake state
-            self.pending_handshake = Some(handshake);
-            self.sessions.start_handshake(sender_index);
-
-            // Send initiation
-            let init_bytes = init_msg.to_bytes();
-            self.socket.send_to(&init_bytes, self.peer_endpoint).await
-                .map_err(|e| NetworkError::SendFailed {
-                    reason: e.to_string(),
-                })?;
+            let sender_index = self.initiate_handshake(PRIMARY).await?;
+            let endpoint = self.peers[PRIMARY].endpoint;
+            emit_event(&self.event_tx, ClientEvent::Progress(HandshakeProgress::InitiationSent { endpoint }));
 
             // Wait for response with timeout
+            let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
             let mut buf = [0u8; BUFFER_SIZE];
-            let response = tokio::time::timeout(
-                HANDSHAKE_TIMEOUT,
-                self.socket.recv_from(&mut buf),
-            ).await
-                .map_err(|_| ProtocolError::HandshakeTimeout { seconds: HANDSHAKE_TIMEOUT.as_secs() })?
-                .map_err(|e| NetworkError::ReceiveFailed { reason: e.to_string() })?;
-
-            let (len, from) = response;
-            let packet = &buf[..len];
-
-            // Process response - returns true if we need to retry (got cookie)
-            match self.process_handshake_packet(packet, from).await? {
+            let mut plaintext_buf = [0u8; BUFFER_SIZE];
+            let result = loop {
+                let (len, from) = tokio::time::timeout_at(deadline, batch::recv_from(&self.socket, &mut buf))
+                    .await
+                    .map_err(|_| ProtocolError::HandshakeTimeout { seconds: HANDSHAKE_TIMEOUT.as_secs() })?
+                    .map_err(|e| self.receive_error(e))?;
+                if self.netsim.as_ref().is_some_and(NetSim::drop_incoming) {
+                    continue;
+                }
+
+                let packet = &buf[..len];
+                if answers_handshake(packet, sender_index) {
+                    break self.process_handshake_packet(packet, from).await?;
+                }
+                if let Err(e) = self.handle_udp_packet(packet, from, &mut plaintext_buf).await {
+                    tracing::trace!("Error handling UDP packet: {}", e);
+                }
+            };
+
+            // Process response - retry if we got a cookie
+            match result {
                 HandshakeResult::Complete => return Ok(()),
                 HandshakeResult::NeedRetry => {
                     tracing::info!("Received cookie, retrying handshake...");
+                    emit_event(&self.event_tx, ClientEvent::Progress(HandshakeProgress::CookieReceived));
                     continue;
                 }
             }
         }
     }
 
+    /// Send a handshake initiation to peer `i` and return its sender index
+    ///
+    /// The response is matched back to the peer by that index.
+    async fn initiate_handshake(&mut self, i: usize) -> Result<u32, MinnowVpnError> {
+        let peer = &mut self.peers[i];
+
+        // Create handshake initiator
+        let sender_index = generate_sender_index();
+        let mut handshake = InitiatorHandshake::new(
+            self.config.interface.private_key,
+            peer.config.public_key,
+            peer.config.preshared_key,
+            sender_index,
+        );
+
+        // Create initiation message (with a cookie if we have one)
+        let init_msg = handshake.create_initiation(peer.cookie_state.get_cookie())?;
+        peer.last_mac1 = init_msg.mac1;
+
+        // Store handshake state
+        peer.pending_handshake = Some(handshake);
+        let public_key = peer.config.public_key;
+        self.sessions.peer(&public_key).start_handshake(sender_index);
+
+        // Send initiation
+        self.send_to_peer(i, &init_msg.to_bytes()).await?;
+        Ok(sender_index)
+    }
+
+    /// Handshake with peer `i`
+    ///
+    /// The primary peer's handshake is awaited. Other peers only get an
+    /// initiation (unless one is already in flight); the event loop picks up
+    /// the response, so an unreachable peer never stalls the tunnel.
+    async fn handshake(&mut self, i: usize) -> Result<(), MinnowVpnError> {
+        if i == PRIMARY {
+            return self.perform_handshake().await;
+        }
+        if self.sessions.peer(&self.peers[i].config.public_key).handshake_in_flight() {
+            return Ok(());
+        }
+        self.initiate_handshake(i).await.map(drop)
+    }
+
+    /// Peer whose pending handshake used sender index `index`
+    fn pending_peer(&self, index: u32) -> Option<usize> {
+        self.peers.iter().position(|peer| {
+            peer.pending_handshake.as_ref().is_some_and(|h| h.sender_index == index)
+        })
+    }
+
     /// Process a handshake packet (response or cookie reply)
     async fn process_handshake_packet(
         &mut self,
@@ -284,11 +607,11 @@ impl WireGuardClient {
                     &crate::crypto::x25519::public_key(&self.config.interface.private_key),
                 )?;
 
-                // Process with pending handshake
-                let handshake = self.pending_handshake.take()
+                // Process with the pending handshake it answers
+                let i = self.pending_peer(response.receiver_index)
+                    .ok_or(ProtocolError::NoSession)?;
+                let mut handshake = self.peers[i].pending_handshake.take()
                     .ok_or(ProtocolError::NoSession)?;
-
-                let mut handshake = handshake;
                 let result = handshake.process_response(&response)?;
 
                 // Create session
@@ -300,19 +623,37 @@ impl WireGuardClient {
                     from,
                 );
 
-                self.sessions.establish_session(session);
-                self.cookie_state.clear(); // Clear cookie after successful handshake
+                let peer = &mut self.peers[i];
+                peer.cookie_state.clear(); // Clear cookie after successful handshake
+                let event = match self.sessions.peer(&peer.config.public_key).current() {
+                    Some(_) => ClientEvent::Rekeyed,
+                    None => ClientEvent::HandshakeCompleted { endpoint: from },
+                };
+                self.sessions.establish_session(&peer.config.public_key, session);
+
+                if i == PRIMARY {
+                    emit_event(&self.event_tx, event);
+                    if let Some(ref stats) = self.traffic_stats {
+                        stats.mark_handshake();
+                    }
+                    self.mark_server_reachable();
+                } else {
+                    tracing::info!("Session established with peer {}", peer.label());
+                }
 
                 Ok(HandshakeResult::Complete)
             }
             MessageType::CookieReply => {
                 let reply = CookieReply::from_bytes(packet)?;
 
-                // Process cookie
-                self.cookie_state.process_cookie_reply(
+                // Process cookie for the peer whose initiation it answers
+                let i = self.pending_peer(reply.receiver_index)
+                    .ok_or(ProtocolError::NoSession)?;
+                let peer = &mut self.peers[i];
+                peer.cookie_state.process_cookie_reply(
                     &reply,
-                    &self.last_mac1,
-                    &self.config.peers[0].public_key,
+                    &peer.last_mac1,
+                    &peer.config.public_key,
                 )?;
 
                 Ok(HandshakeResult::NeedRetry)
@@ -328,24 +669,45 @@ impl WireGuardClient {
     /// Main event loop
     async fn event_loop(&mut self) -> Result<(), MinnowVpnError> {
         let mut tun_buf = [0u8; BUFFER_SIZE];
-        let mut udp_buf = [0u8; BUFFER_SIZE];
-
-        // Keepalive interval
-        let mut keepalive_timer: Option<Interval> = self.keepalive_interval
-            .map(|d| interval(d));
+        // Encrypted and decrypted packets are written here, not allocated per packet
+        let mut send_buf = [0u8; BUFFER_SIZE + TRANSPORT_OVERHEAD];
+        let mut plaintext_buf = [0u8; BUFFER_SIZE];
+        // GRO stays off on the client socket: handshakes still read it with recv_from
+        let mut udp_batch = RecvBatch::new();
+
+        // Per-peer timers. Keepalive periods are jittered once per connection so
+        // clients sharing a PersistentKeepalive drift apart instead of hitting the
+        // server together (send_keepalive checks against the same period). The
+        // first keepalive tick is immediate; the first forced handshake comes
+        // after one full period (we just handshook).
+        let now = Instant::now();
+        for peer in &mut self.peers {
+            if self.keepalive_jitter {
+                peer.keepalive_interval = peer.keepalive_interval.map(jittered);
+            }
+            peer.next_keepalive = peer.keepalive_interval.map(|_| now);
+            peer.next_handshake = peer.handshake_interval.map(|d| now + d);
+        }
 
         // Rekey check interval (every 10 seconds)
         let mut rekey_check = interval(Duration::from_secs(10));
 
         tracing::info!("Entering main event loop...");
+        self.last_activity = Instant::now();
 
         loop {
+            let idle_deadline = self.idle_timeout.map(|timeout| self.last_activity + timeout);
+            let timer_deadline = self.peers.iter()
+                .flat_map(|peer| [peer.next_keepalive, peer.next_handshake, self.passive_keepalive_deadline(peer)])
+                .flatten()
+                .min();
+
             tokio::select! {
                 // Read from TUN -> encrypt -> send via UDP
                 result = self.tun.read(&mut tun_buf) => {
                     match result {
                         Ok(len) => {
-                            if let Err(e) = self.handle_tun_packet(&tun_buf[..len]).await {
+                            if let Err(e) = self.handle_tun_packet(&tun_buf[..len], &mut send_buf).await {
                                 tracing::warn!("Error handling TUN packet: {}", e);
                             }
                         }
@@ -356,75 +718,222 @@ impl WireGuardClient {
                 }
 
                 // Read from UDP -> process incoming packet
-                result = self.socket.recv_from(&mut udp_buf) => {
+                result = udp_batch.recv(&self.socket) => {
                     match result {
-                        Ok((len, from)) => {
-                            if let Err(e) = self.handle_udp_packet(&udp_buf[..len], from).await {
-                                tracing::trace!("Error handling UDP packet: {}", e);
+                        Ok(count) => {
+                            for i in 0..count {
+                                if self.netsim.as_ref().is_some_and(NetSim::drop_incoming) {
+                                    continue;
+                                }
+                                let (packet, from) = udp_batch.get(i);
+                                if let Err(e) = self.handle_udp_packet(packet, from, &mut plaintext_buf).await {
+                                    tracing::trace!("Error handling UDP packet: {}", e);
+                                }
                             }
                         }
+                        Err(e) if is_connection_refused(&e) => {
+                            self.mark_server_unreachable();
+                        }
                         Err(e) => {
                             tracing::error!("UDP recv error: {}", e);
                         }
                     }
                 }
 
-                // Keepalive timer
+                // Keepalive (persistent and passive) and forced handshake timers
                 _ = async {
-                    if let Some(ref mut timer) = keepalive_timer {
-                        timer.tick().await
-                    } else {
-                        std::future::pending::<tokio::time::Instant>().await
+                    match timer_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
                     }
                 } => {
-                    if let Err(e) = self.send_keepalive().await {
-                        tracing::warn!("Keepalive error: {}", e);
-                    }
+                    self.peer_timers().await;
                 }
 
-                // Rekey check
+                // Fresh addresses for the primary Endpoint, looked up in the background
+                _ = self.peers[PRIMARY].lookup_done() => {}
+
+                // Rekey check (also retries a failed resume handshake)
                 _ = rekey_check.tick() => {
-                    if self.sessions.needs_rekey() {
+                    if self.suspended {
+                        continue;
+                    }
+                    if self.resume_pending {
+                        match self.retry_primary_handshake().await {
+                            Ok(()) => {
+                                tracing::info!("Resumed after retry");
+                                self.resume_pending = false;
+                            }
+                            Err(e) => tracing::warn!("Resume handshake failed: {}", e),
+                        }
+                    } else if self.server_unreachable {
+                        if let Err(e) = self.retry_primary_handshake().await {
+                            tracing::warn!("Reconnect handshake failed: {}", e);
+                        }
+                    } else if self.sessions.peer(&self.peers[PRIMARY].config.public_key).needs_rekey() {
                         tracing::info!("Session needs rekey, initiating new handshake...");
-                        if let Err(e) = self.perform_handshake().await {
+                        if let Err(e) = self.retry_primary_handshake().await {
                             tracing::warn!("Rekey handshake failed: {}", e);
                         }
                     }
+
+                    // Other peers rekey in the background
+                    for i in (0..self.peers.len()).filter(|&i| i != PRIMARY) {
+                        if self.sessions.peer(&self.peers[i].config.public_key).needs_rekey() {
+                            tracing::info!("Session with peer {} needs rekey", self.peers[i].label());
+                            if let Err(e) = self.initiate_handshake(i).await {
+                                tracing::warn!("Rekey handshake failed: {}", e);
+                            }
+                        }
+                    }
+                }
+
+                // Idle timeout (keepalives don't count as activity)
+                _ = async {
+                    match idle_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if self.suspended {
+                        // Nothing flows while suspended; start counting again on resume
+                        self.last_activity = Instant::now();
+                        continue;
+                    }
+                    tracing::info!(
+                        "No tunneled traffic for {}s, disconnecting",
+                        self.idle_timeout.unwrap_or_default().as_secs()
+                    );
+                    emit_event(&self.event_tx, ClientEvent::Disconnected { reason: "idle timeout".to_string() });
+                    return Ok(());
+                }
+
+                // Suspend/resume commands from the daemon
+                command = async {
+                    match self.command_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    match command {
+                        Some(command) => self.handle_command(command).await,
+                        // Daemon dropped the sender, stop polling
+                        None => self.command_rx = None,
+                    }
                 }
             }
         }
     }
 
-    /// Handle a packet from the TUN device (outgoing traffic)
-    async fn handle_tun_packet(&mut self, packet: &[u8]) -> Result<(), MinnowVpnError> {
-        // Get current session
-        let session = self.sessions.current_mut()
-            .ok_or(ProtocolError::NoSession)?;
+    /// When `peer` owes a passive keepalive for data it received (see [`KEEPALIVE_TIMEOUT`])
+    fn passive_keepalive_deadline(&self, peer: &ClientPeer) -> Option<Instant> {
+        let deadline = self.sessions.get(&peer.config.public_key)?.current()?.passive_keepalive_deadline()?;
+        Some(Instant::from_std(deadline))
+    }
 
-        // Encrypt and send
-        let encrypted = session.transport.encrypt(session.remote_index, packet)?;
-        session.mark_sent();
+    /// Run the keepalive and forced handshake timers that are due, re-arming them
+    async fn peer_timers(&mut self) {
+        let now = Instant::now();
+        for i in 0..self.peers.len() {
+            let passive_due = self.passive_keepalive_deadline(&self.peers[i]).is_some_and(|at| at <= now);
+            let peer = &mut self.peers[i];
+            let keepalive_due = timer_due(&mut peer.next_keepalive, peer.keepalive_interval, now);
+            let handshake_due = timer_due(&mut peer.next_handshake, peer.handshake_interval, now);
+            if self.suspended {
+                continue;
+            }
 
-        self.socket.send_to(&encrypted, self.peer_endpoint).await
-            .map_err(|e| NetworkError::SendFailed {
-                reason: e.to_string(),
-            })?;
+            if keepalive_due {
+                self.keepalive_tick(i).await;
+            } else if passive_due {
+                tracing::trace!("No reply to peer {} within {:?}, sending keepalive", self.peers[i].label(), KEEPALIVE_TIMEOUT);
+                if let Err(e) = self.send_keepalive(i).await {
+                    tracing::warn!("Keepalive error: {}", e);
+                }
+            }
+            if handshake_due {
+                tracing::debug!("Handshake interval elapsed, initiating new handshake...");
+                if let Err(e) = self.handshake(i).await {
+                    tracing::warn!("Periodic handshake failed: {}", e);
+                }
+            }
+        }
+    }
 
-        // Update traffic statistics
-        if let Some(ref stats) = self.traffic_stats {
-            stats.add_sent(encrypted.len() as u64);
+    /// Apply a suspend/resume command
+    async fn handle_command(&mut self, command: ClientCommand) {
+        match command {
+            ClientCommand::Suspend => {
+                if self.suspended {
+                    return;
+                }
+                tracing::info!("Suspending tunnel (TUN and routes kept, traffic blackholed)");
+                self.suspended = true;
+                self.resume_pending = false;
+                for peer in &mut self.peers {
+                    peer.pending_handshake = None;
+                }
+                self.sessions.clear();
+                emit_event(&self.event_tx, ClientEvent::Disconnected { reason: "suspended".to_string() });
+            }
+            ClientCommand::Resume => {
+                if !self.suspended {
+                    return;
+                }
+                tracing::info!("Resuming tunnel, re-handshaking...");
+                self.suspended = false;
+                self.last_activity = Instant::now();
+                if let Err(e) = self.perform_handshake().await {
+                    tracing::warn!("Resume handshake failed: {}. Will retry.", e);
+                    self.resume_pending = true;
+                }
+            }
         }
+    }
+
+    /// Handle a packet from the TUN device (outgoing traffic), encrypting into `out`
+    async fn handle_tun_packet(&mut self, packet: &[u8], out: &mut [u8]) -> Result<(), MinnowVpnError> {
+        // Blackhole while suspended so nothing leaks around the tunnel
+        if self.suspended {
+            return Ok(());
+        }
+
+        tracing::trace!("TUN -> tunnel: {}", describe_ip_packet(packet));
+
+        // Cryptokey routing: the peer whose AllowedIPs match the destination
+        let Some(i) = parse_ip_dest(packet).ok().and_then(|dest| route_peer(&self.peers, dest)) else {
+            tracing::trace!("No peer allows {}, dropping", describe_ip_packet(packet));
+            return Ok(());
+        };
+
+        // Get current session (other peers come up on first use)
+        let public_key = self.peers[i].config.public_key;
+        let Some(session) = self.sessions.peer(&public_key).current_mut() else {
+            if i != PRIMARY {
+                self.handshake(i).await?;
+            }
+            return Err(ProtocolError::NoSession.into());
+        };
+
+        // Encrypt and send
+        let len = match seal(session, packet, self.traffic_stats.as_deref(), out) {
+            Ok(len) => len,
+            Err(e) => return Err(self.seal_failed(i, e).await),
+        };
+        self.send_to_peer(i, &out[..len]).await?;
+        self.last_activity = Instant::now();
 
         Ok(())
     }
 
-    /// Handle an incoming UDP packet
+    /// Handle an incoming UDP packet (transport data is decrypted into `plaintext_buf`)
     async fn handle_udp_packet(
         &mut self,
         packet: &[u8],
         from: SocketAddr,
+        plaintext_buf: &mut [u8],
     ) -> Result<(), MinnowVpnError> {
-        if packet.is_empty() {
+        if packet.is_empty() || self.suspended {
             return Ok(());
         }
 
@@ -432,30 +941,19 @@ impl WireGuardClient {
 
         match msg_type {
             MessageType::TransportData => {
-                self.handle_transport_packet(packet, from).await
+                self.handle_transport_packet(packet, from, plaintext_buf).await
             }
-            MessageType::HandshakeResponse => {
-                // Process handshake response during event loop
+            MessageType::HandshakeResponse | MessageType::CookieReply => {
+                // Process handshake response or cookie during event loop
                 match self.process_handshake_packet(packet, from).await? {
                     HandshakeResult::Complete => {
                         tracing::info!("Handshake complete during event loop");
-                        Ok(())
                     }
                     HandshakeResult::NeedRetry => {
-                        // Got cookie during event loop - start new handshake
-                        tracing::info!("Received cookie during event loop, will retry on next rekey");
-                        Ok(())
+                        // The next rekey, keepalive or packet starts a new handshake
+                        tracing::info!("Received cookie reply during event loop, will retry");
                     }
                 }
-            }
-            MessageType::CookieReply => {
-                let reply = CookieReply::from_bytes(packet)?;
-                self.cookie_state.process_cookie_reply(
-                    &reply,
-                    &self.last_mac1,
-                    &self.config.peers[0].public_key,
-                )?;
-                tracing::info!("Received cookie reply");
                 Ok(())
             }
             MessageType::HandshakeInitiation => {
@@ -470,67 +968,890 @@ impl WireGuardClient {
         &mut self,
         packet: &[u8],
         from: SocketAddr,
+        plaintext_buf: &mut [u8],
     ) -> Result<(), MinnowVpnError> {
-        // Update traffic statistics (count full encrypted packet)
-        if let Some(ref stats) = self.traffic_stats {
-            stats.add_received(packet.len() as u64);
-        }
-
         let header = TransportHeader::from_bytes(packet)?;
 
-        // Find session by receiver index
-        let session = self.sessions.find_by_index(header.receiver_index)
+        // Find session (and its peer) by receiver index
+        let (public_key, session) = self.sessions.find_by_index(header.receiver_index)
             .ok_or(ProtocolError::InvalidSenderIndex {
                 index: header.receiver_index,
             })?;
 
         // Decrypt
-        let plaintext = session.transport.decrypt(packet)?;
-        session.mark_received();
+        let len = open(session, packet, self.traffic_stats.as_deref(), plaintext_buf)?;
+        let plaintext = &plaintext_buf[..len];
 
         // Update endpoint if changed (roaming)
         if session.endpoint != from {
             tracing::info!("Peer endpoint changed from {} to {}", session.endpoint, from);
             session.endpoint = from;
+            if public_key == self.peers[PRIMARY].config.public_key {
+                emit_event(&self.event_tx, ClientEvent::EndpointChanged { endpoint: from });
+            }
         }
+        self.sessions.confirm_next(&public_key, header.receiver_index);
 
-        // Write decrypted IP packet to TUN
+        // Write decrypted IP packet to TUN (an empty one is a keepalive)
         if !plaintext.is_empty() {
-            self.tun.write(&plaintext).await?;
+            self.last_activity = Instant::now();
+            tracing::trace!("Tunnel -> TUN: {}", describe_ip_packet(plaintext));
+            self.tun.write(plaintext).await?;
         }
 
         Ok(())
     }
 
-    /// Send a keepalive packet (empty encrypted packet)
-    async fn send_keepalive(&mut self) -> Result<(), MinnowVpnError> {
-        let session = self.sessions.current_mut()
+    /// Keepalive timer tick for peer `i`: send a keepalive, or re-handshake if the session is gone
+    ///
+    /// With PersistentKeepalive set, a lost session (e.g. after a brief outage)
+    /// is re-established on the next tick instead of staying down.
+    async fn keepalive_tick(&mut self, i: usize) {
+        let action = self.sessions.update(&self.peers[i].config.public_key, keepalive_action);
+        if let KeepaliveAction::Handshake { expired } = action {
+            if expired && i == PRIMARY {
+                emit_event(&self.event_tx, ClientEvent::Disconnected { reason: "session expired".to_string() });
+            }
+            tracing::info!("No active session at keepalive, initiating handshake...");
+            match self.handshake(i).await {
+                Ok(()) if i == PRIMARY => self.resume_pending = false,
+                Ok(()) => {}
+                Err(e) => tracing::warn!("Keepalive handshake failed: {}", e),
+            }
+            return;
+        }
+
+        if let Err(e) = self.send_keepalive(i).await {
+            tracing::warn!("Keepalive error: {}", e);
+        }
+    }
+
+    /// Send a keepalive packet (empty encrypted packet) to peer `i`
+    async fn send_keepalive(&mut self, i: usize) -> Result<(), MinnowVpnError> {
+        let peer = &self.peers[i];
+        let session = self.sessions.peer(&peer.config.public_key).current_mut()
             .ok_or(ProtocolError::NoSession)?;
 
-        // Check if we actually need to send (no recent traffic)
-        if let Some(keepalive_interval) = self.keepalive_interval {
-            if !session.needs_keepalive(keepalive_interval) {
+        // Check if we actually need to send (no recent traffic, or data left unanswered)
+        if let Some(keepalive_interval) = peer.keepalive_interval {
+            if !session.needs_keepalive(keepalive_interval)
+                && !session.needs_passive_keepalive_at(std::time::Instant::now())
+            {
                 return Ok(());
             }
         }
 
         // Send empty packet
-        let encrypted = session.transport.encrypt(session.remote_index, &[])?;
-        session.mark_sent();
+        let mut keepalive = [0u8; TRANSPORT_OVERHEAD];
+        if let Err(e) = seal(session, &[], self.traffic_stats.as_deref(), &mut keepalive) {
+            return Err(self.seal_failed(i, e).await);
+        }
+        self.send_to_peer(i, &keepalive).await?;
 
-        self.socket.send_to(&encrypted, self.peer_endpoint).await
-            .map_err(|e| NetworkError::SendFailed {
+        Ok(())
+    }
+
+    /// Handle a failed encryption for peer `i`, passing the error on
+    ///
+    /// A session whose sending counter ran out is kept (it still receives) and
+    /// a new handshake is started right away instead of waiting for the rekey
+    /// timer; it replaces the session once it completes.
+    async fn seal_failed(&mut self, i: usize, error: MinnowVpnError) -> MinnowVpnError {
+        if !matches!(error, MinnowVpnError::Protocol(ProtocolError::CounterExhausted)) {
+            return error;
+        }
+        if self.sessions.peer(&self.peers[i].config.public_key).handshake_in_flight() {
+            return error;
+        }
+
+        tracing::info!("Sending counter exhausted for peer {}, initiating new handshake", self.peers[i].label());
+        if let Err(e) = self.initiate_handshake(i).await {
+            tracing::warn!("Rekey handshake failed: {}", e);
+        }
+        error
+    }
+
+    /// Send a datagram to peer `i`, rebinding the socket if its route is gone
+    ///
+    /// When the local network changes (e.g. Wi-Fi to cellular), a socket bound to
+    /// `0.0.0.0:0` can stay attached to an interface that no longer exists. In that
+    /// case we bind a fresh socket and retry once. Sessions are keyed by index, not
+    /// by local port, so the peer simply sees us roam to a new source address.
+    async fn send_to_peer(&mut self, i: usize, data: &[u8]) -> Result<(), MinnowVpnError> {
+        let endpoint = self.peers[i].endpoint;
+        match self.send_datagram(data, endpoint).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_route_gone(&e) => {
+                tracing::warn!("Send failed ({}), rebinding UDP socket...", e);
+                self.rebind_primary_socket().await?;
+                tracing::info!(
+                    "Rebound UDP socket to {}",
+                    self.socket.local_addr().map(|a| a.to_string()).unwrap_or_default()
+                );
+
+                self.send_datagram(data, endpoint).await
+                    .map_err(|e| NetworkError::SendFailed {
+                        reason: e.to_string(),
+                    })?;
+                Ok(())
+            }
+            Err(e) if is_connection_refused(&e) && i == PRIMARY => {
+                self.mark_server_unreachable();
+                Err(self.connection_refused())
+            }
+            Err(e) => Err(NetworkError::SendFailed {
                 reason: e.to_string(),
-            })?;
+            }.into()),
+        }
+    }
 
-        Ok(())
+    /// Send one datagram, through the simulated link if one is configured
+    async fn send_datagram(&self, data: &[u8], to: SocketAddr) -> std::io::Result<usize> {
+        match &self.netsim {
+            Some(sim) => sim.send_to(&self.socket, data, to).await,
+            None => self.socket.send_to(data, to).await,
+        }
+    }
+
+    /// Map a receive error, flagging the server as down on ICMP port unreachable
+    fn receive_error(&mut self, error: std::io::Error) -> MinnowVpnError {
+        if is_connection_refused(&error) {
+            self.mark_server_unreachable();
+            return self.connection_refused();
+        }
+        NetworkError::ReceiveFailed { reason: error.to_string() }.into()
     }
 
-    /// Clean up routes on shutdown
+    /// Error for a primary endpoint that answered with ICMP port unreachable
+    fn connection_refused(&self) -> MinnowVpnError {
+        NetworkError::ConnectionRefused {
+            endpoint: self.peers[PRIMARY].endpoint.to_string(),
+        }.into()
+    }
+
+    /// The server is not listening: drop the session and re-handshake on the rekey timer
+    ///
+    /// The shared traffic stats carry the flag so the daemon can report
+    /// "server not responding" right away instead of waiting for timeouts.
+    fn mark_server_unreachable(&mut self) {
+        if self.server_unreachable {
+            return;
+        }
+        let primary = &mut self.peers[PRIMARY];
+        tracing::warn!("Server {} not responding (port unreachable), reconnecting...", primary.endpoint);
+        self.server_unreachable = true;
+        primary.pending_handshake = None;
+        self.sessions.clear_peer(&primary.config.public_key);
+        emit_event(&self.event_tx, ClientEvent::Disconnected { reason: "server not responding".to_string() });
+        if let Some(ref stats) = self.traffic_stats {
+            stats.set_server_unreachable(true);
+        }
+    }
+
+    /// Clear the unreachable flag once the server answers again
+    fn mark_server_reachable(&mut self) {
+        if !self.server_unreachable {
+            return;
+        }
+        tracing::info!("Server {} is responding again", self.peers[PRIMARY].endpoint);
+        self.server_unreachable = false;
+        if let Some(ref stats) = self.traffic_stats {
+            stats.set_server_unreachable(false);
+        }
+    }
+
+    /// Shut down after the event loop has stopped (the `run` future was dropped)
+    ///
+    /// Order matters:
+    /// 1. Event loop stopped: nothing reads the TUN any more
+    /// 2. Sessions and any pending handshake dropped: no keys left in memory
+    /// 3. Routes removed: the OS stops sending traffic to the TUN
+    /// 4. TUN brought down (the fd closes when the client is dropped)
+    ///
+    /// Removing routes while the loop still ran would let it read packets it can
+    /// neither route nor encrypt. Closing the TUN first would make the OS drop
+    /// its routes behind the route manager's back.
     pub async fn cleanup(&mut self) -> Result<(), MinnowVpnError> {
+        for peer in &mut self.peers {
+            peer.pending_handshake = None;
+        }
+        let primary = &self.peers[PRIMARY].config.public_key;
+        if self.sessions.get(primary).is_some_and(SessionManager::has_session) {
+            emit_event(&self.event_tx, ClientEvent::Disconnected { reason: "shutdown".to_string() });
+        }
+        self.sessions.clear();
+
         tracing::info!("Cleaning up routes...");
-        self.routes.cleanup().await?;
+        let routes = self.routes.cleanup().await;
+
+        if let Err(e) = self.tun.close() {
+            tracing::debug!("Could not bring TUN {} down: {}", self.tun.name(), e);
+        }
+        routes?;
         tracing::info!("Cleanup complete");
         Ok(())
     }
 }
+
+/// Encrypt `packet` for `session` into `out`, counting it in `traffic_stats`
+///
+/// Returns the length of the transport message. Keepalives (empty packets)
+/// count towards the outer bytes only.
+fn seal(
+    session: &mut Session,
+    packet: &[u8],
+    traffic_stats: Option<&TrafficStats>,
+    out: &mut [u8],
+) -> Result<usize, MinnowVpnError> {
+    let len = session.transport.encrypt_into(session.remote_index, packet, out)?;
+    session.mark_sent();
+
+    if let Some(stats) = traffic_stats {
+        stats.add_sent(len as u64);
+        stats.add_inner_sent(packet.len() as u64);
+    }
+    Ok(len)
+}
+
+/// Decrypt a transport packet for `session` into `out`, counting it in `traffic_stats`
+///
+/// Returns the length of the IP packet. Only authenticated packets are
+/// counted, so forged or replayed ones don't inflate the received bytes.
+fn open(
+    session: &mut Session,
+    packet: &[u8],
+    traffic_stats: Option<&TrafficStats>,
+    out: &mut [u8],
+) -> Result<usize, MinnowVpnError> {
+    let len = session.transport.decrypt_into(packet, out)?;
+    if len == 0 {
+        session.mark_received();
+    } else {
+        session.mark_data_received();
+    }
+
+    if let Some(stats) = traffic_stats {
+        stats.add_received(packet.len() as u64);
+        stats.add_inner_received(len as u64);
+    }
+    Ok(len)
+}
+
+/// Peer that traffic to `dest` goes to: the longest matching AllowedIPs
+///
+/// Equal prefixes fall back to the lowest public key, as on the server.
+fn route_peer(peers: &[ClientPeer], dest: IpAddr) -> Option<usize> {
+    peers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, peer)| {
+            let prefix_len = longest_match(&peer.config.allowed_ips, dest)?.prefix_len();
+            Some((prefix_len, peer.config.public_key, i))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, _, i)| i)
+}
+
+/// Address to try after `current`, cycling through `candidates`
+///
+/// Starts over at the first candidate when `current` is no longer among them
+/// (the name resolved to new addresses).
+fn next_endpoint(candidates: &[SocketAddr], current: SocketAddr) -> SocketAddr {
+    match candidates.iter().position(|addr| *addr == current) {
+        Some(i) => candidates[(i + 1) % candidates.len()],
+        None => candidates[0],
+    }
+}
+
+/// Whether `packet` is the handshake response or cookie reply to initiation `sender_index`
+fn answers_handshake(packet: &[u8], sender_index: u32) -> bool {
+    match get_message_type(packet) {
+        Ok(MessageType::HandshakeResponse) => {
+            HandshakeResponse::from_bytes(packet).is_ok_and(|r| r.receiver_index == sender_index)
+        }
+        Ok(MessageType::CookieReply) => {
+            CookieReply::from_bytes(packet).is_ok_and(|r| r.receiver_index == sender_index)
+        }
+        _ => false,
+    }
+}
+
+/// Decide a keepalive tick from the peer's sessions
+///
+/// An expired session is cleared so it is reported only once.
+fn keepalive_action(sessions: &mut SessionManager) -> KeepaliveAction {
+    if sessions.current().is_some() {
+        return KeepaliveAction::Send;
+    }
+    let expired = sessions.has_session();
+    if expired {
+        sessions.clear();
+    }
+    KeepaliveAction::Handshake { expired }
+}
+
+/// Whether the timer at `next` is due by `now`; a due timer is re-armed `period` later
+fn timer_due(next: &mut Option<Instant>, period: Option<Duration>, now: Instant) -> bool {
+    match (*next, period) {
+        (Some(at), Some(period)) if at <= now => {
+            *next = Some(now + period);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Deliver a client event without blocking (dropped if the channel is full or closed)
+fn emit_event(event_tx: &Option<mpsc::Sender<ClientEvent>>, event: ClientEvent) {
+    if let Some(tx) = event_tx {
+        if let Err(e) = tx.try_send(event) {
+            tracing::trace!("Client event not delivered: {}", e);
+        }
+    }
+}
+
+/// Bind a UDP socket suitable for reaching the given peer endpoint
+///
+/// See [`local_address`] for the address bound. With `connect`, the socket is
+/// connected to the endpoint (Linux) so ICMP errors are reported.
+async fn bind_socket(
+    peer_endpoint: SocketAddr,
+    interface: &InterfaceConfig,
+    connect: bool,
+) -> Result<UdpSocket, MinnowVpnError> {
+    let bind_addr = local_address(peer_endpoint, interface);
+
+    let socket = UdpSocket::bind(bind_addr).await
+        .map_err(|e| NetworkError::BindFailed {
+            addr: bind_addr.to_string(),
+            reason: e.to_string(),
+        })?;
+
+    if let Some(mark) = interface.fwmark {
+        set_socket_fwmark(&socket, mark)?;
+    }
+
+    // Linux only reports ICMP port unreachable (as ECONNREFUSED) on connected
+    // UDP sockets. sendto() to the same address still works once connected.
+    #[cfg(target_os = "linux")]
+    if !connect {
+        // Several peers share the socket
+    } else if let Err(e) = socket.connect(peer_endpoint).await {
+        tracing::debug!("Could not connect UDP socket to {}: {}", peer_endpoint, e);
+    }
+
+    Ok(socket)
+}
+
+/// Local address for a socket reaching `peer_endpoint`
+///
+/// `BindAddress` and `ListenPort` pin the address and port. Without
+/// `BindAddress`, localhost endpoints get 127.0.0.1 (or ::1) to ensure the
+/// correct source address, and others the unspecified address so the OS
+/// chooses. Without `ListenPort` the port is ephemeral.
+fn local_address(peer_endpoint: SocketAddr, interface: &InterfaceConfig) -> SocketAddr {
+    let ip = interface.bind_address.unwrap_or(match (peer_endpoint.is_ipv6(), peer_endpoint.ip().is_loopback()) {
+        (false, true) => Ipv4Addr::LOCALHOST.into(),
+        (false, false) => Ipv4Addr::UNSPECIFIED.into(),
+        (true, true) => Ipv6Addr::LOCALHOST.into(),
+        (true, false) => Ipv6Addr::UNSPECIFIED.into(),
+    });
+    SocketAddr::new(ip, interface.listen_port.unwrap_or(0))
+}
+
+/// The steps [`connect_with_retry`] repeats until a handshake completes
+trait HandshakeAttempts {
+    /// Run handshake attempt number `attempt` until it completes or fails
+    async fn attempt(&mut self, attempt: u32) -> Result<(), MinnowVpnError>;
+
+    /// Pick the endpoint for the next attempt
+    async fn next_endpoint(&mut self) -> Result<(), MinnowVpnError>;
+}
+
+impl HandshakeAttempts for WireGuardClient {
+    async fn attempt(&mut self, attempt: u32) -> Result<(), MinnowVpnError> {
+        tracing::info!("Connection attempt {}...", attempt);
+        emit_event(&self.event_tx, ClientEvent::Progress(HandshakeProgress::Attempt { attempt }));
+        self.perform_handshake().await
+    }
+
+    async fn next_endpoint(&mut self) -> Result<(), MinnowVpnError> {
+        self.start_endpoint_lookup();
+        if self.peers[PRIMARY].endpoint_lookup.is_some() {
+            let lookup = self.peers[PRIMARY].lookup_done();
+            if tokio::time::timeout(ENDPOINT_LOOKUP_TIMEOUT, lookup).await.is_err() {
+                tracing::warn!("Endpoint lookup is slow; retrying the previous addresses meanwhile");
+            }
+        }
+        self.try_next_endpoint().await
+    }
+}
+
+/// Connect with automatic retry and exponential backoff
+///
+/// Gives up on an error retrying can't fix, with `MaxRetriesExceeded` after
+/// `max_attempts` failed handshakes, or with `ConnectTimeout` once
+/// `connect_timeout` has passed (None = no limit).
+async fn connect_with_retry(
+    handshakes: &mut impl HandshakeAttempts,
+    max_attempts: Option<u32>,
+    connect_timeout: Option<Duration>,
+) -> Result<(), MinnowVpnError> {
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempts = 0u32;
+    let deadline = connect_timeout.map(|timeout| Instant::now() + timeout);
+    let timed_out = |attempts| {
+        MinnowVpnError::Protocol(ProtocolError::ConnectTimeout {
+            seconds: connect_timeout.map(|t| t.as_secs()).unwrap_or(0),
+            attempts,
+        })
+    };
+
+    loop {
+        attempts += 1;
+        let result = match deadline {
+            Some(deadline) => {
+                match tokio::time::timeout_at(deadline, handshakes.attempt(attempts)).await {
+                    Ok(result) => result,
+                    Err(_) => return Err(timed_out(attempts)),
+                }
+            }
+            None => handshakes.attempt(attempts).await,
+        };
+
+        match result {
+            Ok(_) => {
+                tracing::info!("Handshake complete! Session established.");
+                return Ok(());
+            }
+            Err(e) => {
+                if !should_retry_handshake(&e) {
+                    tracing::error!("Handshake failed: {}", e.user_message());
+                    return Err(e);
+                }
+                if max_attempts.is_some_and(|max| attempts >= max) {
+                    tracing::warn!("Handshake failed: {}", e);
+                    return Err(ProtocolError::MaxRetriesExceeded { attempts }.into());
+                }
+                if let Some(deadline) = deadline {
+                    if Instant::now() + delay >= deadline {
+                        tracing::warn!("Handshake failed: {}", e);
+                        tokio::time::sleep_until(deadline).await;
+                        return Err(timed_out(attempts));
+                    }
+                }
+                tracing::warn!("Handshake failed: {}. Retrying in {:?}...", e, delay);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                handshakes.next_endpoint().await?;
+            }
+        }
+    }
+}
+
+/// Check if a failed handshake is worth retrying
+///
+/// A MAC1 mismatch means the keys are misconfigured; every retry fails the
+/// same way, so the caller should give up with the configuration hint instead.
+fn should_retry_handshake(error: &MinnowVpnError) -> bool {
+    !matches!(error, MinnowVpnError::Protocol(ProtocolError::MacVerificationFailed))
+}
+
+/// `period` scaled by a random factor within ±[`KEEPALIVE_JITTER`]
+fn jittered(period: Duration) -> Duration {
+    use rand::Rng;
+
+    let factor = rand::thread_rng().gen_range(1.0 - KEEPALIVE_JITTER..=1.0 + KEEPALIVE_JITTER);
+    period.mul_f64(factor)
+}
+
+/// Check if a socket error is an ICMP port unreachable from the peer
+fn is_connection_refused(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::ConnectionRefused
+}
+
+/// Check if a send error means the socket's route or local address has disappeared
+fn is_route_gone(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    match error.kind() {
+        ErrorKind::NetworkUnreachable
+        | ErrorKind::HostUnreachable
+        | ErrorKind::AddrNotAvailable
+        | ErrorKind::NetworkDown => true,
+        _ => {
+            #[cfg(unix)]
+            {
+                matches!(
+                    error.raw_os_error(),
+                    Some(libc::ENETUNREACH) | Some(libc::EHOSTUNREACH) | Some(libc::EADDRNOTAVAIL)
+                        | Some(libc::ENETDOWN)
+                )
+            }
+            #[cfg(not(unix))]
+            {
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_CONFIG: &str = "[Interface]\n\
+        PrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+        Address = 10.0.0.2/32\n\
+        [Peer]\n\
+        PublicKey = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\n\
+        Endpoint = 127.0.0.1:51820\n\
+        AllowedIPs = 0.0.0.0/0\n";
+
+    #[test]
+    fn test_keepalive_jitter_bounds() {
+        let period = Duration::from_secs(25);
+        for _ in 0..1000 {
+            let spread = jittered(period);
+            assert!(spread >= Duration::from_millis(22_500) && spread <= Duration::from_millis(27_500));
+        }
+    }
+
+    #[test]
+    fn test_is_route_gone() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(is_route_gone(&Error::from(ErrorKind::NetworkUnreachable)));
+        assert!(is_route_gone(&Error::from(ErrorKind::AddrNotAvailable)));
+        assert!(!is_route_gone(&Error::from(ErrorKind::WouldBlock)));
+        assert!(!is_route_gone(&Error::from(ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn test_keepalive_action_rehandshakes_without_session() {
+        let mut sessions = SessionManager::new();
+        let endpoint: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+
+        // Never connected: handshake, nothing to report
+        assert_eq!(keepalive_action(&mut sessions), KeepaliveAction::Handshake { expired: false });
+
+        sessions.establish_session(Session::new(1, 2, [1u8; 32], [2u8; 32], endpoint));
+        assert_eq!(keepalive_action(&mut sessions), KeepaliveAction::Send);
+
+        // Lost after an outage: handshake and report the expiry once
+        sessions.current_mut().unwrap().created_at = std::time::Instant::now()
+            .checked_sub(crate::protocol::session::REJECT_AFTER_TIME)
+            .unwrap();
+        assert_eq!(keepalive_action(&mut sessions), KeepaliveAction::Handshake { expired: true });
+        assert!(!sessions.has_session());
+        assert_eq!(keepalive_action(&mut sessions), KeepaliveAction::Handshake { expired: false });
+    }
+
+    #[test]
+    fn test_emit_event_never_blocks() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let tx = Some(tx);
+        emit_event(&tx, ClientEvent::Rekeyed);
+        // Channel full: dropped instead of waiting
+        emit_event(&tx, ClientEvent::Disconnected { reason: "shutdown".to_string() });
+
+        assert_eq!(rx.try_recv().unwrap(), ClientEvent::Rekeyed);
+        assert!(rx.try_recv().is_err());
+        emit_event(&None, ClientEvent::Rekeyed);
+    }
+
+    #[test]
+    fn test_traffic_stats_count_sealed_and_opened_packets() {
+        let endpoint: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+        let mut ours = Session::new(1, 2, [1u8; 32], [2u8; 32], endpoint);
+        let mut theirs = Session::new(2, 1, [2u8; 32], [1u8; 32], endpoint);
+        let stats = TrafficStats::new();
+        let (mut buf, mut plaintext) = ([0u8; 64], [0u8; 64]);
+
+        let data = seal(&mut ours, b"hello", Some(&stats), &mut buf).unwrap();
+        let keepalive = seal(&mut ours, &[], Some(&stats), &mut buf).unwrap();
+        assert_eq!(stats.get_sent(), (data + keepalive) as u64);
+        assert_eq!(stats.get_inner_sent(), 5);
+
+        let len = seal(&mut theirs, b"world!", None, &mut buf).unwrap();
+        let reply = &buf[..len];
+        let n = open(&mut ours, reply, Some(&stats), &mut plaintext).unwrap();
+        assert_eq!(&plaintext[..n], b"world!");
+        assert_eq!(stats.get_received(), reply.len() as u64);
+        assert_eq!(stats.get_inner_received(), 6);
+
+        // A replay fails authentication and isn't counted
+        assert!(open(&mut ours, reply, Some(&stats), &mut plaintext).is_err());
+        assert_eq!(stats.get_received(), reply.len() as u64);
+    }
+
+    #[test]
+    fn test_only_data_packets_call_for_a_passive_keepalive() {
+        let endpoint: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+        let mut ours = Session::new(1, 2, [1u8; 32], [2u8; 32], endpoint);
+        let mut theirs = Session::new(2, 1, [2u8; 32], [1u8; 32], endpoint);
+        ours.last_sent -= Duration::from_secs(1);
+        let (mut buf, mut plaintext) = ([0u8; 64], [0u8; 64]);
+
+        let len = seal(&mut theirs, &[], None, &mut buf).unwrap();
+        open(&mut ours, &buf[..len], None, &mut plaintext).unwrap();
+        assert_eq!(ours.passive_keepalive_deadline(), None);
+
+        let len = seal(&mut theirs, b"ping", None, &mut buf).unwrap();
+        open(&mut ours, &buf[..len], None, &mut plaintext).unwrap();
+        assert_eq!(ours.passive_keepalive_deadline(), Some(ours.last_received + KEEPALIVE_TIMEOUT));
+
+        seal(&mut ours, &[], None, &mut buf).unwrap();
+        assert_eq!(ours.passive_keepalive_deadline(), None);
+    }
+
+    #[tokio::test]
+    async fn test_route_peer_longest_prefix() {
+        let peer = |key: u8, allowed_ips: &str| PeerConfig {
+            public_key: [key; 32],
+            preshared_key: None,
+            endpoint: Some(format!("192.0.2.{}:51820", key).parse().unwrap()),
+            endpoint_host: None,
+            allowed_ips: vec![allowed_ips.parse().unwrap()],
+            persistent_keepalive: None,
+            handshake_interval: None,
+            lock_endpoint: false,
+        };
+        let peers = vec![
+            ClientPeer::new(&peer(1, "0.0.0.0/0")).await.unwrap(),
+            ClientPeer::new(&peer(2, "192.168.1.0/24")).await.unwrap(),
+            ClientPeer::new(&peer(3, "192.168.1.0/24")).await.unwrap(),
+        ];
+
+        assert_eq!(route_peer(&peers, "192.168.1.7".parse().unwrap()), Some(1));
+        assert_eq!(route_peer(&peers, "8.8.8.8".parse().unwrap()), Some(0));
+        assert_eq!(route_peer(&peers, "fd00::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_next_endpoint() {
+        let a: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+        let moved: SocketAddr = "198.51.100.7:51820".parse().unwrap();
+
+        assert_eq!(next_endpoint(&[a, b], a), b);
+        assert_eq!(next_endpoint(&[a, b], b), a);
+        // A literal address is retried as is
+        assert_eq!(next_endpoint(&[a], a), a);
+        // The name now resolves elsewhere
+        assert_eq!(next_endpoint(&[moved], a), moved);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_lookup_runs_in_background() {
+        let config = WireGuardConfig::parse(&CLIENT_CONFIG.replace("127.0.0.1:51820", "localhost:51820")).unwrap();
+        let mut peer = ClientPeer::new(&config.peers[0]).await.unwrap();
+        let stale: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        peer.endpoint_candidates = vec![stale];
+
+        // Starting returns right away; one lookup at a time
+        assert!(peer.start_lookup());
+        assert!(!peer.start_lookup());
+        tokio::time::timeout(Duration::from_secs(2), peer.lookup_done()).await.unwrap();
+        assert!(peer.endpoint_lookup.is_none());
+        assert!(peer.endpoint_candidates.contains(&"127.0.0.1:51820".parse().unwrap()));
+        assert!(!peer.endpoint_candidates.contains(&stale));
+
+        // A literal address has nothing to look up, so the event loop arm stays idle
+        let config = WireGuardConfig::parse(CLIENT_CONFIG).unwrap();
+        let mut literal = ClientPeer::new(&config.peers[0]).await.unwrap();
+        assert!(!literal.start_lookup());
+        assert!(tokio::time::timeout(Duration::from_millis(10), literal.lookup_done()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_interval_timer() {
+        let config = WireGuardConfig::parse(&format!("{}HandshakeInterval = 60\n", CLIENT_CONFIG)).unwrap();
+        let mut peer = ClientPeer::new(&config.peers[0]).await.unwrap();
+        let period = Duration::from_secs(60);
+        assert_eq!(peer.handshake_interval, Some(period));
+
+        // Armed one full period after connecting, then re-armed from each firing
+        let start = Instant::now();
+        peer.next_handshake = peer.handshake_interval.map(|d| start + d);
+        assert!(!timer_due(&mut peer.next_handshake, peer.handshake_interval, start));
+        assert!(!timer_due(&mut peer.next_handshake, peer.handshake_interval, start + period / 2));
+        assert!(timer_due(&mut peer.next_handshake, peer.handshake_interval, start + period));
+        assert_eq!(peer.next_handshake, Some(start + period * 2));
+
+        // Off by default: never fires
+        let config = WireGuardConfig::parse(CLIENT_CONFIG).unwrap();
+        let mut peer = ClientPeer::new(&config.peers[0]).await.unwrap();
+        peer.next_handshake = peer.handshake_interval.map(|d| start + d);
+        assert!(!timer_due(&mut peer.next_handshake, peer.handshake_interval, start + period * 10));
+    }
+
+    #[test]
+    fn test_is_connection_refused() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(is_connection_refused(&Error::from(ErrorKind::ConnectionRefused)));
+        #[cfg(unix)]
+        assert!(is_connection_refused(&Error::from_raw_os_error(libc::ECONNREFUSED)));
+        assert!(!is_connection_refused(&Error::from(ErrorKind::TimedOut)));
+    }
+
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn test_bound_socket_reports_port_unreachable() {
+        // Grab a free port, then close it so nothing is listening
+        let closed = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = closed.local_addr().unwrap();
+        drop(closed);
+
+        let interface = WireGuardConfig::parse(CLIENT_CONFIG).unwrap().interface;
+        let socket = bind_socket(endpoint, &interface, true).await.unwrap();
+        socket.send_to(b"ping", endpoint).await.unwrap();
+
+        let mut batch = RecvBatch::new();
+        let err = tokio::time::timeout(Duration::from_secs(2), batch.recv(&socket))
+            .await
+            .expect("ICMP error should arrive promptly")
+            .unwrap_err();
+        assert!(is_connection_refused(&err));
+    }
+
+    /// Handshakes against a port nothing listens on
+    struct ClosedPort {
+        socket: UdpSocket,
+        attempts: Vec<u32>,
+        endpoint_switches: u32,
+    }
+
+    impl HandshakeAttempts for ClosedPort {
+        async fn attempt(&mut self, attempt: u32) -> Result<(), MinnowVpnError> {
+            self.attempts.push(attempt);
+            self.socket.send(b"initiation").await.map_err(|e| NetworkError::SendFailed { reason: e.to_string() })?;
+            let mut buf = [0u8; 64];
+            match batch::recv_from(&self.socket, &mut buf).await {
+                Err(e) if is_connection_refused(&e) => {
+                    Err(NetworkError::ConnectionRefused { endpoint: self.socket.peer_addr().unwrap().to_string() }.into())
+                }
+                other => panic!("expected port unreachable, got {:?}", other),
+            }
+        }
+
+        async fn next_endpoint(&mut self) -> Result<(), MinnowVpnError> {
+            self.endpoint_switches += 1;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_gives_up_after_max_attempts() {
+        let closed = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = closed.local_addr().unwrap();
+        drop(closed);
+
+        let interface = WireGuardConfig::parse(CLIENT_CONFIG).unwrap().interface;
+        let mut handshakes = ClosedPort {
+            socket: bind_socket(endpoint, &interface, true).await.unwrap(),
+            attempts: Vec::new(),
+            endpoint_switches: 0,
+        };
+
+        let result = connect_with_retry(&mut handshakes, Some(2), None).await;
+        assert!(matches!(
+            result,
+            Err(MinnowVpnError::Protocol(ProtocolError::MaxRetriesExceeded { attempts: 2 }))
+        ));
+        assert_eq!(handshakes.attempts, vec![1, 2]);
+        assert_eq!(handshakes.endpoint_switches, 1);
+    }
+
+    /// Handshakes the peer never answers
+    struct Silent {
+        attempts: u32,
+    }
+
+    impl HandshakeAttempts for Silent {
+        async fn attempt(&mut self, attempt: u32) -> Result<(), MinnowVpnError> {
+            self.attempts = attempt;
+            std::future::pending().await
+        }
+
+        async fn next_endpoint(&mut self) -> Result<(), MinnowVpnError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_bounds_retries() {
+        let timeout = Duration::from_millis(200);
+
+        // An attempt still waiting for a response is cut off at the deadline
+        // Combined with the default attempt cap, whichever is reached first
+        let mut silent = Silent { attempts: 0 };
+        let started = Instant::now();
+        let result = connect_with_retry(&mut silent, Some(DEFAULT_MAX_ATTEMPTS), Some(timeout)).await;
+        assert!(matches!(
+            result,
+            Err(MinnowVpnError::Protocol(ProtocolError::ConnectTimeout { attempts: 1, .. }))
+        ));
+        assert!(started.elapsed() >= timeout);
+        assert_eq!(silent.attempts, 1);
+
+        // A failed attempt whose backoff would overrun the deadline gives up at the deadline
+        let closed = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = closed.local_addr().unwrap();
+        drop(closed);
+        let interface = WireGuardConfig::parse(CLIENT_CONFIG).unwrap().interface;
+        let mut refused = ClosedPort {
+            socket: bind_socket(endpoint, &interface, true).await.unwrap(),
+            attempts: Vec::new(),
+            endpoint_switches: 0,
+        };
+        let started = Instant::now();
+        let result = connect_with_retry(&mut refused, Some(DEFAULT_MAX_ATTEMPTS), Some(timeout)).await;
+        assert!(matches!(
+            result,
+            Err(MinnowVpnError::Protocol(ProtocolError::ConnectTimeout { attempts: 1, .. }))
+        ));
+        assert!(started.elapsed() >= timeout && started.elapsed() < INITIAL_RETRY_DELAY);
+        assert_eq!(refused.attempts, vec![1]);
+        assert_eq!(refused.endpoint_switches, 0);
+    }
+
+    #[tokio::test]
+    async fn test_bind_listen_port() {
+        let endpoint: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+        let free = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = free.local_addr().unwrap().port();
+        drop(free);
+
+        let config = CLIENT_CONFIG.replace("[Peer]", &format!("ListenPort = {}\n[Peer]", port));
+        let interface = WireGuardConfig::parse(&config).unwrap().interface;
+        let socket = bind_socket(endpoint, &interface, false).await.unwrap();
+        assert_eq!(socket.local_addr().unwrap(), SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+
+        // The pinned port is never shared; it is free again once the socket is closed
+        assert!(bind_socket(endpoint, &interface, false).await.is_err());
+        drop(socket);
+        let rebound = bind_socket(endpoint, &interface, false).await.unwrap();
+        assert_eq!(rebound.local_addr().unwrap().port(), port);
+
+        // Without ListenPort or BindAddress the OS picks the port
+        let interface = WireGuardConfig::parse(CLIENT_CONFIG).unwrap().interface;
+        let remote: SocketAddr = "198.51.100.1:51820".parse().unwrap();
+        assert_eq!(local_address(remote, &interface), "0.0.0.0:0".parse().unwrap());
+
+        let config = CLIENT_CONFIG.replace("[Peer]", "BindAddress = 192.0.2.10\nListenPort = 40000\n[Peer]");
+        let interface = WireGuardConfig::parse(&config).unwrap().interface;
+        assert_eq!(local_address(remote, &interface), "192.0.2.10:40000".parse().unwrap());
+    }
+
+    #[test]
+    fn test_should_retry_handshake() {
+        let timeout = MinnowVpnError::Protocol(ProtocolError::HandshakeTimeout { seconds: 5 });
+        let mismatch = MinnowVpnError::Protocol(ProtocolError::MacVerificationFailed);
+
+        assert!(should_retry_handshake(&timeout));
+        assert!(!should_retry_handshake(&mismatch));
+        assert!(!mismatch.is_recoverable());
+    }
+}
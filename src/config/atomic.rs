@@ -0,0 +1,120 @@
+//! Atomic file reads and writes for configs and persisted state
+//!
+//! Writers replace files via temp file + rename so readers never see a partial
+//! file; readers re-check size/mtime so an in-place rewrite by another tool is
+//! not parsed half-way through.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Number of read attempts before giving up on a file that keeps changing
+const READ_ATTEMPTS: usize = 3;
+
+/// Delay between read attempts
+const READ_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Atomically replace `path` with `contents`
+///
+/// Writes to a temporary file in the same directory, syncs it, then renames it
+/// over `path`. On Unix, `mode` sets the file permissions before it becomes visible.
+pub fn write_atomic(path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+    let tmp_path = temp_path_for(path);
+
+    let result = (|| {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Read a file as UTF-8, retrying if it changes while being read
+///
+/// The file's size and modification time must be the same before and after the
+/// read. After `READ_ATTEMPTS` unstable reads the last content is returned.
+pub fn read_stable(path: &Path) -> io::Result<String> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+
+        let before = std::fs::metadata(path)?;
+        let content = std::fs::read_to_string(path)?;
+        let after = std::fs::metadata(path)?;
+
+        let stable = before.len() == after.len()
+            && after.len() == content.len() as u64
+            && before.modified().ok() == after.modified().ok();
+
+        if stable || attempt >= READ_ATTEMPTS {
+            if !stable {
+                tracing::warn!("{:?} kept changing while being read", path);
+            }
+            return Ok(content);
+        }
+
+        tracing::debug!("{:?} changed while being read, retrying", path);
+        std::thread::sleep(READ_RETRY_DELAY);
+    }
+}
+
+/// Temporary file path next to `path` (same directory so rename is atomic)
+fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp.{}", file_name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        write_atomic(&path, b"first", None).unwrap();
+        write_atomic(&path, b"second", Some(0o640)).unwrap();
+
+        assert_eq!(read_stable(&path).unwrap(), "second");
+
+        // No temp file left behind
+        let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            // Mode applies only when the temp file is created; umask may clear bits
+            assert_eq!(mode & 0o007, 0);
+        }
+    }
+
+    #[test]
+    fn test_read_stable_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_stable(&dir.path().join("missing.conf")).is_err());
+    }
+}
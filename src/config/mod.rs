@@ -2,6 +2,11 @@
 //!
 //! This module handles parsing of standard WireGuard `.conf` configuration files.
 
+mod atomic;
 mod parser;
 
-pub use parser::{InterfaceConfig, PeerConfig, WireGuardConfig};
+pub use atomic::{read_stable, write_atomic};
+pub use parser::{
+    check_mtu, ConfigLimits, InterfaceConfig, PeerConfig, RouteTable, WireGuardConfig, MAX_MTU, MIN_MTU,
+};
+pub(crate) use parser::parse_key;
@@ -2,16 +2,22 @@
 //!
 //! Parses standard WireGuard `.conf` files with [Interface] and [Peer] sections.
 
-use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
+use std::str::FromStr;
 
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
 use ipnet::IpNet;
+use zeroize::Zeroize;
 
-use crate::error::ConfigError;
+use crate::error::{ConfigError, NetworkError};
+use crate::protocol::TransportHeader;
 
 /// Complete WireGuard configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WireGuardConfig {
     /// Interface configuration (our side)
     pub interface: InterfaceConfig,
@@ -20,42 +26,227 @@ pub struct WireGuardConfig {
 }
 
 /// Interface (local) configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceConfig {
     /// Our private key (32 bytes)
     pub private_key: [u8; 32],
-    /// Our VPN IP addresses with prefix
-    pub address: Vec<ipnet::Ipv4Net>,
+    /// Our VPN IP addresses with prefix (IPv4 and IPv6)
+    pub address: Vec<IpNet>,
     /// DNS servers (optional)
     pub dns: Vec<IpAddr>,
-    /// Listen port (optional, for servers)
+    /// UDP port to bind (required for servers; a client binds an ephemeral port without it)
     pub listen_port: Option<u16>,
+    /// Local address to bind the UDP socket to (None = any, for multi-homed hosts)
+    pub bind_address: Option<IpAddr>,
     /// MTU (optional, default 1420)
     pub mtu: Option<u16>,
-    /// Pre-shared key (optional, stored here for convenience)
-    pub preshared_key: Option<[u8; 32]>,
+    /// Firewall mark for outgoing UDP packets (optional, Linux only)
+    pub fwmark: Option<u32>,
+    /// Replicate broadcast/multicast packets to all matching peers (server, off by default)
+    pub replicate_broadcast: bool,
+    /// TUN device name (None = OS default; the CLI `--interface` flag overrides it)
+    pub tun_name: Option<String>,
+    /// Most peers the client keeps session state for (None = default cap)
+    pub max_sessions: Option<usize>,
+    /// Source ranges the server accepts handshake initiations from (empty = any)
+    pub handshake_sources: Vec<IpNet>,
+    /// Client disconnects after this many seconds without tunneled traffic (None = never)
+    pub idle_timeout: Option<u32>,
+    /// Handshake initiations per second the server accepts from one address (None = default)
+    pub handshake_rate_limit: Option<u32>,
+    /// Routing table for the peers' AllowedIPs (None = `auto`)
+    pub table: Option<RouteTable>,
+}
+
+/// Where the routes for AllowedIPs go (`Table` key, as in wg-quick)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteTable {
+    /// The main table
+    Auto,
+    /// No routes at all; the TUN device is still brought up
+    Off,
+    /// A numbered table (Linux only)
+    Id(u32),
+}
+
+impl FromStr for RouteTable {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("auto") {
+            Ok(RouteTable::Auto)
+        } else if value.eq_ignore_ascii_case("off") {
+            Ok(RouteTable::Off)
+        } else {
+            value.parse().map(RouteTable::Id).map_err(|_| ())
+        }
+    }
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteTable::Auto => write!(f, "auto"),
+            RouteTable::Off => write!(f, "off"),
+            RouteTable::Id(id) => write!(f, "{}", id),
+        }
+    }
 }
 
 /// Peer configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PeerConfig {
     /// Peer's public key (32 bytes)
     pub public_key: [u8; 32],
     /// Pre-shared key (optional, 32 bytes)
     pub preshared_key: Option<[u8; 32]>,
-    /// Peer's endpoint (IP:port)
+    /// Peer's endpoint when given as a literal `IP:port`
     pub endpoint: Option<SocketAddr>,
+    /// Peer's endpoint when given as `hostname:port` (resolved at connect time)
+    pub endpoint_host: Option<String>,
     /// Allowed IP ranges for this peer
     pub allowed_ips: Vec<IpNet>,
     /// Keepalive interval in seconds (optional)
     pub persistent_keepalive: Option<u16>,
+    /// Forced handshake interval in seconds (optional, off by default)
+    pub handshake_interval: Option<u16>,
+    /// Keep the first learned endpoint instead of roaming (server mode)
+    pub lock_endpoint: bool,
+}
+
+/// Limits enforced while parsing, so an oversized config can't exhaust memory
+///
+/// Configs may arrive over IPC from untrusted callers, so every parse is bounded.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigLimits {
+    /// Maximum config size in bytes
+    pub max_config_size: usize,
+    /// Maximum number of [Peer] sections
+    pub max_peers: usize,
+    /// Maximum number of AllowedIPs entries per peer
+    pub max_allowed_ips_per_peer: usize,
+}
+
+impl Default for ConfigLimits {
+    fn default() -> Self {
+        Self {
+            max_config_size: 1024 * 1024,
+            max_peers: 4096,
+            max_allowed_ips_per_peer: 1024,
+        }
+    }
+}
+
+/// Smallest tunnel MTU: the minimum every IPv4 link must carry (RFC 791)
+pub const MIN_MTU: u16 = 68;
+
+/// Largest tunnel MTU: the encrypted packet (plus 32 bytes of WireGuard
+/// header and tag) must still fit in one UDP datagram over IPv6
+pub const MAX_MTU: u16 = (u16::MAX as usize - 40 - 8 - TransportHeader::MIN_SIZE) as u16;
+
+/// Check that an MTU leaves room for a usable inner packet and the transport overhead
+///
+/// Below [`MIN_MTU`] the TUN would come up but not even an IPv4 header with
+/// options fits, so nothing meaningful could pass.
+pub fn check_mtu(mtu: u16) -> Result<(), ConfigError> {
+    if (MIN_MTU..=MAX_MTU).contains(&mtu) {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidMtu { mtu, min: MIN_MTU, max: MAX_MTU })
+    }
+}
+
+impl InterfaceConfig {
+    /// All interface addresses, in config order (each one is assigned to the TUN)
+    pub fn addresses(&self) -> Vec<IpNet> {
+        self.address.clone()
+    }
+
+    /// First IPv4 address: the VPN subnet for broadcasts and the source of ICMP errors
+    pub fn ipv4_address(&self) -> Option<ipnet::Ipv4Net> {
+        self.address.iter().find_map(|net| match net {
+            IpNet::V4(v4) => Some(*v4),
+            IpNet::V6(_) => None,
+        })
+    }
+
+    /// Whether `source` may start a handshake (always, without `HandshakeSources`)
+    pub fn accepts_handshake_from(&self, source: IpAddr) -> bool {
+        // A dual-stack socket reports IPv4 peers as ::ffff:a.b.c.d
+        let source = source.to_canonical();
+        self.handshake_sources.is_empty() || self.handshake_sources.iter().any(|net| net.contains(&source))
+    }
+}
+
+/// The private key is scrubbed when the config is dropped
+impl Zeroize for InterfaceConfig {
+    fn zeroize(&mut self) {
+        self.private_key.zeroize();
+    }
+}
+
+impl Drop for InterfaceConfig {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// The preshared key is scrubbed when the config is dropped
+impl Zeroize for PeerConfig {
+    fn zeroize(&mut self) {
+        self.preshared_key.zeroize();
+    }
+}
+
+impl Drop for PeerConfig {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl PeerConfig {
+    /// Whether an Endpoint is configured (literal or hostname)
+    pub fn has_endpoint(&self) -> bool {
+        self.endpoint.is_some() || self.endpoint_host.is_some()
+    }
+
+    /// The Endpoint as configured, for display
+    pub fn endpoint_string(&self) -> Option<String> {
+        self.endpoint.map(|addr| addr.to_string()).or_else(|| self.endpoint_host.clone())
+    }
+
+    /// Resolve the Endpoint to the addresses to try, in resolver order
+    ///
+    /// A literal address resolves to itself. A hostname is looked up on every
+    /// call, so DNS changes are picked up on reconnect.
+    pub async fn resolve_endpoint(&self) -> Result<Vec<SocketAddr>, NetworkError> {
+        if let Some(addr) = self.endpoint {
+            return Ok(vec![addr]);
+        }
+        let host = self.endpoint_host.as_deref().ok_or(NetworkError::NoEndpoint)?;
+        let failed = || NetworkError::DnsResolutionFailed { host: host.to_string() };
+
+        let mut addrs: Vec<SocketAddr> = Vec::new();
+        for addr in tokio::net::lookup_host(host).await.map_err(|_| failed())? {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        if addrs.is_empty() {
+            return Err(failed());
+        }
+        Ok(addrs)
+    }
 }
 
 impl WireGuardConfig {
     /// Parse a WireGuard configuration from a file
+    ///
+    /// The file is read in full (and re-read if it changes mid-read) before parsing,
+    /// so a config being rewritten by another tool is never parsed half-written.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
         let path = path.as_ref();
-        let content = std::fs::read_to_string(path).map_err(|e| {
+        let content = super::read_stable(path).map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
                 ConfigError::FileNotFound {
                     path: path.display().to_string(),
@@ -74,6 +265,45 @@ impl WireGuardConfig {
 
     /// Parse a WireGuard configuration from a string (alias for from_string)
     pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        Self::parse_with_limits(content, &ConfigLimits::default())
+    }
+
+    /// Parse a WireGuard configuration from a string, enforcing the given limits
+    pub fn parse_with_limits(content: &str, limits: &ConfigLimits) -> Result<Self, ConfigError> {
+        if content.len() > limits.max_config_size {
+            return Err(ConfigError::LimitExceeded {
+                what: format!("config size of {} bytes", content.len()),
+                limit: limits.max_config_size,
+            });
+        }
+
+        let (interface, peers) = Self::parse_sections(content, limits)?;
+
+        let interface = interface.ok_or(ConfigError::MissingField {
+            field: "[Interface] section".to_string(),
+        })?;
+
+        // Validate interface has required fields
+        if interface.private_key == [0u8; 32] {
+            return Err(ConfigError::MissingField {
+                field: "PrivateKey".to_string(),
+            });
+        }
+
+        // Reject our own public key as a peer key (same key pasted on both sides)
+        let own_public_key = crate::crypto::x25519::public_key(&interface.private_key);
+        if let Some(index) = peers.iter().position(|p| p.public_key == own_public_key) {
+            return Err(ConfigError::OwnKeyAsPeer { peer: index + 1 });
+        }
+
+        Ok(WireGuardConfig { interface, peers })
+    }
+
+    /// Parse [Interface] and [Peer] sections without cross-section validation
+    fn parse_sections(
+        content: &str,
+        limits: &ConfigLimits,
+    ) -> Result<(Option<InterfaceConfig>, Vec<PeerConfig>), ConfigError> {
         let mut interface: Option<InterfaceConfig> = None;
         let mut peers: Vec<PeerConfig> = Vec::new();
         let mut current_section: Option<Section> = None;
@@ -81,30 +311,44 @@ impl WireGuardConfig {
         // Temporary storage for current peer being parsed
         let mut current_peer: Option<PeerBuilder> = None;
 
+        // Single-valued keys seen in the current section (key -> first line)
+        let mut seen_keys: HashMap<String, usize> = HashMap::new();
+
         for (line_num, line) in content.lines().enumerate() {
             let line_num = line_num + 1; // 1-indexed
-            let line = line.trim();
+            // Strip comments (whole-line or trailing, as wg-quick does)
+            let line = line.split('#').next().unwrap_or_default().trim();
 
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
+            // Skip empty lines and INI-style `;` comments (whole-line only: `;`
+            // appears inside values such as wg-quick PostUp commands)
+            if line.is_empty() || line.starts_with(';') {
                 continue;
             }
 
             // Check for section headers
-            if line.eq_ignore_ascii_case("[interface]") {
+            let section = section_name(line);
+            if section.is_some_and(|name| name.eq_ignore_ascii_case("interface")) {
                 // Save any pending peer
                 if let Some(peer) = current_peer.take() {
                     peers.push(peer.build()?);
                 }
                 current_section = Some(Section::Interface);
+                seen_keys.clear();
                 continue;
-            } else if line.eq_ignore_ascii_case("[peer]") {
+            } else if section.is_some_and(|name| name.eq_ignore_ascii_case("peer")) {
                 // Save any pending peer
                 if let Some(peer) = current_peer.take() {
                     peers.push(peer.build()?);
                 }
+                if peers.len() >= limits.max_peers {
+                    return Err(ConfigError::LimitExceeded {
+                        what: format!("too many [Peer] sections at line {}", line_num),
+                        limit: limits.max_peers,
+                    });
+                }
                 current_section = Some(Section::Peer);
                 current_peer = Some(PeerBuilder::new());
+                seen_keys.clear();
                 continue;
             }
 
@@ -112,13 +356,25 @@ impl WireGuardConfig {
             let Some((key, value)) = line.split_once('=') else {
                 return Err(ConfigError::ParseError {
                     line: line_num,
-                    message: format!("Expected 'key = value', got: {}", line),
+                    message: "Expected 'key = value'".to_string(),
                 });
             };
 
-            let key = key.trim().to_lowercase();
+            let raw_key = key.trim();
+            let key = raw_key.to_lowercase();
             let value = value.trim();
 
+            // A repeated single-valued key is almost always a copy-paste error
+            if current_section.is_some() && is_single_valued_key(&key) {
+                if let Some(first_line) = seen_keys.get(&key) {
+                    return Err(ConfigError::ParseError {
+                        line: line_num,
+                        message: format!("Duplicate {} (already set on line {})", raw_key, first_line),
+                    });
+                }
+                seen_keys.insert(key.clone(), line_num);
+            }
+
             match current_section {
                 Some(Section::Interface) => {
                     let iface = interface.get_or_insert_with(|| InterfaceConfig {
@@ -126,8 +382,16 @@ impl WireGuardConfig {
                         address: Vec::new(),
                         dns: Vec::new(),
                         listen_port: None,
+                        bind_address: None,
                         mtu: None,
-                        preshared_key: None,
+                        fwmark: None,
+                        replicate_broadcast: false,
+                        tun_name: None,
+                        max_sessions: None,
+                        handshake_sources: Vec::new(),
+                        idle_timeout: None,
+                        handshake_rate_limit: None,
+                        table: None,
                     });
 
                     match key.as_str() {
@@ -141,13 +405,7 @@ impl WireGuardConfig {
                                 if addr_str.is_empty() {
                                     continue;
                                 }
-                                // Parse as IpNet first, then extract Ipv4Net
-                                let ip_net: IpNet = addr_str.parse().map_err(|_| ConfigError::InvalidCidr {
-                                    value: addr_str.to_string(),
-                                })?;
-                                if let IpNet::V4(v4net) = ip_net {
-                                    iface.address.push(v4net);
-                                }
+                                iface.address.push(parse_cidr(addr_str)?);
                             }
                         }
                         "dns" => {
@@ -168,12 +426,75 @@ impl WireGuardConfig {
                                 }
                             })?);
                         }
+                        "bindaddress" => {
+                            iface.bind_address = Some(value.parse().map_err(|_| {
+                                ConfigError::InvalidAddress {
+                                    value: value.to_string(),
+                                }
+                            })?);
+                        }
                         "mtu" => {
-                            iface.mtu =
-                                Some(value.parse().map_err(|_| ConfigError::ParseError {
+                            let mtu = value.parse().map_err(|_| ConfigError::ParseError {
+                                line: line_num,
+                                message: format!("Invalid MTU: {}", value),
+                            })?;
+                            check_mtu(mtu)?;
+                            iface.mtu = Some(mtu);
+                        }
+                        "replicatebroadcast" => {
+                            iface.replicate_broadcast = parse_bool(value).ok_or_else(|| {
+                                ConfigError::ParseError {
                                     line: line_num,
-                                    message: format!("Invalid MTU: {}", value),
-                                })?);
+                                    message: format!("Invalid ReplicateBroadcast: {}", value),
+                                }
+                            })?;
+                        }
+                        "maxsessions" => {
+                            iface.max_sessions = Some(
+                                value.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                                    ConfigError::ParseError {
+                                        line: line_num,
+                                        message: format!("Invalid MaxSessions: {}", value),
+                                    }
+                                })?,
+                            );
+                        }
+                        "handshakeratelimit" => {
+                            iface.handshake_rate_limit = Some(
+                                value.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                                    ConfigError::ParseError {
+                                        line: line_num,
+                                        message: format!("Invalid HandshakeRateLimit: {}", value),
+                                    }
+                                })?,
+                            );
+                        }
+                        "idletimeout" => {
+                            let secs: u32 = value.parse().map_err(|_| ConfigError::ParseError {
+                                line: line_num,
+                                message: format!("Invalid IdleTimeout: {}", value),
+                            })?;
+                            // 0 = off, like PersistentKeepalive
+                            iface.idle_timeout = (secs > 0).then_some(secs);
+                        }
+                        "handshakesources" => {
+                            for cidr in value.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                                iface.handshake_sources.push(parse_cidr(cidr)?);
+                            }
+                        }
+                        "table" => {
+                            iface.table = Some(value.parse().map_err(|_| ConfigError::ParseError {
+                                line: line_num,
+                                message: format!("Invalid Table: {}", value),
+                            })?);
+                        }
+                        "fwmark" => {
+                            iface.fwmark = parse_fwmark(value).ok_or_else(|| {
+                                ConfigError::ParseError {
+                                    line: line_num,
+                                    message: format!("Invalid FwMark: {}", value),
+                                }
+                            })?;
                         }
                         _ => {
                             // Unknown key, ignore (forward compatibility)
@@ -194,7 +515,17 @@ impl WireGuardConfig {
                             peer.preshared_key = Some(parse_key(value, "PresharedKey")?);
                         }
                         "endpoint" => {
-                            peer.endpoint = Some(parse_endpoint(value)?);
+                            let endpoint = parse_endpoint(value)?;
+                            if endpoint.port() == 0 {
+                                tracing::warn!(
+                                    "Endpoint {} at line {} uses port 0; packets to it will not arrive",
+                                    value, line_num
+                                );
+                            }
+                            (peer.endpoint, peer.endpoint_host) = match endpoint {
+                                ParsedEndpoint::Addr(addr) => (Some(addr), None),
+                                ParsedEndpoint::Host { .. } => (None, Some(value.to_string())),
+                            };
                         }
                         "allowedips" => {
                             for ip_str in value.split(',') {
@@ -202,11 +533,13 @@ impl WireGuardConfig {
                                 if ip_str.is_empty() {
                                     continue;
                                 }
-                                let ip: IpNet =
-                                    ip_str.parse().map_err(|_| ConfigError::InvalidCidr {
-                                        value: ip_str.to_string(),
-                                    })?;
-                                peer.allowed_ips.push(ip);
+                                if peer.allowed_ips.len() >= limits.max_allowed_ips_per_peer {
+                                    return Err(ConfigError::LimitExceeded {
+                                        what: format!("too many AllowedIPs for one peer at line {}", line_num),
+                                        limit: limits.max_allowed_ips_per_peer,
+                                    });
+                                }
+                                peer.allowed_ips.push(parse_cidr(ip_str)?);
                             }
                         }
                         "persistentkeepalive" => {
@@ -216,6 +549,25 @@ impl WireGuardConfig {
                                     message: format!("Invalid PersistentKeepalive: {}", value),
                                 })?);
                         }
+                        "handshakeinterval" => {
+                            let secs: u16 = if value.eq_ignore_ascii_case("off") {
+                                0
+                            } else {
+                                value.parse().map_err(|_| ConfigError::ParseError {
+                                    line: line_num,
+                                    message: format!("Invalid HandshakeInterval: {}", value),
+                                })?
+                            };
+                            peer.handshake_interval = (secs > 0).then_some(secs);
+                        }
+                        "lockendpoint" => {
+                            peer.lock_endpoint = parse_bool(value).ok_or_else(|| {
+                                ConfigError::ParseError {
+                                    line: line_num,
+                                    message: format!("Invalid LockEndpoint: {}", value),
+                                }
+                            })?;
+                        }
                         _ => {
                             // Unknown key, ignore (forward compatibility)
                         }
@@ -235,29 +587,201 @@ impl WireGuardConfig {
             peers.push(peer.build()?);
         }
 
-        let mut interface = interface.ok_or(ConfigError::MissingField {
-            field: "[Interface] section".to_string(),
+        Ok((interface, peers))
+    }
+
+    /// Merge peers from a directory of `*.peer` files (one [Peer] section each)
+    ///
+    /// Files are loaded in name order; other files are ignored. Returns the number of
+    /// peers added. Duplicate public keys and the usual config limits are rejected.
+    pub fn merge_peers_from<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize, ConfigError> {
+        let limits = ConfigLimits::default();
+        let dir = dir.as_ref();
+
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConfigError::FileNotFound {
+                    path: dir.display().to_string(),
+                }
+            } else {
+                ConfigError::Io(e)
+            }
         })?;
 
-        // Validate interface has required fields
-        if interface.private_key == [0u8; 32] {
-            return Err(ConfigError::MissingField {
-                field: "PrivateKey".to_string(),
-            });
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "peer"))
+            .collect();
+        paths.sort();
+
+        let own_public_key = self.public_key();
+        let mut added = 0;
+
+        for path in paths {
+            let in_file = |source: ConfigError| ConfigError::InFile {
+                path: path.display().to_string(),
+                source: Box::new(source),
+            };
+
+            let content = super::read_stable(&path).map_err(|e| in_file(ConfigError::Io(e)))?;
+            if content.len() > limits.max_config_size {
+                return Err(in_file(ConfigError::LimitExceeded {
+                    what: format!("config size of {} bytes", content.len()),
+                    limit: limits.max_config_size,
+                }));
+            }
+
+            let (interface, mut peers) =
+                Self::parse_sections(&content, &limits).map_err(in_file)?;
+            if interface.is_some() || peers.len() != 1 {
+                return Err(in_file(ConfigError::ParseError {
+                    line: 0,
+                    message: "Peer file must contain exactly one [Peer] section and no [Interface]"
+                        .to_string(),
+                }));
+            }
+
+            let peer = peers.remove(0);
+            if peer.public_key == own_public_key {
+                return Err(in_file(ConfigError::OwnKeyAsPeer { peer: self.peers.len() + 1 }));
+            }
+            if self.peers.iter().any(|p| p.public_key == peer.public_key) {
+                return Err(in_file(ConfigError::ParseError {
+                    line: 0,
+                    message: "Duplicate peer PublicKey".to_string(),
+                }));
+            }
+            if self.peers.len() >= limits.max_peers {
+                return Err(in_file(ConfigError::LimitExceeded {
+                    what: "too many peers".to_string(),
+                    limit: limits.max_peers,
+                }));
+            }
+
+            tracing::debug!("Loaded peer from {:?}", path);
+            self.peers.push(peer);
+            added += 1;
         }
 
-        // Copy PSK from first peer to interface for convenience
-        if let Some(peer) = peers.first() {
-            interface.preshared_key = peer.preshared_key;
+        Ok(added)
+    }
+
+    /// Check the config can run in client mode (at least one peer, first has an Endpoint)
+    pub fn validate_client(&self) -> Result<(), ConfigError> {
+        let peer = self.peers.first().ok_or_else(|| ConfigError::NoPeers {
+            hint: "client mode needs a [Peer] with an Endpoint".to_string(),
+        })?;
+        if !peer.has_endpoint() {
+            return Err(ConfigError::NoPeerEndpoint);
         }
+        Ok(())
+    }
 
-        Ok(WireGuardConfig { interface, peers })
+    /// Check the config can run in server mode
+    ///
+    /// Zero peers is only allowed when peers can be added at runtime (`dynamic_peers`),
+    /// otherwise the server would start with nothing to route.
+    pub fn validate_server(&self, dynamic_peers: bool) -> Result<(), ConfigError> {
+        if self.peers.is_empty() && !dynamic_peers {
+            return Err(ConfigError::NoPeers {
+                hint: "server has nothing to route; add [Peer] sections or use daemon mode \
+                       to add peers at runtime"
+                    .to_string(),
+            });
+        }
+        if self.interface.listen_port == Some(0) {
+            return Err(ConfigError::ZeroListenPort);
+        }
+        Ok(())
     }
 
     /// Get our public key derived from the private key
     pub fn public_key(&self) -> [u8; 32] {
         crate::crypto::x25519::public_key(&self.interface.private_key)
     }
+
+    /// Write the config as `.conf` text (atomically, owner-only: it holds the private key)
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        super::write_atomic(path.as_ref(), self.to_string().as_bytes(), Some(0o600))
+    }
+}
+
+/// Renders `.conf` text that [`WireGuardConfig::parse`] reads back to an equal config
+///
+/// Only settings that are present are written. `tun_name` is not a config key
+/// (it comes from `--interface`) and is left out.
+impl fmt::Display for WireGuardConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let iface = &self.interface;
+        writeln!(f, "[Interface]")?;
+        writeln!(f, "PrivateKey = {}", STANDARD.encode(iface.private_key))?;
+        if !iface.address.is_empty() {
+            writeln!(f, "Address = {}", join(&iface.address))?;
+        }
+        if !iface.dns.is_empty() {
+            writeln!(f, "DNS = {}", join(&iface.dns))?;
+        }
+        if let Some(port) = iface.listen_port {
+            writeln!(f, "ListenPort = {}", port)?;
+        }
+        if let Some(address) = iface.bind_address {
+            writeln!(f, "BindAddress = {}", address)?;
+        }
+        if let Some(mtu) = iface.mtu {
+            writeln!(f, "MTU = {}", mtu)?;
+        }
+        if let Some(mark) = iface.fwmark {
+            writeln!(f, "FwMark = {:#x}", mark)?;
+        }
+        if iface.replicate_broadcast {
+            writeln!(f, "ReplicateBroadcast = on")?;
+        }
+        if let Some(max) = iface.max_sessions {
+            writeln!(f, "MaxSessions = {}", max)?;
+        }
+        if !iface.handshake_sources.is_empty() {
+            writeln!(f, "HandshakeSources = {}", join(&iface.handshake_sources))?;
+        }
+        if let Some(secs) = iface.idle_timeout {
+            writeln!(f, "IdleTimeout = {}", secs)?;
+        }
+        if let Some(rate) = iface.handshake_rate_limit {
+            writeln!(f, "HandshakeRateLimit = {}", rate)?;
+        }
+        if let Some(table) = iface.table {
+            writeln!(f, "Table = {}", table)?;
+        }
+
+        for peer in &self.peers {
+            writeln!(f)?;
+            writeln!(f, "[Peer]")?;
+            writeln!(f, "PublicKey = {}", STANDARD.encode(peer.public_key))?;
+            if let Some(psk) = peer.preshared_key {
+                writeln!(f, "PresharedKey = {}", STANDARD.encode(psk))?;
+            }
+            if let Some(endpoint) = peer.endpoint_string() {
+                writeln!(f, "Endpoint = {}", endpoint)?;
+            }
+            if !peer.allowed_ips.is_empty() {
+                writeln!(f, "AllowedIPs = {}", join(&peer.allowed_ips))?;
+            }
+            if let Some(secs) = peer.persistent_keepalive {
+                writeln!(f, "PersistentKeepalive = {}", secs)?;
+            }
+            if let Some(secs) = peer.handshake_interval {
+                writeln!(f, "HandshakeInterval = {}", secs)?;
+            }
+            if peer.lock_endpoint {
+                writeln!(f, "LockEndpoint = on")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated list for repeatable keys (`Address = a, b`)
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
 }
 
 /// Section type during parsing
@@ -272,8 +796,11 @@ struct PeerBuilder {
     public_key: Option<[u8; 32]>,
     preshared_key: Option<[u8; 32]>,
     endpoint: Option<SocketAddr>,
+    endpoint_host: Option<String>,
     allowed_ips: Vec<IpNet>,
     persistent_keepalive: Option<u16>,
+    handshake_interval: Option<u16>,
+    lock_endpoint: bool,
 }
 
 impl PeerBuilder {
@@ -282,8 +809,11 @@ impl PeerBuilder {
             public_key: None,
             preshared_key: None,
             endpoint: None,
+            endpoint_host: None,
             allowed_ips: Vec::new(),
             persistent_keepalive: None,
+            handshake_interval: None,
+            lock_endpoint: false,
         }
     }
 
@@ -296,16 +826,65 @@ impl PeerBuilder {
             public_key,
             preshared_key: self.preshared_key,
             endpoint: self.endpoint,
+            endpoint_host: self.endpoint_host,
             allowed_ips: self.allowed_ips,
             persistent_keepalive: self.persistent_keepalive,
+            handshake_interval: self.handshake_interval,
+            lock_endpoint: self.lock_endpoint,
         })
     }
 }
 
+/// Check if a (lowercased) key may appear only once per section
+///
+/// Address, DNS and AllowedIPs are repeatable; unknown keys (e.g. wg-quick's
+/// PostUp) are not checked.
+fn is_single_valued_key(key: &str) -> bool {
+    matches!(
+        key,
+        "privatekey"
+            | "listenport"
+            | "bindaddress"
+            | "mtu"
+            | "fwmark"
+            | "replicatebroadcast"
+            | "maxsessions"
+            | "idletimeout"
+            | "handshakeratelimit"
+            | "table"
+            | "publickey"
+            | "presharedkey"
+            | "endpoint"
+            | "persistentkeepalive"
+            | "handshakeinterval"
+            | "lockendpoint"
+    )
+}
+
+/// Name inside a `[Section]` header, tolerating inner spaces (`[ Peer ]`)
+fn section_name(line: &str) -> Option<&str> {
+    line.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+/// Parse a CIDR, treating a bare IP as a single host (/32 or /128) like wg-quick
+fn parse_cidr(value: &str) -> Result<IpNet, ConfigError> {
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| ConfigError::InvalidCidr {
+            value: value.to_string(),
+        })
+}
+
 /// Parse a base64-encoded 32-byte key
-fn parse_key(value: &str, field_name: &str) -> Result<[u8; 32], ConfigError> {
-    let bytes = BASE64
-        .decode(value)
+///
+/// Standard base64 is tried first, then the URL-safe alphabet (`-`/`_`) some
+/// tools emit. Trailing `=` padding is optional either way.
+pub(crate) fn parse_key(value: &str, field_name: &str) -> Result<[u8; 32], ConfigError> {
+    let unpadded = value.trim_end_matches('=');
+    let bytes = STANDARD_NO_PAD
+        .decode(unpadded)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(unpadded))
         .map_err(|_| ConfigError::InvalidKey {
             field: field_name.to_string(),
         })?;
@@ -321,30 +900,73 @@ fn parse_key(value: &str, field_name: &str) -> Result<[u8; 32], ConfigError> {
     Ok(key)
 }
 
-/// Parse an endpoint (host:port) - supports both IP addresses and hostnames
-fn parse_endpoint(value: &str) -> Result<SocketAddr, ConfigError> {
-    // Try to parse as IP:port first
-    if let Ok(addr) = value.parse::<SocketAddr>() {
-        return Ok(addr);
+/// Parse a boolean option (`true`/`false`, `on`/`off`, `yes`/`no`, `1`/`0`)
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" | "1" => Some(true),
+        "false" | "off" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse an FwMark value (decimal, `0x` hex, or `off`)
+///
+/// Returns `Some(None)` when the mark is disabled (`off` or `0`) and `None` if the
+/// value is not a valid mark.
+fn parse_fwmark(value: &str) -> Option<Option<u32>> {
+    if value.eq_ignore_ascii_case("off") {
+        return Some(None);
     }
 
-    // If that fails, try DNS resolution for hostname:port
-    match value.to_socket_addrs() {
-        Ok(mut addrs) => {
-            // Use the first resolved address
-            addrs.next().ok_or_else(|| ConfigError::InvalidAddress {
-                value: value.to_string(),
-            })
+    let mark = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+
+    Some(if mark == 0 { None } else { Some(mark) })
+}
+
+/// An Endpoint value: a literal address, or a hostname left for connect time
+enum ParsedEndpoint {
+    Addr(SocketAddr),
+    Host { port: u16 },
+}
+
+impl ParsedEndpoint {
+    fn port(&self) -> u16 {
+        match self {
+            ParsedEndpoint::Addr(addr) => addr.port(),
+            ParsedEndpoint::Host { port } => *port,
         }
-        Err(_) => Err(ConfigError::InvalidAddress {
-            value: value.to_string(),
-        }),
     }
 }
 
+/// Parse an endpoint: `IP:port` (IPv6 in brackets) or `hostname:port`
+///
+/// Hostnames are only checked for syntax here; nothing is resolved while parsing.
+fn parse_endpoint(value: &str) -> Result<ParsedEndpoint, ConfigError> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(ParsedEndpoint::Addr(addr));
+    }
+
+    let invalid = || ConfigError::InvalidAddress {
+        value: value.to_string(),
+    };
+    let (host, port) = value.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    // Brackets are only for IPv6 literals, and a bare IPv6 address has no port
+    let valid_host = !host.is_empty()
+        && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_'));
+    if !valid_host {
+        return Err(invalid());
+    }
+    Ok(ParsedEndpoint::Host { port })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
 
     const TEST_CONFIG: &str = r#"
 [Interface]
@@ -375,6 +997,56 @@ PersistentKeepalive = 25
         assert_eq!(peer.endpoint.unwrap().to_string(), "13.239.46.151:51820");
         assert_eq!(peer.persistent_keepalive, Some(25));
         assert_eq!(peer.allowed_ips.len(), 2);
+        assert_eq!(peer.handshake_interval, None);
+        assert!(!peer.lock_endpoint);
+    }
+
+    #[test]
+    fn test_parse_comments_and_section_headers() {
+        let config = TEST_CONFIG
+            .replace("[Interface]", "; INI-style comment\n[ interface ]")
+            .replace("[Peer]", "[\tPEER ] # trailing comment")
+            .replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\n  ; indented comment");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.interface.dns.len(), 1);
+        assert_eq!(config.peers.len(), 1);
+
+        // Unknown sections are still rejected
+        let config = TEST_CONFIG.replace("[Peer]", "[ Peers ]");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_lock_endpoint() {
+        let config = TEST_CONFIG.replace(
+            "PersistentKeepalive = 25",
+            "PersistentKeepalive = 25\nLockEndpoint = on",
+        );
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert!(config.peers[0].lock_endpoint);
+
+        let config = TEST_CONFIG.replace(
+            "PersistentKeepalive = 25",
+            "PersistentKeepalive = 25\nLockEndpoint = maybe",
+        );
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake_interval() {
+        let config = TEST_CONFIG.replace(
+            "PersistentKeepalive = 25",
+            "PersistentKeepalive = 25\nHandshakeInterval = 30",
+        );
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.peers[0].handshake_interval, Some(30));
+
+        let config = TEST_CONFIG.replace(
+            "PersistentKeepalive = 25",
+            "PersistentKeepalive = 25\nHandshakeInterval = off",
+        );
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.peers[0].handshake_interval, None);
     }
 
     #[test]
@@ -391,6 +1063,470 @@ PersistentKeepalive = 25
 
         let result = parse_key("dG9vIHNob3J0", "TestKey"); // "too short" in base64
         assert!(result.is_err());
+
+        // Mixing both alphabets is not a key from any tool
+        assert!(parse_key("UOvtcWdILFwjb1UnsnK-a9lcqYvNTmtPv+fvqIVOz3w=", "TestKey").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_url_safe_and_unpadded() {
+        let expected = parse_key("UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=", "TestKey").unwrap();
+
+        let url_safe = "UOvtcWdILFwjb1UnsnK-a9lcqYvNTmtPv-fvqIVOz3w=";
+        assert_eq!(parse_key(url_safe, "TestKey").unwrap(), expected);
+        assert_eq!(parse_key(url_safe.trim_end_matches('='), "TestKey").unwrap(), expected);
+
+        let unpadded = "UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w";
+        assert_eq!(parse_key(unpadded, "TestKey").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_fwmark() {
+        assert_eq!(parse_fwmark("51820"), Some(Some(51820)));
+        assert_eq!(parse_fwmark("0xca6c"), Some(Some(0xca6c)));
+        assert_eq!(parse_fwmark("off"), Some(None));
+        assert_eq!(parse_fwmark("0"), Some(None));
+        assert_eq!(parse_fwmark("bogus"), None);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nFwMark = 0xca6c");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.interface.fwmark, Some(51820));
+    }
+
+    #[test]
+    fn test_config_limits() {
+        let limits = ConfigLimits {
+            max_config_size: TEST_CONFIG.len(),
+            max_peers: 1,
+            max_allowed_ips_per_peer: 2,
+        };
+        assert!(WireGuardConfig::parse_with_limits(TEST_CONFIG, &limits).is_ok());
+
+        // Too large
+        let small = ConfigLimits { max_config_size: 64, ..limits };
+        let result = WireGuardConfig::parse_with_limits(TEST_CONFIG, &small);
+        assert!(matches!(result, Err(ConfigError::LimitExceeded { limit: 64, .. })));
+
+        // Too many AllowedIPs
+        let one_ip = ConfigLimits { max_allowed_ips_per_peer: 1, ..limits };
+        let result = WireGuardConfig::parse_with_limits(TEST_CONFIG, &one_ip);
+        assert!(matches!(result, Err(ConfigError::LimitExceeded { limit: 1, .. })));
+
+        // Too many peers
+        let two_peers = format!(
+            "{}\n[Peer]\nPublicKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n",
+            TEST_CONFIG
+        );
+        let limits = ConfigLimits { max_config_size: two_peers.len(), ..limits };
+        let result = WireGuardConfig::parse_with_limits(&two_peers, &limits);
+        assert!(matches!(result, Err(ConfigError::LimitExceeded { limit: 1, .. })));
+    }
+
+    #[test]
+    fn test_own_public_key_as_peer() {
+        let private_key = parse_key("UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=", "PrivateKey").unwrap();
+        let own_public = BASE64.encode(crate::crypto::x25519::public_key(&private_key));
+
+        let config = TEST_CONFIG.replace("YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=", &own_public);
+        let result = WireGuardConfig::parse(&config);
+        assert!(matches!(result, Err(ConfigError::OwnKeyAsPeer { peer: 1 })));
+    }
+
+    #[test]
+    fn test_merge_peers_from() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("b.peer"),
+            "[Peer]\nPublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\nAllowedIPs = 10.0.0.4/32\n",
+        ).unwrap();
+        std::fs::write(
+            dir.path().join("a.peer"),
+            "[Peer]\nPublicKey = HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\nAllowedIPs = 10.0.0.3/32\n",
+        ).unwrap();
+        std::fs::write(dir.path().join("README.txt"), "not a peer").unwrap();
+
+        let mut config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.merge_peers_from(dir.path()).unwrap(), 2);
+        assert_eq!(config.peers.len(), 3);
+        // Loaded in file name order
+        assert_eq!(config.peers[1].allowed_ips[0].to_string(), "10.0.0.3/32");
+        assert_eq!(config.peers[2].allowed_ips[0].to_string(), "10.0.0.4/32");
+
+        // Same peers again are duplicates
+        let result = config.merge_peers_from(dir.path());
+        assert!(matches!(result, Err(ConfigError::InFile { .. })));
+    }
+
+    #[test]
+    fn test_validate_no_peers() {
+        let config = WireGuardConfig::parse(
+            "[Interface]\nPrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\nListenPort = 51820\n",
+        ).unwrap();
+        assert!(config.peers.is_empty());
+
+        assert!(matches!(config.validate_client(), Err(ConfigError::NoPeers { .. })));
+        assert!(matches!(config.validate_server(false), Err(ConfigError::NoPeers { .. })));
+        assert!(config.validate_server(true).is_ok());
+
+        let config = WireGuardConfig::parse(&TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820\n", "")).unwrap();
+        assert!(matches!(config.validate_client(), Err(ConfigError::NoPeerEndpoint)));
+        assert!(config.validate_server(false).is_ok());
+
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert!(config.validate_client().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_endpoint() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        let literal = config.peers[0].resolve_endpoint().await.unwrap();
+        assert_eq!(literal, vec!["13.239.46.151:51820".parse().unwrap()]);
+
+        let config =
+            WireGuardConfig::parse(&TEST_CONFIG.replace("13.239.46.151:51820", "localhost:51820")).unwrap();
+        let resolved = config.peers[0].resolve_endpoint().await.unwrap();
+        assert!(!resolved.is_empty());
+        assert!(resolved.iter().all(|addr| addr.ip().is_loopback() && addr.port() == 51820));
+
+        let config = WireGuardConfig::parse(&TEST_CONFIG.replace("13.239.46.151:51820", "nonexistent.invalid:51820"))
+            .unwrap();
+        assert!(matches!(
+            config.peers[0].resolve_endpoint().await,
+            Err(NetworkError::DnsResolutionFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_zero_ports() {
+        let server = "[Interface]\nPrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\nListenPort = 0\n";
+        let config = WireGuardConfig::parse(server).unwrap();
+        assert!(matches!(config.validate_server(true), Err(ConfigError::ZeroListenPort)));
+
+        let config = WireGuardConfig::parse(&server.replace("ListenPort = 0", "ListenPort = 51820")).unwrap();
+        assert!(config.validate_server(true).is_ok());
+
+        // Endpoint port 0 only warns, so the config still parses
+        let config = WireGuardConfig::parse(&TEST_CONFIG.replace("13.239.46.151:51820", "13.239.46.151:0")).unwrap();
+        assert_eq!(config.peers[0].endpoint.unwrap().port(), 0);
+    }
+
+    #[test]
+    fn test_parse_replicate_broadcast() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert!(!config.interface.replicate_broadcast);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nReplicateBroadcast = on");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert!(config.interface.replicate_broadcast);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nReplicateBroadcast = maybe");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_mtu_range() {
+        let with_mtu = |mtu: &str| TEST_CONFIG.replace("DNS = 8.8.8.8", &format!("DNS = 8.8.8.8\nMTU = {}", mtu));
+
+        assert_eq!(WireGuardConfig::parse(&with_mtu("1280")).unwrap().interface.mtu, Some(1280));
+        assert_eq!(WireGuardConfig::parse(&with_mtu("68")).unwrap().interface.mtu, Some(MIN_MTU));
+
+        assert!(matches!(
+            WireGuardConfig::parse(&with_mtu("20")),
+            Err(ConfigError::InvalidMtu { mtu: 20, min: 68, .. })
+        ));
+        assert!(matches!(
+            WireGuardConfig::parse(&with_mtu("65535")),
+            Err(ConfigError::InvalidMtu { mtu: 65535, .. })
+        ));
+        assert_eq!(MAX_MTU, 65455);
+    }
+
+    #[test]
+    fn test_parse_max_sessions() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.interface.max_sessions, None);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nMaxSessions = 16");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.interface.max_sessions, Some(16));
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nMaxSessions = 0");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake_rate_limit() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.interface.handshake_rate_limit, None);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nHandshakeRateLimit = 5");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.interface.handshake_rate_limit, Some(5));
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nHandshakeRateLimit = 0");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_bind_address() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.interface.bind_address, None);
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nBindAddress = fd00::7");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.interface.bind_address, Some("fd00::7".parse().unwrap()));
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nBindAddress = eth0");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.interface.table, None);
+
+        for (value, table) in [("off", RouteTable::Off), ("Auto", RouteTable::Auto), ("1234", RouteTable::Id(1234))] {
+            let config = TEST_CONFIG.replace("DNS = 8.8.8.8", &format!("DNS = 8.8.8.8\nTable = {}", value));
+            let config = WireGuardConfig::parse(&config).unwrap();
+            assert_eq!(config.interface.table, Some(table));
+        }
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nTable = main");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_idle_timeout() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.interface.idle_timeout, None);
+
+        let with_timeout = |value: &str| TEST_CONFIG.replace("DNS = 8.8.8.8", &format!("DNS = 8.8.8.8\nIdleTimeout = {}", value));
+        assert_eq!(WireGuardConfig::parse(&with_timeout("600")).unwrap().interface.idle_timeout, Some(600));
+        assert_eq!(WireGuardConfig::parse(&with_timeout("0")).unwrap().interface.idle_timeout, None);
+        assert!(WireGuardConfig::parse(&with_timeout("soon")).is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake_sources() {
+        let config = WireGuardConfig::parse(TEST_CONFIG).unwrap();
+        assert!(config.interface.handshake_sources.is_empty());
+        assert!(config.interface.accepts_handshake_from("203.0.113.9".parse().unwrap()));
+
+        let config = TEST_CONFIG.replace(
+            "DNS = 8.8.8.8",
+            "DNS = 8.8.8.8\nHandshakeSources = 192.0.2.0/24\nHandshakeSources = 2001:db8::/32",
+        );
+        let iface = WireGuardConfig::parse(&config).unwrap().interface;
+        assert_eq!(iface.handshake_sources.len(), 2);
+        assert!(iface.accepts_handshake_from("192.0.2.77".parse().unwrap()));
+        assert!(iface.accepts_handshake_from("::ffff:192.0.2.77".parse().unwrap()));
+        assert!(iface.accepts_handshake_from("2001:db8::1".parse().unwrap()));
+        assert!(!iface.accepts_handshake_from("203.0.113.9".parse().unwrap()));
+
+        let config = TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nHandshakeSources = 192.0.2.0/33");
+        assert!(WireGuardConfig::parse(&config).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_single_valued_key() {
+        let config = TEST_CONFIG.replace(
+            "Address = 10.0.0.2/24",
+            "Address = 10.0.0.2/24\nprivatekey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=",
+        );
+        match WireGuardConfig::parse(&config) {
+            Err(ConfigError::ParseError { line, message }) => {
+                assert_eq!(line, 5);
+                assert!(message.contains("privatekey"), "{}", message);
+                assert!(message.contains("line 3"), "{}", message);
+            }
+            other => panic!("expected duplicate key error, got {:?}", other),
+        }
+
+        let config = TEST_CONFIG.replace(
+            "PersistentKeepalive = 25",
+            "PersistentKeepalive = 25\nEndpoint = 10.9.9.9:51820",
+        );
+        assert!(matches!(
+            WireGuardConfig::parse(&config),
+            Err(ConfigError::ParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_repeatable_keys_allowed() {
+        let config = TEST_CONFIG
+            .replace("AllowedIPs = 10.0.0.0/24, 0.0.0.0/0", "AllowedIPs = 10.0.0.0/24\nAllowedIPs = 0.0.0.0/0")
+            .replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nDNS = 1.1.1.1\nAddress = 10.0.1.2/24");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        assert_eq!(config.peers[0].allowed_ips.len(), 2);
+        assert_eq!(config.interface.dns.len(), 2);
+        assert_eq!(config.interface.address.len(), 2);
+
+        // Same single-valued key in different sections is fine
+        let two_peers = format!(
+            "{}\n[Peer]\nPublicKey = BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=\nAllowedIPs = 10.0.2.0/24\n",
+            TEST_CONFIG
+        );
+        assert_eq!(WireGuardConfig::parse(&two_peers).unwrap().peers.len(), 2);
+    }
+
+    #[test]
+    fn test_multiple_address_lines() {
+        let config = TEST_CONFIG.replace(
+            "Address = 10.0.0.2/24",
+            "Address = 10.0.0.2/24, 10.0.1.2/24\nAddress = 192.168.50.1/32",
+        );
+        let config = WireGuardConfig::parse(&config).unwrap();
+        let addresses: Vec<String> = config.interface.addresses().iter().map(|a| a.to_string()).collect();
+        assert_eq!(addresses, vec!["10.0.0.2/24", "10.0.1.2/24", "192.168.50.1/32"]);
+    }
+
+    #[test]
+    fn test_dual_stack_address() {
+        let config = TEST_CONFIG.replace("Address = 10.0.0.2/24", "Address = fd00::2/64, 10.0.0.2/24");
+        let config = WireGuardConfig::parse(&config).unwrap();
+        let addresses: Vec<String> = config.interface.addresses().iter().map(|a| a.to_string()).collect();
+        assert_eq!(addresses, vec!["fd00::2/64", "10.0.0.2/24"]);
+        assert_eq!(config.interface.ipv4_address().unwrap().to_string(), "10.0.0.2/24");
+
+        let v6_only = TEST_CONFIG.replace("Address = 10.0.0.2/24", "Address = fd00::2/64");
+        assert!(WireGuardConfig::parse(&v6_only).unwrap().interface.ipv4_address().is_none());
+    }
+
+    /// Expected outcome of a table-driven parse case
+    enum Expect {
+        Ok(fn(&WireGuardConfig)),
+        Err,
+    }
+
+    #[test]
+    fn test_real_world_configs() {
+        let cases: Vec<(&str, String, Expect)> = vec![
+            (
+                "crlf line endings",
+                TEST_CONFIG.replace('\n', "\r\n"),
+                Expect::Ok(|c| {
+                    assert_eq!(c.interface.address[0].to_string(), "10.0.0.2/24");
+                    assert_eq!(c.interface.dns[0].to_string(), "8.8.8.8");
+                    assert_eq!(c.peers[0].endpoint.unwrap().to_string(), "13.239.46.151:51820");
+                    assert_eq!(c.peers[0].persistent_keepalive, Some(25));
+                }),
+            ),
+            (
+                "tabs around keys and values",
+                "[Interface]\n\tPrivateKey\t=\tUOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+                 Address\t= 10.0.0.2/24\n[Peer]\nPublicKey =\tYgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\t\n\
+                 AllowedIPs = 10.0.0.0/24,\t0.0.0.0/0\n"
+                    .to_string(),
+                Expect::Ok(|c| assert_eq!(c.peers[0].allowed_ips.len(), 2)),
+            ),
+            (
+                "mixed-case keys and sections",
+                "[interface]\nprivatekey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\nADDRESS = 10.0.0.2/24\n\
+                 [PEER]\npublicKEY = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\nAllowedIps = 0.0.0.0/0\n\
+                 EndPoint = 13.239.46.151:51820\n"
+                    .to_string(),
+                Expect::Ok(|c| assert!(c.peers[0].endpoint.is_some())),
+            ),
+            (
+                "trailing and indented comments",
+                TEST_CONFIG
+                    .replace("Address = 10.0.0.2/24", "  # home network\nAddress = 10.0.0.2/24 # office")
+                    .replace("Endpoint = 13.239.46.151:51820", "Endpoint = 13.239.46.151:51820#primary"),
+                Expect::Ok(|c| {
+                    assert_eq!(c.interface.address[0].to_string(), "10.0.0.2/24");
+                    assert_eq!(c.peers[0].endpoint.unwrap().port(), 51820);
+                }),
+            ),
+            (
+                "multiple addresses",
+                TEST_CONFIG.replace("Address = 10.0.0.2/24", "Address = 10.0.0.2/24,10.0.1.2/24\nAddress = 10.0.2.2/24"),
+                Expect::Ok(|c| assert_eq!(c.interface.address.len(), 3)),
+            ),
+            (
+                "ipv6 allowed ips",
+                TEST_CONFIG.replace("AllowedIPs = 10.0.0.0/24, 0.0.0.0/0", "AllowedIPs = 0.0.0.0/0, ::/0, fd00::/64"),
+                Expect::Ok(|c| {
+                    let ips: Vec<String> = c.peers[0].allowed_ips.iter().map(|ip| ip.to_string()).collect();
+                    assert_eq!(ips, vec!["0.0.0.0/0", "::/0", "fd00::/64"]);
+                }),
+            ),
+            (
+                "bare ips are single hosts",
+                TEST_CONFIG
+                    .replace("Address = 10.0.0.2/24", "Address = 10.0.0.2")
+                    .replace("AllowedIPs = 10.0.0.0/24, 0.0.0.0/0", "AllowedIPs = 10.0.0.1, fd00::1"),
+                Expect::Ok(|c| {
+                    assert_eq!(c.interface.address[0].to_string(), "10.0.0.2/32");
+                    let ips: Vec<String> = c.peers[0].allowed_ips.iter().map(|ip| ip.to_string()).collect();
+                    assert_eq!(ips, vec!["10.0.0.1/32", "fd00::1/128"]);
+                }),
+            ),
+            (
+                "endpoint hostname",
+                TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820", "Endpoint = vpn.example.com:51820"),
+                Expect::Ok(|c| {
+                    // Resolved at connect time, not while parsing
+                    assert!(c.peers[0].endpoint.is_none());
+                    assert_eq!(c.peers[0].endpoint_host.as_deref(), Some("vpn.example.com:51820"));
+                    assert!(c.validate_client().is_ok());
+                }),
+            ),
+            (
+                "endpoint hostname without port",
+                TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820", "Endpoint = vpn.example.com"),
+                Expect::Err,
+            ),
+            (
+                "endpoint unbracketed ipv6",
+                TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820", "Endpoint = 2001:db8::1:51820"),
+                Expect::Err,
+            ),
+            (
+                "endpoint hostname in brackets",
+                TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820", "Endpoint = [vpn.example.com]:51820"),
+                Expect::Err,
+            ),
+            (
+                "endpoint ipv6 literal",
+                TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820", "Endpoint = [2001:db8::1]:51820"),
+                Expect::Ok(|c| assert_eq!(c.peers[0].endpoint.unwrap().to_string(), "[2001:db8::1]:51820")),
+            ),
+            (
+                "invalid prefix length",
+                TEST_CONFIG.replace("10.0.0.0/24, 0.0.0.0/0", "10.0.0.0/33"),
+                Expect::Err,
+            ),
+            (
+                "endpoint without port",
+                TEST_CONFIG.replace("13.239.46.151:51820", "13.239.46.151"),
+                Expect::Err,
+            ),
+            (
+                "listen port out of range",
+                TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS = 8.8.8.8\nListenPort = 70000"),
+                Expect::Err,
+            ),
+            (
+                "line without equals sign",
+                TEST_CONFIG.replace("DNS = 8.8.8.8", "DNS 8.8.8.8"),
+                Expect::Err,
+            ),
+            (
+                "value before any section",
+                format!("MTU = 1420\n{}", TEST_CONFIG),
+                Expect::Err,
+            ),
+        ];
+
+        for (name, input, expect) in cases {
+            match (expect, WireGuardConfig::parse(&input)) {
+                (Expect::Ok(check), Ok(config)) => check(&config),
+                (Expect::Ok(_), Err(e)) => panic!("{}: expected success, got {}", name, e),
+                (Expect::Err, Ok(_)) => panic!("{}: expected an error", name),
+                (Expect::Err, Err(_)) => {}
+            }
+        }
     }
 
     #[test]
@@ -406,4 +1542,148 @@ PersistentKeepalive = 25
         let result = WireGuardConfig::parse(config);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_rejects_oversized_numbers() {
+        for (from, to) in [
+            ("DNS = 8.8.8.8", "ListenPort = 99999999999999999999"),
+            ("DNS = 8.8.8.8", "ListenPort = -1"),
+            ("DNS = 8.8.8.8", "MTU = 4294967296"),
+            ("DNS = 8.8.8.8", "FwMark = 0xffffffffff"),
+            ("PersistentKeepalive = 25", "PersistentKeepalive = 65536"),
+            ("PersistentKeepalive = 25", "HandshakeInterval = 99999999999999999999"),
+        ] {
+            let config = TEST_CONFIG.replace(from, to);
+            assert!(WireGuardConfig::parse(&config).is_err(), "{}", to);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_does_not_quote_line() {
+        // The daemon reports parse errors to API clients; file contents stay out
+        let error = WireGuardConfig::parse("[Interface]\nroot:x:0:0:root:/root:/bin/sh\n").unwrap_err();
+        assert!(matches!(error, ConfigError::ParseError { line: 2, .. }));
+        assert!(!error.to_string().contains("root:x"));
+    }
+
+    /// Cheap in-tree companion to the `config_parse` fuzz target (see `fuzz/`):
+    /// seeded random edits of a valid config must never panic the parser
+    #[test]
+    fn test_parse_survives_mutated_input() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // No Endpoint line: hostnames would go to the system resolver
+        let seed = TEST_CONFIG.replace("Endpoint = 13.239.46.151:51820\n", "")
+            + "PresharedKey = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\nHandshakeInterval = 30\n";
+        let alphabet: Vec<char> = "[]=#,:./+- \t\r\n0123456789abcdefx\u{0}\u{e9}".chars().collect();
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+
+        for _ in 0..2000 {
+            let mut chars: Vec<char> = seed.chars().collect();
+            for _ in 0..rng.gen_range(1..8) {
+                let len = chars.len();
+                let c = alphabet[rng.gen_range(0..alphabet.len())];
+                match rng.gen_range(0..4) {
+                    0 if len > 0 => chars[rng.gen_range(0..len)] = c,
+                    1 => chars.insert(rng.gen_range(0..=len), c),
+                    2 if len > 0 => {
+                        let start = rng.gen_range(0..len);
+                        let end = (start + rng.gen_range(1..20)).min(len);
+                        chars.drain(start..end);
+                    }
+                    _ => {
+                        let at = rng.gen_range(0..=len);
+                        chars.splice(at..at, "99999999999999999999".chars());
+                    }
+                }
+            }
+
+            let text: String = chars.into_iter().collect();
+            if let Ok(config) = WireGuardConfig::parse(&text) {
+                let _ = config.validate_client();
+                let _ = config.validate_server(false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_per_peer_psk_interop() {
+        use crate::crypto::x25519;
+        use crate::protocol::{InitiatorHandshake, PeerManager, ResponderHandshake};
+
+        let (client_private, client_public) = x25519::generate_keypair();
+        let (server_private, server_public) = x25519::generate_keypair();
+        let psk = BASE64.encode([7u8; 32]);
+
+        // Each side carries the PSK only in its [Peer] section
+        let client = WireGuardConfig::parse(&format!(
+            "[Interface]\nPrivateKey = {}\nAddress = 10.0.0.2/32\n\n\
+             [Peer]\nPublicKey = {}\nPresharedKey = {}\nEndpoint = 127.0.0.1:51820\nAllowedIPs = 10.0.0.0/24\n",
+            BASE64.encode(client_private), BASE64.encode(server_public), psk,
+        ))
+        .unwrap();
+        let server = WireGuardConfig::parse(&format!(
+            "[Interface]\nPrivateKey = {}\nAddress = 10.0.0.1/24\nListenPort = 51820\n\n\
+             [Peer]\nPublicKey = {}\nAllowedIPs = 10.0.0.9/32\n\n\
+             [Peer]\nPublicKey = {}\nPresharedKey = {}\nAllowedIPs = 10.0.0.2/32\n",
+            BASE64.encode(server_private), BASE64.encode([9u8; 32]), BASE64.encode(client_public), psk,
+        ))
+        .unwrap();
+
+        // Client side: PSK from its (only) peer, as in WireGuardClient::perform_handshake
+        let peer = &client.peers[0];
+        let mut initiator = InitiatorHandshake::new(
+            client.interface.private_key,
+            peer.public_key,
+            peer.preshared_key,
+            1,
+        );
+        let initiation = initiator.create_initiation(None).unwrap();
+
+        // Server side: PSK looked up per peer, as in WireGuardServer
+        let mut peers = PeerManager::new();
+        for p in &server.peers {
+            peers.add_peer(p.public_key, p.preshared_key, p.allowed_ips.clone());
+        }
+        let mut responder = ResponderHandshake::new(server.interface.private_key, 2);
+        let initiator_public = responder.process_initiation(&initiation).unwrap();
+        let server_psk = peers.get_peer(&initiator_public).unwrap().psk;
+        assert!(server_psk.is_some());
+
+        let (response, server_keys) = responder.create_response(server_psk, None).unwrap();
+        let client_keys = initiator.process_response(&response).unwrap();
+        assert_eq!(client_keys.sending_key, server_keys.receiving_key);
+        assert_eq!(client_keys.receiving_key, server_keys.sending_key);
+    }
+
+    #[test]
+    fn test_to_string_round_trip() {
+        let full = format!(
+            "[Interface]\nPrivateKey = {}\nAddress = 10.0.0.1/24, fd00::1/64\nDNS = 1.1.1.1, 2606:4700::1111\n\
+             ListenPort = 51820\nBindAddress = 192.0.2.10\nMTU = 1380\nFwMark = 0xca6c\nReplicateBroadcast = on\nMaxSessions = 8\n\
+             HandshakeSources = 192.0.2.0/24\nIdleTimeout = 600\nHandshakeRateLimit = 5\nTable = 1234\n\n\
+             [Peer]\nPublicKey = {}\nPresharedKey = {}\nEndpoint = [2001:db8::1]:51820\n\
+             AllowedIPs = 10.0.0.2/32, fd00::2/128\nPersistentKeepalive = 25\nHandshakeInterval = 90\nLockEndpoint = on\n\n\
+             [Peer]\nPublicKey = {}\nEndpoint = vpn.example.com:51820\nAllowedIPs = 10.0.0.3/32\n",
+            BASE64.encode([1u8; 32]),
+            BASE64.encode([2u8; 32]),
+            BASE64.encode([3u8; 32]),
+            BASE64.encode([4u8; 32]),
+        );
+
+        for text in [TEST_CONFIG, full.as_str()] {
+            let config = WireGuardConfig::parse(text).unwrap();
+            let written = config.to_string();
+            assert_eq!(WireGuardConfig::parse(&written).unwrap(), config, "{}", written);
+        }
+
+        let config = WireGuardConfig::parse(&full).unwrap();
+        assert!(config.to_string().contains("FwMark = 0xca6c"));
+        assert_eq!(config.peers[1].endpoint_host.as_deref(), Some("vpn.example.com:51820"));
+
+        let path = std::env::temp_dir().join(format!("minnowvpn-test-{}.conf", std::process::id()));
+        config.to_file(&path).unwrap();
+        assert_eq!(WireGuardConfig::from_file(&path).unwrap(), config);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -4,8 +4,8 @@
 //! XChaCha20-Poly1305 for cookie decryption.
 
 use chacha20poly1305::{
-    aead::{Aead, KeyInit, Payload},
-    ChaCha20Poly1305, Key, Nonce, XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadInPlace, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce, Tag, XChaCha20Poly1305, XNonce,
 };
 
 use crate::error::CryptoError;
@@ -33,10 +33,7 @@ pub fn encrypt(
     aad: &[u8],
 ) -> Result<Vec<u8>, CryptoError> {
     let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
-
-    // Build nonce: 4 zero bytes + 8 bytes counter (little-endian)
-    let mut nonce_bytes = [0u8; NONCE_LEN];
-    nonce_bytes[4..12].copy_from_slice(&counter.to_le_bytes());
+    let nonce_bytes = counter_nonce(counter);
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     cipher
@@ -64,10 +61,7 @@ pub fn decrypt(
     }
 
     let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
-
-    // Build nonce: 4 zero bytes + 8 bytes counter (little-endian)
-    let mut nonce_bytes = [0u8; NONCE_LEN];
-    nonce_bytes[4..12].copy_from_slice(&counter.to_le_bytes());
+    let nonce_bytes = counter_nonce(counter);
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     cipher
@@ -81,6 +75,67 @@ pub fn decrypt(
         .map_err(|_| CryptoError::Decryption)
 }
 
+/// [`encrypt`] into `out` without allocating
+///
+/// `out` must hold the plaintext plus [`TAG_LEN`]; returns the ciphertext length.
+pub fn encrypt_into(
+    key: &[u8; KEY_LEN],
+    counter: u64,
+    plaintext: &[u8],
+    aad: &[u8],
+    out: &mut [u8],
+) -> Result<usize, CryptoError> {
+    let len = plaintext.len() + TAG_LEN;
+    if out.len() < len {
+        return Err(CryptoError::BufferTooSmall { needed: len, got: out.len() });
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce_bytes = counter_nonce(counter);
+    let (body, tag_out) = out[..len].split_at_mut(plaintext.len());
+    body.copy_from_slice(plaintext);
+    let tag = cipher
+        .encrypt_in_place_detached(Nonce::from_slice(&nonce_bytes), aad, body)
+        .map_err(|_| CryptoError::Encryption)?;
+    tag_out.copy_from_slice(&tag);
+    Ok(len)
+}
+
+/// [`decrypt`] into `out` without allocating
+///
+/// `out` must hold the ciphertext minus [`TAG_LEN`]; returns the plaintext length.
+/// The tag is checked before anything is decrypted.
+pub fn decrypt_into(
+    key: &[u8; KEY_LEN],
+    counter: u64,
+    ciphertext: &[u8],
+    aad: &[u8],
+    out: &mut [u8],
+) -> Result<usize, CryptoError> {
+    let Some(len) = ciphertext.len().checked_sub(TAG_LEN) else {
+        return Err(CryptoError::Decryption);
+    };
+    if out.len() < len {
+        return Err(CryptoError::BufferTooSmall { needed: len, got: out.len() });
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce_bytes = counter_nonce(counter);
+    let (body, tag) = ciphertext.split_at(len);
+    out[..len].copy_from_slice(body);
+    cipher
+        .decrypt_in_place_detached(Nonce::from_slice(&nonce_bytes), aad, &mut out[..len], Tag::from_slice(tag))
+        .map_err(|_| CryptoError::Decryption)?;
+    Ok(len)
+}
+
+/// Nonce for `counter`: 4 zero bytes + 8 bytes counter (little-endian)
+fn counter_nonce(counter: u64) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[4..12].copy_from_slice(&counter.to_le_bytes());
+    nonce
+}
+
 /// Encrypt using XChaCha20-Poly1305 (used for cookie encryption)
 pub fn xencrypt(
     key: &[u8; KEY_LEN],
@@ -145,6 +200,31 @@ mod tests {
         assert_eq!(decrypted, plaintext);
     }
 
+    #[test]
+    fn test_into_matches_allocating_versions() {
+        let key = [7u8; 32];
+        let plaintext = b"Hello, WireGuard!";
+        let mut out = [0u8; 64];
+
+        let len = encrypt_into(&key, 42, plaintext, &[], &mut out).unwrap();
+        assert_eq!(&out[..len], encrypt(&key, 42, plaintext, &[]).unwrap().as_slice());
+
+        let ciphertext = out[..len].to_vec();
+        let mut plain = [0u8; 17];
+        assert_eq!(decrypt_into(&key, 42, &ciphertext, &[], &mut plain).unwrap(), plaintext.len());
+        assert_eq!(&plain, plaintext);
+        assert!(decrypt_into(&key, 43, &ciphertext, &[], &mut plain).is_err());
+
+        assert!(matches!(
+            encrypt_into(&key, 42, plaintext, &[], &mut out[..32]),
+            Err(CryptoError::BufferTooSmall { needed: 33, got: 32 })
+        ));
+        assert!(matches!(
+            decrypt_into(&key, 42, &ciphertext, &[], &mut plain[..16]),
+            Err(CryptoError::BufferTooSmall { needed: 17, got: 16 })
+        ));
+    }
+
     #[test]
     fn test_decrypt_wrong_key() {
         let key = [0u8; 32];
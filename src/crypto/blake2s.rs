@@ -7,6 +7,7 @@ use blake2::{
     Blake2s256, Blake2sMac, Digest,
 };
 use hmac::SimpleHmac;
+use subtle::ConstantTimeEq;
 
 /// Type alias for HMAC-BLAKE2s (RFC 2104 HMAC with BLAKE2s-256)
 /// Uses SimpleHmac which works with any hash that implements the required traits
@@ -49,6 +50,14 @@ pub fn mac_with_cookie(key: &[u8; MAC_LEN], data: &[u8]) -> [u8; MAC_LEN] {
     mac.finalize_fixed().into()
 }
 
+/// Compare a received MAC with the expected one in constant time
+///
+/// A short-circuiting `==` would reveal how many leading bytes of a forged
+/// MAC are right.
+pub fn mac_eq(expected: &[u8; MAC_LEN], actual: &[u8]) -> bool {
+    expected.ct_eq(actual).into()
+}
+
 /// HMAC-BLAKE2s implementation using standard RFC 2104 HMAC construction
 ///
 /// This matches what boringtun and other WireGuard implementations use.
@@ -119,6 +128,15 @@ mod tests {
         assert_eq!(result.len(), 32);
     }
 
+    #[test]
+    fn test_hash_rfc7693_vector() {
+        // RFC 7693 appendix B: BLAKE2s-256("abc")
+        assert_eq!(
+            hex::encode(hash(b"abc")),
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+        );
+    }
+
     #[test]
     fn test_hash_two() {
         let a = b"hello";
@@ -0,0 +1,55 @@
+//! Key generation and the base64 key format
+//!
+//! Keys are written as standard, padded base64 of the 32 key bytes: the format
+//! of `wg genkey`/`wg pubkey`/`wg genpsk` and of `.conf` files, so keys made
+//! here and by the reference tools are interchangeable.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{rng, x25519};
+use crate::error::ConfigError;
+
+/// Key length (private, public and preshared keys are all 32 bytes)
+pub const KEY_LEN: usize = 32;
+
+/// Generate a random preshared key (like `wg genpsk`)
+pub fn generate_preshared_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    rng::fill_bytes(&mut key);
+    key
+}
+
+/// Public key for a base64 private key (like `wg pubkey`)
+pub fn public_key_base64(private_key: &str) -> Result<String, ConfigError> {
+    let private_key = from_base64(private_key)?;
+    Ok(to_base64(&x25519::public_key(&private_key)))
+}
+
+/// Encode a key as standard base64
+pub fn to_base64(key: &[u8; KEY_LEN]) -> String {
+    STANDARD.encode(key)
+}
+
+/// Decode a base64 key, ignoring surrounding whitespace
+///
+/// Accepts the same spellings as config files (URL-safe alphabet, missing padding).
+pub fn from_base64(value: &str) -> Result<[u8; KEY_LEN], ConfigError> {
+    crate::config::parse_key(value.trim(), "key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_matches_wg() {
+        // RFC 7748 Alice's key pair, as `wg pubkey` prints it (clamping happens on use)
+        let private = "dwdtCnMYpX08FsFyUbJmRd9ML4frwJkqsXf7pR25LCo=";
+        assert_eq!(public_key_base64(private).unwrap(), "hSDwCYkwp1R0i33ctD73Wg2/Og0mOBr066SpjqqbTmo=");
+        assert_eq!(public_key_base64(&format!("{}\n", private)).unwrap().len(), 44);
+        assert!(public_key_base64("not a key").is_err());
+
+        let psk = generate_preshared_key();
+        assert_eq!(from_base64(&to_base64(&psk)).unwrap(), psk);
+    }
+}
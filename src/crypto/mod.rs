@@ -5,8 +5,12 @@
 //! - ChaCha20-Poly1305 AEAD encryption (aead)
 //! - X25519 Diffie-Hellman key exchange (x25519)
 //! - Noise IKpsk2 protocol state machine (noise)
+//! - Random source for indices and ephemeral keys, pinnable in tests (rng)
+//! - Key generation and the base64 key format shared with `wg` (keys)
 
 pub mod aead;
 pub mod blake2s;
+pub mod keys;
 pub mod noise;
+pub mod rng;
 pub mod x25519;
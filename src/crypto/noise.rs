@@ -3,6 +3,8 @@
 //! Implements the Noise protocol pattern used by WireGuard for handshakes.
 //! Pattern: Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s
 
+use zeroize::Zeroize;
+
 use super::{aead, blake2s};
 use crate::error::CryptoError;
 
@@ -120,6 +122,19 @@ impl HandshakeState {
     }
 }
 
+impl Zeroize for HandshakeState {
+    fn zeroize(&mut self) {
+        self.chaining_key.zeroize();
+        self.hash.zeroize();
+    }
+}
+
+impl Drop for HandshakeState {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Transport keys derived from a completed handshake
 pub struct TransportKeys {
     /// Key for sending packets (initiator -> responder)
@@ -151,6 +166,19 @@ impl TransportKeys {
     }
 }
 
+impl Zeroize for TransportKeys {
+    fn zeroize(&mut self) {
+        self.sending_key.zeroize();
+        self.receiving_key.zeroize();
+    }
+}
+
+impl Drop for TransportKeys {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Compute the MAC1 key from a peer's public key
 ///
 /// mac1_key = HASH(LABEL_MAC1 || peer_public_key)
@@ -186,6 +214,25 @@ mod tests {
         assert!(!ck.iter().all(|&b| b == 0));
     }
 
+    #[test]
+    fn test_initial_state_matches_wireguard() {
+        // Same precomputed constants as wireguard-go and boringtun
+        assert_eq!(
+            HandshakeState::initial_chain_key(),
+            [
+                96, 226, 109, 174, 243, 39, 239, 192, 46, 195, 53, 226, 160, 37, 210, 208, 22,
+                235, 66, 6, 248, 114, 119, 245, 45, 56, 209, 152, 139, 120, 205, 54,
+            ]
+        );
+        assert_eq!(
+            blake2s::hash_two(&HandshakeState::initial_chain_key(), IDENTIFIER),
+            [
+                34, 17, 179, 97, 8, 26, 197, 102, 105, 18, 67, 219, 69, 138, 213, 50, 45, 156,
+                108, 102, 34, 147, 232, 183, 14, 225, 156, 101, 186, 7, 158, 243,
+            ]
+        );
+    }
+
     #[test]
     fn test_initial_hash() {
         let peer_public = [0u8; 32];
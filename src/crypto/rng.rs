@@ -0,0 +1,86 @@
+//! Random number source for sender indices and ephemeral keys
+//!
+//! Always the OS CSPRNG in production. With the `test-vectors` feature a test
+//! can pin a seeded RNG for the current thread, so sender indices and
+//! ephemeral keys (and therefore handshake wire bytes) are reproducible.
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+#[cfg(feature = "test-vectors")]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "test-vectors")]
+use std::cell::RefCell;
+
+#[cfg(feature = "test-vectors")]
+thread_local! {
+    /// Seeded RNG pinned by the current test (None = OS CSPRNG)
+    static PINNED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Fill `dest` with random bytes
+pub fn fill_bytes(dest: &mut [u8]) {
+    #[cfg(feature = "test-vectors")]
+    {
+        let pinned = PINNED.with(|pinned| match pinned.borrow_mut().as_mut() {
+            Some(rng) => {
+                rng.fill_bytes(dest);
+                true
+            }
+            None => false,
+        });
+        if pinned {
+            return;
+        }
+    }
+    OsRng.fill_bytes(dest);
+}
+
+/// Random `u32`
+pub fn next_u32() -> u32 {
+    let mut bytes = [0u8; 4];
+    fill_bytes(&mut bytes);
+    u32::from_le_bytes(bytes)
+}
+
+/// Use a seeded RNG on this thread until [`unpin`] (test vectors only)
+#[cfg(feature = "test-vectors")]
+pub fn pin_seed(seed: u64) {
+    PINNED.with(|pinned| *pinned.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+}
+
+/// Go back to the OS CSPRNG on this thread (test vectors only)
+#[cfg(feature = "test-vectors")]
+pub fn unpin() {
+    PINNED.with(|pinned| *pinned.borrow_mut() = None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_bytes_is_random() {
+        let mut a = [0u8; 32];
+        let mut b = [0u8; 32];
+        fill_bytes(&mut a);
+        fill_bytes(&mut b);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn test_pinned_seed_is_reproducible() {
+        let draw = |seed| {
+            pin_seed(seed);
+            let index = next_u32();
+            let mut key = [0u8; 32];
+            fill_bytes(&mut key);
+            unpin();
+            (index, key)
+        };
+
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+}
@@ -2,7 +2,6 @@
 //!
 //! Provides key generation and DH operations using Curve25519.
 
-use rand::rngs::OsRng;
 use x25519_dalek::{PublicKey, StaticSecret};
 
 /// Key length for X25519 (both private and public keys are 32 bytes)
@@ -10,11 +9,26 @@ pub const KEY_LEN: usize = 32;
 
 /// Generate a new X25519 keypair
 ///
-/// Returns (private_key, public_key)
+/// Returns (private_key, public_key). Uses [`crate::crypto::rng`], so a pinned
+/// test RNG makes the keypair reproducible.
 pub fn generate_keypair() -> ([u8; KEY_LEN], [u8; KEY_LEN]) {
-    let secret = StaticSecret::random_from_rng(OsRng);
-    let public = PublicKey::from(&secret);
-    (secret.to_bytes(), public.to_bytes())
+    let private = generate_private_key();
+    (private, public_key(&private))
+}
+
+/// Generate a new private key, clamped like `wg genkey` output
+pub fn generate_private_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    crate::crypto::rng::fill_bytes(&mut key);
+    clamp(&mut key);
+    key
+}
+
+/// Apply the Curve25519 scalar clamping (RFC 7748)
+fn clamp(key: &mut [u8; KEY_LEN]) {
+    key[0] &= 248;
+    key[31] &= 127;
+    key[31] |= 64;
 }
 
 /// Derive public key from private key
@@ -50,6 +64,35 @@ pub fn is_valid_public_key(key: &[u8; KEY_LEN]) -> bool {
 mod tests {
     use super::*;
 
+    /// Decode a 32-byte hex test vector
+    fn key(hex_str: &str) -> [u8; KEY_LEN] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_rfc7748_vectors() {
+        // RFC 7748 section 5.2, first scalar multiplication vector
+        assert_eq!(
+            dh(
+                &key("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4"),
+                &key("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c"),
+            ),
+            key("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552")
+        );
+
+        // RFC 7748 section 6.1, Diffie-Hellman between Alice and Bob
+        let alice = key("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = key("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = key("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_public = key("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = key("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+        assert_eq!(public_key(&alice), alice_public);
+        assert_eq!(public_key(&bob), bob_public);
+        assert_eq!(dh(&alice, &bob_public), shared);
+        assert_eq!(dh(&bob, &alice_public), shared);
+    }
+
     #[test]
     fn test_keypair_generation() {
         let (private, public) = generate_keypair();
@@ -62,6 +105,15 @@ mod tests {
         assert_eq!(public_key(&private), public);
     }
 
+    #[test]
+    fn test_private_key_is_clamped() {
+        for _ in 0..32 {
+            let key = generate_private_key();
+            assert_eq!(key[0] & 7, 0);
+            assert_eq!(key[31] & 0xc0, 0x40);
+        }
+    }
+
     #[test]
     fn test_dh_shared_secret() {
         // Generate two keypairs
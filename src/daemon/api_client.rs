@@ -0,0 +1,141 @@
+//! Minimal client for the daemon's REST API (used by `minnowvpn show`)
+//!
+//! One GET to `127.0.0.1` with a Bearer token and `Connection: close`, read
+//! to the end. The daemon sends fixed-length bodies, so that is all the HTTP
+//! this needs, and the binary stays free of an HTTP client dependency.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::{MinnowVpnError, NetworkError};
+
+/// Give up on a daemon that accepts but doesn't answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Address of the daemon API on `port`
+pub fn daemon_addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+/// Whether a daemon is listening on `port`
+pub async fn is_listening(port: u16) -> bool {
+    let connect = TcpStream::connect(daemon_addr(port));
+    matches!(tokio::time::timeout(Duration::from_secs(1), connect).await, Ok(Ok(_)))
+}
+
+/// GET `path` from the daemon on `port`, returning the body of a 2xx response
+///
+/// Error responses become [`NetworkError::DaemonRequestFailed`] carrying the
+/// daemon's message.
+pub async fn get(port: u16, path: &str, token: &str) -> Result<String, MinnowVpnError> {
+    let addr = daemon_addr(port);
+    let request = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        let head = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nConnection: close\r\n\r\n",
+            path, addr, token
+        );
+        stream.write_all(head.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, request).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+            return Err(NetworkError::DaemonNotRunning { addr: addr.to_string() }.into());
+        }
+        Ok(Err(e)) => return Err(NetworkError::Io(e).into()),
+        Err(_) => {
+            return Err(NetworkError::DaemonRequestFailed {
+                status: 0,
+                message: format!("no response from {} within {}s", addr, REQUEST_TIMEOUT.as_secs()),
+            }
+            .into())
+        }
+    };
+
+    let (status, body) = parse_response(&response)?;
+    if !(200..300).contains(&status) {
+        // Error bodies are `{"code": .., "message": ..}`
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+            .unwrap_or(body);
+        return Err(NetworkError::DaemonRequestFailed { status, message }.into());
+    }
+    Ok(body)
+}
+
+/// Status code and body of a raw HTTP/1.1 response
+fn parse_response(response: &[u8]) -> Result<(u16, String), NetworkError> {
+    let malformed = || NetworkError::DaemonRequestFailed {
+        status: 0,
+        message: "malformed HTTP response".to_string(),
+    };
+    let text = String::from_utf8_lossy(response);
+    let (head, body) = text.split_once("\r\n\r\n").ok_or_else(malformed)?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(malformed)?;
+    Ok((status, body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serve one canned response on a free port, returning the port and the request it got
+    async fn serve_once(response: &'static str) -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 1024];
+            let len = stream.read(&mut request).await.unwrap();
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+        (port, server)
+    }
+
+    #[tokio::test]
+    async fn test_get_returns_body_and_sends_token() {
+        let (port, server) =
+            serve_once("HTTP/1.1 200 OK\r\ncontent-length: 13\r\n\r\ninterface: wg0").await;
+        let body = get(port, "/api/v1/show", "secret").await.unwrap();
+        assert_eq!(body, "interface: wg0");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /api/v1/show HTTP/1.1\r\n"));
+        assert!(request.contains("Authorization: Bearer secret\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_get_reports_daemon_errors() {
+        let (port, _server) = serve_once(
+            "HTTP/1.1 409 Conflict\r\ncontent-type: application/json\r\n\r\n{\"code\":-1,\"message\":\"VPN not running\"}",
+        )
+        .await;
+        let err = get(port, "/api/v1/show", "secret").await.unwrap_err();
+        assert!(matches!(
+            err,
+            MinnowVpnError::Network(NetworkError::DaemonRequestFailed { status: 409, ref message }) if message == "VPN not running"
+        ));
+
+        // Nothing listening
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        assert!(!is_listening(port).await);
+        let err = get(port, "/api/v1/show", "secret").await.unwrap_err();
+        assert!(matches!(err, MinnowVpnError::Network(NetworkError::DaemonNotRunning { .. })));
+    }
+}
@@ -1,19 +1,27 @@
 //! Authentication module for REST API
 //!
 //! Handles token generation, storage, and validation for the daemon HTTP server.
+//!
+//! Two tokens are issued: the full token allows everything, while the read-only
+//! token (for untrusted local dashboards) only allows status queries and SSE.
+//! Metrics can also be opened up to scrapers that can't send a token.
 
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{Query, State},
+    http::{Method, Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::RngCore;
-use std::path::PathBuf;
+use subtle::ConstantTimeEq;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use super::ipc::READ_ONLY_TOKEN;
+use super::routes::{ApiError, SseQueryParams};
+
 /// Default token file path for Unix systems
 #[cfg(unix)]
 pub const DEFAULT_TOKEN_PATH: &str = "/var/run/minnowvpn/auth-token";
@@ -22,23 +30,93 @@ pub const DEFAULT_TOKEN_PATH: &str = "/var/run/minnowvpn/auth-token";
 #[cfg(windows)]
 pub const DEFAULT_TOKEN_PATH: &str = r"C:\ProgramData\MinnowVPN\auth-token";
 
+/// Group that owns the full token file
+#[cfg(unix)]
+const TOKEN_GROUP: &str = "minnowvpn";
+
+/// Group that owns the read-only token file (for status dashboards)
+#[cfg(unix)]
+pub const READ_ONLY_TOKEN_GROUP: &str = "minnowvpn-status";
+
+/// What a presented token is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Every endpoint
+    Full,
+    /// Status, peer queries, version and SSE only
+    ReadOnly,
+}
+
 /// Authentication state shared across handlers
 #[derive(Clone)]
 pub struct AuthState {
     /// The valid authentication token
     token: Arc<String>,
+    /// Status-only token (None = not issued)
+    read_only_token: Option<Arc<String>>,
+    /// Serve `/api/v1/metrics` without a token
+    public_metrics: bool,
 }
 
 impl AuthState {
     pub fn new(token: String) -> Self {
         Self {
             token: Arc::new(token),
+            read_only_token: None,
+            public_metrics: false,
         }
     }
 
+    /// Also accept a status-only token
+    pub fn with_read_only_token(mut self, token: String) -> Self {
+        self.read_only_token = Some(Arc::new(token));
+        self
+    }
+
+    /// Let `GET /api/v1/metrics` through without a token
+    pub fn with_public_metrics(mut self) -> Self {
+        self.public_metrics = true;
+        self
+    }
+
     pub fn token(&self) -> &str {
         &self.token
     }
+
+    /// Capability granted to `token`, or None if it is not valid
+    ///
+    /// Compared in constant time, so response timing doesn't leak token bytes.
+    pub fn capability(&self, token: &str) -> Option<Capability> {
+        let matches = |expected: &str| bool::from(expected.as_bytes().ct_eq(token.as_bytes()));
+        if matches(&self.token) {
+            Some(Capability::Full)
+        } else if self.read_only_token.as_deref().is_some_and(|t| matches(t)) {
+            Some(Capability::ReadOnly)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check if a request may be made with the read-only token
+///
+/// Allowed: status, metrics, server peer list and peer status, route lookups, version, and the SSE stream.
+pub fn read_only_allowed(method: &Method, path: &str) -> bool {
+    if method != Method::GET {
+        return false;
+    }
+    match path {
+        "/api/v1/status"
+        | "/api/v1/version"
+        | "/api/v1/events"
+        | "/api/v1/show"
+        | "/api/v1/metrics"
+        | "/api/v1/server/peers" => true,
+        _ => ["/api/v1/server/peers/", "/api/v1/server/route/"].iter().any(|prefix| {
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| !rest.is_empty() && !rest.contains('/'))
+        }),
+    }
 }
 
 /// Generate a cryptographically secure 32-byte token, base64-encoded
@@ -62,7 +140,7 @@ pub fn write_token_file(token: &str, path: Option<PathBuf>) -> Result<PathBuf, s
 
     // Set appropriate permissions
     #[cfg(unix)]
-    set_unix_permissions(&token_path)?;
+    set_unix_permissions(&token_path, TOKEN_GROUP)?;
 
     #[cfg(windows)]
     set_windows_permissions(&token_path)?;
@@ -71,18 +149,41 @@ pub fn write_token_file(token: &str, path: Option<PathBuf>) -> Result<PathBuf, s
     Ok(token_path)
 }
 
+/// Path of the read-only token file next to the full token file
+pub fn read_only_token_path(token_path: &Path) -> PathBuf {
+    let mut name = token_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".readonly");
+    token_path.with_file_name(name)
+}
+
+/// Write the read-only token next to the full token file
+///
+/// Not world-readable on Unix: status, peers and routes expose peer keys and
+/// endpoints. The file is 0o640 in the [`READ_ONLY_TOKEN_GROUP`] group, so a
+/// dashboard user added to that group can read it without also getting the
+/// full token (group `minnowvpn`). Without that group only root can read it.
+pub fn write_read_only_token_file(token: &str, token_path: &Path) -> Result<PathBuf, std::io::Error> {
+    let path = read_only_token_path(token_path);
+    std::fs::write(&path, token)?;
+
+    #[cfg(unix)]
+    set_unix_permissions(&path, READ_ONLY_TOKEN_GROUP)?;
+
+    Ok(path)
+}
+
 /// Set Unix file permissions (0o640 - owner rw, group r)
-/// Also sets group ownership to 'minnowvpn' for secure token access
+/// Also sets group ownership to `group_name` for secure token access
 #[cfg(unix)]
-fn set_unix_permissions(path: &PathBuf) -> Result<(), std::io::Error> {
+fn set_unix_permissions(path: &PathBuf, group_name: &str) -> Result<(), std::io::Error> {
     use std::os::unix::fs::PermissionsExt;
 
     // Set file permissions to 0o640 (owner read/write, group read)
     let permissions = std::fs::Permissions::from_mode(0o640);
     std::fs::set_permissions(path, permissions)?;
 
-    // Set group ownership to 'minnowvpn' if the group exists
-    set_group_ownership(path, "minnowvpn");
+    // Set group ownership if the group exists
+    set_group_ownership(path, group_name);
 
     tracing::debug!("Token file created with 0o640 permissions");
     Ok(())
@@ -152,44 +253,75 @@ pub fn read_token_file(path: Option<PathBuf>) -> Result<String, std::io::Error>
 }
 
 /// Axum middleware for Bearer token authentication
+///
+/// The SSE stream also takes the token as `?token=` (percent-encoded), since
+/// browsers' `EventSource` can't set headers.
 pub async fn auth_middleware(
     State(auth_state): State<AuthState>,
     request: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
+    if auth_state.public_metrics && request.method() == Method::GET && request.uri().path() == "/api/v1/metrics" {
+        return Ok(next.run(request).await);
+    }
+
     // Extract Authorization header
     let auth_header = request
         .headers()
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    match auth_header {
-        Some(header) if header.starts_with("Bearer ") => {
-            let token = &header[7..]; // Skip "Bearer "
-            if token == auth_state.token() {
-                Ok(next.run(request).await)
-            } else {
-                tracing::warn!("Invalid auth token provided");
-                Err(StatusCode::UNAUTHORIZED)
+    let token = match auth_header {
+        Some(header) => match header.strip_prefix("Bearer ") {
+            Some(token) => token.to_string(),
+            None => {
+                tracing::warn!("Malformed Authorization header");
+                return Err(StatusCode::UNAUTHORIZED);
             }
-        }
-        Some(_) => {
-            tracing::warn!("Malformed Authorization header");
-            Err(StatusCode::UNAUTHORIZED)
+        },
+        None => match sse_query_token(&request) {
+            Some(token) => token,
+            None => {
+                tracing::warn!("Missing Authorization header");
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        },
+    };
+
+    match auth_state.capability(&token) {
+        Some(Capability::Full) => Ok(next.run(request).await),
+        Some(Capability::ReadOnly) => {
+            if read_only_allowed(request.method(), request.uri().path()) {
+                return Ok(next.run(request).await);
+            }
+            tracing::warn!(
+                "Read-only token refused for {} {}",
+                request.method(),
+                request.uri().path()
+            );
+            Ok(ApiError {
+                code: READ_ONLY_TOKEN,
+                message: format!(
+                    "Read-only token cannot call {} {}; use the full auth token",
+                    request.method(),
+                    request.uri().path()
+                ),
+            }
+            .into_response())
         }
         None => {
-            tracing::warn!("Missing Authorization header");
+            tracing::warn!("Invalid auth token provided");
             Err(StatusCode::UNAUTHORIZED)
         }
     }
 }
 
-/// Extract and validate token from request (for SSE which may use query param)
-pub fn validate_token_from_query(query_token: Option<&str>, auth_state: &AuthState) -> bool {
-    match query_token {
-        Some(token) => token == auth_state.token(),
-        None => false,
+/// The `?token=` query parameter of an SSE stream request, if any
+fn sse_query_token(request: &Request<Body>) -> Option<String> {
+    if request.method() != Method::GET || request.uri().path() != "/api/v1/events" {
+        return None;
     }
+    Query::<SseQueryParams>::try_from_uri(request.uri()).ok()?.0.token
 }
 
 #[cfg(test)]
@@ -213,6 +345,105 @@ mod tests {
         assert!(BASE64.decode(&token2).is_ok());
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_read_only_token_file_is_not_world_readable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_read_only_token_file("ro", &dir.path().join("auth-token")).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ro");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_read_only_capability() {
+        let auth_state = AuthState::new("full".to_string()).with_read_only_token("ro".to_string());
+        assert_eq!(auth_state.capability("full"), Some(Capability::Full));
+        assert_eq!(auth_state.capability("ro"), Some(Capability::ReadOnly));
+        assert_eq!(auth_state.capability("other"), None);
+        assert_eq!(AuthState::new("full".to_string()).capability("ro"), None);
+
+        assert!(read_only_allowed(&Method::GET, "/api/v1/status"));
+        assert!(read_only_allowed(&Method::GET, "/api/v1/events"));
+        assert!(read_only_allowed(&Method::GET, "/api/v1/show"));
+        assert!(read_only_allowed(&Method::GET, "/api/v1/server/peers/abc%2Bdef="));
+        assert!(read_only_allowed(&Method::GET, "/api/v1/server/route/10.0.0.5"));
+        assert!(!read_only_allowed(&Method::GET, "/api/v1/server/route/"));
+        assert!(!read_only_allowed(&Method::POST, "/api/v1/connect"));
+        assert!(!read_only_allowed(&Method::POST, "/api/v1/server/peers"));
+        assert!(!read_only_allowed(&Method::DELETE, "/api/v1/server/peers/abc"));
+        assert!(!read_only_allowed(&Method::GET, "/api/v1/debug/routes"));
+
+        assert_eq!(
+            read_only_token_path(Path::new("/run/minnowvpn/auth-token")),
+            PathBuf::from("/run/minnowvpn/auth-token.readonly")
+        );
+    }
+
+    /// A router with one endpoint of each kind behind the auth middleware
+    fn protected_router(auth_state: AuthState) -> axum::Router {
+        use axum::routing::{get, post};
+
+        axum::Router::new()
+            .route("/api/v1/status", get(|| async { "status" }))
+            .route("/api/v1/connect", post(|| async { "connected" }))
+            .route("/api/v1/events", get(|| async { "events" }))
+            .route("/api/v1/metrics", get(|| async { "metrics" }))
+            .layer(axum::middleware::from_fn_with_state(auth_state, auth_middleware))
+    }
+
+    fn two_token_state() -> AuthState {
+        AuthState::new("full+token=".to_string()).with_read_only_token("ro".to_string())
+    }
+
+    async fn status_with(auth_state: AuthState, method: Method, uri: &str, bearer: Option<&str>) -> StatusCode {
+        use tower::ServiceExt;
+
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = bearer {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let request = request.body(Body::empty()).unwrap();
+        protected_router(auth_state).oneshot(request).await.unwrap().status()
+    }
+
+    async fn status_of(method: Method, uri: &str, bearer: Option<&str>) -> StatusCode {
+        status_with(two_token_state(), method, uri, bearer).await
+    }
+
+    #[tokio::test]
+    async fn test_middleware_requires_token() {
+        assert_eq!(status_of(Method::POST, "/api/v1/connect", Some("full+token=")).await, StatusCode::OK);
+        assert_eq!(status_of(Method::GET, "/api/v1/status", Some("ro")).await, StatusCode::OK);
+
+        assert_eq!(status_of(Method::POST, "/api/v1/connect", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(Method::POST, "/api/v1/connect", Some("wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(Method::POST, "/api/v1/connect", Some("ro")).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_sse_query_token() {
+        assert_eq!(status_of(Method::GET, "/api/v1/events?token=full%2Btoken%3D", None).await, StatusCode::OK);
+        assert_eq!(status_of(Method::GET, "/api/v1/events?token=ro", None).await, StatusCode::OK);
+
+        assert_eq!(status_of(Method::GET, "/api/v1/events", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(Method::GET, "/api/v1/events?token=wrong", None).await, StatusCode::UNAUTHORIZED);
+        // Only the SSE stream takes the query form
+        assert_eq!(status_of(Method::GET, "/api/v1/status?token=ro", None).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_public_metrics() {
+        assert_eq!(status_of(Method::GET, "/api/v1/metrics", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(Method::GET, "/api/v1/metrics", Some("ro")).await, StatusCode::OK);
+
+        let public = || two_token_state().with_public_metrics();
+        assert_eq!(status_with(public(), Method::GET, "/api/v1/metrics", None).await, StatusCode::OK);
+        // Nothing else opens up
+        assert_eq!(status_with(public(), Method::GET, "/api/v1/status", None).await, StatusCode::UNAUTHORIZED);
+    }
+
     #[test]
     fn test_auth_state() {
         let token = generate_token();
@@ -0,0 +1,220 @@
+//! Bounded in-memory connection event log
+//!
+//! Keeps a timeline of state transitions, handshakes, errors and config
+//! updates for troubleshooting intermittent disconnects. Entries are derived
+//! from the notifications the daemon already broadcasts, so the log sees the
+//! same events as SSE subscribers.
+
+use std::collections::VecDeque;
+
+use super::ipc::{ConnectionState, EventKind, EventLogEntry};
+
+/// Maximum number of entries kept (oldest are dropped first)
+pub const EVENT_LOG_CAPACITY: usize = 200;
+
+/// Ring buffer of recent connection events
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: VecDeque<EventLogEntry>,
+    /// Last state recorded, so periodic status updates don't repeat it
+    last_state: Option<ConnectionState>,
+    /// Last client handshake time recorded (ms since epoch)
+    last_handshake_ms: Option<u64>,
+}
+
+impl EventLog {
+    /// Create an empty event log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry timestamped now
+    pub fn record(&mut self, kind: EventKind, message: impl Into<String>) {
+        self.push(now_ms(), kind, message.into());
+    }
+
+    /// Record a client handshake if `timestamp_ms` is newer than the last one seen
+    pub fn record_handshake(&mut self, timestamp_ms: u64) {
+        if self.last_handshake_ms.is_some_and(|last| last >= timestamp_ms) {
+            return;
+        }
+        self.last_handshake_ms = Some(timestamp_ms);
+        self.push(timestamp_ms, EventKind::Handshake, "Handshake completed".to_string());
+    }
+
+    /// Record whatever a broadcast JSON-RPC notification says happened
+    ///
+    /// `error_message` is the daemon's current error, attached to transitions
+    /// into the error state.
+    pub fn record_notification(&mut self, notification: &serde_json::Value, error_message: Option<&str>) {
+        let method = notification.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+        let params = notification.get("params").cloned().unwrap_or_default();
+        let param = |key: &str| params.get(key).and_then(|v| v.as_str()).unwrap_or("?").to_string();
+
+        match method {
+            "status_changed" | "server_status_changed" => {
+                let Some(state) = params
+                    .get("state")
+                    .and_then(|s| serde_json::from_value::<ConnectionState>(s.clone()).ok())
+                else {
+                    return;
+                };
+                self.record_state(state, error_message);
+            }
+            "config_updated" => {
+                self.record(
+                    EventKind::ConfigUpdated,
+                    format!("Config updated (VPN IP {}, endpoint {})", param("vpn_ip"), param("server_endpoint")),
+                );
+            }
+            "config_update_failed" => {
+                let rolled_back = params.get("rolled_back").and_then(|v| v.as_bool()).unwrap_or(false);
+                self.record(
+                    EventKind::Error,
+                    format!(
+                        "Config update failed{}: {}",
+                        if rolled_back { " (rolled back)" } else { "" },
+                        param("error")
+                    ),
+                );
+            }
+            "peer_connected" => {
+                self.record(
+                    EventKind::Handshake,
+                    format!("Peer {} handshake from {}", param("public_key"), param("endpoint")),
+                );
+            }
+            "peer_disconnected" => {
+                self.record(
+                    EventKind::Peer,
+                    format!("Peer {} disconnected: {}", param("public_key"), param("reason")),
+                );
+            }
+            "peer_added" => {
+                self.record(EventKind::Peer, format!("Peer {} added", param("public_key")));
+            }
+            "peer_removed" => {
+                self.record(EventKind::Peer, format!("Peer {} removed", param("public_key")));
+            }
+            _ => {}
+        }
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Vec<EventLogEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Record a state transition (ignores repeats of the current state)
+    fn record_state(&mut self, state: ConnectionState, error_message: Option<&str>) {
+        let previous = self.last_state.replace(state);
+        if previous == Some(state) {
+            return;
+        }
+
+        let from = previous.map(state_name).unwrap_or("startup");
+        match (state, error_message) {
+            (ConnectionState::Error, Some(error)) => {
+                self.record(EventKind::Error, format!("{} -> error: {}", from, error));
+            }
+            _ => {
+                self.record(EventKind::StateChanged, format!("{} -> {}", from, state_name(state)));
+            }
+        }
+    }
+
+    fn push(&mut self, timestamp_ms: u64, kind: EventKind, message: String) {
+        if self.entries.len() == EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(EventLogEntry {
+            timestamp_ms,
+            kind,
+            message,
+        });
+    }
+}
+
+/// Wire name of a connection state (matches its serde form)
+fn state_name(state: ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Disconnected => "disconnected",
+        ConnectionState::Connecting => "connecting",
+        ConnectionState::Connected => "connected",
+        ConnectionState::Suspended => "suspended",
+        ConnectionState::ServerUnreachable => "server_unreachable",
+        ConnectionState::Draining => "draining",
+        ConnectionState::Disconnecting => "disconnecting",
+        ConnectionState::Error => "error",
+    }
+}
+
+/// Current time in milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(state: &str) -> serde_json::Value {
+        serde_json::json!({"jsonrpc": "2.0", "method": "status_changed", "params": {"state": state}})
+    }
+
+    #[test]
+    fn test_records_transitions_once() {
+        let mut log = EventLog::new();
+
+        log.record_notification(&status("connecting"), None);
+        log.record_notification(&status("connected"), None);
+        // Periodic bandwidth updates repeat the same state
+        log.record_notification(&status("connected"), None);
+        log.record_notification(&status("error"), Some("Handshake timed out after 30s"));
+
+        let messages: Vec<_> = log.entries().into_iter().map(|e| (e.kind, e.message)).collect();
+        assert_eq!(
+            messages,
+            vec![
+                (EventKind::StateChanged, "startup -> connecting".to_string()),
+                (EventKind::StateChanged, "connecting -> connected".to_string()),
+                (EventKind::Error, "connected -> error: Handshake timed out after 30s".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handshakes_and_config_updates() {
+        let mut log = EventLog::new();
+
+        log.record_handshake(1_000);
+        log.record_handshake(1_000); // same handshake seen on the next tick
+        log.record_handshake(2_000);
+        log.record_notification(
+            &serde_json::json!({"method": "config_updated", "params": {"vpn_ip": "10.0.0.2", "server_endpoint": "1.2.3.4:51820"}}),
+            None,
+        );
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].timestamp_ms, 1_000);
+        assert_eq!(entries[1].kind, EventKind::Handshake);
+        assert_eq!(entries[2].kind, EventKind::ConfigUpdated);
+        assert!(entries[2].message.contains("1.2.3.4:51820"));
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let mut log = EventLog::new();
+        for i in 0..EVENT_LOG_CAPACITY + 5 {
+            log.record(EventKind::Peer, format!("event {}", i));
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), EVENT_LOG_CAPACITY);
+        assert_eq!(entries[0].message, "event 5");
+    }
+}
@@ -3,6 +3,8 @@
 //! Defines the request/response types for communication between
 //! the Flutter UI client and the Rust VPN daemon.
 
+use std::path::{Path, PathBuf};
+
 use serde::{Deserialize, Serialize};
 
 /// JSON-RPC 2.0 request
@@ -57,6 +59,7 @@ pub const CONNECTION_FAILED: i32 = -3;
 pub const INVALID_CONFIG: i32 = -4;
 pub const CONFIG_VALIDATION_FAILED: i32 = -5;
 pub const UPDATE_FAILED: i32 = -6;
+pub const NOT_SUSPENDED: i32 = -7;
 
 // Application-specific error codes (server mode)
 pub const SERVER_NOT_RUNNING: i32 = -10;
@@ -66,11 +69,52 @@ pub const PEER_ALREADY_EXISTS: i32 = -13;
 pub const INVALID_PUBLIC_KEY: i32 = -14;
 pub const INVALID_ALLOWED_IPS: i32 = -15;
 
+// Authorization error codes
+pub const READ_ONLY_TOKEN: i32 = -20;
+
 /// Connect request parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectParams {
     /// WireGuard configuration content (not a file path)
+    #[serde(default)]
     pub config: String,
+    /// Config file to read instead of `config` (remembered for `reload_config`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_path: Option<PathBuf>,
+    /// Leave the system resolver alone instead of applying the config's DNS
+    #[serde(default)]
+    pub no_dns: bool,
+}
+
+/// Config text from a connect/start request: inline `config` or the file at `config_path`
+pub fn read_config_source(
+    config: &str,
+    config_path: Option<&Path>,
+    config_dir: Option<&Path>,
+) -> Result<String, String> {
+    match config_path {
+        Some(_) if !config.is_empty() => Err("Pass either config or config_path, not both".to_string()),
+        Some(path) => read_config_file(path, config_dir),
+        None => Ok(config.to_string()),
+    }
+}
+
+/// Read the config file at `path`, which must resolve to a file inside `config_dir`
+///
+/// The daemon runs as root, so API clients only get it to read files from the
+/// directory given with `--config-dir`; without one, `config_path` is refused.
+/// Errors don't tell files outside it apart from missing ones.
+pub fn read_config_file(path: &Path, config_dir: Option<&Path>) -> Result<String, String> {
+    let config_dir = config_dir
+        .ok_or_else(|| "config_path is disabled; start the daemon with --config-dir".to_string())?;
+    let outside = || format!("config_path must be a file inside {}", config_dir.display());
+
+    let dir = config_dir.canonicalize().map_err(|_| outside())?;
+    let resolved = path.canonicalize().map_err(|_| outside())?;
+    if !resolved.starts_with(&dir) || !resolved.is_file() {
+        return Err(outside());
+    }
+    std::fs::read_to_string(&resolved).map_err(|e| format!("Cannot read {}: {}", path.display(), e))
 }
 
 /// VPN connection state
@@ -80,10 +124,30 @@ pub enum ConnectionState {
     Disconnected,
     Connecting,
     Connected,
+    /// Tunnel paused: TUN and routes kept, traffic blackholed until resume
+    Suspended,
+    /// Server not responding (ICMP port unreachable); the client keeps re-handshaking
+    ServerUnreachable,
+    /// Server still serving established sessions but refusing new ones (before a `stop`)
+    Draining,
     Disconnecting,
     Error,
 }
 
+impl ConnectionState {
+    /// Check if a VPN is up or coming up (a new connect/start must be refused)
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Connecting
+                | ConnectionState::Connected
+                | ConnectionState::Suspended
+                | ConnectionState::ServerUnreachable
+                | ConnectionState::Draining
+        )
+    }
+}
+
 /// Status response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusResponse {
@@ -96,12 +160,121 @@ pub struct StatusResponse {
     pub connected_at: Option<String>,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    #[serde(default)]
+    pub inner_bytes_sent: u64,
+    #[serde(default)]
+    pub inner_bytes_received: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_handshake: Option<String>,
+    /// Sessions re-established since connecting (rekeys and silent reconnects)
+    #[serde(default)]
+    pub reconnect_count: u64,
+    /// Seconds since the current session was established
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_session_age: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
 }
 
+/// Ping response (liveness check, takes no daemon locks)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingResponse {
+    pub pong: bool,
+    /// Daemon time in milliseconds since the Unix epoch
+    pub ts: u64,
+}
+
+/// Daemon version response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub version: String,
+}
+
+/// Log level accepted by `PUT /api/v1/loglevel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Set log level request params
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLogLevelParams {
+    pub level: LogLevel,
+}
+
+/// Log level response (active filter directives)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevelResponse {
+    pub level: String,
+}
+
+/// Kind of entry in the connection event log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Connection state transition (e.g. connecting -> connected)
+    StateChanged,
+    /// Handshake completed (client session or server peer)
+    Handshake,
+    /// Connection or config update error
+    Error,
+    /// Config applied via update_config
+    ConfigUpdated,
+    /// Server peer added, removed or disconnected
+    Peer,
+}
+
+/// One entry in the connection event log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLogEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub kind: EventKind,
+    pub message: String,
+}
+
+/// Event log response (oldest entry first)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLogResponse {
+    pub entries: Vec<EventLogEntry>,
+}
+
+/// Output layout for `show` / `GET /api/v1/show`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShowFormat {
+    /// Human-readable, like `wg show`
+    #[default]
+    Pretty,
+    /// Tab-separated, like `wg show <if> dump`
+    Dump,
+}
+
+/// Show request params
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShowParams {
+    #[serde(default)]
+    pub format: ShowFormat,
+}
+
+/// Show response (`wg`-compatible text)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowResponse {
+    pub output: String,
+}
+
+/// State dump response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpStateResponse {
+    /// File the dump was written to
+    pub path: String,
+}
+
 /// Status changed notification params
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusChangedParams {
@@ -114,6 +287,25 @@ pub struct StatusChangedParams {
     pub connected_at: Option<String>,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    #[serde(default)]
+    pub inner_bytes_sent: u64,
+    #[serde(default)]
+    pub inner_bytes_received: u64,
+}
+
+/// Throughput notification params (sent every second while connected)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThroughputParams {
+    /// Time covered by the rates (0 on the first sample after connecting)
+    pub interval_ms: u64,
+    pub tx_bytes_per_sec: u64,
+    pub rx_bytes_per_sec: u64,
+    pub inner_tx_bytes_per_sec: u64,
+    pub inner_rx_bytes_per_sec: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub inner_bytes_sent: u64,
+    pub inner_bytes_received: u64,
 }
 
 /// Error notification params
@@ -146,6 +338,28 @@ pub struct UpdateConfigResponse {
     pub server_endpoint: Option<String>,
 }
 
+/// Reload config response
+///
+/// Client mode reconnects through the `update_config` path and reports the new
+/// address; server mode applies the peer changes to the running server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReloadConfigResponse {
+    pub reloaded: bool,
+    /// File the config was re-read from
+    pub config_path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vpn_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_endpoint: Option<String>,
+    /// Server mode: peers added, removed and re-added with new settings
+    #[serde(default)]
+    pub peers_added: usize,
+    #[serde(default)]
+    pub peers_removed: usize,
+    #[serde(default)]
+    pub peers_changed: usize,
+}
+
 /// Config update notification params (for Flutter client)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigUpdatedParams {
@@ -171,7 +385,26 @@ pub struct ConfigUpdateFailedParams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartServerParams {
     /// WireGuard configuration content (bootstrap config, peers optional)
+    #[serde(default)]
     pub config: String,
+    /// Config file to read instead of `config` (remembered for `reload_config`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_path: Option<PathBuf>,
+}
+
+/// Drain request parameters (server mode)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DrainParams {
+    /// Also refuse rekeys, so sessions end when their keys expire (about 3 minutes)
+    #[serde(default)]
+    pub reject_rekeys: bool,
+}
+
+/// Drain response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainResponse {
+    pub draining: bool,
+    pub reject_rekeys: bool,
 }
 
 /// Add peer request parameters
@@ -184,6 +417,12 @@ pub struct AddPeerParams {
     /// Optional base64-encoded 32-byte preshared key
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preshared_key: Option<String>,
+    /// Keep the first learned endpoint instead of following roaming
+    #[serde(default)]
+    pub lock_endpoint: bool,
+    /// Optional human-readable name, shown in peer listings and used as the metrics label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 /// Remove peer request parameters
@@ -200,6 +439,41 @@ pub struct PeerStatusParams {
     pub public_key: String,
 }
 
+/// A peer whose AllowedIPs contain the looked-up address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteMatch {
+    pub public_key: String,
+    /// Most specific of the peer's AllowedIPs containing the address
+    pub allowed_ip: String,
+}
+
+/// Route lookup response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveRouteResponse {
+    pub ip: String,
+    /// Peer traffic to `ip` is sent to (None = no AllowedIPs match, dropped)
+    pub peer: Option<RouteMatch>,
+    /// Other peers whose AllowedIPs contain `ip` but lose to `peer`
+    pub shadowed: Vec<RouteMatch>,
+}
+
+impl ResolveRouteResponse {
+    /// Look `ip` up in the server's AllowedIPs, the same way packets are routed
+    pub fn resolve(peers: &crate::protocol::PeerManager, ip: std::net::IpAddr) -> Self {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let mut matches = peers.route_candidates(ip).into_iter().map(|(peer, net)| RouteMatch {
+            public_key: BASE64.encode(peer.public_key),
+            allowed_ip: net.to_string(),
+        });
+        Self {
+            ip: ip.to_string(),
+            peer: matches.next(),
+            shadowed: matches.collect(),
+        }
+    }
+}
+
 /// Server status response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerStatusResponse {
@@ -214,6 +488,10 @@ pub struct ServerStatusResponse {
     pub started_at: Option<String>,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    #[serde(default)]
+    pub inner_bytes_sent: u64,
+    #[serde(default)]
+    pub inner_bytes_received: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
 }
@@ -223,8 +501,17 @@ pub struct ServerStatusResponse {
 pub struct PeerInfo {
     /// Base64-encoded public key
     pub public_key: String,
+    /// Name given when the peer was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     /// Allowed IPs in CIDR notation
     pub allowed_ips: Vec<String>,
+    /// IPv4 subset of `allowed_ips`
+    #[serde(default)]
+    pub allowed_ips_v4: Vec<String>,
+    /// IPv6 subset of `allowed_ips`
+    #[serde(default)]
+    pub allowed_ips_v6: Vec<String>,
     /// Last known endpoint address (IP:port)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint: Option<String>,
@@ -233,15 +520,22 @@ pub struct PeerInfo {
     /// ISO 8601 timestamp of last successful handshake
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_handshake: Option<String>,
-    /// Bytes sent to this peer
+    /// Bytes sent to this peer (on-wire, encrypted)
     pub bytes_sent: u64,
-    /// Bytes received from this peer
+    /// Bytes received from this peer (on-wire, encrypted)
     pub bytes_received: u64,
+    /// Plaintext bytes sent to this peer
+    #[serde(default)]
+    pub inner_bytes_sent: u64,
+    /// Plaintext bytes received from this peer
+    #[serde(default)]
+    pub inner_bytes_received: u64,
 }
 
 /// List peers response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListPeersResponse {
+    /// Sorted by public key bytes, so the order is the same on every call
     pub peers: Vec<PeerInfo>,
 }
 
@@ -311,6 +605,10 @@ pub struct ServerStatusChangedParams {
     pub connected_peer_count: usize,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    #[serde(default)]
+    pub inner_bytes_sent: u64,
+    #[serde(default)]
+    pub inner_bytes_received: u64,
 }
 
 impl JsonRpcResponse {
@@ -347,6 +645,67 @@ impl JsonRpcNotification {
     }
 }
 
+impl VersionResponse {
+    /// Version of the running daemon binary
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl PingResponse {
+    /// Build a pong stamped with the current time
+    pub fn now() -> Self {
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self { pong: true, ts }
+    }
+}
+
+impl LogLevel {
+    /// Filter directive for this level
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+/// Longest accepted peer name, in bytes
+pub const MAX_PEER_NAME_LEN: usize = 64;
+
+/// Trim a peer name from `add_peer`; blank names count as no name
+pub fn validate_peer_name(name: Option<&str>) -> Result<Option<String>, String> {
+    let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+    if name.len() > MAX_PEER_NAME_LEN {
+        return Err(format!("Peer name longer than {} bytes", MAX_PEER_NAME_LEN));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("Peer name contains control characters".to_string());
+    }
+    Ok(Some(name.to_string()))
+}
+
+impl PeerInfo {
+    /// Format the AllowedIPs of a single address family in CIDR notation
+    pub fn allowed_ips_of_family(allowed_ips: &[ipnet::IpNet], ipv6: bool) -> Vec<String> {
+        allowed_ips
+            .iter()
+            .filter(|ip| matches!(ip, ipnet::IpNet::V6(_)) == ipv6)
+            .map(|ip| ip.to_string())
+            .collect()
+    }
+}
+
 impl Default for StatusResponse {
     fn default() -> Self {
         Self {
@@ -356,7 +715,11 @@ impl Default for StatusResponse {
             connected_at: None,
             bytes_sent: 0,
             bytes_received: 0,
+            inner_bytes_sent: 0,
+            inner_bytes_received: 0,
             last_handshake: None,
+            reconnect_count: 0,
+            current_session_age: None,
             error_message: None,
         }
     }
@@ -373,7 +736,49 @@ impl Default for ServerStatusResponse {
             started_at: None,
             bytes_sent: 0,
             bytes_received: 0,
+            inner_bytes_sent: 0,
+            inner_bytes_received: 0,
             error_message: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_config_source_only_from_config_dir() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let inside = config_dir.path().join("wg0.conf");
+        let outside = elsewhere.path().join("secret");
+        std::fs::write(&inside, "[Interface]\n").unwrap();
+        std::fs::write(&outside, "root:x:0:0\n").unwrap();
+        let dir = Some(config_dir.path());
+
+        assert_eq!(read_config_source("", Some(&inside), dir).unwrap(), "[Interface]\n");
+        assert_eq!(read_config_source("inline", None, None).unwrap(), "inline");
+        assert!(read_config_source("inline", Some(&inside), dir).is_err());
+
+        // Paths are refused unless the daemon was given a config dir
+        assert!(read_config_source("", Some(&inside), None).is_err());
+
+        // Files outside it, escapes through `..`, and missing files read the same
+        let escape = config_dir.path().join("..").join(elsewhere.path().file_name().unwrap()).join("secret");
+        for path in [&outside, &escape, &config_dir.path().join("missing.conf")] {
+            let error = read_config_source("", Some(path), dir).unwrap_err();
+            assert!(error.starts_with("config_path must be a file inside"), "{}", error);
+            assert!(!error.contains("root:x"));
+        }
+        assert!(read_config_source("", Some(config_dir.path()), dir).is_err());
+    }
+
+    #[test]
+    fn test_connect_params_no_dns() {
+        let params: ConnectParams = serde_json::from_str(r#"{"config": "[Interface]"}"#).unwrap();
+        assert!(!params.no_dns);
+        let params: ConnectParams = serde_json::from_str(r#"{"config": "[Interface]", "no_dns": true}"#).unwrap();
+        assert!(params.no_dns);
+    }
+}
@@ -0,0 +1,61 @@
+//! Runtime log level control
+//!
+//! Wraps a tracing-subscriber reload handle so the daemon can swap the active
+//! `EnvFilter` without a restart (and without dropping the VPN).
+
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use super::ipc::LogLevel;
+
+/// Reload handle for the global `EnvFilter` layer
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Handle for querying and changing the log level at runtime
+#[derive(Clone)]
+pub struct LogControl {
+    handle: FilterHandle,
+}
+
+impl LogControl {
+    /// Wrap the reload handle of the installed filter layer
+    pub fn new(handle: FilterHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Current filter directives (e.g. "info"), or None if the subscriber is gone
+    pub fn current(&self) -> Option<String> {
+        self.handle.with_current(|filter| filter.to_string()).ok()
+    }
+
+    /// Replace the active filter with a single global level
+    pub fn set_level(&self, level: LogLevel) -> Result<(), String> {
+        self.handle
+            .reload(EnvFilter::new(level.as_str()))
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_set_level_reloads_filter() {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let _subscriber = Registry::default().with(layer);
+
+        let control = LogControl::new(handle);
+        assert_eq!(control.current().as_deref(), Some("info"));
+
+        control.set_level(LogLevel::Trace).unwrap();
+        assert_eq!(control.current().as_deref(), Some("trace"));
+    }
+
+    #[test]
+    fn test_log_level_parse() {
+        let level: LogLevel = serde_json::from_str("\"debug\"").unwrap();
+        assert_eq!(level, LogLevel::Debug);
+        assert!(serde_json::from_str::<LogLevel>("\"verbose\"").is_err());
+    }
+}
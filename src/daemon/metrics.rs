@@ -0,0 +1,157 @@
+//! Prometheus metrics (`GET /api/v1/metrics`)
+//!
+//! Renders traffic totals, peer counts and per-peer handshake ages in the
+//! Prometheus text exposition format. In client mode the server is the single
+//! peer. Peers are labelled by name, else by the start of their public key.
+
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use super::{ConnectionState, DaemonState, VpnMode};
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Snapshot of everything the endpoint reports
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub peers: Vec<PeerMetrics>,
+}
+
+/// Snapshot of one peer
+#[derive(Debug, Clone)]
+pub struct PeerMetrics {
+    pub label: String,
+    pub connected: bool,
+    pub last_handshake: Option<SystemTime>,
+}
+
+impl Metrics {
+    /// Capture the running client or server (only the totals when idle)
+    pub async fn capture(state: &DaemonState) -> Self {
+        let stats = &state.traffic_stats;
+        let peers = match &state.mode {
+            Some(VpnMode::Client { current_config, .. }) => current_config
+                .peers
+                .first()
+                .map(|peer| {
+                    let last_handshake = stats.last_handshake_ms().map(|ms| UNIX_EPOCH + Duration::from_millis(ms));
+                    PeerMetrics {
+                        label: BASE64.encode(peer.public_key)[..8].to_string(),
+                        connected: state.connection_state == ConnectionState::Connected && last_handshake.is_some(),
+                        last_handshake,
+                    }
+                })
+                .into_iter()
+                .collect(),
+            Some(VpnMode::Server { peers, .. }) => peers
+                .read()
+                .await
+                .sorted()
+                .into_iter()
+                .map(|peer| PeerMetrics {
+                    label: peer.metrics_label(),
+                    connected: peer.has_session(),
+                    last_handshake: peer.last_handshake_at,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self {
+            bytes_sent: stats.get_sent(),
+            bytes_received: stats.get_received(),
+            peers,
+        }
+    }
+
+    /// Text exposition format, with handshake ages measured at `now`
+    pub fn render(&self, now: SystemTime) -> String {
+        let mut out = String::new();
+        metric(&mut out, "minnowvpn_bytes_sent_total", "counter", "Bytes sent through the tunnel");
+        let _ = writeln!(out, "minnowvpn_bytes_sent_total {}", self.bytes_sent);
+        metric(&mut out, "minnowvpn_bytes_received_total", "counter", "Bytes received through the tunnel");
+        let _ = writeln!(out, "minnowvpn_bytes_received_total {}", self.bytes_received);
+        metric(&mut out, "minnowvpn_peers_total", "gauge", "Configured peers");
+        let _ = writeln!(out, "minnowvpn_peers_total {}", self.peers.len());
+        metric(&mut out, "minnowvpn_peers_connected", "gauge", "Peers with an active session");
+        let _ = writeln!(out, "minnowvpn_peers_connected {}", self.peers.iter().filter(|p| p.connected).count());
+
+        metric(
+            &mut out,
+            "minnowvpn_peer_last_handshake_age_seconds",
+            "gauge",
+            "Seconds since the peer's last completed handshake",
+        );
+        for peer in &self.peers {
+            // Peers that never completed a handshake have no age
+            let Some(at) = peer.last_handshake else {
+                continue;
+            };
+            let age = now.duration_since(at).unwrap_or_default().as_secs();
+            let _ = writeln!(
+                out,
+                "minnowvpn_peer_last_handshake_age_seconds{{peer=\"{}\"}} {}",
+                escape_label(&peer.label),
+                age
+            );
+        }
+        out
+    }
+}
+
+/// `# HELP` and `# TYPE` lines for a metric
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Label value with `\`, `"` and newlines escaped
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_706_600_000);
+        let metrics = Metrics {
+            bytes_sent: 1024,
+            bytes_received: 2048,
+            peers: vec![
+                PeerMetrics {
+                    label: "alice \"laptop\"".to_string(),
+                    connected: true,
+                    last_handshake: Some(now - Duration::from_secs(42)),
+                },
+                PeerMetrics {
+                    label: "AQEBAQEB".to_string(),
+                    connected: false,
+                    last_handshake: None,
+                },
+            ],
+        };
+
+        let text = metrics.render(now);
+        assert!(text.contains("# TYPE minnowvpn_bytes_sent_total counter\nminnowvpn_bytes_sent_total 1024\n"));
+        assert!(text.contains("\nminnowvpn_bytes_received_total 2048\n"));
+        assert!(text.contains("\nminnowvpn_peers_total 2\n"));
+        assert!(text.contains("\nminnowvpn_peers_connected 1\n"));
+        assert!(text.contains("\nminnowvpn_peer_last_handshake_age_seconds{peer=\"alice \\\"laptop\\\"\"} 42\n"));
+        assert!(!text.contains("AQEBAQEB"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_daemon_reports_totals() {
+        let text = Metrics::capture(&DaemonState::default()).await.render(SystemTime::now());
+        assert!(text.contains("\nminnowvpn_bytes_sent_total 0\n"));
+        assert!(text.contains("\nminnowvpn_peers_total 0\n"));
+    }
+}
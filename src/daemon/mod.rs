@@ -6,27 +6,58 @@
 //!
 //! Authentication is provided via Bearer token stored in a protected file.
 
+pub mod api_client;
 pub mod auth;
+pub mod event_log;
 pub mod ipc;
+pub mod log_level;
+pub mod metrics;
 pub mod persistence;
+pub mod pid_file;
 pub mod routes;
+pub mod state_dump;
+pub mod throughput;
+pub mod timestamp;
+pub mod wg_show;
 
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch, Mutex, RwLock};
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use ipnet::IpNet;
 
-use crate::error::ConfigError;
+use crate::error::{ConfigError, NetworkError, ProtocolError};
 use crate::protocol::session::PeerManager;
 use crate::server::{PeerEvent, PeerUpdate};
+use crate::client::{ClientCommand, ClientEvent, HandshakeProgress};
 use crate::{MinnowVpnError, WireGuardClient, WireGuardConfig, WireGuardServer};
 
+use event_log::EventLog;
 use ipc::*;
+use log_level::LogControl;
+use persistence::PersistedMode;
+use pid_file::PidFile;
+use state_dump::ModeDump;
+use throughput::ThroughputMeter;
 
 // Re-export TrafficStats from protocol layer for backwards compatibility
 pub use crate::protocol::session::TrafficStats;
 
+/// How long a new client connection may run without completing a handshake
+pub const CONNECT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client events buffered for forwarding (more are dropped, never blocking the tunnel)
+const CLIENT_EVENT_CAPACITY: usize = 32;
+
+/// Waits between auto-connect attempts at startup (the last one repeats forever)
+const AUTO_CONNECT_DELAYS: [Duration; 4] = [
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+];
+
 // ============================================================================
 // VPN Mode and State Types
 // ============================================================================
@@ -40,16 +71,18 @@ pub enum VpnMode {
         /// Current config (for rollback on update failure)
         current_config: WireGuardConfig,
         /// Previous working config (set after successful handshake)
-        previous_config: Option<WireGuardConfig>,
+        previous_config: Option<Box<WireGuardConfig>>,
     },
     /// Server mode - accepts connections from VPN clients
     Server {
         listen_port: u16,
         interface_address: String,
+        /// Config the server was started with (peers added later live in `peers`)
+        config: WireGuardConfig,
         /// Channel to send peer updates to the server event loop
         peer_update_tx: mpsc::Sender<PeerUpdate>,
-        /// Shared peer manager for IPC queries
-        peers: Arc<Mutex<PeerManager>>,
+        /// Shared peer manager for IPC queries (read-locked; the server event loop writes)
+        peers: Arc<RwLock<PeerManager>>,
     },
 }
 
@@ -57,6 +90,12 @@ pub enum VpnMode {
 pub struct DaemonService {
     state: Arc<Mutex<DaemonState>>,
     status_tx: broadcast::Sender<String>,
+    /// Runtime log level control (None if the subscriber has no reload layer)
+    log_control: Option<LogControl>,
+    /// PID file removed on shutdown (`--pid-file`)
+    pid_file: Option<PidFile>,
+    /// Serve `/api/v1/metrics` without a token (`--metrics-no-auth`)
+    public_metrics: bool,
 }
 
 pub struct DaemonState {
@@ -72,6 +111,16 @@ pub struct DaemonState {
     pub error_message: Option<String>,
     /// Shutdown signal sender - send true to stop the VPN
     pub shutdown_tx: Option<watch::Sender<bool>>,
+    /// Suspend/resume commands for the running client (client mode only)
+    pub client_command_tx: Option<mpsc::Sender<ClientCommand>>,
+    /// Recent connection events for troubleshooting (bounded)
+    pub event_log: EventLog,
+    /// File the running config was read from (what `POST /api/v1/config/reload` re-reads)
+    pub config_path: Option<std::path::PathBuf>,
+    /// Directory `config_path` must be inside (`--config-dir`); None refuses config paths
+    pub config_dir: Option<std::path::PathBuf>,
+    /// The client leaves the system resolver alone (`no_dns` on connect)
+    pub no_dns: bool,
 }
 
 impl Default for DaemonState {
@@ -83,6 +132,11 @@ impl Default for DaemonState {
             traffic_stats: Arc::new(TrafficStats::new()),
             error_message: None,
             shutdown_tx: None,
+            client_command_tx: None,
+            event_log: EventLog::new(),
+            config_path: None,
+            config_dir: None,
+            no_dns: false,
         }
     }
 }
@@ -95,7 +149,50 @@ impl DaemonService {
         Self {
             state: Arc::new(Mutex::new(DaemonState::default())),
             status_tx,
+            log_control: None,
+            pid_file: None,
+            public_metrics: false,
+        }
+    }
+
+    /// Enable `PUT /api/v1/loglevel` using the subscriber's reload handle
+    pub fn with_log_control(mut self, log_control: LogControl) -> Self {
+        self.log_control = Some(log_control);
+        self
+    }
+
+    /// Remove `pid_file` when the daemon shuts down
+    pub fn with_pid_file(mut self, pid_file: PidFile) -> Self {
+        self.pid_file = Some(pid_file);
+        self
+    }
+
+    /// Allow connect/start to read `config_path` files from inside `config_dir`
+    pub fn with_config_dir(self, config_dir: std::path::PathBuf) -> Self {
+        self.state
+            .try_lock()
+            .expect("daemon state is not shared before the service runs")
+            .config_dir = Some(config_dir);
+        self
+    }
+
+    /// Let Prometheus scrape `/api/v1/metrics` without a bearer token
+    pub fn with_public_metrics(mut self, public_metrics: bool) -> Self {
+        self.public_metrics = public_metrics;
+        self
+    }
+
+    /// Refuse to start if a daemon already answers on the local HTTP port
+    ///
+    /// A second daemon would otherwise overwrite the running one's auth token
+    /// files (and restore a second tunnel) before failing to bind.
+    pub async fn ensure_not_running(port: u16) -> Result<(), MinnowVpnError> {
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        let probe = tokio::time::timeout(Duration::from_secs(1), tokio::net::TcpStream::connect(addr)).await;
+        if matches!(probe, Ok(Ok(_))) {
+            return Err(NetworkError::DaemonAlreadyRunning { addr: addr.to_string() }.into());
         }
+        Ok(())
     }
 
     /// Run the daemon service as an HTTP REST API server
@@ -106,8 +203,21 @@ impl DaemonService {
         use axum::middleware;
         use std::net::SocketAddr;
 
-        // Generate auth token
+        // Bind to localhost only, before writing tokens (loses any race with another daemon)
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::AddrInUse {
+                return NetworkError::DaemonAlreadyRunning { addr: addr.to_string() }.into();
+            }
+            MinnowVpnError::Config(ConfigError::ParseError {
+                line: 0,
+                message: format!("Failed to bind HTTP server to {}: {}", addr, e),
+            })
+        })?;
+
+        // Generate auth tokens (full control, and status-only for dashboards)
         let token = auth::generate_token();
+        let read_only_token = auth::generate_token();
 
         // Write token to file
         let token_file_path = auth::write_token_file(&token, token_path).map_err(|e| {
@@ -119,17 +229,26 @@ impl DaemonService {
 
         tracing::info!("Auth token written to {:?}", token_file_path);
 
+        let read_only_path = auth::write_read_only_token_file(&read_only_token, &token_file_path).map_err(|e| {
+            MinnowVpnError::Config(ConfigError::ParseError {
+                line: 0,
+                message: format!("Failed to write read-only auth token: {}", e),
+            })
+        })?;
+        tracing::info!("Read-only auth token written to {:?}", read_only_path);
+
         // In debug mode, also log the token for testing (remove in production)
         tracing::debug!("Auth token (for testing): {}", token);
 
         // Create auth state
-        let auth_state = auth::AuthState::new(token);
+        let mut auth_state = auth::AuthState::new(token).with_read_only_token(read_only_token);
+        if self.public_metrics {
+            tracing::info!("Serving /api/v1/metrics without authentication");
+            auth_state = auth_state.with_public_metrics();
+        }
 
         // Create app state for routes
-        let app_state = routes::AppState {
-            daemon_state: Arc::clone(&self.state),
-            status_tx: self.status_tx.clone(),
-        };
+        let app_state = self.app_state();
 
         // Build router with auth middleware
         // Note: The routes already have AppState via build_router
@@ -137,34 +256,49 @@ impl DaemonService {
         let app = routes::build_router(app_state)
             .layer(middleware::from_fn_with_state(auth_state, auth::auth_middleware));
 
-        // Bind to localhost only
-        let addr = SocketAddr::from(([127, 0, 0, 1], port));
-        let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
-            MinnowVpnError::Config(ConfigError::ParseError {
-                line: 0,
-                message: format!("Failed to bind HTTP server to {}: {}", addr, e),
-            })
-        })?;
-
         tracing::info!("HTTP daemon listening on http://{}", addr);
 
-        // Spawn bandwidth update task - sends status updates every second when connected
+        // Spawn event log recorder - keeps a timeline of every broadcast notification
+        Self::spawn_event_recorder(Arc::clone(&self.state), self.status_tx.subscribe());
+
+        // Spawn bandwidth update task - sends status and throughput updates every second when connected
         let bandwidth_state = Arc::clone(&self.state);
         let bandwidth_status_tx = self.status_tx.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+            let mut meter = ThroughputMeter::new();
             loop {
                 interval.tick().await;
 
                 // Only send updates when connected
-                let should_send = {
-                    let s = bandwidth_state.lock().await;
-                    s.connection_state == ConnectionState::Connected && s.mode.is_some()
+                let (should_send, throughput) = {
+                    let mut s = bandwidth_state.lock().await;
+                    // Client handshakes are only visible through the shared stats
+                    if let Some(handshake_ms) = s.traffic_stats.last_handshake_ms() {
+                        s.event_log.record_handshake(handshake_ms);
+                    }
+                    let changed = sync_server_reachability(&mut s);
+                    let connected = matches!(s.connection_state, ConnectionState::Connected | ConnectionState::Draining)
+                        && s.mode.is_some();
+                    let throughput = if connected {
+                        Some(meter.sample(&s.traffic_stats, std::time::Instant::now()))
+                    } else {
+                        meter.reset();
+                        None
+                    };
+                    (changed || connected, throughput)
                 };
 
                 if should_send {
                     let _ = Self::send_status_notification(&bandwidth_state, &bandwidth_status_tx).await;
                 }
+                if let Some(params) = throughput {
+                    let notification =
+                        JsonRpcNotification::new("throughput", serde_json::to_value(params).unwrap_or_default());
+                    if let Ok(json) = serde_json::to_string(&notification) {
+                        let _ = bandwidth_status_tx.send(json);
+                    }
+                }
             }
         });
 
@@ -179,6 +313,165 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Record every status/peer/config notification into the daemon's event log
+    fn spawn_event_recorder(state: Arc<Mutex<DaemonState>>, mut rx: broadcast::Receiver<String>) {
+        tokio::spawn(async move {
+            loop {
+                let json = match rx.recv().await {
+                    Ok(json) => json,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::debug!("Event log missed {} notifications", missed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                if let Ok(notification) = serde_json::from_str::<serde_json::Value>(&json) {
+                    let mut s = state.lock().await;
+                    let error_message = s.error_message.clone();
+                    s.event_log.record_notification(&notification, error_message.as_deref());
+                }
+            }
+        });
+    }
+
+    /// Shared state handed to the REST route handlers
+    fn app_state(&self) -> routes::AppState {
+        routes::AppState {
+            daemon_state: Arc::clone(&self.state),
+            status_tx: self.status_tx.clone(),
+            log_control: self.log_control.clone(),
+        }
+    }
+
+    /// Bring back the VPN described by a state dump
+    ///
+    /// TUN and socket are created fresh and sessions re-handshake. Returns false
+    /// if nothing was running when the dump was taken.
+    pub async fn restore_state(&self, path: &std::path::Path) -> Result<bool, MinnowVpnError> {
+        let restore_error = |reason: String| MinnowVpnError::Config(ConfigError::StateRestore { reason });
+
+        let dump = state_dump::read_state_dump(path).map_err(|e| ConfigError::InFile {
+            path: path.display().to_string(),
+            source: Box::new(ConfigError::Io(e)),
+        })?;
+        if !dump.wants_restore() {
+            tracing::info!("State dump {:?} has no active VPN, nothing to restore", path);
+            return Ok(false);
+        }
+
+        {
+            let mut s = self.state.lock().await;
+            if s.connection_state.is_active() {
+                return Err(restore_error("a VPN is already running".to_string()));
+            }
+            s.connection_state = ConnectionState::Connecting;
+            s.error_message = None;
+        }
+
+        let app_state = self.app_state();
+        let result = match dump.mode {
+            Some(ModeDump::Client { config, previous_config, no_dns }) => {
+                let config = WireGuardConfig::try_from(&config)?;
+                self.state.lock().await.no_dns = no_dns;
+                tracing::info!("Restoring client connection from {:?}", path);
+                let result = routes::start_client(&app_state, config, None).await.map(|_| ());
+                if let (Ok(()), Some(previous)) = (&result, previous_config) {
+                    let previous = WireGuardConfig::try_from(&*previous)?;
+                    if let Some(VpnMode::Client { previous_config, .. }) = &mut self.state.lock().await.mode {
+                        *previous_config = Some(Box::new(previous));
+                    }
+                }
+                result
+            }
+            Some(ModeDump::Server { config: dump, .. }) => {
+                let config = WireGuardConfig::try_from(&dump)?;
+                tracing::info!("Restoring server with {} peers from {:?}", config.peers.len(), path);
+                let result = routes::start_server(&app_state, config, None).await.map(|_| ());
+                if result.is_ok() {
+                    if let Some(VpnMode::Server { peers, .. }) = &self.state.lock().await.mode {
+                        dump.restore_peer_details(&mut *peers.write().await);
+                    }
+                }
+                result
+            }
+            None => Ok(()),
+        };
+
+        result.map(|_| true).map_err(|e| restore_error(e.message))
+    }
+
+    /// Bring back the VPN the user left running when the daemon last stopped
+    ///
+    /// Reads the persisted connection state and, if `desired_state` is connected,
+    /// keeps trying in the background (the network may not be up yet at boot)
+    /// until the VPN starts, the user disconnects, or another VPN is started.
+    pub fn auto_connect(&self) {
+        let Some(saved) = persistence::load_connection_state() else {
+            return;
+        };
+        let Some(config_text) = saved.config_to_restore() else {
+            tracing::debug!("Desired state is disconnected, not auto-connecting");
+            return;
+        };
+
+        let config = WireGuardConfig::from_string(config_text).and_then(|c| match saved.mode {
+            PersistedMode::Client => c.validate_client().map(|_| c),
+            PersistedMode::Server => Ok(c),
+        });
+        let config = match config {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("Stored config is invalid, not auto-connecting: {}", e);
+                return;
+            }
+        };
+
+        let app_state = self.app_state();
+        let config_path = saved.config_path.clone().map(std::path::PathBuf::from);
+        let (mode, no_dns) = (saved.mode, saved.no_dns);
+        tokio::spawn(async move {
+            let mut attempt = 0u32;
+            loop {
+                let still_wanted = persistence::load_connection_state()
+                    .is_some_and(|s| s.config_to_restore().is_some());
+                {
+                    let mut s = app_state.daemon_state.lock().await;
+                    if !still_wanted || s.connection_state.is_active() {
+                        tracing::info!("Auto-connect cancelled");
+                        return;
+                    }
+                    s.connection_state = ConnectionState::Connecting;
+                    s.error_message = None;
+                    s.config_path = config_path.clone();
+                    s.no_dns = no_dns;
+                }
+
+                let result = match mode {
+                    PersistedMode::Client => {
+                        tracing::info!("Auto-connecting to the last VPN server");
+                        routes::start_client(&app_state, config.clone(), None).await.map(|_| ())
+                    }
+                    PersistedMode::Server => {
+                        tracing::info!("Restarting the VPN server from the last session");
+                        routes::start_server(&app_state, config.clone(), None).await.map(|_| ())
+                    }
+                };
+                let Err(e) = result else {
+                    return;
+                };
+
+                let delay = auto_connect_delay(attempt);
+                attempt += 1;
+                if let Err(e) = persistence::update_retry_count(attempt) {
+                    tracing::debug!("Failed to update retry count: {}", e);
+                }
+                tracing::warn!("Auto-connect attempt {} failed: {}. Retrying in {:?}...", attempt, e.message, delay);
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+
     /// Process a JSON-RPC request (used internally by handlers)
     async fn process_request(
         request_str: &str,
@@ -240,9 +533,7 @@ impl DaemonService {
         // Check if already running (client or server)
         {
             let s = state.lock().await;
-            if s.connection_state == ConnectionState::Connected
-                || s.connection_state == ConnectionState::Connecting
-            {
+            if s.connection_state.is_active() {
                 return JsonRpcResponse::error(
                     request.id,
                     ALREADY_CONNECTED,
@@ -252,17 +543,22 @@ impl DaemonService {
         }
 
         // Update state to connecting
-        {
+        let config_dir = {
             let mut s = state.lock().await;
             s.connection_state = ConnectionState::Connecting;
             s.error_message = None;
-        }
+            s.config_path = params.config_path.clone();
+            s.no_dns = params.no_dns;
+            s.config_dir.clone()
+        };
 
         // Send status notification
         let _ = Self::send_status_notification(state, status_tx).await;
 
         // Parse config
-        let config = match WireGuardConfig::from_string(&params.config) {
+        let config = match read_config_source(&params.config, params.config_path.as_deref(), config_dir.as_deref())
+            .and_then(|text| WireGuardConfig::from_string(&text).map_err(|e| e.to_string()))
+        {
             Ok(c) => c,
             Err(e) => {
                 let mut s = state.lock().await;
@@ -281,8 +577,7 @@ impl DaemonService {
         let server_endpoint = config
             .peers
             .first()
-            .and_then(|p| p.endpoint.as_ref())
-            .map(|e| e.to_string())
+            .and_then(|p| p.endpoint_string())
             .unwrap_or_default();
 
         // Extract VPN IP for status
@@ -291,9 +586,9 @@ impl DaemonService {
             .unwrap_or_default();
 
         // Get traffic stats to pass to client
-        let traffic_stats = {
+        let (traffic_stats, no_dns) = {
             let s = state.lock().await;
-            Arc::clone(&s.traffic_stats)
+            (Arc::clone(&s.traffic_stats), s.no_dns)
         };
 
         // Clone config for storage before moving to client
@@ -301,7 +596,8 @@ impl DaemonService {
 
         // Create and start client with traffic stats
         match WireGuardClient::new(config, Some(traffic_stats)).await {
-            Ok(client) => {
+            Ok(mut client) => {
+                client.set_apply_dns(!no_dns);
                 // Create shutdown channel
                 let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
@@ -314,7 +610,7 @@ impl DaemonService {
                         current_config: config_for_storage,
                         previous_config: None,
                     });
-                    s.started_at = Some(chrono_now());
+                    s.started_at = Some(timestamp::now());
                     s.traffic_stats.reset(); // Reset counters for new connection
                     s.shutdown_tx = Some(shutdown_tx);
                 }
@@ -403,14 +699,18 @@ impl DaemonService {
                     connected_at: s.started_at.clone(),
                     bytes_sent: s.traffic_stats.get_sent(),
                     bytes_received: s.traffic_stats.get_received(),
+                    inner_bytes_sent: s.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: s.traffic_stats.get_inner_received(),
                     last_handshake: None,
+                    reconnect_count: s.traffic_stats.reconnect_count(),
+                    current_session_age: s.traffic_stats.current_session_age(),
                     error_message: s.error_message.clone(),
                 };
                 JsonRpcResponse::success(request.id, serde_json::to_value(status).unwrap())
             }
             Some(VpnMode::Server { listen_port, interface_address, peers, .. }) => {
                 // Get peer counts
-                let peers_guard = peers.blocking_lock();
+                let peers_guard = peers.read().await;
                 let peer_count = peers_guard.len();
                 let connected_peer_count = peers_guard.connected_count();
                 drop(peers_guard);
@@ -424,6 +724,8 @@ impl DaemonService {
                     started_at: s.started_at.clone(),
                     bytes_sent: s.traffic_stats.get_sent(),
                     bytes_received: s.traffic_stats.get_received(),
+                    inner_bytes_sent: s.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: s.traffic_stats.get_inner_received(),
                     error_message: s.error_message.clone(),
                 };
                 JsonRpcResponse::success(request.id, serde_json::to_value(status).unwrap())
@@ -436,7 +738,11 @@ impl DaemonService {
                     connected_at: None,
                     bytes_sent: 0,
                     bytes_received: 0,
+                    inner_bytes_sent: 0,
+                    inner_bytes_received: 0,
                     last_handshake: None,
+                    reconnect_count: 0,
+                    current_session_age: None,
                     error_message: s.error_message.clone(),
                 };
                 JsonRpcResponse::success(request.id, serde_json::to_value(status).unwrap())
@@ -461,6 +767,8 @@ impl DaemonService {
                     connected_at: s.started_at.clone(),
                     bytes_sent: s.traffic_stats.get_sent(),
                     bytes_received: s.traffic_stats.get_received(),
+                    inner_bytes_sent: s.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: s.traffic_stats.get_inner_received(),
                 };
                 JsonRpcNotification::new(
                     "status_changed",
@@ -469,7 +777,7 @@ impl DaemonService {
             }
             Some(VpnMode::Server { peers, .. }) => {
                 // For server mode, we send a different notification
-                let peers_guard = peers.blocking_lock();
+                let peers_guard = peers.read().await;
                 let peer_count = peers_guard.len();
                 let connected_peer_count = peers_guard.connected_count();
                 drop(peers_guard);
@@ -480,6 +788,8 @@ impl DaemonService {
                     connected_peer_count,
                     bytes_sent: s.traffic_stats.get_sent(),
                     bytes_received: s.traffic_stats.get_received(),
+                    inner_bytes_sent: s.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: s.traffic_stats.get_inner_received(),
                 };
                 JsonRpcNotification::new(
                     "server_status_changed",
@@ -494,6 +804,8 @@ impl DaemonService {
                     connected_at: None,
                     bytes_sent: 0,
                     bytes_received: 0,
+                    inner_bytes_sent: 0,
+                    inner_bytes_received: 0,
                 };
                 JsonRpcNotification::new(
                     "status_changed",
@@ -529,9 +841,11 @@ impl DaemonService {
         tokio::spawn(async move {
             let mut client = client;
             let mut shutdown_rx = shutdown_rx;
+            let traffic_stats = attach_client_commands(&mut client, &state, &status_tx).await;
 
             let result = tokio::select! {
                 result = client.run() => result,
+                e = connect_watchdog(traffic_stats, CONNECT_HANDSHAKE_TIMEOUT) => Err(e),
                 _ = async {
                     loop {
                         shutdown_rx.changed().await.ok();
@@ -562,6 +876,7 @@ impl DaemonService {
                 s.mode = None;
                 s.started_at = None;
                 s.shutdown_tx = None;
+                s.client_command_tx = None;
             }
 
             // Send status notification
@@ -609,20 +924,11 @@ impl DaemonService {
         };
 
         // Validate config has required fields for client mode
-        if new_config.peers.is_empty() {
-            return JsonRpcResponse::error(
-                request.id,
-                CONFIG_VALIDATION_FAILED,
-                "Config must have at least one peer",
-            );
-        }
-
-        let peer = &new_config.peers[0];
-        if peer.endpoint.is_none() {
+        if let Err(e) = new_config.validate_client() {
             return JsonRpcResponse::error(
                 request.id,
                 CONFIG_VALIDATION_FAILED,
-                "Peer must have an endpoint for client mode",
+                format!("Invalid config: {}", e),
             );
         }
 
@@ -636,7 +942,7 @@ impl DaemonService {
         let new_server_endpoint = new_config
             .peers
             .first()
-            .and_then(|p| p.endpoint.map(|e| e.to_string()))
+            .and_then(|p| p.endpoint_string())
             .unwrap_or_default();
 
         let mut s = state.lock().await;
@@ -644,7 +950,10 @@ impl DaemonService {
         // Step 2: Check current state
         let (current_config, was_connected) = match &s.mode {
             Some(VpnMode::Client { current_config, .. }) => {
-                let connected = s.connection_state == ConnectionState::Connected;
+                let connected = matches!(
+                    s.connection_state,
+                    ConnectionState::Connected | ConnectionState::ServerUnreachable
+                );
                 (Some(current_config.clone()), connected)
             }
             Some(VpnMode::Server { .. }) => {
@@ -683,9 +992,9 @@ impl DaemonService {
         }
 
         // Step 4: Reconnect with new config
-        let traffic_stats = {
+        let (traffic_stats, no_dns) = {
             let s = state.lock().await;
-            Arc::clone(&s.traffic_stats)
+            (Arc::clone(&s.traffic_stats), s.no_dns)
         };
 
         // Clone new config for storage
@@ -693,7 +1002,8 @@ impl DaemonService {
 
         // Create and start client with new config
         match WireGuardClient::new(new_config, Some(traffic_stats)).await {
-            Ok(client) => {
+            Ok(mut client) => {
+                client.set_apply_dns(!no_dns);
                 // Create shutdown channel
                 let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
@@ -704,9 +1014,9 @@ impl DaemonService {
                         vpn_ip: new_vpn_ip.clone(),
                         server_endpoint: new_server_endpoint.clone(),
                         current_config: config_for_storage,
-                        previous_config: current_config, // Store old config for potential future rollback
+                        previous_config: current_config.map(Box::new), // Store old config for potential future rollback
                     });
-                    s.started_at = Some(chrono_now());
+                    s.started_at = Some(timestamp::now());
                     s.shutdown_tx = Some(shutdown_tx);
                 }
 
@@ -754,7 +1064,7 @@ impl DaemonService {
                     let rollback_endpoint = prev_config
                         .peers
                         .first()
-                        .and_then(|p| p.endpoint.map(|ep| ep.to_string()))
+                        .and_then(|p| p.endpoint_string())
                         .unwrap_or_default();
 
                     // Get fresh traffic stats for rollback attempt
@@ -766,7 +1076,8 @@ impl DaemonService {
                     match WireGuardClient::new(prev_config.clone(), Some(rollback_traffic_stats))
                         .await
                     {
-                        Ok(rollback_client) => {
+                        Ok(mut rollback_client) => {
+                            rollback_client.set_apply_dns(!no_dns);
                             tracing::info!(
                                 "Rollback successful, reconnected with previous config"
                             );
@@ -784,7 +1095,7 @@ impl DaemonService {
                                     current_config: prev_config,
                                     previous_config: None, // No previous after rollback
                                 });
-                                s.started_at = Some(chrono_now());
+                                s.started_at = Some(timestamp::now());
                                 s.shutdown_tx = Some(rollback_shutdown_tx);
                             }
 
@@ -908,9 +1219,7 @@ impl DaemonService {
         // Check if already running (client or server)
         {
             let s = state.lock().await;
-            if s.connection_state == ConnectionState::Connected
-                || s.connection_state == ConnectionState::Connecting
-            {
+            if s.connection_state.is_active() {
                 return JsonRpcResponse::error(
                     request.id,
                     ALREADY_RUNNING,
@@ -920,16 +1229,20 @@ impl DaemonService {
         }
 
         // Update state to connecting
-        {
+        let config_dir = {
             let mut s = state.lock().await;
             s.connection_state = ConnectionState::Connecting;
             s.error_message = None;
-        }
+            s.config_path = params.config_path.clone();
+            s.config_dir.clone()
+        };
 
         let _ = Self::send_status_notification(state, status_tx).await;
 
         // Parse config
-        let config = match WireGuardConfig::from_string(&params.config) {
+        let config = match read_config_source(&params.config, params.config_path.as_deref(), config_dir.as_deref())
+            .and_then(|text| WireGuardConfig::from_string(&text).map_err(|e| e.to_string()))
+        {
             Ok(c) => c,
             Err(e) => {
                 let mut s = state.lock().await;
@@ -964,11 +1277,11 @@ impl DaemonService {
         let (peer_event_tx, mut peer_event_rx) = mpsc::channel(32);
 
         // Create shared peer manager
-        let peers = Arc::new(Mutex::new(PeerManager::new()));
+        let peers = Arc::new(RwLock::new(PeerManager::new()));
 
         // Initialize peers from bootstrap config (if any)
         {
-            let mut peers_guard = peers.lock().await;
+            let mut peers_guard = peers.write().await;
             for peer_config in &config.peers {
                 let allowed_ips: Vec<IpNet> = peer_config
                     .allowed_ips
@@ -984,12 +1297,15 @@ impl DaemonService {
                     peer_config.preshared_key,
                     allowed_ips,
                 );
+                if let Some(mut peer) = peers_guard.get_peer(&peer_config.public_key) {
+                    peer.lock_endpoint = peer_config.lock_endpoint;
+                }
             }
         }
 
         // Create server with channels
         match WireGuardServer::new_with_channels(
-            config,
+            config.clone(),
             Arc::clone(&peers),
             peer_update_rx,
             peer_event_tx,
@@ -1007,10 +1323,11 @@ impl DaemonService {
                     s.mode = Some(VpnMode::Server {
                         listen_port,
                         interface_address: interface_address.clone(),
+                        config,
                         peer_update_tx: peer_update_tx.clone(),
                         peers: Arc::clone(&peers),
                     });
-                    s.started_at = Some(chrono_now());
+                    s.started_at = Some(timestamp::now());
                     s.traffic_stats.reset();
                     s.shutdown_tx = Some(shutdown_tx);
                 }
@@ -1159,22 +1476,28 @@ impl DaemonService {
         };
         drop(s);
 
-        let peers_guard = peers.lock().await;
+        let peers_guard = peers.read().await;
         let peer_list: Vec<PeerInfo> = peers_guard
-            .iter()
+            .sorted()
+            .into_iter()
             .map(|peer_state| {
                 PeerInfo {
                     public_key: BASE64.encode(&peer_state.public_key),
+                    name: peer_state.name.clone(),
                     allowed_ips: peer_state
                         .allowed_ips
                         .iter()
                         .map(|ip| ip.to_string())
                         .collect(),
                     endpoint: peer_state.endpoint.map(|e| e.to_string()),
+                    allowed_ips_v4: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, false),
+                    allowed_ips_v6: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, true),
                     has_session: peer_state.session.is_some(),
-                    last_handshake: peer_state.last_handshake.map(|_| chrono_now()),
+                    last_handshake: peer_state.last_handshake_at.map(timestamp::format),
                     bytes_sent: peer_state.traffic_stats.get_sent(),
                     bytes_received: peer_state.traffic_stats.get_received(),
+                    inner_bytes_sent: peer_state.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: peer_state.traffic_stats.get_inner_received(),
                 }
             })
             .collect();
@@ -1230,26 +1553,32 @@ impl DaemonService {
         };
         drop(s);
 
-        let peers_guard = peers.lock().await;
-        match peers_guard.get_peer(&public_key) {
+        let peers_guard = peers.read().await;
+        let response = match peers_guard.get_peer(&public_key) {
             Some(peer_state) => {
                 let info = PeerInfo {
                     public_key: params.public_key,
+                    name: peer_state.name.clone(),
                     allowed_ips: peer_state
                         .allowed_ips
                         .iter()
                         .map(|ip| ip.to_string())
                         .collect(),
                     endpoint: peer_state.endpoint.map(|e| e.to_string()),
+                    allowed_ips_v4: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, false),
+                    allowed_ips_v6: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, true),
                     has_session: peer_state.session.is_some(),
-                    last_handshake: peer_state.last_handshake.map(|_| chrono_now()),
+                    last_handshake: peer_state.last_handshake_at.map(timestamp::format),
                     bytes_sent: peer_state.traffic_stats.get_sent(),
                     bytes_received: peer_state.traffic_stats.get_received(),
+                    inner_bytes_sent: peer_state.traffic_stats.get_inner_sent(),
+                    inner_bytes_received: peer_state.traffic_stats.get_inner_received(),
                 };
                 JsonRpcResponse::success(request.id, serde_json::to_value(info).unwrap())
             }
             None => JsonRpcResponse::error(request.id, PEER_NOT_FOUND, "Peer not found"),
-        }
+        };
+        response
     }
 
     /// Handle add peer request (server mode - dynamic peer management)
@@ -1323,6 +1652,11 @@ impl DaemonService {
             None => None,
         };
 
+        let name = match validate_peer_name(params.name.as_deref()) {
+            Ok(name) => name,
+            Err(message) => return JsonRpcResponse::error(request.id, INVALID_PARAMS, message),
+        };
+
         let s = state.lock().await;
 
         let (peer_update_tx, peers) = match &s.mode {
@@ -1343,7 +1677,7 @@ impl DaemonService {
 
         // Check peer doesn't already exist
         {
-            let peers_guard = peers.lock().await;
+            let peers_guard = peers.read().await;
             if peers_guard.has_peer(&public_key) {
                 return JsonRpcResponse::error(
                     request.id,
@@ -1359,6 +1693,8 @@ impl DaemonService {
                 public_key,
                 psk,
                 allowed_ips,
+                lock_endpoint: params.lock_endpoint,
+                name,
             })
             .await
             .is_err()
@@ -1431,13 +1767,14 @@ impl DaemonService {
 
         // Check peer exists and get connection status
         let was_connected = {
-            let peers_guard = peers.lock().await;
-            match peers_guard.get_peer(&public_key) {
+            let peers_guard = peers.read().await;
+            let was_connected = match peers_guard.get_peer(&public_key) {
                 Some(peer) => peer.session.is_some(),
                 None => {
                     return JsonRpcResponse::error(request.id, PEER_NOT_FOUND, "Peer not found");
                 }
-            }
+            };
+            was_connected
         };
 
         // Send update to server event loop
@@ -1522,15 +1859,303 @@ impl DaemonService {
         // Give background task time to clean up
         tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
 
+        if let Some(ref pid_file) = self.pid_file {
+            pid_file.remove();
+        }
+
         Ok(())
     }
 }
 
-/// Get current time as ISO string (simple implementation without chrono crate)
-fn chrono_now() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    format!("{}s since epoch", duration.as_secs())
+/// Give a client its suspend/resume channel and store the sender in daemon state
+///
+/// Also forwards the client's handshake progress to `status_tx` (see
+/// [`progress_notification`]) and finishes a resume once its handshake
+/// completes (see [`finish_resume`]). Returns the shared traffic stats for
+/// the connect watchdog.
+pub(crate) async fn attach_client_commands(
+    client: &mut WireGuardClient,
+    state: &Arc<Mutex<DaemonState>>,
+    status_tx: &broadcast::Sender<String>,
+) -> Arc<TrafficStats> {
+    let (command_tx, command_rx) = mpsc::channel(4);
+    client.set_command_channel(command_rx);
+
+    // Ends when the client (and with it the sender) is dropped
+    let (event_tx, mut event_rx) = mpsc::channel(CLIENT_EVENT_CAPACITY);
+    client.set_event_channel(event_tx);
+    let status_tx = status_tx.clone();
+    let events_state = Arc::clone(state);
+    tokio::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            if matches!(event, ClientEvent::HandshakeCompleted { .. }) && finish_resume(&events_state).await {
+                let _ = DaemonService::send_status_notification(&events_state, &status_tx).await;
+            }
+            if let Some(notification) = progress_notification(&event) {
+                if let Ok(json) = serde_json::to_string(&notification) {
+                    let _ = status_tx.send(json);
+                }
+            }
+        }
+    });
+
+    let mut s = state.lock().await;
+    s.client_command_tx = Some(command_tx);
+    Arc::clone(&s.traffic_stats)
+}
+
+/// `handshake_progress` notification for a client event (None for other events)
+///
+/// Completed handshakes, including rekeys, are reported as the `complete` step.
+fn progress_notification(event: &ClientEvent) -> Option<JsonRpcNotification> {
+    let params = match event {
+        ClientEvent::Progress(HandshakeProgress::ResolvingEndpoint { host }) => {
+            serde_json::json!({"step": "resolving_endpoint", "host": host})
+        }
+        ClientEvent::Progress(HandshakeProgress::Attempt { attempt }) => {
+            serde_json::json!({"step": "attempt", "attempt": attempt})
+        }
+        ClientEvent::Progress(HandshakeProgress::InitiationSent { endpoint }) => {
+            serde_json::json!({"step": "initiation_sent", "endpoint": endpoint.to_string()})
+        }
+        ClientEvent::Progress(HandshakeProgress::CookieReceived) => serde_json::json!({"step": "cookie_received"}),
+        ClientEvent::HandshakeCompleted { endpoint } => {
+            serde_json::json!({"step": "complete", "endpoint": endpoint.to_string()})
+        }
+        ClientEvent::Rekeyed => serde_json::json!({"step": "complete"}),
+        ClientEvent::EndpointChanged { .. } | ClientEvent::Disconnected { .. } => return None,
+    };
+    Some(JsonRpcNotification::new("handshake_progress", params))
+}
+
+/// Move a resuming client from `Connecting` to `Connected` once its handshake completed
+///
+/// Returns false (and changes nothing) if the client was not resuming.
+async fn finish_resume(state: &Arc<Mutex<DaemonState>>) -> bool {
+    let mut s = state.lock().await;
+    if !matches!(s.mode, Some(VpnMode::Client { .. })) || s.connection_state != ConnectionState::Connecting {
+        return false;
+    }
+    s.connection_state = ConnectionState::Connected;
+    tracing::info!("Resumed: handshake completed");
+    true
+}
+
+/// Send suspend/resume to the running client and update the connection state
+///
+/// A resumed client is `Connecting` until its handshake completes.
+/// Returns an IPC error code and message if the client is not in a suitable state.
+pub(crate) async fn send_client_command(
+    state: &Arc<Mutex<DaemonState>>,
+    command: ClientCommand,
+) -> Result<(), (i32, String)> {
+    let mut s = state.lock().await;
+
+    if !matches!(s.mode, Some(VpnMode::Client { .. })) {
+        return Err((NOT_CONNECTED, "Not connected in client mode".to_string()));
+    }
+
+    let (expected, next) = match command {
+        ClientCommand::Suspend => (ConnectionState::Connected, ConnectionState::Suspended),
+        ClientCommand::Resume => (ConnectionState::Suspended, ConnectionState::Connecting),
+    };
+    if s.connection_state != expected {
+        return Err(match command {
+            ClientCommand::Suspend => (NOT_CONNECTED, "Not connected".to_string()),
+            ClientCommand::Resume => (NOT_SUSPENDED, "Not suspended".to_string()),
+        });
+    }
+
+    let command_tx = s.client_command_tx.clone().ok_or_else(|| {
+        (INTERNAL_ERROR, "Client is not accepting commands".to_string())
+    })?;
+    command_tx
+        .try_send(command)
+        .map_err(|e| (INTERNAL_ERROR, format!("Client did not accept command: {}", e)))?;
+
+    s.connection_state = next;
+    tracing::info!("Client {:?} requested", command);
+    Ok(())
+}
+
+/// Resolve with an error if no new handshake is recorded in `stats` within `timeout`
+///
+/// Never resolves once a handshake completes, so it can race the client run loop
+/// in a `select!` and end a connection that is stuck before its first handshake.
+pub(crate) async fn connect_watchdog(stats: Arc<TrafficStats>, timeout: Duration) -> MinnowVpnError {
+    let baseline = stats.last_handshake_ms();
+    tokio::time::sleep(timeout).await;
+
+    if stats.last_handshake_ms() != baseline {
+        std::future::pending::<()>().await;
+    }
+
+    tracing::warn!("No handshake completed within {}s, giving up", timeout.as_secs());
+    MinnowVpnError::Protocol(ProtocolError::HandshakeTimeout { seconds: timeout.as_secs() })
+}
+
+/// Mirror the client's server-unreachable flag into the connection state
+///
+/// Returns true if the state changed, so the caller can notify the UI at once.
+pub(crate) fn sync_server_reachability(s: &mut DaemonState) -> bool {
+    if !matches!(s.mode, Some(VpnMode::Client { .. })) {
+        return false;
+    }
+
+    let unreachable = s.traffic_stats.server_unreachable();
+    let next = match s.connection_state {
+        ConnectionState::Connected if unreachable => ConnectionState::ServerUnreachable,
+        ConnectionState::ServerUnreachable if !unreachable => ConnectionState::Connected,
+        _ => return false,
+    };
+
+    tracing::info!("Connection state {:?} -> {:?}", s.connection_state, next);
+    s.connection_state = next;
+    true
+}
+
+/// Wait after failed auto-connect attempt number `attempt` (from 0)
+fn auto_connect_delay(attempt: u32) -> Duration {
+    AUTO_CONNECT_DELAYS[(attempt as usize).min(AUTO_CONNECT_DELAYS.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_CONFIG: &str = "[Interface]\n\
+        PrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+        Address = 10.0.0.2/24\n\
+        [Peer]\n\
+        PublicKey = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\n\
+        AllowedIPs = 0.0.0.0/0\n\
+        Endpoint = 127.0.0.1:51820\n";
+
+    fn connected_client_state() -> (Arc<Mutex<DaemonState>>, mpsc::Receiver<ClientCommand>) {
+        let (command_tx, command_rx) = mpsc::channel(4);
+        let state = DaemonState {
+            connection_state: ConnectionState::Connected,
+            mode: Some(VpnMode::Client {
+                vpn_ip: "10.0.0.2/24".to_string(),
+                server_endpoint: "127.0.0.1:51820".to_string(),
+                current_config: WireGuardConfig::parse(CLIENT_CONFIG).unwrap(),
+                previous_config: None,
+            }),
+            client_command_tx: Some(command_tx),
+            ..Default::default()
+        };
+        (Arc::new(Mutex::new(state)), command_rx)
+    }
+
+    #[tokio::test]
+    async fn test_suspend_resume_transitions() {
+        let (state, mut command_rx) = connected_client_state();
+
+        // Resume is only valid while suspended
+        let err = send_client_command(&state, ClientCommand::Resume).await.unwrap_err();
+        assert_eq!(err.0, NOT_SUSPENDED);
+
+        send_client_command(&state, ClientCommand::Suspend).await.unwrap();
+        assert_eq!(state.lock().await.connection_state, ConnectionState::Suspended);
+        assert_eq!(command_rx.recv().await, Some(ClientCommand::Suspend));
+
+        // A suspended client still blocks new connections
+        assert!(state.lock().await.connection_state.is_active());
+
+        // Not connected again until the resume handshake completes
+        send_client_command(&state, ClientCommand::Resume).await.unwrap();
+        assert_eq!(state.lock().await.connection_state, ConnectionState::Connecting);
+        assert_eq!(command_rx.recv().await, Some(ClientCommand::Resume));
+        assert!(send_client_command(&state, ClientCommand::Suspend).await.is_err());
+
+        assert!(finish_resume(&state).await);
+        assert_eq!(state.lock().await.connection_state, ConnectionState::Connected);
+        // Later handshakes (reconnects) leave the state alone
+        assert!(!finish_resume(&state).await);
+    }
+
+    #[tokio::test]
+    async fn test_server_status_notification_inside_runtime() {
+        let (peer_update_tx, _peer_update_rx) = mpsc::channel(4);
+        let mut peers = PeerManager::new();
+        peers.add_peer([7u8; 32], None, Vec::new());
+        let state = Arc::new(Mutex::new(DaemonState {
+            connection_state: ConnectionState::Connected,
+            mode: Some(VpnMode::Server {
+                listen_port: 51820,
+                interface_address: "10.0.0.1/24".to_string(),
+                config: WireGuardConfig::parse(CLIENT_CONFIG).unwrap(),
+                peer_update_tx,
+                peers: Arc::new(RwLock::new(peers)),
+            }),
+            ..Default::default()
+        }));
+        let (status_tx, mut status_rx) = broadcast::channel(4);
+
+        // Runs on the runtime like the bandwidth task (a blocking lock would panic here)
+        DaemonService::send_status_notification(&state, &status_tx).await.unwrap();
+        let notification: serde_json::Value = serde_json::from_str(&status_rx.recv().await.unwrap()).unwrap();
+        assert_eq!(notification["method"], "server_status_changed");
+        assert_eq!(notification["params"]["peer_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_not_running() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let err = DaemonService::ensure_not_running(port).await.unwrap_err();
+        assert!(matches!(err, MinnowVpnError::Network(NetworkError::DaemonAlreadyRunning { .. })));
+
+        drop(listener);
+        assert!(DaemonService::ensure_not_running(port).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_suspend_requires_client_mode() {
+        let state = Arc::new(Mutex::new(DaemonState::default()));
+        let err = send_client_command(&state, ClientCommand::Suspend).await.unwrap_err();
+        assert_eq!(err.0, NOT_CONNECTED);
+    }
+
+    #[tokio::test]
+    async fn test_sync_server_reachability() {
+        let (state, _command_rx) = connected_client_state();
+        let mut s = state.lock().await;
+
+        assert!(!sync_server_reachability(&mut s));
+
+        s.traffic_stats.set_server_unreachable(true);
+        assert!(sync_server_reachability(&mut s));
+        assert_eq!(s.connection_state, ConnectionState::ServerUnreachable);
+        assert!(s.connection_state.is_active());
+        assert!(!sync_server_reachability(&mut s));
+
+        s.traffic_stats.set_server_unreachable(false);
+        assert!(sync_server_reachability(&mut s));
+        assert_eq!(s.connection_state, ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_auto_connect_backoff() {
+        let delays: Vec<u64> = (0..6).map(|n| auto_connect_delay(n).as_secs()).collect();
+        assert_eq!(delays, [5, 10, 30, 60, 60, 60]);
+        assert_eq!(auto_connect_delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_progress_notification() {
+        let endpoint: std::net::SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let params = |event| progress_notification(&event).map(|n| {
+            assert_eq!(n.method, "handshake_progress");
+            n.params
+        });
+
+        let attempt = params(ClientEvent::Progress(HandshakeProgress::Attempt { attempt: 3 })).unwrap();
+        assert_eq!(attempt, serde_json::json!({"step": "attempt", "attempt": 3}));
+        let sent = params(ClientEvent::Progress(HandshakeProgress::InitiationSent { endpoint })).unwrap();
+        assert_eq!(sent["endpoint"], "192.0.2.1:51820");
+        assert_eq!(params(ClientEvent::HandshakeCompleted { endpoint }).unwrap()["step"], "complete");
+        assert!(params(ClientEvent::Disconnected { reason: "shutdown".to_string() }).is_none());
+    }
 }
@@ -9,7 +9,7 @@
 //! - Last known connection info
 //!
 //! On daemon startup, if desired_state is "connected", the daemon will
-//! automatically attempt to reconnect using the stored config.
+//! automatically reconnect (or restart the server) using the stored config.
 
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -28,6 +28,17 @@ impl Default for DesiredState {
     }
 }
 
+/// Which side of the tunnel the stored config runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistedMode {
+    /// Started with `POST /connect` (files written before servers were persisted)
+    #[default]
+    Client,
+    /// Started with `POST /server/start`
+    Server,
+}
+
 /// Persistent connection state stored to disk
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionStateFile {
@@ -35,9 +46,18 @@ pub struct ConnectionStateFile {
     pub schema_version: u32,
     /// User's desired state - connected or disconnected
     pub desired_state: DesiredState,
+    /// Client or server
+    #[serde(default)]
+    pub mode: PersistedMode,
     /// WireGuard config string (if available)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub config: Option<String>,
+    /// File the config was read from, so reload_config keeps working after a restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_path: Option<String>,
+    /// Client connected with `no_dns`: leave the system resolver alone on reconnect
+    #[serde(default)]
+    pub no_dns: bool,
     /// VPN IP address from last connection
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vpn_ip: Option<String>,
@@ -59,7 +79,10 @@ impl Default for ConnectionStateFile {
         Self {
             schema_version: 1,
             desired_state: DesiredState::Disconnected,
+            mode: PersistedMode::Client,
             config: None,
+            config_path: None,
+            no_dns: false,
             vpn_ip: None,
             server_endpoint: None,
             last_connected_at: None,
@@ -69,6 +92,16 @@ impl Default for ConnectionStateFile {
     }
 }
 
+impl ConnectionStateFile {
+    /// Config to bring back on startup (None unless the user left the VPN up)
+    pub fn config_to_restore(&self) -> Option<&str> {
+        match self.desired_state {
+            DesiredState::Connected => self.config.as_deref(),
+            DesiredState::Disconnected => None,
+        }
+    }
+}
+
 /// Get platform-specific state directory
 pub fn get_state_dir() -> PathBuf {
     #[cfg(target_os = "windows")]
@@ -148,14 +181,9 @@ pub fn save_connection_state(state: &ConnectionStateFile) -> Result<(), std::io:
     let json = serde_json::to_string_pretty(state)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
-    std::fs::write(&path, json)?;
-
-    // Set file permissions on Unix (readable by minnowvpn group)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).ok();
-    }
+    // Write atomically so a crash or concurrent load never sees a partial file
+    // (0o640: readable by minnowvpn group on Unix)
+    crate::config::write_atomic(&path, json.as_bytes(), Some(0o640))?;
 
     tracing::debug!("Saved connection state to {:?}", path);
     Ok(())
@@ -206,13 +234,9 @@ pub fn update_retry_count(count: u32) -> Result<(), std::io::Error> {
     }
 }
 
-/// Get current timestamp as Unix epoch seconds string
+/// Current time as an RFC 3339 timestamp
 pub fn iso_now() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    duration.as_secs().to_string()
+    super::timestamp::now()
 }
 
 #[cfg(test)]
@@ -226,7 +250,10 @@ mod tests {
         let state = ConnectionStateFile {
             schema_version: 1,
             desired_state: DesiredState::Connected,
+            mode: PersistedMode::Client,
             config: Some("[Interface]\nPrivateKey = test\n".to_string()),
+            config_path: None,
+            no_dns: true,
             vpn_ip: Some("10.0.0.2".to_string()),
             server_endpoint: Some("vpn.example.com:51820".to_string()),
             last_connected_at: Some("1706600000".to_string()),
@@ -245,6 +272,7 @@ mod tests {
         assert_eq!(parsed.desired_state, DesiredState::Connected);
         assert_eq!(parsed.vpn_ip, Some("10.0.0.2".to_string()));
         assert_eq!(parsed.retry_count, 3);
+        assert!(parsed.no_dns);
     }
 
     #[test]
@@ -252,7 +280,10 @@ mod tests {
         let state = ConnectionStateFile {
             schema_version: 1,
             desired_state: DesiredState::Disconnected,
+            mode: PersistedMode::Client,
             config: None,
+            config_path: None,
+            no_dns: false,
             vpn_ip: None,
             server_endpoint: None,
             last_connected_at: None,
@@ -282,6 +313,24 @@ mod tests {
         assert_eq!(state.retry_count, 0);
     }
 
+    #[test]
+    fn test_config_to_restore() {
+        // Files from before server mode was persisted are client connections
+        let json = r#"{"schema_version":1,"desired_state":"connected","config":"[Interface]","last_updated_at":"0"}"#;
+        let mut state: ConnectionStateFile = serde_json::from_str(json).unwrap();
+        assert_eq!(state.mode, PersistedMode::Client);
+        assert_eq!(state.config_path, None);
+        assert!(!state.no_dns);
+        assert_eq!(state.config_to_restore(), Some("[Interface]"));
+
+        state.mode = PersistedMode::Server;
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains("\"mode\":\"server\""));
+
+        state.desired_state = DesiredState::Disconnected;
+        assert_eq!(state.config_to_restore(), None);
+    }
+
     #[test]
     fn test_state_file_roundtrip() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -289,7 +338,10 @@ mod tests {
         let state = ConnectionStateFile {
             schema_version: 1,
             desired_state: DesiredState::Connected,
+            mode: PersistedMode::Client,
             config: Some("[Interface]\nAddress = 10.0.0.2/32\n".to_string()),
+            config_path: None,
+            no_dns: false,
             vpn_ip: Some("10.0.0.2".to_string()),
             server_endpoint: Some("1.2.3.4:51820".to_string()),
             last_connected_at: Some("1706600000".to_string()),
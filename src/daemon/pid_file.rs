@@ -0,0 +1,126 @@
+//! PID file for service managers (`--pid-file`)
+//!
+//! The daemon always runs in the foreground; it never forks or detaches, so
+//! the PID written here is the PID the service manager started. A PID file
+//! left behind by a crashed daemon is replaced, but one naming a live process
+//! stops a second daemon from starting.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{MinnowVpnError, NetworkError};
+
+/// A PID file written at startup and removed on shutdown
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write this process's PID to `path`
+    ///
+    /// Fails if the file already names a process that is still alive.
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self, MinnowVpnError> {
+        let path = path.into();
+
+        if let Some(pid) = read_pid(&path) {
+            if pid != std::process::id() && is_alive(pid) {
+                return Err(NetworkError::DaemonPidFileInUse {
+                    path: path.display().to_string(),
+                    pid,
+                }
+                .into());
+            }
+            tracing::info!("Replacing stale PID file {} (PID {} is gone)", path.display(), pid);
+        }
+
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, format!("{}\n", std::process::id()))?;
+        tracing::debug!("Wrote PID file {}", path.display());
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Delete the file (it's fine if it is already gone)
+    pub fn remove(&self) {
+        match fs::remove_file(&self.path) {
+            Ok(()) => tracing::debug!("Removed PID file {}", self.path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove PID file {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// PID recorded in `path` (None if missing or not a PID)
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok().filter(|&pid| pid > 0)
+}
+
+/// Whether a process with this PID exists
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 checks for existence; EPERM means it exists under another user
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a cheap liveness probe, treat an existing PID file as stale
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("minnowvpn-test-{}-{}.pid", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_create_and_remove() {
+        let path = temp_path("create");
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+
+        pid_file.remove();
+        assert!(!path.exists());
+        drop(pid_file);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_live_pid_refuses_and_stale_pid_is_replaced() {
+        let path = temp_path("live");
+
+        // PID 1 is always alive
+        fs::write(&path, "1\n").unwrap();
+        let err = PidFile::create(&path).unwrap_err();
+        assert!(matches!(err, MinnowVpnError::Network(NetworkError::DaemonPidFileInUse { pid: 1, .. })));
+        assert_eq!(read_pid(&path), Some(1));
+
+        // Far above any pid_max
+        fs::write(&path, "2000000000\n").unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+}
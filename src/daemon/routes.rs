@@ -3,8 +3,9 @@
 //! Provides HTTP endpoints that map to the existing daemon functionality.
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse, Json, Response,
@@ -15,14 +16,24 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
 
 use super::ipc::*;
-use super::persistence::{self, ConnectionStateFile, DesiredState};
-use super::{DaemonState, VpnMode};
+use super::log_level::LogControl;
+use super::metrics::{self, Metrics};
+use super::persistence::{self, ConnectionStateFile, DesiredState, PersistedMode};
+use super::state_dump::{self, StateDump};
+use super::timestamp;
+use super::wg_show::{self, WgInterface};
+use super::{
+    attach_client_commands, connect_watchdog, send_client_command, DaemonState, VpnMode,
+    CONNECT_HANDSHAKE_TIMEOUT,
+};
+use crate::client::ClientCommand;
 use crate::protocol::session::PeerManager;
+use crate::tunnel::RouteReport;
 use crate::{WireGuardClient, WireGuardConfig, WireGuardServer};
 
 /// Shared application state for route handlers
@@ -30,6 +41,7 @@ use crate::{WireGuardClient, WireGuardConfig, WireGuardServer};
 pub struct AppState {
     pub daemon_state: Arc<Mutex<DaemonState>>,
     pub status_tx: broadcast::Sender<String>,
+    pub log_control: Option<LogControl>,
 }
 
 /// API error response
@@ -42,12 +54,13 @@ pub struct ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status = match self.code {
-            code if code == NOT_CONNECTED => StatusCode::CONFLICT,
+            code if code == NOT_CONNECTED || code == NOT_SUSPENDED => StatusCode::CONFLICT,
             code if code == ALREADY_CONNECTED || code == ALREADY_RUNNING => StatusCode::CONFLICT,
             code if code == INVALID_CONFIG || code == INVALID_PARAMS => StatusCode::BAD_REQUEST,
             code if code == PEER_NOT_FOUND => StatusCode::NOT_FOUND,
             code if code == PEER_ALREADY_EXISTS => StatusCode::CONFLICT,
             code if code == UPDATE_FAILED => StatusCode::INTERNAL_SERVER_ERROR,
+            code if code == READ_ONLY_TOKEN => StatusCode::FORBIDDEN,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
         (status, Json(self)).into_response()
@@ -57,21 +70,42 @@ impl IntoResponse for ApiError {
 /// Build the API router with all routes
 pub fn build_router(state: AppState) -> Router {
     Router::new()
+        // Liveness check
+        .route("/api/v1/ping", get(handle_ping))
+        .route("/api/v1/version", get(handle_version))
+        // Runtime log level
+        .route("/api/v1/loglevel", get(handle_get_log_level))
+        .route("/api/v1/loglevel", put(handle_set_log_level))
+        // Debugging
+        .route("/api/v1/debug/routes", get(handle_debug_routes))
+        // Hot upgrade: dump mode, config and live peers (restore with --restore-state)
+        .route("/api/v1/state/dump", post(handle_dump_state))
+        // `wg show` / `wg show <if> dump` compatible text
+        .route("/api/v1/show", get(handle_show))
+        // Prometheus scrape target
+        .route("/api/v1/metrics", get(handle_metrics))
         // Client mode endpoints
         .route("/api/v1/connect", post(handle_connect))
         .route("/api/v1/disconnect", post(handle_disconnect))
         .route("/api/v1/status", get(handle_status))
         .route("/api/v1/config", put(handle_update_config))
+        .route("/api/v1/config/reload", post(handle_reload_config))
+        .route("/api/v1/suspend", post(handle_suspend))
+        .route("/api/v1/resume", post(handle_resume))
         // Server mode lifecycle
         .route("/api/v1/server/start", post(handle_start_server))
         .route("/api/v1/server/stop", post(handle_stop_server))
+        .route("/api/v1/server/drain", post(handle_drain_server))
         // Server mode peer management
         .route("/api/v1/server/peers", get(handle_list_peers))
         .route("/api/v1/server/peers", post(handle_add_peer))
         .route("/api/v1/server/peers/:pubkey", get(handle_peer_status))
         .route("/api/v1/server/peers/:pubkey", delete(handle_remove_peer))
+        .route("/api/v1/server/route/:ip", get(handle_resolve_route))
         // SSE events stream
         .route("/api/v1/events", get(handle_events_sse))
+        // Event log (recent history, unlike the live SSE stream)
+        .route("/api/v1/events/log", get(handle_event_log))
         .with_state(state)
 }
 
@@ -81,7 +115,14 @@ pub fn build_router(state: AppState) -> Router {
 
 #[derive(Debug, Deserialize)]
 pub struct ConnectRequest {
+    #[serde(default)]
     pub config: String,
+    /// Read the config from this file instead (and remember it for reload)
+    #[serde(default)]
+    pub config_path: Option<std::path::PathBuf>,
+    /// Leave the system resolver alone instead of applying the config's DNS
+    #[serde(default)]
+    pub no_dns: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -94,6 +135,16 @@ pub struct DisconnectResponse {
     pub disconnected: bool,
 }
 
+#[derive(Debug, Serialize)]
+pub struct SuspendResponse {
+    pub suspended: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResumeResponse {
+    pub resumed: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateConfigRequest {
     pub config: String,
@@ -101,7 +152,11 @@ pub struct UpdateConfigRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct StartServerRequest {
+    #[serde(default)]
     pub config: String,
+    /// Read the config from this file instead (and remember it for reload)
+    #[serde(default)]
+    pub config_path: Option<std::path::PathBuf>,
 }
 
 #[derive(Debug, Serialize)]
@@ -119,6 +174,10 @@ pub struct AddPeerRequest {
     pub public_key: String,
     pub allowed_ips: Vec<String>,
     pub preshared_key: Option<String>,
+    #[serde(default)]
+    pub lock_endpoint: bool,
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -151,9 +210,7 @@ pub async fn handle_connect(
     // Check if already running
     {
         let s = state.daemon_state.lock().await;
-        if s.connection_state == ConnectionState::Connected
-            || s.connection_state == ConnectionState::Connecting
-        {
+        if s.connection_state.is_active() {
             return Err(ApiError {
                 code: ALREADY_CONNECTED,
                 message: "Already connected or connecting".to_string(),
@@ -162,37 +219,58 @@ pub async fn handle_connect(
     }
 
     // Update state to connecting
-    {
+    let config_dir = {
         let mut s = state.daemon_state.lock().await;
         s.connection_state = ConnectionState::Connecting;
         s.error_message = None;
-    }
+        s.config_path = request.config_path.clone();
+        s.no_dns = request.no_dns;
+        s.config_dir.clone()
+    };
 
     send_status_notification(&state).await;
 
-    // Parse config
-    let config = WireGuardConfig::from_string(&request.config).map_err(|e| {
-        let error_msg = format!("Invalid config: {}", e);
-        // Reset state on error
-        let state_clone = state.clone();
-        let error_msg_clone = error_msg.clone();
-        tokio::spawn(async move {
-            let mut s = state_clone.daemon_state.lock().await;
-            s.connection_state = ConnectionState::Error;
-            s.error_message = Some(error_msg_clone);
-        });
-        ApiError {
-            code: INVALID_CONFIG,
-            message: error_msg,
-        }
-    })?;
+    // Parse config and check it can run in client mode
+    let (config, config_text) = read_config_source(&request.config, request.config_path.as_deref(), config_dir.as_deref())
+        .and_then(|text| {
+            WireGuardConfig::from_string(&text)
+                .and_then(|c| c.validate_client().map(|_| c))
+                .map(|c| (c, text))
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| {
+            let error_msg = format!("Invalid config: {}", e);
+            // Reset state on error
+            let state_clone = state.clone();
+            let error_msg_clone = error_msg.clone();
+            tokio::spawn(async move {
+                let mut s = state_clone.daemon_state.lock().await;
+                s.connection_state = ConnectionState::Error;
+                s.error_message = Some(error_msg_clone);
+            });
+            ApiError {
+                code: INVALID_CONFIG,
+                message: error_msg,
+            }
+        })?;
+
+    start_client(&state, config, Some(config_text)).await.map(Json)
+}
 
+/// Create and spawn the VPN client for a validated config
+///
+/// Expects the state to be `Connecting` already. `config_text` is persisted for
+/// auto-reconnect; a restored state dump passes None and keeps the existing file.
+pub(crate) async fn start_client(
+    state: &AppState,
+    config: WireGuardConfig,
+    config_text: Option<String>,
+) -> Result<ConnectResponse, ApiError> {
     // Extract endpoint and VPN IP
     let server_endpoint = config
         .peers
         .first()
-        .and_then(|p| p.endpoint.as_ref())
-        .map(|e| e.to_string())
+        .and_then(|p| p.endpoint_string())
         .unwrap_or_default();
 
     let vpn_ip = config
@@ -203,32 +281,38 @@ pub async fn handle_connect(
         .unwrap_or_default();
 
     // Get traffic stats
-    let traffic_stats = {
+    let (traffic_stats, config_path, no_dns) = {
         let s = state.daemon_state.lock().await;
-        Arc::clone(&s.traffic_stats)
+        (Arc::clone(&s.traffic_stats), s.config_path.clone(), s.no_dns)
     };
 
     let config_for_storage = config.clone();
 
     // PERSIST STATE: Save state BEFORE connecting (so crash during connect still has config)
     // This enables auto-reconnect on daemon restart
-    let state_file = ConnectionStateFile {
-        schema_version: 1,
-        desired_state: DesiredState::Connected,
-        config: Some(request.config.clone()),
-        vpn_ip: Some(vpn_ip.clone()),
-        server_endpoint: Some(server_endpoint.clone()),
-        last_connected_at: None, // Will be set on successful connection
-        last_updated_at: persistence::iso_now(),
-        retry_count: 0,
-    };
-    if let Err(e) = persistence::save_connection_state(&state_file) {
-        tracing::warn!("Failed to persist connection state: {} (auto-reconnect may not work)", e);
+    if let Some(config_text) = config_text {
+        let state_file = ConnectionStateFile {
+            schema_version: 1,
+            desired_state: DesiredState::Connected,
+            mode: PersistedMode::Client,
+            config: Some(config_text),
+            config_path: config_path.map(|p| p.display().to_string()),
+            no_dns,
+            vpn_ip: Some(vpn_ip.clone()),
+            server_endpoint: Some(server_endpoint.clone()),
+            last_connected_at: None, // Will be set on successful connection
+            last_updated_at: persistence::iso_now(),
+            retry_count: 0,
+        };
+        if let Err(e) = persistence::save_connection_state(&state_file) {
+            tracing::warn!("Failed to persist connection state: {} (auto-reconnect may not work)", e);
+        }
     }
 
     // Create client
     match WireGuardClient::new(config, Some(traffic_stats)).await {
-        Ok(client) => {
+        Ok(mut client) => {
+            client.set_apply_dns(!no_dns);
             let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
             {
@@ -240,12 +324,12 @@ pub async fn handle_connect(
                     current_config: config_for_storage,
                     previous_config: None,
                 });
-                s.started_at = Some(chrono_now());
+                s.started_at = Some(timestamp::now());
                 s.traffic_stats.reset();
                 s.shutdown_tx = Some(shutdown_tx);
             }
 
-            send_status_notification(&state).await;
+            send_status_notification(state).await;
 
             // PERSIST STATE: Update last_connected_at on successful connection
             if let Err(e) = persistence::update_last_connected() {
@@ -255,7 +339,7 @@ pub async fn handle_connect(
             // Spawn client task
             spawn_client_task(client, shutdown_rx, state.daemon_state.clone(), state.status_tx.clone());
 
-            Ok(Json(ConnectResponse { connected: true }))
+            Ok(ConnectResponse { connected: true })
         }
         Err(e) => {
             let mut s = state.daemon_state.lock().await;
@@ -263,7 +347,7 @@ pub async fn handle_connect(
             s.error_message = Some(format!("{}", e));
             drop(s);
 
-            send_status_notification(&state).await;
+            send_status_notification(state).await;
 
             Err(ApiError {
                 code: CONNECTION_FAILED,
@@ -314,6 +398,129 @@ pub async fn handle_disconnect(
     Ok(Json(DisconnectResponse { disconnected: true }))
 }
 
+/// POST /api/v1/suspend - Pause the tunnel, keeping TUN and routes for fast resume
+///
+/// Traffic is blackholed (not leaked) while suspended.
+pub async fn handle_suspend(
+    State(state): State<AppState>,
+) -> Result<Json<SuspendResponse>, ApiError> {
+    send_client_command(&state.daemon_state, ClientCommand::Suspend)
+        .await
+        .map_err(|(code, message)| ApiError { code, message })?;
+
+    send_status_notification(&state).await;
+    Ok(Json(SuspendResponse { suspended: true }))
+}
+
+/// POST /api/v1/resume - Re-handshake without rebuilding the interface
+pub async fn handle_resume(
+    State(state): State<AppState>,
+) -> Result<Json<ResumeResponse>, ApiError> {
+    send_client_command(&state.daemon_state, ClientCommand::Resume)
+        .await
+        .map_err(|(code, message)| ApiError { code, message })?;
+
+    send_status_notification(&state).await;
+    Ok(Json(ResumeResponse { resumed: true }))
+}
+
+/// GET /api/v1/ping - Liveness check
+///
+/// Takes no locks on daemon state, so UI heartbeats never contend with the VPN.
+pub async fn handle_ping() -> Json<PingResponse> {
+    Json(PingResponse::now())
+}
+
+/// GET /api/v1/version - Daemon version
+pub async fn handle_version() -> Json<VersionResponse> {
+    Json(VersionResponse::current())
+}
+
+/// GET /api/v1/debug/routes - Tracked TUN/route state and whether it is still installed
+pub async fn handle_debug_routes() -> Result<Json<RouteReport>, ApiError> {
+    tokio::task::spawn_blocking(crate::tunnel::route_report)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError {
+            code: INTERNAL_ERROR,
+            message: format!("Route check failed: {}", e),
+        })
+}
+
+/// GET /api/v1/show - Running interface and peers as `wg show` text (`?format=dump` for dump)
+pub async fn handle_show(
+    State(state): State<AppState>,
+    Query(params): Query<ShowParams>,
+) -> Result<String, ApiError> {
+    let interface = WgInterface::capture(&*state.daemon_state.lock().await)
+        .await
+        .ok_or(ApiError {
+            code: NOT_CONNECTED,
+            message: "VPN not running".to_string(),
+        })?;
+    Ok(match params.format {
+        ShowFormat::Pretty => interface.show(wg_show::unix_now()),
+        ShowFormat::Dump => interface.dump(),
+    })
+}
+
+/// GET /api/v1/metrics - Traffic and peer metrics in Prometheus text format
+pub async fn handle_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = Metrics::capture(&*state.daemon_state.lock().await).await;
+    (
+        [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics.render(std::time::SystemTime::now()),
+    )
+}
+
+/// POST /api/v1/state/dump - Write the full daemon state to the default dump path
+pub async fn handle_dump_state(State(state): State<AppState>) -> Result<Json<DumpStateResponse>, ApiError> {
+    let path = state_dump::get_state_dump_path();
+    let dump = StateDump::capture(&*state.daemon_state.lock().await).await;
+    state_dump::write_state_dump(&path, &dump).map_err(|e| ApiError {
+        code: INTERNAL_ERROR,
+        message: format!("State dump failed: {}", e),
+    })?;
+    tracing::info!("Dumped daemon state to {:?}", path);
+    Ok(Json(DumpStateResponse { path: path.display().to_string() }))
+}
+
+/// GET /api/v1/loglevel - Get the active log filter
+pub async fn handle_get_log_level(
+    State(state): State<AppState>,
+) -> Result<Json<LogLevelResponse>, ApiError> {
+    state
+        .log_control
+        .as_ref()
+        .and_then(|c| c.current())
+        .map(|level| Json(LogLevelResponse { level }))
+        .ok_or_else(|| ApiError {
+            code: INTERNAL_ERROR,
+            message: "Log level control not available".to_string(),
+        })
+}
+
+/// PUT /api/v1/loglevel - Change the log level without restarting
+pub async fn handle_set_log_level(
+    State(state): State<AppState>,
+    Json(request): Json<SetLogLevelParams>,
+) -> Result<Json<LogLevelResponse>, ApiError> {
+    let log_control = state.log_control.as_ref().ok_or_else(|| ApiError {
+        code: INTERNAL_ERROR,
+        message: "Log level control not available".to_string(),
+    })?;
+
+    log_control.set_level(request.level).map_err(|e| ApiError {
+        code: INTERNAL_ERROR,
+        message: format!("Failed to set log level: {}", e),
+    })?;
+
+    tracing::info!("Log level set to {}", request.level.as_str());
+    Ok(Json(LogLevelResponse {
+        level: request.level.as_str().to_string(),
+    }))
+}
+
 /// GET /api/v1/status - Get current status
 pub async fn handle_status(State(state): State<AppState>) -> Json<serde_json::Value> {
     let s = state.daemon_state.lock().await;
@@ -327,6 +534,10 @@ pub async fn handle_status(State(state): State<AppState>) -> Json<serde_json::Va
                 "connected_at": s.started_at,
                 "bytes_sent": s.traffic_stats.get_sent(),
                 "bytes_received": s.traffic_stats.get_received(),
+                "inner_bytes_sent": s.traffic_stats.get_inner_sent(),
+                "inner_bytes_received": s.traffic_stats.get_inner_received(),
+                "reconnect_count": s.traffic_stats.reconnect_count(),
+                "current_session_age": s.traffic_stats.current_session_age(),
                 "error_message": s.error_message,
             }))
         }
@@ -338,10 +549,12 @@ pub async fn handle_status(State(state): State<AppState>) -> Json<serde_json::Va
             let started_at = s.started_at.clone();
             let bytes_sent = s.traffic_stats.get_sent();
             let bytes_received = s.traffic_stats.get_received();
+            let inner_bytes_sent = s.traffic_stats.get_inner_sent();
+            let inner_bytes_received = s.traffic_stats.get_inner_received();
             let error_message = s.error_message.clone();
             drop(s); // Release daemon_state lock before acquiring peers lock
 
-            let peers_guard = peers.lock().await;
+            let peers_guard = peers.read().await;
             let peer_count = peers_guard.len();
             let connected_peer_count = peers_guard.connected_count();
             drop(peers_guard);
@@ -356,6 +569,8 @@ pub async fn handle_status(State(state): State<AppState>) -> Json<serde_json::Va
                 "started_at": started_at,
                 "bytes_sent": bytes_sent,
                 "bytes_received": bytes_received,
+                "inner_bytes_sent": inner_bytes_sent,
+                "inner_bytes_received": inner_bytes_received,
                 "error_message": error_message,
             }))
         }
@@ -364,6 +579,8 @@ pub async fn handle_status(State(state): State<AppState>) -> Json<serde_json::Va
                 "state": s.connection_state,
                 "bytes_sent": 0,
                 "bytes_received": 0,
+                "inner_bytes_sent": 0,
+                "inner_bytes_received": 0,
                 "error_message": s.error_message,
             }))
         }
@@ -379,27 +596,27 @@ pub async fn handle_update_config(
     State(state): State<AppState>,
     Json(request): Json<UpdateConfigRequest>,
 ) -> Result<Json<UpdateConfigResponse>, ApiError> {
+    update_client_config(&state, request.config).await.map(Json)
+}
+
+/// Validate `config_text`, reconnect with it and roll back on failure
+///
+/// Shared by `PUT /api/v1/config` and client-mode `reload_config`.
+pub(crate) async fn update_client_config(
+    state: &AppState,
+    config_text: String,
+) -> Result<UpdateConfigResponse, ApiError> {
     // Step 1: Parse and validate new config BEFORE disconnecting
-    let new_config = WireGuardConfig::from_string(&request.config).map_err(|e| ApiError {
+    let new_config = WireGuardConfig::from_string(&config_text).map_err(|e| ApiError {
         code: INVALID_CONFIG,
         message: format!("Invalid config: {}", e),
     })?;
 
     // Validate config has required fields for client mode
-    if new_config.peers.is_empty() {
-        return Err(ApiError {
-            code: INVALID_CONFIG,
-            message: "Config must have at least one peer".to_string(),
-        });
-    }
-
-    let peer = &new_config.peers[0];
-    if peer.endpoint.is_none() {
-        return Err(ApiError {
-            code: INVALID_CONFIG,
-            message: "Peer must have an endpoint for client mode".to_string(),
-        });
-    }
+    new_config.validate_client().map_err(|e| ApiError {
+        code: INVALID_CONFIG,
+        message: format!("Invalid config: {}", e),
+    })?;
 
     // Extract new connection info
     let new_vpn_ip = new_config
@@ -412,8 +629,7 @@ pub async fn handle_update_config(
     let new_endpoint = new_config
         .peers
         .first()
-        .and_then(|p| p.endpoint.as_ref())
-        .map(|e| e.to_string())
+        .and_then(|p| p.endpoint_string())
         .unwrap_or_default();
 
     // Step 2: Check current state and get current config for potential rollback
@@ -422,7 +638,10 @@ pub async fn handle_update_config(
 
         match &s.mode {
             Some(VpnMode::Client { current_config, .. }) => {
-                let connected = s.connection_state == ConnectionState::Connected;
+                let connected = matches!(
+                    s.connection_state,
+                    ConnectionState::Connected | ConnectionState::ServerUnreachable
+                );
                 (Some(current_config.clone()), connected)
             }
             Some(VpnMode::Server { .. }) => {
@@ -457,15 +676,16 @@ pub async fn handle_update_config(
     }
 
     // Step 4: Reconnect with new config
-    let traffic_stats = {
+    let (traffic_stats, no_dns) = {
         let s = state.daemon_state.lock().await;
-        Arc::clone(&s.traffic_stats)
+        (Arc::clone(&s.traffic_stats), s.no_dns)
     };
 
     let config_for_storage = new_config.clone();
 
     match WireGuardClient::new(new_config, Some(traffic_stats)).await {
-        Ok(client) => {
+        Ok(mut client) => {
+            client.set_apply_dns(!no_dns);
             // Create shutdown channel
             let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
@@ -476,13 +696,13 @@ pub async fn handle_update_config(
                     vpn_ip: new_vpn_ip.clone(),
                     server_endpoint: new_endpoint.clone(),
                     current_config: config_for_storage,
-                    previous_config: current_config, // Store old config for potential future rollback
+                    previous_config: current_config.map(Box::new), // Store old config for potential future rollback
                 });
-                s.started_at = Some(chrono_now());
+                s.started_at = Some(timestamp::now());
                 s.shutdown_tx = Some(shutdown_tx);
             }
 
-            send_status_notification(&state).await;
+            send_status_notification(state).await;
 
             // Send config_updated notification
             let notification = serde_json::json!({
@@ -501,7 +721,10 @@ pub async fn handle_update_config(
             let state_file = ConnectionStateFile {
                 schema_version: 1,
                 desired_state: DesiredState::Connected,
-                config: Some(request.config.clone()),
+                mode: PersistedMode::Client,
+                config: Some(config_text),
+                config_path: None,
+                no_dns,
                 vpn_ip: Some(new_vpn_ip.clone()),
                 server_endpoint: Some(new_endpoint.clone()),
                 last_connected_at: Some(persistence::iso_now()),
@@ -515,11 +738,11 @@ pub async fn handle_update_config(
             // Start the client run loop in background
             spawn_client_task(client, shutdown_rx, state.daemon_state.clone(), state.status_tx.clone());
 
-            Ok(Json(UpdateConfigResponse {
+            Ok(UpdateConfigResponse {
                 updated: true,
                 vpn_ip: Some(new_vpn_ip),
                 server_endpoint: Some(new_endpoint),
-            }))
+            })
         }
         Err(e) => {
             tracing::warn!("Config update failed: {}, attempting rollback", e);
@@ -535,7 +758,7 @@ pub async fn handle_update_config(
                 let rollback_endpoint = prev_config
                     .peers
                     .first()
-                    .and_then(|p| p.endpoint.map(|ep| ep.to_string()))
+                    .and_then(|p| p.endpoint_string())
                     .unwrap_or_default();
 
                 // Get fresh traffic stats for rollback attempt
@@ -545,7 +768,8 @@ pub async fn handle_update_config(
                 };
 
                 match WireGuardClient::new(prev_config.clone(), Some(rollback_traffic_stats)).await {
-                    Ok(rollback_client) => {
+                    Ok(mut rollback_client) => {
+                        rollback_client.set_apply_dns(!no_dns);
                         tracing::info!("Rollback successful, reconnected with previous config");
 
                         let (rollback_shutdown_tx, rollback_shutdown_rx) = tokio::sync::watch::channel(false);
@@ -559,11 +783,11 @@ pub async fn handle_update_config(
                                 current_config: prev_config,
                                 previous_config: None, // No previous after rollback
                             });
-                            s.started_at = Some(chrono_now());
+                            s.started_at = Some(timestamp::now());
                             s.shutdown_tx = Some(rollback_shutdown_tx);
                         }
 
-                        send_status_notification(&state).await;
+                        send_status_notification(state).await;
 
                         // NOTE: We don't update the state file here - it already has the old (working) config
                         // from the original connect. On reboot, auto-connect will use that config.
@@ -611,7 +835,7 @@ pub async fn handle_update_config(
                             s.mode = None;
                         }
 
-                        send_status_notification(&state).await;
+                        send_status_notification(state).await;
 
                         return Err(ApiError {
                             code: UPDATE_FAILED,
@@ -641,7 +865,7 @@ pub async fn handle_update_config(
                     s.mode = None;
                 }
 
-                send_status_notification(&state).await;
+                send_status_notification(state).await;
 
                 Err(ApiError {
                     code: UPDATE_FAILED,
@@ -652,6 +876,192 @@ pub async fn handle_update_config(
     }
 }
 
+/// POST /api/v1/config/reload - Re-read the config file given to connect/start
+pub async fn handle_reload_config(
+    State(state): State<AppState>,
+) -> Result<Json<ReloadConfigResponse>, ApiError> {
+    reload_config(&state).await.map(Json)
+}
+
+/// Re-read the tracked config file and apply it to the running VPN
+///
+/// Client mode goes through [`update_client_config`] (validate, reconnect,
+/// roll back on failure). Server mode keeps the interface and brings the live
+/// peer set in line with the file's `[Peer]` sections.
+async fn reload_config(state: &AppState) -> Result<ReloadConfigResponse, ApiError> {
+    let (config_path, config_dir, server_mode) = {
+        let s = state.daemon_state.lock().await;
+        let server_mode = match &s.mode {
+            Some(VpnMode::Client { .. }) => false,
+            Some(VpnMode::Server { .. }) => true,
+            None => {
+                return Err(ApiError {
+                    code: NOT_CONNECTED,
+                    message: "Nothing is running".to_string(),
+                });
+            }
+        };
+        let config_path = s.config_path.clone().ok_or_else(|| ApiError {
+            code: INVALID_PARAMS,
+            message: "Config was passed inline; connect or start with config_path to enable reload"
+                .to_string(),
+        })?;
+        (config_path, s.config_dir.clone(), server_mode)
+    };
+
+    // Checked again: the file may have been swapped for a link out of the dir
+    let config_text = read_config_file(&config_path, config_dir.as_deref()).map_err(|message| ApiError {
+        code: INVALID_CONFIG,
+        message,
+    })?;
+    tracing::info!("Reloading config from {}", config_path.display());
+
+    if !server_mode {
+        let updated = update_client_config(state, config_text).await?;
+        return Ok(ReloadConfigResponse {
+            reloaded: true,
+            config_path,
+            vpn_ip: updated.vpn_ip,
+            server_endpoint: updated.server_endpoint,
+            peers_added: 0,
+            peers_removed: 0,
+            peers_changed: 0,
+        });
+    }
+
+    let new_config = WireGuardConfig::from_string(&config_text)
+        .and_then(|c| c.validate_server(true).map(|_| c))
+        .map_err(|e| ApiError {
+            code: INVALID_CONFIG,
+            message: format!("Invalid config: {}", e),
+        })?;
+    let (added, removed, changed) = reload_server_peers(state, new_config).await?;
+
+    let notification = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "config_reloaded",
+        "params": {
+            "config_path": config_path,
+            "peers_added": added,
+            "peers_removed": removed,
+            "peers_changed": changed,
+        }
+    });
+    let _ = state.status_tx.send(serde_json::to_string(&notification).unwrap());
+
+    Ok(ReloadConfigResponse {
+        reloaded: true,
+        config_path,
+        vpn_ip: None,
+        server_endpoint: None,
+        peers_added: added,
+        peers_removed: removed,
+        peers_changed: changed,
+    })
+}
+
+/// Diff the running server's peers against `new_config` and apply the difference
+///
+/// Returns the number of peers added, removed and changed (see
+/// [`server_peer_updates`]).
+async fn reload_server_peers(
+    state: &AppState,
+    new_config: WireGuardConfig,
+) -> Result<(usize, usize, usize), ApiError> {
+    let (current, peer_update_tx, peers) = {
+        let s = state.daemon_state.lock().await;
+        match &s.mode {
+            Some(VpnMode::Server { config, peer_update_tx, peers, .. }) => {
+                (config.clone(), peer_update_tx.clone(), peers.clone())
+            }
+            _ => {
+                return Err(ApiError {
+                    code: SERVER_NOT_RUNNING,
+                    message: "Server not running".to_string(),
+                });
+            }
+        }
+    };
+
+    if new_config.interface != current.interface {
+        return Err(ApiError {
+            code: INVALID_CONFIG,
+            message: "Reload only applies [Peer] changes; restart the server to change [Interface]"
+                .to_string(),
+        });
+    }
+
+    let (updates, added, removed, changed) = server_peer_updates(&current, &new_config, &*peers.read().await);
+
+    for update in updates {
+        peer_update_tx.send(update).await.map_err(|_| ApiError {
+            code: INTERNAL_ERROR,
+            message: "Failed to send peer update".to_string(),
+        })?;
+    }
+
+    let mut s = state.daemon_state.lock().await;
+    if let Some(VpnMode::Server { config, .. }) = &mut s.mode {
+        *config = new_config;
+    }
+
+    Ok((added, removed, changed))
+}
+
+/// Peer updates that bring the live peer set from config `current` to `new_config`
+///
+/// Peers dropped from the file are removed; peers added at runtime through the
+/// API were never in it and are kept. Peers whose key, allowed IPs or endpoint
+/// lock changed are removed and added again, which drops their session;
+/// untouched peers keep theirs. Also returns the number of peers added,
+/// removed and changed.
+fn server_peer_updates(
+    current: &WireGuardConfig,
+    new_config: &WireGuardConfig,
+    live: &PeerManager,
+) -> (Vec<crate::server::PeerUpdate>, usize, usize, usize) {
+    let mut updates = Vec::new();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+    for peer in &current.peers {
+        if live.has_peer(&peer.public_key)
+            && !new_config.peers.iter().any(|p| p.public_key == peer.public_key)
+        {
+            updates.push(crate::server::PeerUpdate::Remove { public_key: peer.public_key });
+            removed += 1;
+        }
+    }
+    for peer in &new_config.peers {
+        let name = match live.get_peer(&peer.public_key) {
+            Some(existing)
+                if existing.psk == peer.preshared_key
+                    && existing.allowed_ips == peer.allowed_ips
+                    && existing.lock_endpoint == peer.lock_endpoint =>
+            {
+                continue;
+            }
+            Some(existing) => {
+                updates.push(crate::server::PeerUpdate::Remove { public_key: peer.public_key });
+                changed += 1;
+                existing.name.clone()
+            }
+            None => {
+                added += 1;
+                None
+            }
+        };
+        updates.push(crate::server::PeerUpdate::Add {
+            public_key: peer.public_key,
+            psk: peer.preshared_key,
+            allowed_ips: peer.allowed_ips.clone(),
+            lock_endpoint: peer.lock_endpoint,
+            name,
+        });
+    }
+
+    (updates, added, removed, changed)
+}
+
 // ============================================================================
 // Server Mode Handlers
 // ============================================================================
@@ -662,31 +1072,51 @@ pub async fn handle_start_server(
     Json(request): Json<StartServerRequest>,
 ) -> Result<Json<StartServerResponse>, ApiError> {
     // Check if already running
-    {
+    let config_dir = {
         let s = state.daemon_state.lock().await;
-        if s.connection_state == ConnectionState::Connected {
+        if s.connection_state.is_active() {
             return Err(ApiError {
                 code: ALREADY_RUNNING,
                 message: "Server or client already running".to_string(),
             });
         }
-    }
+        s.config_dir.clone()
+    };
 
     // Parse config
-    let config = WireGuardConfig::from_string(&request.config).map_err(|e| ApiError {
-        code: INVALID_CONFIG,
-        message: format!("Invalid config: {}", e),
-    })?;
+    let (config, config_text) = read_config_source(&request.config, request.config_path.as_deref(), config_dir.as_deref())
+        .and_then(|text| {
+            WireGuardConfig::from_string(&text)
+                .map(|c| (c, text))
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| ApiError {
+            code: INVALID_CONFIG,
+            message: format!("Invalid config: {}", e),
+        })?;
 
     // Update state
     {
         let mut s = state.daemon_state.lock().await;
         s.connection_state = ConnectionState::Connecting;
         s.error_message = None;
+        s.config_path = request.config_path;
     }
 
     send_status_notification(&state).await;
 
+    start_server(&state, config, Some(config_text)).await.map(Json)
+}
+
+/// Create and spawn the VPN server for a parsed config
+///
+/// Expects the state to be `Connecting` already. `config_text` is persisted so
+/// the server comes back after a daemon restart; a restored state dump passes None.
+pub(crate) async fn start_server(
+    state: &AppState,
+    config: WireGuardConfig,
+    config_text: Option<String>,
+) -> Result<StartServerResponse, ApiError> {
     let listen_port = config.interface.listen_port.unwrap_or(51820);
     let interface_address = config
         .interface
@@ -695,15 +1125,30 @@ pub async fn handle_start_server(
         .map(|a| a.to_string())
         .unwrap_or_default();
 
-    let traffic_stats = {
+    let (traffic_stats, config_path) = {
         let s = state.daemon_state.lock().await;
-        Arc::clone(&s.traffic_stats)
+        (Arc::clone(&s.traffic_stats), s.config_path.clone())
     };
 
+    // PERSIST STATE: Restart the server when the daemon comes back
+    if let Some(config_text) = config_text {
+        let state_file = ConnectionStateFile {
+            mode: PersistedMode::Server,
+            desired_state: DesiredState::Connected,
+            config: Some(config_text),
+            config_path: config_path.map(|p| p.display().to_string()),
+            vpn_ip: Some(interface_address.clone()),
+            ..ConnectionStateFile::default()
+        };
+        if let Err(e) = persistence::save_connection_state(&state_file) {
+            tracing::warn!("Failed to persist server state: {} (it won't restart with the daemon)", e);
+        }
+    }
+
     // Create server with channels for dynamic peer management
     let (peer_update_tx, peer_update_rx) = tokio::sync::mpsc::channel(16);
     let (peer_event_tx, mut peer_event_rx) = tokio::sync::mpsc::channel(16);
-    let peers = Arc::new(Mutex::new(PeerManager::new()));
+    let peers = Arc::new(RwLock::new(PeerManager::new()));
 
     match WireGuardServer::new_with_channels(
         config.clone(),
@@ -721,10 +1166,11 @@ pub async fn handle_start_server(
                 s.mode = Some(VpnMode::Server {
                     listen_port,
                     interface_address: interface_address.clone(),
+                    config,
                     peer_update_tx,
                     peers: peers.clone(),
                 });
-                s.started_at = Some(chrono_now());
+                s.started_at = Some(timestamp::now());
                 s.traffic_stats.reset();
                 s.shutdown_tx = Some(shutdown_tx);
             }
@@ -765,7 +1211,7 @@ pub async fn handle_start_server(
                 }
             });
 
-            Ok(Json(StartServerResponse { started: true }))
+            Ok(StartServerResponse { started: true })
         }
         Err(e) => {
             let mut s = state.daemon_state.lock().await;
@@ -773,7 +1219,7 @@ pub async fn handle_start_server(
             s.error_message = Some(format!("{}", e));
             drop(s);
 
-            send_status_notification(&state).await;
+            send_status_notification(state).await;
 
             Err(ApiError {
                 code: CONNECTION_FAILED,
@@ -783,6 +1229,69 @@ pub async fn handle_start_server(
     }
 }
 
+/// POST /api/v1/server/drain - Keep established sessions, refuse new ones
+///
+/// Body is optional: `{"reject_rekeys": true}` also refuses rekeys. A body
+/// that isn't valid `DrainParams` is rejected rather than read as the default.
+pub async fn handle_drain_server(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<DrainResponse>, ApiError> {
+    let params: DrainParams = if body.is_empty() {
+        DrainParams::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ApiError {
+            code: INVALID_PARAMS,
+            message: format!("Invalid params: {}", e),
+        })?
+    };
+    drain_server(&state, params).await.map(Json)
+}
+
+/// Put the running server into drain mode until it is stopped
+async fn drain_server(
+    state: &AppState,
+    params: DrainParams,
+) -> Result<DrainResponse, ApiError> {
+    let peer_update_tx = {
+        let s = state.daemon_state.lock().await;
+        match &s.mode {
+            Some(VpnMode::Server { peer_update_tx, .. })
+                if matches!(s.connection_state, ConnectionState::Connected | ConnectionState::Draining) =>
+            {
+                peer_update_tx.clone()
+            }
+            _ => {
+                return Err(ApiError {
+                    code: SERVER_NOT_RUNNING,
+                    message: "Server not running".to_string(),
+                });
+            }
+        }
+    };
+
+    let mode = if params.reject_rekeys {
+        crate::server::DrainMode::RejectRekeys
+    } else {
+        crate::server::DrainMode::AllowRekeys
+    };
+    peer_update_tx
+        .send(crate::server::PeerUpdate::Drain { mode })
+        .await
+        .map_err(|_| ApiError {
+            code: INTERNAL_ERROR,
+            message: "Failed to send drain request".to_string(),
+        })?;
+
+    state.daemon_state.lock().await.connection_state = ConnectionState::Draining;
+    send_status_notification(state).await;
+
+    Ok(DrainResponse {
+        draining: true,
+        reject_rekeys: params.reject_rekeys,
+    })
+}
+
 /// POST /api/v1/server/stop - Stop VPN server
 pub async fn handle_stop_server(
     State(state): State<AppState>,
@@ -812,6 +1321,11 @@ pub async fn handle_stop_server(
     }
     drop(s);
 
+    // PERSIST STATE: Don't restart the server with the daemon
+    if let Err(e) = persistence::update_desired_state(DesiredState::Disconnected) {
+        tracing::warn!("Failed to persist stopped state: {}", e);
+    }
+
     send_status_notification(&state).await;
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
@@ -833,17 +1347,23 @@ pub async fn handle_list_peers(State(state): State<AppState>) -> Result<Json<Lis
     };
     drop(s);
 
-    let peers_guard = peers.lock().await;
+    let peers_guard = peers.read().await;
     let peer_list: Vec<PeerInfo> = peers_guard
-        .iter()
+        .sorted()
+        .into_iter()
         .map(|peer_state| PeerInfo {
             public_key: base64::engine::general_purpose::STANDARD.encode(peer_state.public_key),
+            name: peer_state.name.clone(),
             endpoint: peer_state.endpoint.map(|e: std::net::SocketAddr| e.to_string()),
             allowed_ips: peer_state.allowed_ips.iter().map(|ip: &ipnet::IpNet| ip.to_string()).collect(),
+            allowed_ips_v4: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, false),
+            allowed_ips_v6: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, true),
             has_session: peer_state.session.is_some(),
-            last_handshake: peer_state.last_handshake.map(|_| chrono_now()),
+            last_handshake: peer_state.last_handshake_at.map(timestamp::format),
             bytes_sent: peer_state.traffic_stats.get_sent(),
             bytes_received: peer_state.traffic_stats.get_received(),
+            inner_bytes_sent: peer_state.traffic_stats.get_inner_sent(),
+            inner_bytes_received: peer_state.traffic_stats.get_inner_received(),
         })
         .collect();
 
@@ -881,7 +1401,7 @@ pub async fn handle_peer_status(
             message: "Public key must be 32 bytes".to_string(),
         })?;
 
-    let peers_guard = peers.lock().await;
+    let peers_guard = peers.read().await;
     let peer_state = peers_guard.get_peer(&pubkey_bytes).ok_or(ApiError {
         code: PEER_NOT_FOUND,
         message: "Peer not found".to_string(),
@@ -889,15 +1409,46 @@ pub async fn handle_peer_status(
 
     Ok(Json(PeerInfo {
         public_key: pubkey,
+        name: peer_state.name.clone(),
         endpoint: peer_state.endpoint.map(|e| e.to_string()),
         allowed_ips: peer_state.allowed_ips.iter().map(|ip| ip.to_string()).collect(),
+        allowed_ips_v4: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, false),
+        allowed_ips_v6: PeerInfo::allowed_ips_of_family(&peer_state.allowed_ips, true),
         has_session: peer_state.session.is_some(),
-        last_handshake: peer_state.last_handshake.map(|_| chrono_now()),
+        last_handshake: peer_state.last_handshake_at.map(timestamp::format),
         bytes_sent: peer_state.traffic_stats.get_sent(),
         bytes_received: peer_state.traffic_stats.get_received(),
+        inner_bytes_sent: peer_state.traffic_stats.get_inner_sent(),
+        inner_bytes_received: peer_state.traffic_stats.get_inner_received(),
     }))
 }
 
+/// GET /api/v1/server/route/:ip - Which peer traffic to an IP is routed to
+pub async fn handle_resolve_route(
+    State(state): State<AppState>,
+    Path(ip): Path<String>,
+) -> Result<Json<ResolveRouteResponse>, ApiError> {
+    let ip: std::net::IpAddr = ip.parse().map_err(|_| ApiError {
+        code: INVALID_PARAMS,
+        message: format!("Invalid IP address: {}", ip),
+    })?;
+
+    let s = state.daemon_state.lock().await;
+    let peers = match &s.mode {
+        Some(VpnMode::Server { peers, .. }) => peers.clone(),
+        _ => {
+            return Err(ApiError {
+                code: SERVER_NOT_RUNNING,
+                message: "Server not running".to_string(),
+            });
+        }
+    };
+    drop(s);
+
+    let peers_guard = peers.read().await;
+    Ok(Json(ResolveRouteResponse::resolve(&peers_guard, ip)))
+}
+
 /// POST /api/v1/server/peers - Add a new peer
 pub async fn handle_add_peer(
     State(state): State<AppState>,
@@ -958,12 +1509,19 @@ pub async fn handle_add_peer(
         None
     };
 
+    let name = validate_peer_name(request.name.as_deref()).map_err(|message| ApiError {
+        code: INVALID_PARAMS,
+        message,
+    })?;
+
     // Send peer update
     peer_update_tx
         .send(crate::server::PeerUpdate::Add {
             public_key: pubkey_bytes,
             psk,
             allowed_ips,
+            lock_endpoint: request.lock_endpoint,
+            name,
         })
         .await
         .map_err(|_| ApiError {
@@ -1021,7 +1579,7 @@ pub async fn handle_remove_peer(
 
     // Check if peer exists and was connected
     let was_connected = {
-        let peers_guard = peers.lock().await;
+        let peers_guard = peers.read().await;
         peers_guard
             .get_peer(&pubkey_bytes)
             .map(|p| p.session.is_some())
@@ -1075,20 +1633,18 @@ pub async fn handle_events_sse(
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
+/// GET /api/v1/events/log - Recent connection events (bounded, oldest first)
+pub async fn handle_event_log(State(state): State<AppState>) -> Json<EventLogResponse> {
+    let s = state.daemon_state.lock().await;
+    Json(EventLogResponse {
+        entries: s.event_log.entries(),
+    })
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-/// Get current timestamp in ISO 8601 format
-fn chrono_now() -> String {
-    use std::time::SystemTime;
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    format!("{}", now)
-}
-
 /// Send status notification to all connected clients
 async fn send_status_notification(state: &AppState) {
     let s = state.daemon_state.lock().await;
@@ -1105,6 +1661,8 @@ async fn send_status_notification(state: &AppState) {
                     "connected_at": s.started_at,
                     "bytes_sent": s.traffic_stats.get_sent(),
                     "bytes_received": s.traffic_stats.get_received(),
+                    "inner_bytes_sent": s.traffic_stats.get_inner_sent(),
+                    "inner_bytes_received": s.traffic_stats.get_inner_received(),
                 }
             })
         }
@@ -1116,9 +1674,11 @@ async fn send_status_notification(state: &AppState) {
             let started_at = s.started_at.clone();
             let bytes_sent = s.traffic_stats.get_sent();
             let bytes_received = s.traffic_stats.get_received();
+            let inner_bytes_sent = s.traffic_stats.get_inner_sent();
+            let inner_bytes_received = s.traffic_stats.get_inner_received();
             drop(s); // Release daemon_state lock before acquiring peers lock
 
-            let peers_guard = peers.lock().await;
+            let peers_guard = peers.read().await;
             let peer_count = peers_guard.len();
             let connected_peer_count = peers_guard.connected_count();
 
@@ -1134,6 +1694,8 @@ async fn send_status_notification(state: &AppState) {
                     "started_at": started_at,
                     "bytes_sent": bytes_sent,
                     "bytes_received": bytes_received,
+                    "inner_bytes_sent": inner_bytes_sent,
+                    "inner_bytes_received": inner_bytes_received,
                 }
             })
         }
@@ -1145,6 +1707,8 @@ async fn send_status_notification(state: &AppState) {
                     "state": s.connection_state,
                     "bytes_sent": 0,
                     "bytes_received": 0,
+                    "inner_bytes_sent": 0,
+                    "inner_bytes_received": 0,
                 }
             })
         }
@@ -1163,9 +1727,12 @@ fn spawn_client_task(
     tokio::spawn(async move {
         let mut client = client;
         let mut shutdown_rx = shutdown_rx;
+        let traffic_stats = attach_client_commands(&mut client, &state, &status_tx).await;
 
         let result = tokio::select! {
             result = client.run() => result,
+            // Ends connections stuck before their first handshake (UI would show Connected)
+            e = connect_watchdog(traffic_stats, CONNECT_HANDSHAKE_TIMEOUT) => Err(e),
             _ = async {
                 loop {
                     shutdown_rx.changed().await.ok();
@@ -1180,7 +1747,7 @@ fn spawn_client_task(
         };
 
         // Update state on completion
-        {
+        let final_state = {
             let mut s = state.lock().await;
             match result {
                 Ok(_) => {
@@ -1196,16 +1763,20 @@ fn spawn_client_task(
             s.mode = None;
             s.started_at = None;
             s.shutdown_tx = None;
-        }
+            s.client_command_tx = None;
+            s.connection_state
+        };
 
         // Send final status notification
         let notification = serde_json::json!({
             "jsonrpc": "2.0",
             "method": "status_changed",
             "params": {
-                "state": "disconnected",
+                "state": final_state,
                 "bytes_sent": 0,
                 "bytes_received": 0,
+                "inner_bytes_sent": 0,
+                "inner_bytes_received": 0,
             }
         });
         let _ = status_tx.send(serde_json::to_string(&notification).unwrap());
@@ -1272,6 +1843,8 @@ fn spawn_server_task(
                 "connected_peer_count": 0,
                 "bytes_sent": 0,
                 "bytes_received": 0,
+                "inner_bytes_sent": 0,
+                "inner_bytes_received": 0,
             }
         });
         let _ = status_tx.send(serde_json::to_string(&notification).unwrap());
@@ -1284,3 +1857,114 @@ fn spawn_server_task(
 }
 
 use base64::Engine;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::PeerUpdate;
+
+    const SERVER_INTERFACE: &str = "[Interface]\n\
+        PrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+        Address = 10.0.0.1/24\n\
+        ListenPort = 51820\n";
+
+    fn server_config(peers: &[(&str, &str)]) -> WireGuardConfig {
+        let mut text = SERVER_INTERFACE.to_string();
+        for (public_key, allowed_ips) in peers {
+            text.push_str(&format!("\n[Peer]\nPublicKey = {}\nAllowedIPs = {}\n", public_key, allowed_ips));
+        }
+        WireGuardConfig::from_string(&text).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_drain_rejects_malformed_body() {
+        let (peer_update_tx, mut peer_update_rx) = tokio::sync::mpsc::channel(4);
+        let daemon_state = DaemonState {
+            connection_state: ConnectionState::Connected,
+            mode: Some(VpnMode::Server {
+                listen_port: 51820,
+                interface_address: "10.0.0.1/24".to_string(),
+                config: server_config(&[]),
+                peer_update_tx,
+                peers: Arc::new(RwLock::new(PeerManager::new())),
+            }),
+            ..Default::default()
+        };
+        let state = AppState {
+            daemon_state: Arc::new(Mutex::new(daemon_state)),
+            status_tx: broadcast::channel(4).0,
+            log_control: None,
+        };
+        let drain = |body: &'static str| handle_drain_server(State(state.clone()), Bytes::from(body));
+
+        // Malformed or mistyped: 400, and the server keeps accepting handshakes
+        for body in ["{\"reject_rekeys\": tru", "{\"reject_rekeys\": \"yes\"}"] {
+            let error = drain(body).await.unwrap_err();
+            assert_eq!(error.code, INVALID_PARAMS);
+            assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(peer_update_rx.try_recv().is_err());
+        assert_eq!(state.daemon_state.lock().await.connection_state, ConnectionState::Connected);
+
+        // No body still means the default
+        let Json(response) = drain("").await.unwrap();
+        assert!(response.draining && !response.reject_rekeys);
+        assert!(matches!(
+            peer_update_rx.try_recv(),
+            Ok(PeerUpdate::Drain { mode: crate::server::DrainMode::AllowRekeys })
+        ));
+
+        let Json(response) = drain("{\"reject_rekeys\": true}").await.unwrap();
+        assert!(response.reject_rekeys);
+        assert!(matches!(
+            peer_update_rx.try_recv(),
+            Ok(PeerUpdate::Drain { mode: crate::server::DrainMode::RejectRekeys })
+        ));
+        assert_eq!(state.daemon_state.lock().await.connection_state, ConnectionState::Draining);
+    }
+
+    #[test]
+    fn test_reload_keeps_peers_added_at_runtime() {
+        let (a, b, c, d) = (
+            "YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=",
+            "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=",
+            "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=",
+            "AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM=",
+        );
+        let current = server_config(&[(a, "10.0.0.2/32"), (b, "10.0.0.3/32")]);
+        let mut live = PeerManager::new();
+        for peer in &current.peers {
+            live.add_peer(peer.public_key, peer.preshared_key, peer.allowed_ips.clone());
+        }
+        // Added through the API, so not in the file
+        let runtime = server_config(&[(c, "10.0.0.4/32")]).peers[0].clone();
+        live.add_peer(runtime.public_key, None, runtime.allowed_ips.clone());
+
+        // The file drops b, moves a to another address and adds d
+        let new_config = server_config(&[(a, "10.0.0.5/32"), (d, "10.0.0.6/32")]);
+        let (updates, added, removed, changed) = server_peer_updates(&current, &new_config, &live);
+        assert_eq!((added, removed, changed), (1, 1, 1));
+
+        let removed_keys: Vec<_> = updates
+            .iter()
+            .filter_map(|u| match u {
+                PeerUpdate::Remove { public_key } => Some(*public_key),
+                _ => None,
+            })
+            .collect();
+        let added_keys: Vec<_> = updates
+            .iter()
+            .filter_map(|u| match u {
+                PeerUpdate::Add { public_key, .. } => Some(*public_key),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(removed_keys, vec![current.peers[1].public_key, current.peers[0].public_key]);
+        assert_eq!(added_keys, vec![new_config.peers[0].public_key, new_config.peers[1].public_key]);
+        assert!(!removed_keys.contains(&runtime.public_key));
+
+        // Reloading an unchanged file changes nothing
+        let (updates, ..) = server_peer_updates(&current, &current, &live);
+        assert!(updates.is_empty());
+    }
+}
@@ -0,0 +1,452 @@
+//! Full daemon state dump for hot upgrades
+//!
+//! Unlike `persistence.rs` (desired client connect state only), a dump captures
+//! the active mode, its config and the live server peer set, so a new daemon
+//! binary can bring the same tunnel back up. TUN devices and sockets are always
+//! recreated on restore.
+//!
+//! Session keys are deliberately left out: restoring a key without its exact
+//! nonce counter risks nonce reuse, and a fresh handshake costs one round trip.
+//! Only session indices are recorded, for diagnostics.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::ipc::ConnectionState;
+use super::persistence;
+use super::{DaemonState, VpnMode};
+use crate::config::{parse_key, InterfaceConfig, PeerConfig, WireGuardConfig};
+use crate::error::ConfigError;
+use crate::protocol::session::PeerManager;
+
+/// Current dump schema version
+pub const STATE_DUMP_SCHEMA_VERSION: u32 = 1;
+
+/// Default location of the state dump
+pub fn get_state_dump_path() -> PathBuf {
+    persistence::get_state_dir().join("daemon-state.json")
+}
+
+/// Serialized daemon state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDump {
+    /// Schema version for future migrations
+    pub schema_version: u32,
+    /// When the dump was taken (Unix epoch seconds)
+    pub dumped_at: String,
+    /// Connection state at dump time
+    pub connection_state: ConnectionState,
+    /// Active mode (None when nothing was running)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ModeDump>,
+}
+
+/// Mode-specific part of a dump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ModeDump {
+    Client {
+        config: ConfigDump,
+        /// Last known-good config (rollback target)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous_config: Option<Box<ConfigDump>>,
+        /// Connected with `no_dns`: the system resolver is left alone
+        #[serde(default)]
+        no_dns: bool,
+    },
+    Server {
+        /// Interface settings plus the live peer set (including peers added over the API)
+        config: ConfigDump,
+        /// Session indices per peer at dump time (diagnostics only, not restored)
+        #[serde(default)]
+        sessions: Vec<SessionDump>,
+    },
+}
+
+/// Serializable mirror of [`WireGuardConfig`] (keys in base64)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigDump {
+    pub interface: InterfaceDump,
+    pub peers: Vec<PeerDump>,
+}
+
+/// Serializable mirror of [`InterfaceConfig`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceDump {
+    pub private_key: String,
+    pub address: Vec<String>,
+    #[serde(default)]
+    pub dns: Vec<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<u32>,
+    #[serde(default)]
+    pub replicate_broadcast: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tun_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sessions: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handshake_sources: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handshake_rate_limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+}
+
+/// Serializable mirror of [`PeerConfig`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerDump {
+    pub public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_host: Option<String>,
+    pub allowed_ips: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handshake_interval: Option<u16>,
+    #[serde(default)]
+    pub lock_endpoint: bool,
+    /// Name given with `add_peer` (server peers only, not part of the config format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Session indices of one server peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDump {
+    pub public_key: String,
+    pub local_index: u32,
+    pub remote_index: u32,
+}
+
+impl StateDump {
+    /// Capture the current daemon state
+    pub async fn capture(state: &DaemonState) -> Self {
+        let mode = match &state.mode {
+            Some(VpnMode::Client { current_config, previous_config, .. }) => Some(ModeDump::Client {
+                config: ConfigDump::from(current_config),
+                previous_config: previous_config.as_deref().map(ConfigDump::from).map(Box::new),
+                no_dns: state.no_dns,
+            }),
+            Some(VpnMode::Server { config, peers, .. }) => {
+                let peers = peers.read().await;
+                let mut live = config.clone();
+                live.peers = peers
+                    .sorted()
+                    .into_iter()
+                    .map(|peer| {
+                        // Peers added over the API have no config entry, only live state
+                        let configured = config.peers.iter().find(|p| p.public_key == peer.public_key);
+                        PeerConfig {
+                            public_key: peer.public_key,
+                            preshared_key: peer.psk,
+                            endpoint: configured.and_then(|p| p.endpoint).or(peer.endpoint),
+                            endpoint_host: configured.and_then(|p| p.endpoint_host.clone()),
+                            allowed_ips: peer.allowed_ips.clone(),
+                            persistent_keepalive: configured.and_then(|p| p.persistent_keepalive),
+                            handshake_interval: configured.and_then(|p| p.handshake_interval),
+                            lock_endpoint: peer.lock_endpoint,
+                        }
+                    })
+                    .collect();
+                let mut live = ConfigDump::from(&live);
+                for (dump, peer) in live.peers.iter_mut().zip(peers.sorted()) {
+                    dump.name = peer.name.clone();
+                }
+                let sessions = peers
+                    .sorted()
+                    .into_iter()
+                    .filter_map(|peer| {
+                        peer.current_session().map(|session| SessionDump {
+                            public_key: BASE64.encode(peer.public_key),
+                            local_index: session.local_index,
+                            remote_index: session.remote_index,
+                        })
+                    })
+                    .collect();
+                Some(ModeDump::Server { config: live, sessions })
+            }
+            None => None,
+        };
+
+        Self {
+            schema_version: STATE_DUMP_SCHEMA_VERSION,
+            dumped_at: persistence::iso_now(),
+            connection_state: state.connection_state,
+            mode,
+        }
+    }
+
+    /// Whether the dumped VPN was up (or coming up) and should be restored
+    pub fn wants_restore(&self) -> bool {
+        self.mode.is_some() && self.connection_state.is_active()
+    }
+}
+
+/// Write a dump atomically (0o600: it contains private keys)
+pub fn write_state_dump(path: &Path, dump: &StateDump) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(dump)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    crate::config::write_atomic(path, json.as_bytes(), Some(0o600))
+}
+
+/// Read a dump written by [`write_state_dump`]
+pub fn read_state_dump(path: &Path) -> std::io::Result<StateDump> {
+    let json = crate::config::read_stable(path)?;
+    let dump: StateDump = serde_json::from_str(&json)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    if dump.schema_version != STATE_DUMP_SCHEMA_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unsupported state dump schema version {}", dump.schema_version),
+        ));
+    }
+    Ok(dump)
+}
+
+impl From<&WireGuardConfig> for ConfigDump {
+    fn from(config: &WireGuardConfig) -> Self {
+        let interface = &config.interface;
+        Self {
+            interface: InterfaceDump {
+                private_key: BASE64.encode(interface.private_key),
+                address: interface.address.iter().map(|a| a.to_string()).collect(),
+                dns: interface.dns.clone(),
+                listen_port: interface.listen_port,
+                bind_address: interface.bind_address,
+                mtu: interface.mtu,
+                fwmark: interface.fwmark,
+                replicate_broadcast: interface.replicate_broadcast,
+                tun_name: interface.tun_name.clone(),
+                max_sessions: interface.max_sessions,
+                handshake_sources: interface.handshake_sources.iter().map(|net| net.to_string()).collect(),
+                idle_timeout: interface.idle_timeout,
+                handshake_rate_limit: interface.handshake_rate_limit,
+                table: interface.table.map(|t| t.to_string()),
+            },
+            peers: config
+                .peers
+                .iter()
+                .map(|peer| PeerDump {
+                    public_key: BASE64.encode(peer.public_key),
+                    preshared_key: peer.preshared_key.map(|k| BASE64.encode(k)),
+                    endpoint: peer.endpoint,
+                    endpoint_host: peer.endpoint_host.clone(),
+                    allowed_ips: peer.allowed_ips.iter().map(|ip| ip.to_string()).collect(),
+                    persistent_keepalive: peer.persistent_keepalive,
+                    handshake_interval: peer.handshake_interval,
+                    lock_endpoint: peer.lock_endpoint,
+                    name: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ConfigDump {
+    /// Give a restored server's peers back their names and endpoints
+    ///
+    /// Neither is part of what the server starts from: names come from
+    /// `add_peer` and endpoints are learned from the peers' packets.
+    pub fn restore_peer_details(&self, peers: &mut PeerManager) {
+        for dump in &self.peers {
+            let Ok(public_key) = parse_key(&dump.public_key, "PublicKey") else {
+                continue;
+            };
+            if let Some(mut peer) = peers.get_peer(&public_key) {
+                peer.name = dump.name.clone();
+                peer.endpoint = dump.endpoint;
+            }
+        }
+    }
+}
+
+impl TryFrom<&ConfigDump> for WireGuardConfig {
+    type Error = ConfigError;
+
+    fn try_from(dump: &ConfigDump) -> Result<Self, Self::Error> {
+        let interface = &dump.interface;
+        let address = interface
+            .address
+            .iter()
+            .map(|a| a.parse().map_err(|_| ConfigError::InvalidCidr { value: a.clone() }))
+            .collect::<Result<_, _>>()?;
+
+        let peers = dump
+            .peers
+            .iter()
+            .map(|peer| {
+                Ok(PeerConfig {
+                    public_key: parse_key(&peer.public_key, "PublicKey")?,
+                    preshared_key: peer
+                        .preshared_key
+                        .as_deref()
+                        .map(|k| parse_key(k, "PresharedKey"))
+                        .transpose()?,
+                    endpoint: peer.endpoint,
+                    endpoint_host: peer.endpoint_host.clone(),
+                    allowed_ips: peer
+                        .allowed_ips
+                        .iter()
+                        .map(|ip| ip.parse().map_err(|_| ConfigError::InvalidCidr { value: ip.clone() }))
+                        .collect::<Result<_, _>>()?,
+                    persistent_keepalive: peer.persistent_keepalive,
+                    handshake_interval: peer.handshake_interval,
+                    lock_endpoint: peer.lock_endpoint,
+                })
+            })
+            .collect::<Result<_, ConfigError>>()?;
+
+        Ok(WireGuardConfig {
+            interface: InterfaceConfig {
+                private_key: parse_key(&interface.private_key, "PrivateKey")?,
+                address,
+                dns: interface.dns.clone(),
+                listen_port: interface.listen_port,
+                bind_address: interface.bind_address,
+                mtu: interface.mtu,
+                fwmark: interface.fwmark,
+                replicate_broadcast: interface.replicate_broadcast,
+                tun_name: interface.tun_name.clone(),
+                max_sessions: interface.max_sessions,
+                handshake_sources: interface
+                    .handshake_sources
+                    .iter()
+                    .map(|net| net.parse().map_err(|_| ConfigError::InvalidCidr { value: net.clone() }))
+                    .collect::<Result<_, _>>()?,
+                idle_timeout: interface.idle_timeout,
+                handshake_rate_limit: interface.handshake_rate_limit,
+                table: interface
+                    .table
+                    .as_ref()
+                    .map(|t| {
+                        t.parse().map_err(|_| ConfigError::InvalidValue {
+                            field: "Table".to_string(),
+                            value: t.clone(),
+                        })
+                    })
+                    .transpose()?,
+            },
+            peers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::session::Session;
+    use std::sync::Arc;
+    use tokio::sync::{mpsc, RwLock};
+
+    const SERVER_CONFIG: &str = "[Interface]\n\
+        PrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+        Address = 10.0.0.1/24\n\
+        ListenPort = 51820\n\
+        MTU = 1380\n\
+        [Peer]\n\
+        PublicKey = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\n\
+        PresharedKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+        Endpoint = 192.0.2.9:51820\n\
+        AllowedIPs = 10.0.0.2/32\n\
+        PersistentKeepalive = 25\n\
+        HandshakeInterval = 60\n";
+
+    #[tokio::test]
+    async fn test_server_dump_includes_live_peers() {
+        let config = WireGuardConfig::parse(SERVER_CONFIG).unwrap();
+        let mut manager = PeerManager::new();
+        for peer in &config.peers {
+            manager.add_peer(peer.public_key, peer.preshared_key, peer.allowed_ips.clone());
+        }
+        // Added over the API after start, so not in the original config
+        manager.add_peer([7u8; 32], None, vec!["10.0.0.3/32".parse().unwrap()]);
+        let endpoint: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        manager.establish_session(&[7u8; 32], Session::new(5, 6, [1u8; 32], [2u8; 32], endpoint));
+        {
+            let mut runtime_peer = manager.get_peer(&[7u8; 32]).unwrap();
+            runtime_peer.endpoint = Some(endpoint);
+            runtime_peer.name = Some("laptop".to_string());
+        }
+
+        let (peer_update_tx, _rx) = mpsc::channel(1);
+        let state = DaemonState {
+            connection_state: ConnectionState::Connected,
+            mode: Some(VpnMode::Server {
+                listen_port: 51820,
+                interface_address: "10.0.0.1/24".to_string(),
+                config: config.clone(),
+                peer_update_tx,
+                peers: Arc::new(RwLock::new(manager)),
+            }),
+            ..Default::default()
+        };
+
+        let dump = StateDump::capture(&state).await;
+        assert!(dump.wants_restore());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon-state.json");
+        write_state_dump(&path, &dump).unwrap();
+        let restored = read_state_dump(&path).unwrap();
+
+        let (config_dump, sessions) = match restored.mode {
+            Some(ModeDump::Server { config, sessions }) => (config, sessions),
+            other => panic!("unexpected mode {:?}", other),
+        };
+        let restored_config = WireGuardConfig::try_from(&config_dump).unwrap();
+        assert_eq!(restored_config.interface.private_key, config.interface.private_key);
+        assert_eq!(restored_config.interface.mtu, Some(1380));
+        assert_eq!(restored_config.peers.len(), 2);
+        assert_eq!(restored_config.peers[0].public_key, [7u8; 32]);
+        assert_eq!(restored_config.peers[1].preshared_key, config.peers[0].preshared_key);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].local_index, 5);
+
+        // Per-peer settings from the file survive, and API peers keep their live state
+        let configured = &restored_config.peers[1];
+        assert_eq!(configured.endpoint, config.peers[0].endpoint);
+        assert_eq!(configured.persistent_keepalive, Some(25));
+        assert_eq!(configured.handshake_interval, Some(60));
+        assert_eq!(restored_config.peers[0].endpoint, Some(endpoint));
+
+        let mut fresh = PeerManager::new();
+        for peer in &restored_config.peers {
+            fresh.add_peer(peer.public_key, peer.preshared_key, peer.allowed_ips.clone());
+        }
+        config_dump.restore_peer_details(&mut fresh);
+        let runtime_peer = fresh.get_peer(&[7u8; 32]).unwrap();
+        assert_eq!(runtime_peer.name.as_deref(), Some("laptop"));
+        assert_eq!(runtime_peer.endpoint, Some(endpoint));
+        assert_eq!(fresh.get_peer(&config.peers[0].public_key).unwrap().name, None);
+
+        // No key material beyond the configured keys ends up in the file
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains(&BASE64.encode([1u8; 32])));
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_dump_does_not_restore() {
+        let dump = StateDump::capture(&DaemonState::default()).await;
+        assert!(dump.mode.is_none());
+        assert!(!dump.wants_restore());
+    }
+}
@@ -0,0 +1,107 @@
+//! Per-interval throughput for live graphs
+//!
+//! The bandwidth task samples the daemon's [`TrafficStats`] once a second and
+//! broadcasts the difference as a `throughput` notification, so dashboards get
+//! bytes per second without polling or tracking counters themselves. A counter
+//! that went backwards (stats reset on reconnect) reports 0 for that interval.
+
+use std::time::Instant;
+
+use crate::protocol::TrafficStats;
+
+use super::ipc::ThroughputParams;
+
+/// Remembers the previous sample of the traffic counters
+#[derive(Debug, Default)]
+pub struct ThroughputMeter {
+    last: Option<(Instant, Counters)>,
+}
+
+/// Totals in the order outer sent, outer received, inner sent, inner received
+type Counters = [u64; 4];
+
+impl ThroughputMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample `stats` at `now` and return rates since the previous sample
+    ///
+    /// The first sample after [`ThroughputMeter::reset`] has nothing to compare
+    /// against and reports zero rates.
+    pub fn sample(&mut self, stats: &TrafficStats, now: Instant) -> ThroughputParams {
+        let current = [
+            stats.get_sent(),
+            stats.get_received(),
+            stats.get_inner_sent(),
+            stats.get_inner_received(),
+        ];
+
+        let (interval_ms, rates) = match self.last {
+            Some((at, previous)) => {
+                let elapsed = now.saturating_duration_since(at);
+                let rate = |i: usize| {
+                    let delta = current[i].saturating_sub(previous[i]);
+                    if elapsed.is_zero() {
+                        0
+                    } else {
+                        (delta as f64 / elapsed.as_secs_f64()).round() as u64
+                    }
+                };
+                (elapsed.as_millis() as u64, [rate(0), rate(1), rate(2), rate(3)])
+            }
+            None => (0, [0; 4]),
+        };
+        self.last = Some((now, current));
+
+        ThroughputParams {
+            interval_ms,
+            tx_bytes_per_sec: rates[0],
+            rx_bytes_per_sec: rates[1],
+            inner_tx_bytes_per_sec: rates[2],
+            inner_rx_bytes_per_sec: rates[3],
+            bytes_sent: current[0],
+            bytes_received: current[1],
+            inner_bytes_sent: current[2],
+            inner_bytes_received: current[3],
+        }
+    }
+
+    /// Forget the previous sample (the tunnel went down)
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rates_and_counter_reset() {
+        let stats = TrafficStats::new();
+        let mut meter = ThroughputMeter::new();
+        let start = Instant::now();
+
+        let first = meter.sample(&stats, start);
+        assert_eq!(first.interval_ms, 0);
+        assert_eq!(first.tx_bytes_per_sec, 0);
+
+        stats.add_sent(3000);
+        stats.add_received(1000);
+        let second = meter.sample(&stats, start + Duration::from_secs(2));
+        assert_eq!(second.interval_ms, 2000);
+        assert_eq!(second.tx_bytes_per_sec, 1500);
+        assert_eq!(second.rx_bytes_per_sec, 500);
+        assert_eq!(second.bytes_sent, 3000);
+
+        // Reconnect zeroes the counters: no negative spike
+        stats.reset();
+        stats.add_sent(100);
+        let third = meter.sample(&stats, start + Duration::from_secs(3));
+        assert_eq!(third.tx_bytes_per_sec, 0);
+        assert_eq!(third.rx_bytes_per_sec, 0);
+        assert_eq!(third.bytes_sent, 100);
+    }
+}
@@ -0,0 +1,35 @@
+//! RFC 3339 timestamps for the REST API, notifications and state files
+//!
+//! Every time the daemon reports (`connected_at`, `started_at`,
+//! `last_handshake`, ...) goes through here, so clients can parse them all the
+//! same way. Times are UTC with whole seconds, e.g. `2024-01-30T07:33:20Z`.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// The current time
+pub fn now() -> String {
+    format(SystemTime::now())
+}
+
+/// The time an event happened
+pub fn format(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_timestamps_parse_back() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_706_600_000);
+        assert_eq!(format(at), "2024-01-30T07:33:20Z");
+
+        let parsed = DateTime::parse_from_rfc3339(&now()).unwrap();
+        let drift = SystemTime::from(parsed).elapsed().unwrap_or_default();
+        assert!(drift < Duration::from_secs(5));
+    }
+}
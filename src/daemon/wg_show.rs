@@ -0,0 +1,290 @@
+//! `wg show`-compatible interface and peer output
+//!
+//! Renders the running VPN in the layouts of `wg show` and `wg show <if> dump`,
+//! so monitoring scripts written for `wg` work against minnowvpn. Private and
+//! preshared keys are never printed; they appear as `(hidden)`.
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ipnet::IpNet;
+
+use super::{DaemonState, VpnMode};
+use crate::config::WireGuardConfig;
+
+/// Interface name shown when the config doesn't set one
+const DEFAULT_INTERFACE_NAME: &str = "minnowvpn";
+
+/// Snapshot of the running interface
+#[derive(Debug, Clone)]
+pub struct WgInterface {
+    pub name: String,
+    pub public_key: [u8; 32],
+    pub listen_port: u16,
+    pub fwmark: Option<u32>,
+    pub peers: Vec<WgPeer>,
+}
+
+/// Snapshot of one peer
+#[derive(Debug, Clone)]
+pub struct WgPeer {
+    pub public_key: [u8; 32],
+    pub has_psk: bool,
+    pub endpoint: Option<SocketAddr>,
+    pub allowed_ips: Vec<IpNet>,
+    /// Last completed handshake (seconds since the Unix epoch)
+    pub latest_handshake: Option<u64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub persistent_keepalive: Option<u16>,
+}
+
+impl WgInterface {
+    /// Capture the client or server the daemon is running (None when idle)
+    pub async fn capture(state: &DaemonState) -> Option<Self> {
+        match state.mode.as_ref()? {
+            VpnMode::Client { current_config, .. } => {
+                let stats = &state.traffic_stats;
+                let peers = current_config
+                    .peers
+                    .first()
+                    .map(|peer| WgPeer {
+                        public_key: peer.public_key,
+                        has_psk: peer.preshared_key.is_some(),
+                        endpoint: peer.endpoint,
+                        allowed_ips: peer.allowed_ips.clone(),
+                        latest_handshake: stats.last_handshake_ms().map(|ms| ms / 1000),
+                        rx_bytes: stats.get_received(),
+                        tx_bytes: stats.get_sent(),
+                        persistent_keepalive: peer.persistent_keepalive,
+                    })
+                    .into_iter()
+                    .collect();
+                Some(Self::new(current_config, peers))
+            }
+            VpnMode::Server { config, peers, .. } => {
+                let peers = peers
+                    .read()
+                    .await
+                    .sorted()
+                    .into_iter()
+                    .map(|peer| WgPeer {
+                        public_key: peer.public_key,
+                        has_psk: peer.psk.is_some(),
+                        endpoint: peer.endpoint,
+                        allowed_ips: peer.allowed_ips.clone(),
+                        latest_handshake: peer
+                            .last_handshake_at
+                            .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs()),
+                        rx_bytes: peer.traffic_stats.get_received(),
+                        tx_bytes: peer.traffic_stats.get_sent(),
+                        persistent_keepalive: config
+                            .peers
+                            .iter()
+                            .find(|p| p.public_key == peer.public_key)
+                            .and_then(|p| p.persistent_keepalive),
+                    })
+                    .collect();
+                Some(Self::new(config, peers))
+            }
+        }
+    }
+
+    fn new(config: &WireGuardConfig, peers: Vec<WgPeer>) -> Self {
+        Self {
+            name: config
+                .interface
+                .tun_name
+                .clone()
+                .unwrap_or_else(|| DEFAULT_INTERFACE_NAME.to_string()),
+            public_key: config.public_key(),
+            listen_port: config.interface.listen_port.unwrap_or(0),
+            fwmark: config.interface.fwmark,
+            peers,
+        }
+    }
+
+    /// `wg show <if> dump` layout: tab-separated, interface line then one line per peer
+    pub fn dump(&self) -> String {
+        let mut out = format!(
+            "(hidden)\t{}\t{}\t{}\n",
+            BASE64.encode(self.public_key),
+            self.listen_port,
+            self.fwmark.map_or("off".to_string(), |mark| format!("0x{:x}", mark)),
+        );
+        for peer in &self.peers {
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                BASE64.encode(peer.public_key),
+                if peer.has_psk { "(hidden)" } else { "(none)" },
+                peer.endpoint.map_or("(none)".to_string(), |e| e.to_string()),
+                allowed_ips_list(&peer.allowed_ips, ","),
+                peer.latest_handshake.unwrap_or(0),
+                peer.rx_bytes,
+                peer.tx_bytes,
+                peer.persistent_keepalive.map_or("off".to_string(), |secs| secs.to_string()),
+            );
+        }
+        out
+    }
+
+    /// Human-readable `wg show` layout (`now` in seconds since the Unix epoch)
+    pub fn show(&self, now: u64) -> String {
+        let mut out = format!("interface: {}\n", self.name);
+        let _ = writeln!(out, "  public key: {}", BASE64.encode(self.public_key));
+        out.push_str("  private key: (hidden)\n");
+        if self.listen_port != 0 {
+            let _ = writeln!(out, "  listening port: {}", self.listen_port);
+        }
+        if let Some(mark) = self.fwmark {
+            let _ = writeln!(out, "  fwmark: 0x{:x}", mark);
+        }
+
+        for peer in &self.peers {
+            let _ = write!(out, "\npeer: {}\n", BASE64.encode(peer.public_key));
+            if peer.has_psk {
+                out.push_str("  preshared key: (hidden)\n");
+            }
+            if let Some(endpoint) = peer.endpoint {
+                let _ = writeln!(out, "  endpoint: {}", endpoint);
+            }
+            let _ = writeln!(out, "  allowed ips: {}", allowed_ips_list(&peer.allowed_ips, ", "));
+            if let Some(at) = peer.latest_handshake {
+                let _ = writeln!(out, "  latest handshake: {}", ago(now.saturating_sub(at)));
+            }
+            if peer.rx_bytes > 0 || peer.tx_bytes > 0 {
+                let _ = writeln!(
+                    out,
+                    "  transfer: {} received, {} sent",
+                    bytes(peer.rx_bytes),
+                    bytes(peer.tx_bytes)
+                );
+            }
+            if let Some(secs) = peer.persistent_keepalive {
+                let _ = writeln!(out, "  persistent keepalive: every {}", duration(secs as u64));
+            }
+        }
+        out
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn allowed_ips_list(allowed_ips: &[IpNet], separator: &str) -> String {
+    if allowed_ips.is_empty() {
+        return "(none)".to_string();
+    }
+    allowed_ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+/// "1 minute, 5 seconds ago" (or "Now"), like wg
+fn ago(secs: u64) -> String {
+    if secs == 0 {
+        return "Now".to_string();
+    }
+    format!("{} ago", duration(secs))
+}
+
+/// "2 days, 1 hour, 5 seconds" with zero units left out, like wg
+fn duration(secs: u64) -> String {
+    let units = [
+        (secs / 86400, "day"),
+        (secs / 3600 % 24, "hour"),
+        (secs / 60 % 60, "minute"),
+        (secs % 60, "second"),
+    ];
+    units
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{} {}{}", n, unit, if *n == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// "1.50 KiB", like wg
+fn bytes(n: u64) -> String {
+    const UNITS: [(u64, &str); 4] = [(1 << 40, "TiB"), (1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")];
+    UNITS
+        .iter()
+        .find(|(size, _)| n >= *size)
+        .map_or(format!("{} B", n), |(size, unit)| format!("{:.2} {}", n as f64 / *size as f64, unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface() -> WgInterface {
+        WgInterface {
+            name: "wg0".to_string(),
+            public_key: [1u8; 32],
+            listen_port: 51820,
+            fwmark: None,
+            peers: vec![
+                WgPeer {
+                    public_key: [2u8; 32],
+                    has_psk: true,
+                    endpoint: Some("192.0.2.1:51820".parse().unwrap()),
+                    allowed_ips: vec!["10.0.0.2/32".parse().unwrap(), "fd00::2/128".parse().unwrap()],
+                    latest_handshake: Some(1_000),
+                    rx_bytes: 1536,
+                    tx_bytes: 100,
+                    persistent_keepalive: Some(25),
+                },
+                WgPeer {
+                    public_key: [3u8; 32],
+                    has_psk: false,
+                    endpoint: None,
+                    allowed_ips: vec![],
+                    latest_handshake: None,
+                    rx_bytes: 0,
+                    tx_bytes: 0,
+                    persistent_keepalive: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_dump_layout() {
+        let dump = interface().dump();
+        let lines: Vec<Vec<&str>> = dump.lines().map(|l| l.split('\t').collect()).collect();
+
+        assert_eq!(lines[0], ["(hidden)", &BASE64.encode([1u8; 32]), "51820", "off"]);
+        assert_eq!(
+            lines[1][1..],
+            ["(hidden)", "192.0.2.1:51820", "10.0.0.2/32,fd00::2/128", "1000", "1536", "100", "25"]
+        );
+        assert_eq!(lines[2][1..], ["(none)", "(none)", "(none)", "0", "0", "0", "off"]);
+    }
+
+    #[test]
+    fn test_show_layout() {
+        let show = interface().show(1_065);
+        assert!(show.starts_with("interface: wg0\n"));
+        assert!(show.contains("  listening port: 51820\n"));
+        assert!(show.contains("  latest handshake: 1 minute, 5 seconds ago\n"));
+        assert!(show.contains("  transfer: 1.50 KiB received, 100 B sent\n"));
+        assert!(show.contains("  persistent keepalive: every 25 seconds\n"));
+        assert!(!show.contains("fwmark"));
+    }
+
+    #[test]
+    fn test_duration_units() {
+        assert_eq!(ago(0), "Now");
+        assert_eq!(duration(1), "1 second");
+        assert_eq!(duration(90061), "1 day, 1 hour, 1 minute, 1 second");
+        assert_eq!(duration(7200), "2 hours");
+        assert_eq!(bytes(3 << 30), "3.00 GiB");
+    }
+}
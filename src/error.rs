@@ -51,6 +51,41 @@ pub enum ConfigError {
     #[error("Invalid CIDR notation: {value}")]
     InvalidCidr { value: String },
 
+    #[error(
+        "Peer #{peer} PublicKey is this interface's own public key (derived from PrivateKey). \
+         The key pairing is almost certainly wrong: [Peer] PublicKey must be the other side's key"
+    )]
+    OwnKeyAsPeer { peer: usize },
+
+    #[error("No [Peer] sections in config: {hint}")]
+    NoPeers { hint: String },
+
+    #[error("First [Peer] has no Endpoint; client mode needs the server's address")]
+    NoPeerEndpoint,
+
+    #[error("ListenPort = 0 would bind a random port; a server needs the fixed port clients connect to")]
+    ZeroListenPort,
+
+    #[error("MTU {mtu} is outside the usable range {min}-{max} (inner packets plus the 32-byte WireGuard and UDP/IP overhead must fit)")]
+    InvalidMtu { mtu: u16, min: u16, max: u16 },
+
+    #[error("{path}: {source}")]
+    InFile {
+        path: String,
+        source: Box<ConfigError>,
+    },
+
+    #[error("Config exceeds limit: {what} (max {limit})")]
+    LimitExceeded { what: String, limit: usize },
+
+    /// A setting outside the config file format (e.g. in a state dump) with a bad value
+    #[error("Invalid {field}: {value}")]
+    InvalidValue { field: String, value: String },
+
+    /// A state dump that was read but could not be brought back up
+    #[error("Cannot restore state: {reason}")]
+    StateRestore { reason: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -73,6 +108,9 @@ pub enum CryptoError {
     #[error("Invalid nonce")]
     InvalidNonce,
 
+    #[error("Output buffer too small: need {needed} bytes, got {got}")]
+    BufferTooSmall { needed: usize, got: usize },
+
     #[error("DH computation failed")]
     DiffieHellman,
 }
@@ -83,6 +121,12 @@ pub enum ProtocolError {
     #[error("Handshake timeout after {seconds}s")]
     HandshakeTimeout { seconds: u64 },
 
+    #[error("No handshake completed within {seconds}s ({attempts} attempts)")]
+    ConnectTimeout { seconds: u64, attempts: u32 },
+
+    #[error("No handshake completed after {attempts} attempts")]
+    MaxRetriesExceeded { attempts: u32 },
+
     #[error("Handshake failed: {reason}")]
     HandshakeFailed { reason: String },
 
@@ -101,12 +145,32 @@ pub enum ProtocolError {
     #[error("Session expired")]
     SessionExpired,
 
+    /// REJECT_AFTER_MESSAGES packets were sent on these keys; a new handshake is needed
+    #[error("Sending counter exhausted, a new handshake is needed")]
+    CounterExhausted,
+
     #[error("No active session")]
     NoSession,
 
     #[error("Invalid sender index: {index}")]
     InvalidSenderIndex { index: u32 },
 
+    /// A valid initiation from a public key that is not a configured peer
+    #[error("Unknown peer: {public_key_prefix}")]
+    UnknownPeer { public_key_prefix: String },
+
+    /// The server is draining and won't start a session for this peer
+    #[error("Server is draining; handshake refused for peer {public_key_prefix}")]
+    ServerDraining { public_key_prefix: String },
+
+    /// An initiation whose TAI64N timestamp is not newer than the peer's last one
+    #[error("Replayed handshake initiation from peer {public_key_prefix}")]
+    StaleTimestamp { public_key_prefix: String },
+
+    /// An initiation for a peer locked to another endpoint
+    #[error("Handshake from {from} refused: peer {public_key_prefix} is locked to its endpoint")]
+    EndpointLocked { public_key_prefix: String, from: String },
+
     #[error("Cookie required but not available")]
     CookieRequired,
 }
@@ -135,6 +199,21 @@ pub enum NetworkError {
     #[error("Endpoint not set")]
     NoEndpoint,
 
+    #[error("Daemon already running on {addr}")]
+    DaemonAlreadyRunning { addr: String },
+
+    #[error("PID file {path} belongs to running process {pid}")]
+    DaemonPidFileInUse { path: String, pid: u32 },
+
+    #[error("No daemon running on {addr}")]
+    DaemonNotRunning { addr: String },
+
+    #[error("Daemon request failed ({status}): {message}")]
+    DaemonRequestFailed { status: u16, message: String },
+
+    #[error("Cannot read daemon auth token {path}: {reason}")]
+    DaemonTokenUnreadable { path: String, reason: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -145,6 +224,9 @@ pub enum TunnelError {
     #[error("Failed to create TUN device: {reason}")]
     CreateFailed { reason: String },
 
+    #[error("Failed to assign address {address} to TUN device: {reason}")]
+    AddressAssignmentFailed { address: String, reason: String },
+
     #[error("TUN read failed: {reason}")]
     ReadFailed { reason: String },
 
@@ -163,6 +245,12 @@ pub enum TunnelError {
     #[error("Platform not supported: {platform}")]
     UnsupportedPlatform { platform: String },
 
+    #[error("Failed to enter network namespace {name}: {reason}")]
+    NetnsFailed { name: String, reason: String },
+
+    #[error("DNS setup failed: {reason}")]
+    DnsSetupFailed { reason: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -190,6 +278,14 @@ impl MinnowVpnError {
                 return format!("{}", self);
             }
 
+            Self::Tunnel(TunnelError::AddressAssignmentFailed { address, reason }) => {
+                format!(
+                    "Created the TUN device but could not assign {}: {}\n  \
+                    Another interface may already use this address - check Address in [Interface].",
+                    address, reason
+                )
+            }
+
             Self::Config(ConfigError::FileNotFound { path }) => {
                 format!(
                     "Config file not found: {}\n  Check the path and try again.",
@@ -220,8 +316,25 @@ impl MinnowVpnError {
                 )
             }
 
+            Self::Protocol(ProtocolError::ConnectTimeout { seconds, attempts }) => {
+                format!(
+                    "Could not connect within {}s ({} handshake attempts).\n  \
+                    Check the peer endpoint, keys, and firewall rules for UDP.",
+                    seconds, attempts
+                )
+            }
+
+            Self::Protocol(ProtocolError::MaxRetriesExceeded { attempts }) => {
+                format!(
+                    "Could not connect after {} handshake attempts.\n  \
+                    Check the peer endpoint, keys, and firewall rules for UDP.",
+                    attempts
+                )
+            }
+
             Self::Protocol(ProtocolError::MacVerificationFailed) => {
-                "MAC verification failed. The peer's public key may be incorrect.".to_string()
+                "MAC verification failed. The peer's public key may be incorrect.\n  \
+                Not retrying: check PublicKey in the [Peer] section.".to_string()
             }
 
             _ => format!("{}", self),
@@ -235,15 +348,18 @@ impl MinnowVpnError {
             Self::Config(_) => false,
             Self::Tunnel(TunnelError::InsufficientPrivileges { .. }) => false,
             Self::Tunnel(TunnelError::CreateFailed { .. }) => false,
+            Self::Tunnel(TunnelError::AddressAssignmentFailed { .. }) => false,
             Self::Tunnel(TunnelError::UnsupportedPlatform { .. }) => false,
+            // Key mismatch: retrying with the same keys never succeeds
+            Self::Protocol(ProtocolError::MacVerificationFailed) => false,
 
             // Recoverable errors
             Self::Protocol(ProtocolError::HandshakeTimeout { .. }) => true,
             Self::Protocol(ProtocolError::SessionExpired) => true,
+            Self::Protocol(ProtocolError::CounterExhausted) => true,
             Self::Protocol(ProtocolError::NoSession) => true,
             Self::Network(_) => true,
             Self::Crypto(CryptoError::Decryption) => true,
-            Self::Protocol(ProtocolError::MacVerificationFailed) => true,
             Self::Protocol(ProtocolError::ReplayDetected { .. }) => true,
 
             // Default to non-recoverable for safety
@@ -4,13 +4,23 @@
 //! a client (initiator) or server (responder) using standard WireGuard
 //! configuration files. Can also run as a daemon service for IPC control.
 
+use std::io::Read;
 use std::path::PathBuf;
 use std::process::ExitCode;
-
-use clap::Parser;
-use tracing_subscriber::{fmt, EnvFilter};
-
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter};
+
+use minnowvpn::client::DEFAULT_MAX_ATTEMPTS;
+use minnowvpn::crypto::{keys, x25519};
+use minnowvpn::daemon::{api_client, auth};
+use minnowvpn::daemon::log_level::LogControl;
+use minnowvpn::daemon::pid_file::PidFile;
 use minnowvpn::error::{ConfigError, NetworkError, ProtocolError, TunnelError};
+use minnowvpn::tunnel::netns;
 use minnowvpn::{DaemonService, MinnowVpnError, WireGuardClient, WireGuardConfig, WireGuardServer};
 
 /// Operating mode for direct VPN connection
@@ -24,14 +34,18 @@ enum Mode {
 #[derive(Parser, Debug)]
 #[command(name = "minnowvpn")]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to WireGuard configuration file (required for --client/--server modes)
     #[arg(short, long, required_unless_present = "daemon")]
     config: Option<PathBuf>,
 
-    /// Enable verbose logging
-    #[arg(short, long)]
-    verbose: bool,
+    /// Enable verbose logging (-v for debug, -vv for per-packet trace)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
 
     /// Force server mode (listen for incoming connections)
     #[arg(long, conflicts_with_all = ["client", "daemon"])]
@@ -41,6 +55,30 @@ struct Args {
     #[arg(long, conflicts_with_all = ["server", "daemon"])]
     client: bool,
 
+    /// Give up (exit code 4) if no handshake completes within SECS (default: no time limit)
+    #[arg(long, value_name = "SECS", conflicts_with_all = ["server", "daemon"])]
+    connect_timeout: Option<u64>,
+
+    /// Give up (exit code 4) after N failed handshake attempts (default: 10); 0 retries forever
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ATTEMPTS, conflicts_with_all = ["server", "daemon"])]
+    max_attempts: u32,
+
+    /// Leave the system resolvers alone (ignore DNS in the config)
+    #[arg(long, conflicts_with_all = ["server", "daemon"])]
+    no_dns: bool,
+
+    /// TUN device name (overrides the config; default: chosen by the OS)
+    #[arg(long, value_name = "NAME", conflicts_with = "daemon")]
+    interface: Option<String>,
+
+    /// Directory of `*.peer` files (one [Peer] section each) to merge into the config
+    #[arg(long, conflicts_with = "daemon")]
+    peers_dir: Option<PathBuf>,
+
+    /// Run the TUN and UDP socket inside this network namespace (Linux; created if missing)
+    #[arg(long, value_name = "NAME", conflicts_with = "daemon")]
+    netns: Option<String>,
+
     /// Run as a daemon service (IPC mode for Flutter UI)
     #[arg(long, conflicts_with_all = ["server", "client"])]
     daemon: bool,
@@ -52,26 +90,95 @@ struct Args {
     /// Path to write the auth token file (default: /var/run/minnowvpn/auth-token)
     #[arg(long, requires = "daemon")]
     token_path: Option<PathBuf>,
+
+    /// Restore the VPN from a state dump (written by POST /api/v1/state/dump) on startup
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    restore_state: Option<PathBuf>,
+
+    /// Write the daemon's PID to this file (removed on shutdown). The daemon
+    /// always stays in the foreground.
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    pid_file: Option<PathBuf>,
+
+    /// Let connect/start requests pass a config_path to a file in this directory
+    /// (config files can then be reloaded). Without it only inline configs work.
+    #[arg(long, value_name = "DIR", requires = "daemon")]
+    config_dir: Option<PathBuf>,
+
+    /// Serve GET /api/v1/metrics without a bearer token, for Prometheus scrapers
+    #[arg(long, requires = "daemon")]
+    metrics_no_auth: bool,
+}
+
+/// Key and status utilities, compatible with the `wg` tool
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Print a new base64 private key
+    Genkey,
+    /// Read a base64 private key on stdin and print its public key
+    Pubkey,
+    /// Print a new base64 preshared key
+    Genpsk,
+    /// Show the running daemons' interface and peers, like `wg show`
+    Show {
+        /// Daemon HTTP port (default: both 51820 and 51821)
+        #[arg(long)]
+        http_port: Option<u16>,
+
+        /// Auth token file (default: /var/run/minnowvpn/auth-token; its read-only token is preferred)
+        #[arg(long)]
+        token_path: Option<PathBuf>,
+
+        /// Tab-separated output, like `wg show <if> dump`
+        #[arg(long)]
+        dump: bool,
+    },
 }
 
-#[tokio::main]
-async fn main() -> ExitCode {
+/// Ports `show` looks for daemons on: client mode, then server mode
+const SHOW_PORTS: [u16; 2] = [51820, 51821];
+
+fn main() -> ExitCode {
     let args = Args::parse();
 
+    // Utilities print only their result, so they run before logging is set up
+    if let Some(command) = args.command {
+        return run_command(command);
+    }
+
     // Set up logging
-    let filter = if args.verbose {
-        EnvFilter::new("debug")
-    } else {
-        EnvFilter::new("info")
+    let filter = match args.verbose {
+        0 => EnvFilter::new("info"),
+        1 => EnvFilter::new("debug"),
+        _ => EnvFilter::new("trace"),
     };
 
-    fmt()
-        .with_env_filter(filter)
-        .with_target(false)
+    // Reloadable filter so the daemon can change the level at runtime
+    let (filter, filter_handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_target(false))
         .init();
 
+    // setns only moves the calling thread, so enter the namespace before the
+    // runtime starts: its worker threads then all begin inside it
+    if let Some(ref name) = args.netns {
+        if let Err(e) = netns::enter(name) {
+            eprintln!("Error: {}", user_message(&e));
+            return exit_code(&e);
+        }
+    }
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: failed to start the async runtime: {}", e);
+            return ExitCode::from(255);
+        }
+    };
+
     // Run the client
-    match run(args).await {
+    match runtime.block_on(run(args, LogControl::new(filter_handle))) {
         Ok(_) => ExitCode::SUCCESS,
         Err(e) => {
             eprintln!("Error: {}", user_message(&e));
@@ -80,10 +187,10 @@ async fn main() -> ExitCode {
     }
 }
 
-async fn run(args: Args) -> Result<(), MinnowVpnError> {
+async fn run(args: Args, log_control: LogControl) -> Result<(), MinnowVpnError> {
     // Check if running in daemon mode
     if args.daemon {
-        return run_daemon(args).await;
+        return run_daemon(args, log_control).await;
     }
 
     // Normal client/server mode requires a config file
@@ -94,7 +201,18 @@ async fn run(args: Args) -> Result<(), MinnowVpnError> {
         .to_string();
     tracing::info!("Loading configuration from: {}", config_path);
 
-    let config = WireGuardConfig::from_file(&config_path)?;
+    let mut config = WireGuardConfig::from_file(&config_path)?;
+
+    if let Some(ref peers_dir) = args.peers_dir {
+        let added = config.merge_peers_from(peers_dir)?;
+        tracing::info!("Loaded {} peer(s) from {}", added, peers_dir.display());
+    }
+
+    // Precedence for the TUN name: --interface > config > OS default
+    if let Some(ref name) = args.interface {
+        tracing::info!("Using TUN device name from --interface: {}", name);
+        config.interface.tun_name = Some(name.clone());
+    }
 
     // Determine operating mode
     let mode = determine_mode(&args, &config)?;
@@ -103,6 +221,9 @@ async fn run(args: Args) -> Result<(), MinnowVpnError> {
         Mode::Client => {
             tracing::info!("MinnowVPN WireGuard Client starting...");
             let mut client = WireGuardClient::new(config, None).await?;
+            client.set_connect_timeout(args.connect_timeout.map(Duration::from_secs));
+            client.set_max_attempts((args.max_attempts > 0).then_some(args.max_attempts));
+            client.set_apply_dns(!args.no_dns);
             run_with_cleanup_client(&mut client).await
         }
         Mode::Server => {
@@ -114,17 +235,53 @@ async fn run(args: Args) -> Result<(), MinnowVpnError> {
 }
 
 /// Run in daemon mode (REST API service for Flutter UI)
-async fn run_daemon(args: Args) -> Result<(), MinnowVpnError> {
+async fn run_daemon(args: Args, log_control: LogControl) -> Result<(), MinnowVpnError> {
     tracing::info!("MinnowVPN Daemon starting (REST API mode)...");
 
-    let daemon = DaemonService::new();
+    let mut daemon = DaemonService::new()
+        .with_log_control(log_control)
+        .with_public_metrics(args.metrics_no_auth);
+    if let Some(dir) = args.config_dir.clone() {
+        daemon = daemon.with_config_dir(dir);
+    }
 
     // Default port: 51820 for client mode
     let port = args.http_port.unwrap_or(51820);
 
-    // Run with cleanup on Ctrl+C
+    // Before restoring anything or touching the token files
+    DaemonService::ensure_not_running(port).await?;
+    if let Some(ref path) = args.pid_file {
+        daemon = daemon.with_pid_file(PidFile::create(path)?);
+    }
+
+    // Hot upgrade: bring back what the previous daemon was running
+    let mut restored = false;
+    if let Some(ref path) = args.restore_state {
+        match daemon.restore_state(path).await {
+            Ok(r) => restored = r,
+            Err(e) => tracing::error!("{}", e),
+        }
+    }
+
+    // Otherwise reconnect if the user left the VPN up when the daemon stopped
+    if !restored {
+        daemon.auto_connect();
+    }
+
+    // Run with cleanup on Ctrl+C or SIGTERM (what service managers send)
     let ctrl_c = tokio::signal::ctrl_c();
 
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<Option<()>>();
+
     tokio::select! {
         result = daemon.run_http(port, args.token_path) => {
             result
@@ -134,6 +291,11 @@ async fn run_daemon(args: Args) -> Result<(), MinnowVpnError> {
             daemon.cleanup().await?;
             Ok(())
         }
+        _ = terminate => {
+            tracing::info!("\nReceived SIGTERM, shutting down daemon...");
+            daemon.cleanup().await?;
+            Ok(())
+        }
     }
 }
 
@@ -149,8 +311,8 @@ fn determine_mode(args: &Args, config: &WireGuardConfig) -> Result<Mode, MinnowV
 
     // Auto-detect based on config
     let has_listen_port = config.interface.listen_port.is_some();
-    let all_peers_no_endpoint = config.peers.iter().all(|p| p.endpoint.is_none());
-    let any_peer_has_endpoint = config.peers.iter().any(|p| p.endpoint.is_some());
+    let all_peers_no_endpoint = config.peers.iter().all(|p| !p.has_endpoint());
+    let any_peer_has_endpoint = config.peers.iter().any(|p| p.has_endpoint());
 
     if has_listen_port && all_peers_no_endpoint {
         // Server config: has ListenPort, peers don't have Endpoint
@@ -253,6 +415,12 @@ fn user_message(error: &MinnowVpnError) -> String {
             return format!("{}", error);
         }
 
+        MinnowVpnError::Tunnel(TunnelError::AddressAssignmentFailed { address, reason }) => {
+            format!("TUN device created, but address {} could not be assigned: {}\n  \
+                    Another interface may already use this address - check Address in [Interface].",
+                    address, reason)
+        }
+
         MinnowVpnError::Config(ConfigError::FileNotFound { path }) => {
             format!("Configuration file not found: {}\n  \
                     Check the path and try again.", path)
@@ -268,20 +436,142 @@ fn user_message(error: &MinnowVpnError) -> String {
                     Check that the WireGuard server is running and accessible.", endpoint)
         }
 
+        MinnowVpnError::Network(NetworkError::DnsResolutionFailed { host }) => {
+            format!("Could not resolve peer Endpoint {}.\n  \
+                    Check the hostname and that DNS works without the VPN.", host)
+        }
+
+        MinnowVpnError::Network(NetworkError::DaemonAlreadyRunning { addr }) => {
+            format!("A MinnowVPN daemon is already running on {}.\n  \
+                    Stop it first, or pass --http-port to run a second one.", addr)
+        }
+
+        MinnowVpnError::Network(NetworkError::DaemonNotRunning { addr }) => {
+            format!("No MinnowVPN daemon is running on {}.\n  \
+                    Start one with --daemon, or pass --http-port if it uses another port.", addr)
+        }
+
+        MinnowVpnError::Network(NetworkError::DaemonTokenUnreadable { path, reason }) => {
+            format!("Cannot read the daemon auth token {}: {}\n  \
+                    Run with sudo, or pass --token-path.", path, reason)
+        }
+
+        MinnowVpnError::Network(NetworkError::DaemonPidFileInUse { path, pid }) => {
+            format!("A MinnowVPN daemon (PID {}) is already running according to {}.\n  \
+                    Stop it first, or delete the file if that PID belongs to something else.", pid, path)
+        }
+
         MinnowVpnError::Protocol(ProtocolError::HandshakeTimeout { seconds }) => {
             format!("Handshake timed out after {}s.\n  \
                     Check network connectivity and firewall rules for UDP.", seconds)
         }
 
+        MinnowVpnError::Protocol(ProtocolError::ConnectTimeout { seconds, attempts }) => {
+            format!("No handshake completed within {}s ({} attempts).\n  \
+                    Check the peer endpoint, keys, and firewall rules for UDP.", seconds, attempts)
+        }
+
+        MinnowVpnError::Protocol(ProtocolError::MaxRetriesExceeded { .. }) => error.user_message(),
+
         MinnowVpnError::Protocol(ProtocolError::MacVerificationFailed) => {
             "MAC verification failed.\n  \
-             The peer's public key may be incorrect.".to_string()
+             The peer's public key may be incorrect - check PublicKey in the [Peer] section.".to_string()
         }
 
         _ => format!("{}", error),
     }
 }
 
+/// Run a subcommand; `genkey`, `pubkey` and `genpsk` write base64 exactly like `wg`
+fn run_command(command: Command) -> ExitCode {
+    let key = match command {
+        Command::Genkey => keys::to_base64(&x25519::generate_private_key()),
+        Command::Genpsk => keys::to_base64(&keys::generate_preshared_key()),
+        Command::Pubkey => {
+            let mut input = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+                eprintln!("Error: failed to read the private key from stdin: {}", e);
+                return ExitCode::from(1);
+            }
+            match keys::public_key_base64(&input) {
+                Ok(key) => key,
+                Err(_) => {
+                    eprintln!("Error: stdin is not a base64 private key");
+                    return ExitCode::from(1);
+                }
+            }
+        }
+        Command::Show { http_port, token_path, dump } => {
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| MinnowVpnError::Network(NetworkError::Io(e)))
+                .and_then(|runtime| runtime.block_on(show(http_port, token_path, dump)));
+            return match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Error: {}", user_message(&e));
+                    exit_code(&e)
+                }
+            };
+        }
+    };
+    println!("{}", key);
+    ExitCode::SUCCESS
+}
+
+/// Print `GET /api/v1/show` from every daemon that is running
+///
+/// A daemon without a tunnel up is reported on stderr; it's an error only if
+/// no daemon is reachable at all.
+async fn show(http_port: Option<u16>, token_path: Option<PathBuf>, dump: bool) -> Result<(), MinnowVpnError> {
+    let candidates = http_port.map(|port| vec![port]).unwrap_or_else(|| SHOW_PORTS.to_vec());
+    let mut ports = Vec::new();
+    for port in &candidates {
+        if api_client::is_listening(*port).await {
+            ports.push(*port);
+        }
+    }
+    if ports.is_empty() {
+        let addr = candidates.iter().map(|port| api_client::daemon_addr(*port).to_string()).collect::<Vec<_>>();
+        return Err(NetworkError::DaemonNotRunning { addr: addr.join(" or ") }.into());
+    }
+
+    let token = read_show_token(token_path)?;
+    let path = if dump { "/api/v1/show?format=dump" } else { "/api/v1/show" };
+    let mut first = true;
+    for port in ports {
+        match api_client::get(port, path, &token).await {
+            Ok(text) => {
+                if !first && !dump {
+                    println!();
+                }
+                print!("{}", text);
+                first = false;
+            }
+            Err(MinnowVpnError::Network(NetworkError::DaemonRequestFailed { status: 409, message })) => {
+                eprintln!("{}: {}", api_client::daemon_addr(port), message);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Token for `show`: the read-only one if it can be read, else the full token
+fn read_show_token(token_path: Option<PathBuf>) -> Result<String, MinnowVpnError> {
+    let path = token_path.unwrap_or_else(|| PathBuf::from(auth::DEFAULT_TOKEN_PATH));
+    auth::read_token_file(Some(auth::read_only_token_path(&path)))
+        .or_else(|_| auth::read_token_file(Some(path.clone())))
+        .map_err(|e| {
+            NetworkError::DaemonTokenUnreadable {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            }
+            .into()
+        })
+}
+
 /// Get exit code for error
 fn exit_code(error: &MinnowVpnError) -> ExitCode {
     match error {
@@ -3,16 +3,26 @@
 //! When a server is under load, it responds with a Cookie Reply message
 //! instead of processing the handshake. The client must include the
 //! decrypted cookie in MAC2 of subsequent handshake attempts.
+//!
+//! [`CookieState`] is the initiator's side (storing cookies it received),
+//! [`CookieChecker`] the responder's (issuing cookies and checking MAC2).
 
-use std::time::Instant;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
-use crate::crypto::{aead, noise};
+use crate::crypto::{aead, blake2s, noise, rng};
 use crate::error::{CryptoError, MinnowVpnError};
-use crate::protocol::messages::CookieReply;
+use crate::protocol::messages::{CookieReply, HandshakeInitiation};
 
 /// Cookie validity duration (120 seconds per WireGuard spec)
 const COOKIE_VALIDITY_SECS: u64 = 120;
 
+/// Initiations per second above which the responder demands cookies
+pub const COOKIE_LOAD_THRESHOLD: u32 = 64;
+
+/// Offset of MAC1 in a handshake initiation (MAC2 follows it)
+const INITIATION_MAC1_OFFSET: usize = 116;
+
 /// State for tracking received cookies
 #[derive(Debug, Clone)]
 pub struct CookieState {
@@ -99,6 +109,112 @@ impl CookieState {
     }
 }
 
+/// Responder state for cookie replies
+///
+/// Counts initiations per second. Below the threshold every initiation is
+/// processed as usual; above it, only initiations whose MAC2 carries a valid
+/// cookie for their source address are, and the rest get a Cookie Reply. The
+/// cookie is a MAC of the source address under a random secret that rotates
+/// every two minutes, so proving it costs a spoofed sender a round trip.
+#[derive(Debug)]
+pub struct CookieChecker {
+    /// Key for encrypting cookies: HASH(LABEL_COOKIE || our public key)
+    encryption_key: [u8; 32],
+    /// Random secret cookies are derived from
+    secret: [u8; 32],
+    /// When the secret was generated
+    secret_created_at: Instant,
+    /// Start of the current one-second load window
+    window_start: Instant,
+    /// Initiations seen in the current window
+    initiations: u32,
+    /// Initiations per second that count as load
+    load_threshold: u32,
+}
+
+impl CookieChecker {
+    /// Create a checker for a responder with static public key `our_public`
+    pub fn new(our_public: &[u8; 32]) -> Self {
+        let now = Instant::now();
+        let mut secret = [0u8; 32];
+        rng::fill_bytes(&mut secret);
+        Self {
+            encryption_key: noise::cookie_key(our_public),
+            secret,
+            secret_created_at: now,
+            window_start: now,
+            initiations: 0,
+            load_threshold: COOKIE_LOAD_THRESHOLD,
+        }
+    }
+
+    /// Demand cookies above `initiations` per second instead of the default
+    pub fn set_load_threshold(&mut self, initiations: u32) {
+        self.load_threshold = initiations;
+    }
+
+    /// Record an initiation (MAC1 already verified) and decide whether to answer it with a cookie
+    ///
+    /// Returns the Cookie Reply to send when we are under load and the
+    /// initiation's MAC2 is missing or stale, and None when it may be processed.
+    pub fn check_initiation(
+        &mut self,
+        packet: &[u8],
+        from: SocketAddr,
+    ) -> Result<Option<CookieReply>, MinnowVpnError> {
+        let now = Instant::now();
+        if !self.under_load(now) {
+            return Ok(None);
+        }
+
+        let initiation = HandshakeInitiation::from_bytes(packet)?;
+        let cookie = self.cookie_for(from, now);
+        let mac2_data = &packet[..INITIATION_MAC1_OFFSET + 16];
+        if blake2s::mac_eq(&blake2s::mac_with_cookie(&cookie, mac2_data), &initiation.mac2) {
+            return Ok(None);
+        }
+
+        // cookie is encrypted with the initiation's MAC1 as associated data
+        let mut nonce = [0u8; 24];
+        rng::fill_bytes(&mut nonce);
+        let encrypted = aead::xencrypt(&self.encryption_key, &nonce, &cookie, &initiation.mac1)?;
+        let mut encrypted_cookie = [0u8; 32];
+        encrypted_cookie.copy_from_slice(&encrypted);
+
+        Ok(Some(CookieReply {
+            receiver_index: initiation.sender_index,
+            nonce,
+            encrypted_cookie,
+        }))
+    }
+
+    /// Count one initiation in the current window and report whether it is over the threshold
+    fn under_load(&mut self, now: Instant) -> bool {
+        if now.saturating_duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.initiations = 0;
+        }
+        self.initiations = self.initiations.saturating_add(1);
+        self.initiations > self.load_threshold
+    }
+
+    /// Cookie for `from`: MAC(secret, IP || port), rotating the secret when it is too old
+    fn cookie_for(&mut self, from: SocketAddr, now: Instant) -> [u8; 16] {
+        if now.saturating_duration_since(self.secret_created_at).as_secs() >= COOKIE_VALIDITY_SECS {
+            rng::fill_bytes(&mut self.secret);
+            self.secret_created_at = now;
+        }
+
+        let mut address = Vec::with_capacity(18);
+        match from.ip() {
+            IpAddr::V4(ip) => address.extend_from_slice(&ip.octets()),
+            IpAddr::V6(ip) => address.extend_from_slice(&ip.octets()),
+        }
+        address.extend_from_slice(&from.port().to_be_bytes());
+        blake2s::mac(&self.secret, &address)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +238,41 @@ mod tests {
         assert_eq!(state.get_cookie(), Some(&[42u8; 16]));
     }
 
+    #[test]
+    fn test_cookie_replies_under_load() {
+        use crate::crypto::x25519;
+        use crate::protocol::InitiatorHandshake;
+
+        let (_, server_public) = x25519::generate_keypair();
+        let (client_private, _) = x25519::generate_keypair();
+        let from: SocketAddr = "192.0.2.7:40000".parse().unwrap();
+        let mut checker = CookieChecker::new(&server_public);
+        checker.set_load_threshold(4);
+
+        // Flood: the first few are processed, then cookie replies come back
+        let mut handshake = InitiatorHandshake::new(client_private, server_public, None, 1);
+        let mut replies = Vec::new();
+        for _ in 0..10 {
+            let init = handshake.create_initiation(None).unwrap();
+            if let Some(reply) = checker.check_initiation(&init.to_bytes(), from).unwrap() {
+                replies.push((init.mac1, reply));
+            }
+        }
+        assert_eq!(replies.len(), 6);
+        let (mac1, reply) = &replies[0];
+        assert_eq!(reply.receiver_index, 1);
+
+        // The initiator decrypts the cookie; MAC2 with it gets through
+        let mut state = CookieState::new();
+        state.process_cookie_reply(reply, mac1, &server_public).unwrap();
+        let init = handshake.create_initiation(state.get_cookie()).unwrap();
+        assert!(checker.check_initiation(&init.to_bytes(), from).unwrap().is_none());
+
+        // The cookie is bound to the source address
+        let elsewhere: SocketAddr = "192.0.2.8:40000".parse().unwrap();
+        assert!(checker.check_initiation(&init.to_bytes(), elsewhere).unwrap().is_some());
+    }
+
     #[test]
     fn test_cookie_clear() {
         let mut state = CookieState::new();
@@ -3,6 +3,7 @@
 //! Implements the Noise IKpsk2 handshake pattern for key exchange.
 
 use tai64::Tai64N;
+use zeroize::Zeroize;
 
 use crate::crypto::{blake2s, noise, x25519};
 use crate::error::{CryptoError, ProtocolError, MinnowVpnError};
@@ -26,6 +27,9 @@ pub struct InitiatorHandshake {
     pub noise_state: noise::HandshakeState,
     /// Last MAC1 we sent (needed for cookie processing)
     pub last_mac1: [u8; 16],
+    /// Fixed ephemeral private key for known-answer tests
+    #[cfg(feature = "test-vectors")]
+    fixed_ephemeral: Option<[u8; 32]>,
 }
 
 impl InitiatorHandshake {
@@ -46,18 +50,45 @@ impl InitiatorHandshake {
             ephemeral_private: [0u8; 32],
             noise_state: noise::HandshakeState::new_initiator(&peer_static),
             last_mac1: [0u8; 16],
+            #[cfg(feature = "test-vectors")]
+            fixed_ephemeral: None,
         }
     }
 
+    /// Use a fixed ephemeral private key instead of a random one (test vectors only)
+    #[cfg(feature = "test-vectors")]
+    pub fn with_fixed_ephemeral(mut self, ephemeral_private: [u8; 32]) -> Self {
+        self.fixed_ephemeral = Some(ephemeral_private);
+        self
+    }
+
+    /// Generate the ephemeral keypair for this handshake
+    fn ephemeral_keypair(&self) -> ([u8; 32], [u8; 32]) {
+        #[cfg(feature = "test-vectors")]
+        if let Some(private) = self.fixed_ephemeral {
+            return (private, x25519::public_key(&private));
+        }
+        x25519::generate_keypair()
+    }
+
     /// Create the handshake initiation message
     ///
     /// Returns the message and updates internal state for response processing.
     pub fn create_initiation(
         &mut self,
         cookie: Option<&[u8; 16]>,
+    ) -> Result<HandshakeInitiation, MinnowVpnError> {
+        self.create_initiation_at(cookie, Tai64N::now())
+    }
+
+    /// Create the handshake initiation message stamped with `timestamp`
+    pub fn create_initiation_at(
+        &mut self,
+        cookie: Option<&[u8; 16]>,
+        timestamp: Tai64N,
     ) -> Result<HandshakeInitiation, MinnowVpnError> {
         // Generate ephemeral keypair
-        let (ephemeral_private, ephemeral_public) = x25519::generate_keypair();
+        let (ephemeral_private, ephemeral_public) = self.ephemeral_keypair();
         self.ephemeral_private = ephemeral_private;
 
         // e: Mix ephemeral into hash, then update chaining key
@@ -79,7 +110,6 @@ impl InitiatorHandshake {
         let key = self.noise_state.mix_key(&shared_ss);
 
         // Encrypt timestamp (TAI64N)
-        let timestamp = Tai64N::now();
         let encrypted_timestamp = self.noise_state.encrypt_and_hash(&key, &timestamp.to_bytes())?;
         let encrypted_timestamp: [u8; 28] = encrypted_timestamp
             .try_into()
@@ -183,8 +213,13 @@ pub struct ResponderHandshake {
     pub initiator_static: [u8; 32],
     /// Initiator's sender index (becomes our receiver_index)
     pub initiator_index: u32,
+    /// TAI64N timestamp decrypted from the initiation (replay check)
+    pub initiator_timestamp: [u8; 12],
     /// Last MAC1 we sent (needed for cookie processing)
     pub last_mac1: [u8; 16],
+    /// Fixed ephemeral private key for known-answer tests
+    #[cfg(feature = "test-vectors")]
+    fixed_ephemeral: Option<[u8; 32]>,
 }
 
 impl ResponderHandshake {
@@ -199,10 +234,29 @@ impl ResponderHandshake {
             initiator_ephemeral: [0u8; 32],
             initiator_static: [0u8; 32],
             initiator_index: 0,
+            initiator_timestamp: [0u8; 12],
             last_mac1: [0u8; 16],
+            #[cfg(feature = "test-vectors")]
+            fixed_ephemeral: None,
         }
     }
 
+    /// Use a fixed ephemeral private key instead of a random one (test vectors only)
+    #[cfg(feature = "test-vectors")]
+    pub fn with_fixed_ephemeral(mut self, ephemeral_private: [u8; 32]) -> Self {
+        self.fixed_ephemeral = Some(ephemeral_private);
+        self
+    }
+
+    /// Generate the ephemeral keypair for this handshake
+    fn ephemeral_keypair(&self) -> ([u8; 32], [u8; 32]) {
+        #[cfg(feature = "test-vectors")]
+        if let Some(private) = self.fixed_ephemeral {
+            return (private, x25519::public_key(&private));
+        }
+        x25519::generate_keypair()
+    }
+
     /// Process an incoming handshake initiation (Type 1)
     ///
     /// This decrypts and validates the initiation, extracting the peer's
@@ -240,10 +294,13 @@ impl ResponderHandshake {
         let shared_ss = x25519::dh(&self.static_private, &self.initiator_static);
         let key = self.noise_state.mix_key(&shared_ss);
 
-        // Decrypt timestamp (we don't validate it here, caller should)
-        let _timestamp = self
+        // Decrypt timestamp (the caller checks it against the peer's last one)
+        let timestamp = self
             .noise_state
             .decrypt_and_hash(&key, &initiation.encrypted_timestamp)?;
+        self.initiator_timestamp = timestamp
+            .try_into()
+            .map_err(|_| CryptoError::Decryption)?;
 
         Ok(self.initiator_static)
     }
@@ -260,7 +317,7 @@ impl ResponderHandshake {
         let psk = psk.unwrap_or([0u8; 32]);
 
         // Generate ephemeral keypair
-        let (ephemeral_private, ephemeral_public) = x25519::generate_keypair();
+        let (ephemeral_private, ephemeral_public) = self.ephemeral_keypair();
 
         // e: Mix our ephemeral into hash, then update chaining key
         self.noise_state.mix_hash(&ephemeral_public);
@@ -317,6 +374,54 @@ impl ResponderHandshake {
     }
 }
 
+// Handshake state and results hold private keys and session keys: scrub them
+// when dropped so they don't linger in freed memory.
+
+impl Zeroize for InitiatorHandshake {
+    fn zeroize(&mut self) {
+        self.static_private.zeroize();
+        self.psk.zeroize();
+        self.ephemeral_private.zeroize();
+        self.noise_state.zeroize();
+        #[cfg(feature = "test-vectors")]
+        self.fixed_ephemeral.zeroize();
+    }
+}
+
+impl Drop for InitiatorHandshake {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Zeroize for ResponderHandshake {
+    fn zeroize(&mut self) {
+        self.static_private.zeroize();
+        self.noise_state.zeroize();
+        #[cfg(feature = "test-vectors")]
+        self.fixed_ephemeral.zeroize();
+    }
+}
+
+impl Drop for ResponderHandshake {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Zeroize for HandshakeResult {
+    fn zeroize(&mut self) {
+        self.sending_key.zeroize();
+        self.receiving_key.zeroize();
+    }
+}
+
+impl Drop for HandshakeResult {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 /// Verify MAC1 on a handshake initiation
 ///
 /// We are the responder, so MAC1 is computed with OUR public key
@@ -337,7 +442,7 @@ pub fn verify_initiation_mac1(
     let expected_mac1 = blake2s::mac(&mac1_key, mac1_data);
 
     let actual_mac1 = &initiation_bytes[116..132];
-    if actual_mac1 != expected_mac1 {
+    if !blake2s::mac_eq(&expected_mac1, actual_mac1) {
         return Err(ProtocolError::MacVerificationFailed.into());
     }
 
@@ -364,7 +469,7 @@ pub fn verify_response_mac1(
     let expected_mac1 = blake2s::mac(&mac1_key, mac1_data);
 
     let actual_mac1 = &response_bytes[60..76];
-    if actual_mac1 != expected_mac1 {
+    if !blake2s::mac_eq(&expected_mac1, actual_mac1) {
         return Err(ProtocolError::MacVerificationFailed.into());
     }
 
@@ -469,6 +574,50 @@ mod tests {
         assert_eq!(responder_result.remote_index, 1001);
     }
 
+    #[test]
+    fn test_mac1_verification_rejects_any_wrong_byte() {
+        let (initiator_static_private, initiator_static_public) = x25519::generate_keypair();
+        let (responder_static_private, responder_static_public) = x25519::generate_keypair();
+
+        let mut initiator =
+            InitiatorHandshake::new(initiator_static_private, responder_static_public, None, 1);
+        let initiation = initiator.create_initiation(None).unwrap().to_bytes();
+        let mut responder = ResponderHandshake::new(responder_static_private, 2);
+        responder
+            .process_initiation(&HandshakeInitiation::from_bytes(&initiation).unwrap())
+            .unwrap();
+        let response = responder.create_response(None, None).unwrap().0.to_bytes();
+
+        assert!(verify_initiation_mac1(&initiation, &responder_static_public).is_ok());
+        assert!(verify_response_mac1(&response, &initiator_static_public).is_ok());
+
+        // First and last MAC1 byte: no early exit either way
+        for offset in [116, 131] {
+            let mut forged = initiation;
+            forged[offset] ^= 1;
+            assert!(verify_initiation_mac1(&forged, &responder_static_public).is_err());
+        }
+        for offset in [60, 75] {
+            let mut forged = response;
+            forged[offset] ^= 1;
+            assert!(verify_response_mac1(&forged, &initiator_static_public).is_err());
+        }
+    }
+
+    #[test]
+    fn test_zeroize_scrubs_handshake_secrets() {
+        let (static_private, _) = x25519::generate_keypair();
+        let (_, peer_public) = x25519::generate_keypair();
+        let mut handshake = InitiatorHandshake::new(static_private, peer_public, Some([7u8; 32]), 1);
+        handshake.create_initiation(None).unwrap();
+
+        handshake.zeroize();
+        assert_eq!(handshake.static_private, [0u8; 32]);
+        assert_eq!(handshake.psk, [0u8; 32]);
+        assert_eq!(handshake.ephemeral_private, [0u8; 32]);
+        assert_eq!(handshake.noise_state.chaining_key, [0u8; 32]);
+    }
+
     #[test]
     fn test_handshake_with_psk() {
         let (initiator_static_private, initiator_static_public) = x25519::generate_keypair();
@@ -498,4 +647,95 @@ mod tests {
         assert_eq!(initiator_result.sending_key, responder_result.receiving_key);
         assert_eq!(initiator_result.receiving_key, responder_result.sending_key);
     }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn test_fixed_ephemeral_is_deterministic() {
+        let initiator_static = [0x11u8; 32];
+        let responder_static = [0x22u8; 32];
+        let responder_public = x25519::public_key(&responder_static);
+
+        let run = || {
+            let mut initiator = InitiatorHandshake::new(initiator_static, responder_public, None, 1)
+                .with_fixed_ephemeral([0x33u8; 32]);
+            let initiation = initiator.create_initiation(None).unwrap();
+
+            let mut responder = ResponderHandshake::new(responder_static, 2)
+                .with_fixed_ephemeral([0x44u8; 32]);
+            responder.process_initiation(&initiation).unwrap();
+            let (response, responder_result) = responder.create_response(None, None).unwrap();
+            let initiator_result = initiator.process_response(&response).unwrap();
+
+            assert_eq!(initiation.ephemeral_public, x25519::public_key(&[0x33u8; 32]));
+            assert_eq!(initiator_result.sending_key, responder_result.receiving_key);
+            (initiator_result.sending_key, initiator_result.receiving_key)
+        };
+
+        // Transport keys depend only on static/ephemeral keys, not the timestamp
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn test_transport_keys_known_answer() {
+        let key = |hex_str: &str| -> [u8; 32] { hex::decode(hex_str).unwrap().try_into().unwrap() };
+
+        // Static keys are Alice/Bob from RFC 7748 section 6.1, ephemerals are the
+        // two RFC 7748 section 5.2 scalars. Expected transport keys were computed
+        // with an independent implementation of the WireGuard whitepaper handshake.
+        let initiator_static = key("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let responder_static = key("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let initiator_ephemeral = key("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let responder_ephemeral = key("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d");
+
+        let mut initiator = InitiatorHandshake::new(
+            initiator_static,
+            x25519::public_key(&responder_static),
+            None,
+            1,
+        )
+        .with_fixed_ephemeral(initiator_ephemeral);
+        let initiation = initiator.create_initiation(None).unwrap();
+        assert_eq!(
+            initiation.ephemeral_public,
+            key("1c9fd88f45606d932a80c71824ae151d15d73e77de38e8e000852e614fae7019")
+        );
+
+        let mut responder = ResponderHandshake::new(responder_static, 2)
+            .with_fixed_ephemeral(responder_ephemeral);
+        responder.process_initiation(&initiation).unwrap();
+        let (response, responder_result) = responder.create_response(None, None).unwrap();
+        assert_eq!(
+            response.ephemeral_public,
+            key("ff63fe57bfbf43fa3f563628b149af704d3db625369c49983650347a6a71e00e")
+        );
+
+        let initiator_result = initiator.process_response(&response).unwrap();
+        let initiator_send = key("3880b9e3302d4d37f433f9194cf11e8a12a848446f8376bbefa3647fd1dd8a34");
+        let initiator_recv = key("03425fc03b98e4f5c993045d6dc4bdd58b2e9d5d42ddacab24a6a4255c431ed9");
+        assert_eq!(initiator_result.sending_key, initiator_send);
+        assert_eq!(initiator_result.receiving_key, initiator_recv);
+        assert_eq!(responder_result.sending_key, initiator_recv);
+        assert_eq!(responder_result.receiving_key, initiator_send);
+    }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn test_pinned_rng_reproduces_initiation() {
+        use crate::crypto::rng;
+        use crate::protocol::session::generate_sender_index;
+
+        let responder_public = x25519::public_key(&[0x22u8; 32]);
+        let run = || {
+            rng::pin_seed(42);
+            let mut initiator =
+                InitiatorHandshake::new([0x11u8; 32], responder_public, None, generate_sender_index());
+            let initiation = initiator.create_initiation(None).unwrap();
+            rng::unpin();
+            (initiation.sender_index, initiation.ephemeral_public)
+        };
+
+        // Index and ephemeral come from the pinned RNG; only the timestamp varies
+        assert_eq!(run(), run());
+    }
 }
@@ -102,6 +102,8 @@ impl HandshakeInitiation {
     }
 
     /// Parse from bytes
+    ///
+    /// The reserved bytes (1..4) are ignored, matching the reference implementation.
     pub fn from_bytes(data: &[u8]) -> Result<Self, ProtocolError> {
         if data.len() < Self::SIZE {
             return Err(ProtocolError::InvalidMessageLength {
@@ -114,6 +116,7 @@ impl HandshakeInitiation {
             return Err(ProtocolError::InvalidMessageType { msg_type: data[0] });
         }
 
+        // data[1..4] reserved: tolerated even if nonzero
         let sender_index = u32::from_le_bytes(data[4..8].try_into().unwrap());
 
         let mut ephemeral_public = [0u8; 32];
@@ -212,6 +215,8 @@ impl HandshakeResponse {
     }
 
     /// Parse from bytes
+    ///
+    /// The reserved bytes (1..4) are ignored, matching the reference implementation.
     pub fn from_bytes(data: &[u8]) -> Result<Self, ProtocolError> {
         if data.len() < Self::SIZE {
             return Err(ProtocolError::InvalidMessageLength {
@@ -224,6 +229,7 @@ impl HandshakeResponse {
             return Err(ProtocolError::InvalidMessageType { msg_type: data[0] });
         }
 
+        // data[1..4] reserved: tolerated even if nonzero
         let sender_index = u32::from_le_bytes(data[4..8].try_into().unwrap());
         let receiver_index = u32::from_le_bytes(data[8..12].try_into().unwrap());
 
@@ -272,6 +278,8 @@ impl CookieReply {
     pub const SIZE: usize = 64;
 
     /// Parse from bytes
+    ///
+    /// The reserved bytes (1..4) are ignored, matching the reference implementation.
     pub fn from_bytes(data: &[u8]) -> Result<Self, ProtocolError> {
         if data.len() < Self::SIZE {
             return Err(ProtocolError::InvalidMessageLength {
@@ -284,6 +292,7 @@ impl CookieReply {
             return Err(ProtocolError::InvalidMessageType { msg_type: data[0] });
         }
 
+        // data[1..4] reserved: tolerated even if nonzero
         let receiver_index = u32::from_le_bytes(data[4..8].try_into().unwrap());
 
         let mut nonce = [0u8; 24];
@@ -298,6 +307,19 @@ impl CookieReply {
             encrypted_cookie,
         })
     }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+
+        buf[0] = MessageType::CookieReply as u8;
+        // buf[1..4] reserved (zeros)
+        buf[4..8].copy_from_slice(&self.receiver_index.to_le_bytes());
+        buf[8..32].copy_from_slice(&self.nonce);
+        buf[32..64].copy_from_slice(&self.encrypted_cookie);
+
+        buf
+    }
 }
 
 /// Transport Data message header (16 bytes, followed by encrypted payload)
@@ -321,17 +343,24 @@ impl TransportHeader {
     /// Build a transport message with encrypted payload
     pub fn build_message(receiver_index: u32, counter: u64, encrypted_payload: &[u8]) -> Vec<u8> {
         let mut buf = Vec::with_capacity(Self::SIZE + encrypted_payload.len());
-
-        buf.push(MessageType::TransportData as u8);
-        buf.extend_from_slice(&[0, 0, 0]); // reserved
-        buf.extend_from_slice(&receiver_index.to_le_bytes());
-        buf.extend_from_slice(&counter.to_le_bytes());
+        buf.extend_from_slice(&Self::header_bytes(receiver_index, counter));
         buf.extend_from_slice(encrypted_payload);
-
         buf
     }
 
+    /// The header that starts a transport message
+    pub fn header_bytes(receiver_index: u32, counter: u64) -> [u8; Self::SIZE] {
+        let mut header = [0u8; Self::SIZE];
+        header[0] = MessageType::TransportData as u8;
+        // header[1..4] reserved
+        header[4..8].copy_from_slice(&receiver_index.to_le_bytes());
+        header[8..16].copy_from_slice(&counter.to_le_bytes());
+        header
+    }
+
     /// Parse header from bytes
+    ///
+    /// The reserved bytes (1..4) are ignored, matching the reference implementation.
     pub fn from_bytes(data: &[u8]) -> Result<Self, ProtocolError> {
         if data.len() < Self::SIZE {
             return Err(ProtocolError::InvalidMessageLength {
@@ -344,6 +373,7 @@ impl TransportHeader {
             return Err(ProtocolError::InvalidMessageType { msg_type: data[0] });
         }
 
+        // data[1..4] reserved: tolerated even if nonzero
         let receiver_index = u32::from_le_bytes(data[4..8].try_into().unwrap());
         let counter = u64::from_le_bytes(data[8..16].try_into().unwrap());
 
@@ -449,4 +479,60 @@ mod tests {
         assert_eq!(parsed.ephemeral_public, response.ephemeral_public);
         assert_eq!(parsed.encrypted_nothing, response.encrypted_nothing);
     }
+
+    #[test]
+    fn test_reserved_bytes_zeroed_on_send() {
+        let init = HandshakeInitiation::new(1, [1u8; 32], [2u8; 48], [3u8; 28]);
+        assert_eq!(&init.to_bytes()[1..4], &[0, 0, 0]);
+
+        let response = HandshakeResponse::new(1, 2, [1u8; 32], [2u8; 16]);
+        assert_eq!(&response.to_bytes()[1..4], &[0, 0, 0]);
+
+        let transport = TransportHeader::build_message(1, 2, &[0u8; 16]);
+        assert_eq!(&transport[1..4], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_reserved_bytes_tolerated_on_receive() {
+        let init = HandshakeInitiation::new(0xAABBCCDD, [1u8; 32], [2u8; 48], [3u8; 28]);
+        let mut bytes = init.to_bytes();
+        bytes[1..4].copy_from_slice(&[0xFF, 0x01, 0x80]);
+        let parsed = HandshakeInitiation::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.sender_index, 0xAABBCCDD);
+        assert_eq!(parsed.ephemeral_public, [1u8; 32]);
+        // Re-serializing normalizes reserved bytes back to zero
+        assert_eq!(&parsed.to_bytes()[1..4], &[0, 0, 0]);
+
+        let mut bytes = HandshakeResponse::new(7, 8, [1u8; 32], [2u8; 16]).to_bytes();
+        bytes[1..4].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+        let parsed = HandshakeResponse::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.sender_index, 7);
+        assert_eq!(parsed.receiver_index, 8);
+
+        let mut bytes = [0u8; CookieReply::SIZE];
+        bytes[0] = MessageType::CookieReply as u8;
+        bytes[1..4].copy_from_slice(&[1, 2, 3]);
+        bytes[4..8].copy_from_slice(&9u32.to_le_bytes());
+        assert_eq!(CookieReply::from_bytes(&bytes).unwrap().receiver_index, 9);
+
+        let mut msg = TransportHeader::build_message(42, 1234, &[0xAA; 16]);
+        msg[1..4].copy_from_slice(&[0x10, 0x20, 0x30]);
+        let header = TransportHeader::from_bytes(&msg).unwrap();
+        assert_eq!(header.receiver_index, 42);
+        assert_eq!(header.counter, 1234);
+        assert_eq!(get_message_type(&msg).unwrap(), MessageType::TransportData);
+    }
+
+    #[test]
+    fn test_wrong_message_type_rejected() {
+        let bytes = HandshakeResponse::new(1, 2, [1u8; 32], [2u8; 16]).to_bytes();
+        assert!(matches!(
+            HandshakeInitiation::from_bytes(&[0u8; HandshakeInitiation::SIZE]),
+            Err(ProtocolError::InvalidMessageType { msg_type: 0 })
+        ));
+        assert!(matches!(
+            TransportHeader::from_bytes(&bytes),
+            Err(ProtocolError::InvalidMessageType { msg_type: 2 })
+        ));
+    }
 }
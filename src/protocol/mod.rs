@@ -13,12 +13,12 @@ pub mod messages;
 pub mod session;
 pub mod transport;
 
-pub use cookie::CookieState;
+pub use cookie::{CookieChecker, CookieState};
 pub use handshake::{
     verify_initiation_mac1, HandshakeResult, InitiatorHandshake, ResponderHandshake,
 };
 pub use messages::{
     CookieReply, HandshakeInitiation, HandshakeResponse, MessageType, TransportHeader,
 };
-pub use session::{PeerManager, PeerState, Session, SessionManager, TrafficStats};
+pub use session::{PeerManager, PeerState, Session, SessionManager, SessionTable, TrafficStats};
 pub use transport::{ReplayWindow, TransportState};
@@ -3,12 +3,13 @@
 //! Tracks active sessions and handles rekey timing.
 
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, SocketAddr};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
-use ipnet::IpNet;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ipnet::{IpNet, Ipv4Net};
 
 use crate::protocol::transport::TransportState;
 
@@ -19,10 +20,22 @@ use crate::protocol::transport::TransportState;
 /// Thread-safe traffic statistics using atomic counters
 ///
 /// Uses `AtomicU64` for lock-free updates from the packet processing loop.
+///
+/// `bytes_sent`/`bytes_received` count outer (on-wire, encrypted) bytes, while
+/// the `inner_*` counters count plaintext IP bytes as seen by applications.
+/// The difference between the two is the VPN overhead.
 #[derive(Debug, Default)]
 pub struct TrafficStats {
     pub bytes_sent: AtomicU64,
     pub bytes_received: AtomicU64,
+    pub inner_bytes_sent: AtomicU64,
+    pub inner_bytes_received: AtomicU64,
+    /// Last completed handshake (milliseconds since the Unix epoch, 0 = never)
+    pub last_handshake_ms: AtomicU64,
+    /// Handshakes completed since the last reset (first connect, rekeys and reconnects)
+    pub handshakes: AtomicU64,
+    /// Client saw ICMP port unreachable from the server and is re-handshaking
+    pub server_unreachable: AtomicBool,
 }
 
 impl TrafficStats {
@@ -30,9 +43,61 @@ impl TrafficStats {
         Self {
             bytes_sent: AtomicU64::new(0),
             bytes_received: AtomicU64::new(0),
+            inner_bytes_sent: AtomicU64::new(0),
+            inner_bytes_received: AtomicU64::new(0),
+            last_handshake_ms: AtomicU64::new(0),
+            handshakes: AtomicU64::new(0),
+            server_unreachable: AtomicBool::new(false),
+        }
+    }
+
+    /// Record that a handshake just completed
+    pub fn mark_handshake(&self) {
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(1)
+            .max(1);
+        self.last_handshake_ms.store(now_ms, Ordering::Relaxed);
+        self.handshakes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sessions established after the first one (rekeys and silent reconnects)
+    ///
+    /// A "connected" tunnel whose count keeps climbing faster than one per
+    /// rekey interval is flapping underneath.
+    pub fn reconnect_count(&self) -> u64 {
+        self.handshakes.load(Ordering::Relaxed).saturating_sub(1)
+    }
+
+    /// Seconds since the current session was established, if any
+    pub fn current_session_age(&self) -> Option<u64> {
+        let since = self.last_handshake_ms()?;
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(since);
+        Some(now_ms.saturating_sub(since) / 1000)
+    }
+
+    /// Time of the last completed handshake (ms since the Unix epoch), if any
+    pub fn last_handshake_ms(&self) -> Option<u64> {
+        match self.last_handshake_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(ms),
         }
     }
 
+    /// Flag (or clear) that the server is not responding
+    pub fn set_server_unreachable(&self, unreachable: bool) {
+        self.server_unreachable.store(unreachable, Ordering::Relaxed);
+    }
+
+    /// Check if the client currently considers the server unreachable
+    pub fn server_unreachable(&self) -> bool {
+        self.server_unreachable.load(Ordering::Relaxed)
+    }
+
     /// Add to bytes sent counter
     pub fn add_sent(&self, bytes: u64) {
         self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
@@ -43,6 +108,16 @@ impl TrafficStats {
         self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
     }
 
+    /// Add to inner (plaintext) bytes sent counter
+    pub fn add_inner_sent(&self, bytes: u64) {
+        self.inner_bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Add to inner (plaintext) bytes received counter
+    pub fn add_inner_received(&self, bytes: u64) {
+        self.inner_bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
     /// Get bytes sent
     pub fn get_sent(&self) -> u64 {
         self.bytes_sent.load(Ordering::Relaxed)
@@ -53,10 +128,25 @@ impl TrafficStats {
         self.bytes_received.load(Ordering::Relaxed)
     }
 
+    /// Get inner (plaintext) bytes sent
+    pub fn get_inner_sent(&self) -> u64 {
+        self.inner_bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Get inner (plaintext) bytes received
+    pub fn get_inner_received(&self) -> u64 {
+        self.inner_bytes_received.load(Ordering::Relaxed)
+    }
+
     /// Reset counters to zero
     pub fn reset(&self) {
         self.bytes_sent.store(0, Ordering::Relaxed);
         self.bytes_received.store(0, Ordering::Relaxed);
+        self.inner_bytes_sent.store(0, Ordering::Relaxed);
+        self.inner_bytes_received.store(0, Ordering::Relaxed);
+        self.last_handshake_ms.store(0, Ordering::Relaxed);
+        self.handshakes.store(0, Ordering::Relaxed);
+        self.server_unreachable.store(false, Ordering::Relaxed);
     }
 }
 
@@ -69,9 +159,14 @@ pub const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
 /// Rekey timeout - abandon handshake after this long
 pub const REKEY_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Keepalive timeout - send keepalive if no packet sent within this time
+/// Keepalive timeout - answer received data with a keepalive if nothing was sent within this time
 pub const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// A peer heard from neither this long is gone: its keys expired and a live
+/// client would have rekeyed or sent a keepalive by now
+pub const DEAD_PEER_TIMEOUT: Duration =
+    Duration::from_secs(REJECT_AFTER_TIME.as_secs() + KEEPALIVE_TIMEOUT.as_secs());
+
 /// Session state for an established WireGuard connection
 #[derive(Debug)]
 pub struct Session {
@@ -87,6 +182,8 @@ pub struct Session {
     pub last_sent: Instant,
     /// Last time we received a packet
     pub last_received: Instant,
+    /// Last time we received a data (non-keepalive) packet
+    pub last_data_received: Option<Instant>,
     /// Peer's endpoint address
     pub endpoint: SocketAddr,
 }
@@ -108,6 +205,7 @@ impl Session {
             created_at: now,
             last_sent: now,
             last_received: now,
+            last_data_received: None,
             endpoint,
         }
     }
@@ -119,12 +217,27 @@ impl Session {
 
     /// Check if this session should initiate a rekey
     pub fn needs_rekey(&self) -> bool {
-        self.age() >= REKEY_AFTER_TIME || self.transport.needs_rekey_by_counter()
+        self.needs_rekey_at(Instant::now())
+    }
+
+    /// Whether the session is due a rekey at `now` (REKEY_AFTER_TIME or REKEY_AFTER_MESSAGES)
+    pub fn needs_rekey_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.created_at) >= REKEY_AFTER_TIME
+            || self.transport.needs_rekey_by_counter()
     }
 
     /// Check if this session is expired and should be rejected
     pub fn is_expired(&self) -> bool {
-        self.age() >= REJECT_AFTER_TIME
+        self.is_expired_at(Instant::now())
+    }
+
+    /// Whether the keys are past REJECT_AFTER_TIME at `now`
+    ///
+    /// A session whose sending counter ran out (REJECT_AFTER_MESSAGES) is not
+    /// expired: it still receives while the `CounterExhausted` error from
+    /// sending triggers a new handshake.
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.created_at) >= REJECT_AFTER_TIME
     }
 
     /// Check if we should send a keepalive (no packet sent recently)
@@ -142,6 +255,27 @@ impl Session {
         self.last_received = Instant::now();
     }
 
+    /// Mark that we received a data packet, which must be answered within KEEPALIVE_TIMEOUT
+    pub fn mark_data_received(&mut self) {
+        self.mark_received();
+        self.last_data_received = Some(self.last_received);
+    }
+
+    /// When a passive keepalive is due: KEEPALIVE_TIMEOUT after data we sent nothing back for
+    ///
+    /// Independent of PersistentKeepalive. Received keepalives never call for
+    /// one, so two idle peers don't keep answering each other.
+    pub fn passive_keepalive_deadline(&self) -> Option<Instant> {
+        self.last_data_received
+            .filter(|&received| received > self.last_sent)
+            .map(|received| received + KEEPALIVE_TIMEOUT)
+    }
+
+    /// Whether a passive keepalive is due at `now`
+    pub fn needs_passive_keepalive_at(&self, now: Instant) -> bool {
+        self.passive_keepalive_deadline().is_some_and(|deadline| now >= deadline)
+    }
+
     /// Time since last received packet
     pub fn time_since_last_received(&self) -> Duration {
         self.last_received.elapsed()
@@ -159,13 +293,37 @@ pub enum HandshakeState {
     Complete,
 }
 
+/// Whether a previous session, retired at `retired_at`, may still decrypt
+///
+/// The previous session is kept for REJECT_AFTER_TIME after a rekey replaced
+/// it, so packets in flight on the old keys still arrive. Retiring it does not
+/// restart its own clock: keys past REJECT_AFTER_TIME are rejected regardless.
+fn previous_usable(previous: Option<&Session>, retired_at: Option<Instant>) -> bool {
+    match (previous, retired_at) {
+        (Some(session), Some(retired)) => {
+            retired.elapsed() < REJECT_AFTER_TIME && !session.is_expired()
+        }
+        _ => false,
+    }
+}
+
 /// Session manager for tracking active and pending sessions
+///
+/// Holds up to three sessions, as WireGuard does: `next` (keys from a
+/// handshake we answered, not yet used by the peer), `current` (what we send
+/// on) and `previous` (replaced by a rekey, still decrypting packets that were
+/// in flight). Incoming packets are decrypted with whichever one their
+/// receiver index names, so nothing is lost while a rekey completes.
 #[derive(Debug)]
 pub struct SessionManager {
+    /// Session awaiting the peer's first packet before we send on it
+    next_session: Option<Session>,
     /// Current active session (if any)
     current_session: Option<Session>,
     /// Previous session (kept briefly during rekey)
     previous_session: Option<Session>,
+    /// When the previous session was replaced (start of the overlap window)
+    previous_retired_at: Option<Instant>,
     /// State of pending handshake
     handshake_state: HandshakeState,
     /// When the current handshake was initiated
@@ -184,8 +342,10 @@ impl SessionManager {
     /// Create a new session manager
     pub fn new() -> Self {
         Self {
+            next_session: None,
             current_session: None,
             previous_session: None,
+            previous_retired_at: None,
             handshake_state: HandshakeState::None,
             handshake_started: None,
             pending_sender_index: None,
@@ -247,9 +407,11 @@ impl SessionManager {
 
     /// Establish a new session from handshake result
     pub fn establish_session(&mut self, session: Session) {
-        // Move current to previous (for brief overlap during rekey)
+        // Move current to previous so packets in flight on the old keys still
+        // decrypt for REJECT_AFTER_TIME after the switch
         if let Some(current) = self.current_session.take() {
             self.previous_session = Some(current);
+            self.previous_retired_at = Some(Instant::now());
         }
 
         self.current_session = Some(session);
@@ -260,33 +422,108 @@ impl SessionManager {
         tracing::info!("Session established");
     }
 
+    /// Hold a session from a handshake we answered until the peer uses it
+    ///
+    /// The responder can't know its response arrived, so it keeps sending on
+    /// the current keys until [`SessionManager::confirm_next`]. Without a
+    /// usable current session there is nothing to wait for and the session
+    /// is established right away.
+    pub fn establish_next(&mut self, session: Session) {
+        if self.current().is_none() {
+            self.establish_session(session);
+            return;
+        }
+        self.next_session = Some(session);
+        self.handshake_state = HandshakeState::Complete;
+        self.handshake_started = None;
+        self.pending_sender_index = None;
+    }
+
+    /// Promote the next session to current after a packet on `index` authenticated
+    ///
+    /// Returns true if `index` was the next session's.
+    pub fn confirm_next(&mut self, index: u32) -> bool {
+        if self.next_session.as_ref().map(|s| s.local_index) != Some(index) {
+            return false;
+        }
+        if let Some(current) = self.current_session.take() {
+            self.previous_session = Some(current);
+            self.previous_retired_at = Some(Instant::now());
+        }
+        self.current_session = self.next_session.take();
+        tracing::info!("Session confirmed by peer");
+        true
+    }
+
+    /// Local indexes of the sessions held (next, current and previous)
+    pub fn local_indexes(&self) -> impl Iterator<Item = u32> + '_ {
+        [&self.next_session, &self.current_session, &self.previous_session]
+            .into_iter()
+            .flatten()
+            .map(|s| s.local_index)
+    }
+
     /// Clear the previous session (after rekey transition)
     pub fn clear_previous(&mut self) {
         self.previous_session = None;
+        self.previous_retired_at = None;
+    }
+
+    /// Check if the previous session may still decrypt (see [`previous_usable`])
+    fn previous_usable(&self) -> bool {
+        previous_usable(self.previous_session.as_ref(), self.previous_retired_at)
     }
 
     /// Find session by receiver index (for incoming packets)
     pub fn find_by_index(&mut self, index: u32) -> Option<&mut Session> {
-        if let Some(ref mut session) = self.current_session {
-            if session.local_index == index && !session.is_expired() {
-                return Some(session);
-            }
+        let in_current = self.current_session
+            .as_ref()
+            .is_some_and(|s| s.local_index == index && !s.is_expired());
+        if in_current {
+            return self.current_session.as_mut();
         }
 
-        if let Some(ref mut session) = self.previous_session {
-            if session.local_index == index && !session.is_expired() {
-                return Some(session);
-            }
+        let in_next = self.next_session
+            .as_ref()
+            .is_some_and(|s| s.local_index == index && !s.is_expired());
+        if in_next {
+            return self.next_session.as_mut();
+        }
+
+        if self.previous_session.is_some() && !self.previous_usable() {
+            self.clear_previous();
         }
 
-        None
+        self.previous_session
+            .as_mut()
+            .filter(|s| s.local_index == index)
     }
 
     /// Check if any session needs rekey
     pub fn needs_rekey(&self) -> bool {
-        match &self.current_session {
-            Some(session) => session.needs_rekey() && self.handshake_state == HandshakeState::None,
-            None => false,
+        self.needs_rekey_at(Instant::now())
+    }
+
+    /// Whether the current session is due a rekey at `now` and no handshake is in flight
+    ///
+    /// A handshake that went unanswered for REKEY_TIMEOUT no longer counts as
+    /// in flight, so a failed rekey is retried.
+    pub fn needs_rekey_at(&self, now: Instant) -> bool {
+        !self.handshake_in_flight_at(now)
+            && self.current_session.as_ref().is_some_and(|s| s.needs_rekey_at(now))
+    }
+
+    /// Whether an initiation was sent and is still waiting for its response
+    pub fn handshake_in_flight(&self) -> bool {
+        self.handshake_in_flight_at(Instant::now())
+    }
+
+    fn handshake_in_flight_at(&self, now: Instant) -> bool {
+        match (self.handshake_state, self.handshake_started) {
+            (HandshakeState::WaitingForResponse, Some(started)) => {
+                now.saturating_duration_since(started) < REKEY_TIMEOUT
+            }
+            _ => false,
         }
     }
 
@@ -312,18 +549,167 @@ impl SessionManager {
 
     /// Clear all sessions
     pub fn clear(&mut self) {
+        self.next_session = None;
         self.current_session = None;
         self.previous_session = None;
+        self.previous_retired_at = None;
         self.handshake_state = HandshakeState::None;
         self.handshake_started = None;
         self.pending_sender_index = None;
     }
 }
 
+/// Default cap on the peers a [`SessionTable`] keeps session state for
+pub const DEFAULT_MAX_SESSIONS: usize = 256;
+
+/// Per-peer [`SessionManager`]s, bounded with LRU eviction
+///
+/// Looking a peer up marks it most recently used. Adding a peer beyond the cap
+/// evicts the least recently used one, preferring peers with no handshake in
+/// flight, so a client that talks to many peers over time stays bounded.
+#[derive(Debug)]
+pub struct SessionTable {
+    peers: HashMap<[u8; 32], (SessionManager, Instant)>,
+    /// Local session index -> peer public key, for O(1) lookup on receive
+    index_to_peer: HashMap<u32, [u8; 32]>,
+    max_sessions: usize,
+}
+
+impl Default for SessionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SESSIONS)
+    }
+}
+
+impl SessionTable {
+    /// Create a table holding at most `max_sessions` peers (at least one)
+    pub fn new(max_sessions: usize) -> Self {
+        Self {
+            peers: HashMap::new(),
+            index_to_peer: HashMap::new(),
+            max_sessions: max_sessions.max(1),
+        }
+    }
+
+    /// Sessions for `public_key`, created (and an LRU peer evicted) if needed
+    ///
+    /// Changes that add or drop sessions go through [`SessionTable::update`]
+    /// so their indexes stay findable.
+    pub fn peer(&mut self, public_key: &[u8; 32]) -> &mut SessionManager {
+        if !self.peers.contains_key(public_key) && self.peers.len() >= self.max_sessions {
+            self.evict_lru();
+        }
+
+        let (sessions, last_used) = self
+            .peers
+            .entry(*public_key)
+            .or_insert_with(|| (SessionManager::new(), Instant::now()));
+        *last_used = Instant::now();
+        sessions
+    }
+
+    /// Change a peer's sessions, keeping the index map in step with the sessions added or dropped
+    pub fn update<R>(&mut self, public_key: &[u8; 32], f: impl FnOnce(&mut SessionManager) -> R) -> R {
+        let sessions = self.peer(public_key);
+        let before: Vec<u32> = sessions.local_indexes().collect();
+        let result = f(sessions);
+        let after: Vec<u32> = sessions.local_indexes().collect();
+
+        for index in before.into_iter().filter(|index| !after.contains(index)) {
+            self.index_to_peer.remove(&index);
+        }
+        for index in after {
+            self.index_to_peer.insert(index, *public_key);
+        }
+        result
+    }
+
+    /// Establish a new session for `public_key`
+    pub fn establish_session(&mut self, public_key: &[u8; 32], session: Session) {
+        self.update(public_key, |sessions| sessions.establish_session(session));
+    }
+
+    /// Promote the peer's next session after a packet on `index` (see [`SessionManager::confirm_next`])
+    pub fn confirm_next(&mut self, public_key: &[u8; 32], index: u32) -> bool {
+        self.update(public_key, |sessions| sessions.confirm_next(index))
+    }
+
+    /// Drop a peer's sessions and handshake state, keeping it in the table
+    pub fn clear_peer(&mut self, public_key: &[u8; 32]) {
+        self.update(public_key, SessionManager::clear);
+    }
+
+    /// Session with local index `index` (current or previous), with its peer's key
+    pub fn find_by_index(&mut self, index: u32) -> Option<([u8; 32], &mut Session)> {
+        let key = *self.index_to_peer.get(&index)?;
+        let session = self
+            .peers
+            .get_mut(&key)
+            .and_then(|(sessions, _)| sessions.find_by_index(index));
+        if session.is_none() {
+            // The session expired since it was indexed
+            self.index_to_peer.remove(&index);
+        }
+        Some((key, session?))
+    }
+
+    /// Sessions for `public_key`, if the table has them
+    pub fn get(&self, public_key: &[u8; 32]) -> Option<&SessionManager> {
+        self.peers.get(public_key).map(|(sessions, _)| sessions)
+    }
+
+    /// Forget a peer's sessions
+    pub fn remove(&mut self, public_key: &[u8; 32]) -> Option<SessionManager> {
+        let (sessions, _) = self.peers.remove(public_key)?;
+        for index in sessions.local_indexes() {
+            self.index_to_peer.remove(&index);
+        }
+        Some(sessions)
+    }
+
+    /// Number of peers with session state
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Check if no peer has session state
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Drop every peer's sessions
+    pub fn clear(&mut self) {
+        self.peers.clear();
+        self.index_to_peer.clear();
+    }
+
+    fn evict_lru(&mut self) {
+        let idle = |sessions: &SessionManager| sessions.handshake_state() != HandshakeState::WaitingForResponse;
+        let lru = |(key, (_, last_used)): (&[u8; 32], &(SessionManager, Instant))| (*last_used, *key);
+
+        let victim = self
+            .peers
+            .iter()
+            .filter(|(_, (sessions, _))| idle(sessions))
+            .map(lru)
+            .min()
+            .or_else(|| self.peers.iter().map(lru).min());
+
+        if let Some((last_used, key)) = victim {
+            self.remove(&key);
+            tracing::trace!(
+                "Evicted sessions for peer {} (idle {:?}, cap {})",
+                BASE64.encode(&key[..8]),
+                last_used.elapsed(),
+                self.max_sessions
+            );
+        }
+    }
+}
+
 /// Generate a random sender index
 pub fn generate_sender_index() -> u32 {
-    use rand::Rng;
-    rand::thread_rng().gen()
+    crate::crypto::rng::next_u32()
 }
 
 // ============================================================================
@@ -343,8 +729,12 @@ pub struct PeerState {
     pub allowed_ips: Vec<IpNet>,
     /// Current session with this peer
     pub session: Option<Session>,
+    /// Session from a rekey we answered, until the peer's first packet on it
+    pub next_session: Option<Session>,
     /// Previous session (during rekey)
     pub previous_session: Option<Session>,
+    /// When the previous session was replaced (start of the overlap window)
+    pub previous_retired_at: Option<Instant>,
     /// Last known endpoint (learned from incoming packets)
     pub endpoint: Option<SocketAddr>,
     /// Last timestamp seen (replay protection for handshakes)
@@ -354,6 +744,12 @@ pub struct PeerState {
     pub traffic_stats: Arc<TrafficStats>,
     /// Timestamp of last successful handshake
     pub last_handshake: Option<Instant>,
+    /// Wall-clock time of the last successful handshake (for reporting)
+    pub last_handshake_at: Option<SystemTime>,
+    /// Keep the first learned endpoint instead of following roaming
+    pub lock_endpoint: bool,
+    /// Human-readable name set by the operator (`add_peer`)
+    pub name: Option<String>,
 }
 
 impl PeerState {
@@ -364,11 +760,36 @@ impl PeerState {
             psk,
             allowed_ips,
             session: None,
+            next_session: None,
             previous_session: None,
+            previous_retired_at: None,
             endpoint: None,
             last_timestamp: None,
             traffic_stats: Arc::new(TrafficStats::new()),
             last_handshake: None,
+            last_handshake_at: None,
+            lock_endpoint: false,
+            name: None,
+        }
+    }
+
+    /// Move the endpoint to `from` after an authenticated transport packet
+    ///
+    /// Returns false, keeping the current endpoint, if the peer is locked to a
+    /// different one.
+    pub fn roam_to(&mut self, from: SocketAddr) -> bool {
+        if self.lock_endpoint && self.endpoint.is_some_and(|endpoint| endpoint != from) {
+            return false;
+        }
+        self.endpoint = Some(from);
+        true
+    }
+
+    /// Label identifying this peer in metrics: its name, else the start of its key
+    pub fn metrics_label(&self) -> String {
+        match self.name {
+            Some(ref name) => name.clone(),
+            None => BASE64.encode(self.public_key)[..8].to_string(),
         }
     }
 
@@ -398,29 +819,62 @@ impl PeerState {
 
     /// Find session by local index
     pub fn find_session_by_index(&mut self, index: u32) -> Option<&mut Session> {
+        let usable = self.previous_usable();
+
         if let Some(ref mut session) = self.session {
             if session.local_index == index && !session.is_expired() {
                 return Some(session);
             }
         }
 
-        if let Some(ref mut session) = self.previous_session {
+        if let Some(ref mut session) = self.next_session {
             if session.local_index == index && !session.is_expired() {
                 return Some(session);
             }
         }
 
-        None
+        self.previous_session
+            .as_mut()
+            .filter(|session| session.local_index == index && usable)
+    }
+
+    /// Check if the previous session may still decrypt (see [`previous_usable`])
+    fn previous_usable(&self) -> bool {
+        previous_usable(self.previous_session.as_ref(), self.previous_retired_at)
     }
 
     /// Establish a new session for this peer
     pub fn establish_session(&mut self, session: Session) {
-        // Move current to previous
+        // Move current to previous so packets in flight on the old keys still
+        // decrypt for REJECT_AFTER_TIME after the client rekeys
         if let Some(current) = self.session.take() {
             self.previous_session = Some(current);
+            self.previous_retired_at = Some(Instant::now());
         }
         self.session = Some(session);
+        self.mark_handshake();
+    }
+
+    /// Record that a handshake with this peer just completed
+    fn mark_handshake(&mut self) {
         self.last_handshake = Some(Instant::now());
+        self.last_handshake_at = Some(SystemTime::now());
+    }
+
+    /// Promote the next session to current after a packet on `index` authenticated
+    ///
+    /// Returns the previous session this pushes out, if any.
+    pub fn confirm_next(&mut self, index: u32) -> Option<Session> {
+        if self.next_session.as_ref().map(|s| s.local_index) != Some(index) {
+            return None;
+        }
+        let dropped = self.previous_session.take();
+        if let Some(current) = self.session.take() {
+            self.previous_session = Some(current);
+            self.previous_retired_at = Some(Instant::now());
+        }
+        self.session = self.next_session.take();
+        dropped
     }
 
     /// Check if an IP is in this peer's allowed IPs
@@ -443,14 +897,37 @@ impl PeerState {
     }
 }
 
+/// A server peer's state behind its own lock
+///
+/// Packet paths look the peer up under a read lock on the [`PeerManager`]
+/// and then lock only that peer, so traffic for different peers and API
+/// queries don't wait on each other. The lock is never held across an await.
+pub type SharedPeer = Arc<Mutex<PeerState>>;
+
+/// Lock a peer, even if a thread panicked while holding it (its state is
+/// plain data and stays usable)
+pub fn lock_peer(peer: &Mutex<PeerState>) -> MutexGuard<'_, PeerState> {
+    peer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A peer in the [`PeerManager`], with its AllowedIPs kept outside the lock
+/// so routing never has to lock peers
+#[derive(Debug)]
+struct PeerEntry {
+    allowed_ips: Vec<IpNet>,
+    state: SharedPeer,
+}
+
 /// Manager for multiple peers (server mode)
 ///
 /// Maintains peer state indexed by public key and provides session
-/// lookup by index for fast packet processing.
+/// lookup by index for fast packet processing. Adding and removing peers
+/// and sessions takes `&mut self`; everything a packet needs works on `&self`
+/// plus the peer's own lock (see [`SharedPeer`]).
 #[derive(Debug, Default)]
 pub struct PeerManager {
     /// Map from public key to peer state
-    peers: HashMap<[u8; 32], PeerState>,
+    peers: HashMap<[u8; 32], PeerEntry>,
     /// Map from session local_index to public key (for fast lookup on transport)
     index_to_peer: HashMap<u32, [u8; 32]>,
 }
@@ -465,35 +942,114 @@ impl PeerManager {
     }
 
     /// Add a peer
+    ///
+    /// A peer's AllowedIPs are fixed once added; to change them, remove the
+    /// peer and add it again.
     pub fn add_peer(&mut self, public_key: [u8; 32], psk: Option<[u8; 32]>, allowed_ips: Vec<IpNet>) {
-        self.peers
-            .insert(public_key, PeerState::new(public_key, psk, allowed_ips));
+        let state = Arc::new(Mutex::new(PeerState::new(public_key, psk, allowed_ips.clone())));
+        self.peers.insert(public_key, PeerEntry { allowed_ips, state });
     }
 
-    /// Get peer by public key
-    pub fn get_peer(&self, public_key: &[u8; 32]) -> Option<&PeerState> {
-        self.peers.get(public_key)
+    /// Lock the peer with this public key
+    pub fn get_peer(&self, public_key: &[u8; 32]) -> Option<MutexGuard<'_, PeerState>> {
+        self.peers.get(public_key).map(|entry| lock_peer(&entry.state))
     }
 
-    /// Get mutable reference to peer by public key
-    pub fn get_peer_mut(&mut self, public_key: &[u8; 32]) -> Option<&mut PeerState> {
-        self.peers.get_mut(public_key)
+    /// Handle to the peer with this public key, to lock after releasing the manager
+    pub fn peer(&self, public_key: &[u8; 32]) -> Option<SharedPeer> {
+        self.peers.get(public_key).map(|entry| Arc::clone(&entry.state))
     }
 
     /// Find peer by session local_index (for incoming transport packets)
-    pub fn find_by_index(&mut self, index: u32) -> Option<&mut PeerState> {
-        let public_key = self.index_to_peer.get(&index)?;
-        self.peers.get_mut(public_key)
+    pub fn find_by_index(&self, index: u32) -> Option<SharedPeer> {
+        self.peer(self.index_to_peer.get(&index)?)
     }
 
     /// Find peer whose allowed IPs contain the given destination
-    pub fn find_by_allowed_ip(&self, ip: Ipv4Addr) -> Option<&PeerState> {
-        self.peers.values().find(|peer| peer.allows_ip(ip))
+    ///
+    /// See [`PeerManager::route_candidates`] for how overlaps are resolved.
+    pub fn find_by_allowed_ip(&self, ip: IpAddr) -> Option<SharedPeer> {
+        self.peer(&self.route(ip)?)
     }
 
-    /// Find peer (mutable) whose allowed IPs contain the given destination
-    pub fn find_by_allowed_ip_mut(&mut self, ip: Ipv4Addr) -> Option<&mut PeerState> {
-        self.peers.values_mut().find(|peer| peer.allows_ip(ip))
+    /// Public key of the peer traffic to `ip` is sent to (no allocation, per-packet path)
+    fn route(&self, ip: IpAddr) -> Option<[u8; 32]> {
+        self.peers
+            .iter()
+            .filter_map(|(public_key, entry)| Some((longest_match(&entry.allowed_ips, ip)?.prefix_len(), *public_key)))
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(_, public_key)| public_key)
+    }
+
+    /// Every peer whose AllowedIPs contain `ip`, with its most specific matching prefix
+    ///
+    /// Sorted the way routing picks: the longest prefix wins, and equal
+    /// prefixes fall back to the lowest public key, so overlapping AllowedIPs
+    /// route deterministically. The first entry is the peer traffic to `ip`
+    /// goes to; the rest are shadowed.
+    pub fn route_candidates(&self, ip: IpAddr) -> Vec<(MutexGuard<'_, PeerState>, IpNet)> {
+        let mut candidates: Vec<_> = self
+            .peers
+            .iter()
+            .filter_map(|(public_key, entry)| Some((public_key, entry, longest_match(&entry.allowed_ips, ip)?)))
+            .collect();
+        candidates.sort_by(|a, b| b.2.prefix_len().cmp(&a.2.prefix_len()).then(a.0.cmp(b.0)));
+        candidates
+            .into_iter()
+            .map(|(_, entry, net)| (lock_peer(&entry.state), net))
+            .collect()
+    }
+
+    /// Find all peers that should receive a broadcast/multicast packet
+    ///
+    /// Peers whose AllowedIPs contain `dest` (e.g. a peer routing 224.0.0.0/4) are
+    /// used first. If none match, which is the usual case for 255.255.255.255 or
+    /// multicast, every peer with an AllowedIPs range inside the VPN subnet `lan`
+    /// receives a copy, as if they shared an Ethernet segment.
+    pub fn find_all_for_broadcast(&self, dest: Ipv4Addr, lan: Option<Ipv4Net>) -> Vec<[u8; 32]> {
+        let dest_addr = IpAddr::V4(dest);
+        let matching: Vec<[u8; 32]> = self
+            .peers
+            .iter()
+            .filter(|(_, entry)| entry.allowed_ips.iter().any(|net| net.contains(&dest_addr)))
+            .map(|(public_key, _)| *public_key)
+            .collect();
+
+        if !matching.is_empty() {
+            return matching;
+        }
+
+        let Some(lan) = lan else {
+            return Vec::new();
+        };
+
+        self.peers
+            .iter()
+            .filter(|(_, entry)| {
+                entry.allowed_ips.iter().any(|net| match net {
+                    IpNet::V4(v4) => lan.contains(v4),
+                    IpNet::V6(_) => false,
+                })
+            })
+            .map(|(public_key, _)| *public_key)
+            .collect()
+    }
+
+    /// A random local index no registered session uses
+    ///
+    /// Indexes are 32-bit random, so a repeat is rare, but one would silently
+    /// route another peer's transport packets here; draw again instead.
+    pub fn unused_index(&self) -> u32 {
+        self.unused_index_from(generate_sender_index)
+    }
+
+    fn unused_index_from(&self, mut next: impl FnMut() -> u32) -> u32 {
+        loop {
+            let index = next();
+            if !self.index_to_peer.contains_key(&index) {
+                return index;
+            }
+        }
     }
 
     /// Register a session index for a peer (call after establishing session)
@@ -507,18 +1063,119 @@ impl PeerManager {
     }
 
     /// Establish a session for a peer and register its index
+    ///
+    /// The replaced session stays registered as the peer's previous session;
+    /// whatever was in the previous slot before is dropped and unregistered,
+    /// as is an unconfirmed next session.
     pub fn establish_session(&mut self, public_key: &[u8; 32], session: Session) {
         let local_index = session.local_index;
-        if let Some(peer) = self.peers.get_mut(public_key) {
-            // Unregister old session index if present
-            if let Some(ref old_session) = peer.session {
-                self.index_to_peer.remove(&old_session.local_index);
+        if let Some(entry) = self.peers.get(public_key) {
+            let mut peer = lock_peer(&entry.state);
+            if peer.session.is_some() {
+                if let Some(ref dropped) = peer.previous_session {
+                    self.index_to_peer.remove(&dropped.local_index);
+                }
+            }
+            if let Some(dropped) = peer.next_session.take() {
+                self.index_to_peer.remove(&dropped.local_index);
             }
             peer.establish_session(session);
             self.index_to_peer.insert(local_index, *public_key);
         }
     }
 
+    /// Hold a session from a handshake we answered until the peer uses it
+    ///
+    /// We keep sending on the current session until a packet on the new one
+    /// confirms the peer has the keys ([`PeerState::confirm_next`]), so a
+    /// lost handshake response doesn't blackhole our traffic. A peer without
+    /// a usable current session gets the session right away.
+    pub fn establish_next(&mut self, public_key: &[u8; 32], session: Session) {
+        let Some(entry) = self.peers.get(public_key) else {
+            return;
+        };
+        let mut peer = lock_peer(&entry.state);
+        if !peer.has_session() {
+            drop(peer);
+            self.establish_session(public_key, session);
+            return;
+        }
+
+        let local_index = session.local_index;
+        if let Some(replaced) = peer.next_session.replace(session) {
+            self.index_to_peer.remove(&replaced.local_index);
+        }
+        peer.mark_handshake();
+        self.index_to_peer.insert(local_index, *public_key);
+    }
+
+    /// Drop previous sessions whose overlap window has passed, and next
+    /// sessions the peer never used before they expired
+    ///
+    /// Unregisters their indexes so stale receiver indexes no longer resolve
+    /// to a peer. Returns the number of sessions dropped.
+    pub fn expire_previous_sessions(&mut self) -> usize {
+        let mut expired = 0;
+        for entry in self.peers.values() {
+            let mut peer = lock_peer(&entry.state);
+            if peer.next_session.as_ref().is_some_and(Session::is_expired) {
+                if let Some(session) = peer.next_session.take() {
+                    self.index_to_peer.remove(&session.local_index);
+                }
+                expired += 1;
+            }
+            if peer.previous_session.is_some() && !peer.previous_usable() {
+                if let Some(session) = peer.previous_session.take() {
+                    self.index_to_peer.remove(&session.local_index);
+                }
+                peer.previous_retired_at = None;
+                expired += 1;
+            }
+        }
+        expired
+    }
+
+    /// Drop all sessions of peers not heard from for DEAD_PEER_TIMEOUT at `now`
+    ///
+    /// Counts from the latest handshake or received packet. Returns the public
+    /// keys of the peers that lost their sessions; the peers themselves stay.
+    pub fn expire_idle_sessions(&mut self, now: Instant) -> Vec<[u8; 32]> {
+        let mut timed_out = Vec::new();
+        for entry in self.peers.values() {
+            let mut peer = lock_peer(&entry.state);
+            let sessions = [&peer.session, &peer.next_session];
+            let Some(last_received) = sessions.iter().filter_map(|s| s.as_ref()).map(|s| s.last_received).max()
+            else {
+                continue;
+            };
+            let last_heard = peer.last_handshake.map_or(last_received, |h| h.max(last_received));
+            if now.saturating_duration_since(last_heard) < DEAD_PEER_TIMEOUT {
+                continue;
+            }
+
+            for session in [peer.session.take(), peer.next_session.take(), peer.previous_session.take()]
+                .into_iter()
+                .flatten()
+            {
+                self.index_to_peer.remove(&session.local_index);
+            }
+            peer.previous_retired_at = None;
+            timed_out.push(peer.public_key);
+        }
+        timed_out
+    }
+
+    /// Drop every peer's sessions (keys) and their indexes, keeping the peers
+    pub fn clear_sessions(&mut self) {
+        for mut peer in self.iter() {
+            peer.session = None;
+            peer.next_session = None;
+            peer.previous_session = None;
+            peer.previous_retired_at = None;
+        }
+        self.index_to_peer.clear();
+    }
+
     /// Get number of peers
     pub fn len(&self) -> usize {
         self.peers.len()
@@ -536,39 +1193,49 @@ impl PeerManager {
 
     /// Get count of peers with active sessions
     pub fn connected_count(&self) -> usize {
-        self.peers.values().filter(|p| p.has_session()).count()
+        self.iter().filter(|p| p.has_session()).count()
     }
 
     /// Remove a peer and clean up associated session indexes
     ///
-    /// Returns the removed `PeerState` if found, `None` otherwise.
+    /// Returns the removed peer if found, `None` otherwise.
     /// This will terminate any active session for the peer.
-    pub fn remove_peer(&mut self, public_key: &[u8; 32]) -> Option<PeerState> {
-        if let Some(peer) = self.peers.remove(public_key) {
-            // Clean up index mappings for both sessions
-            if let Some(ref session) = peer.session {
+    pub fn remove_peer(&mut self, public_key: &[u8; 32]) -> Option<SharedPeer> {
+        let entry = self.peers.remove(public_key)?;
+        {
+            // Clean up index mappings for all sessions
+            let peer = lock_peer(&entry.state);
+            for session in [&peer.session, &peer.next_session, &peer.previous_session].into_iter().flatten() {
                 self.index_to_peer.remove(&session.local_index);
             }
-            if let Some(ref session) = peer.previous_session {
-                self.index_to_peer.remove(&session.local_index);
-            }
-            Some(peer)
-        } else {
-            None
         }
+        Some(entry.state)
     }
 
-    /// Iterate over all peers
-    pub fn iter(&self) -> impl Iterator<Item = &PeerState> {
-        self.peers.values()
+    /// Iterate over all peers, locking each in turn
+    pub fn iter(&self) -> impl Iterator<Item = MutexGuard<'_, PeerState>> {
+        self.peers.values().map(|entry| lock_peer(&entry.state))
     }
 
-    /// Iterate over all peers mutably
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut PeerState> {
-        self.peers.values_mut()
+    /// All peers ordered by public key bytes (stable across calls, unlike `iter`)
+    ///
+    /// Holds every peer's lock until the guards are dropped.
+    pub fn sorted(&self) -> Vec<MutexGuard<'_, PeerState>> {
+        let mut keys: Vec<&[u8; 32]> = self.peers.keys().collect();
+        keys.sort();
+        keys.into_iter().map(|key| lock_peer(&self.peers[key].state)).collect()
     }
 }
 
+/// Most specific network in `allowed_ips` containing `ip`
+pub(crate) fn longest_match(allowed_ips: &[IpNet], ip: IpAddr) -> Option<IpNet> {
+    allowed_ips
+        .iter()
+        .filter(|net| net.contains(&ip))
+        .max_by_key(|net| net.prefix_len())
+        .copied()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -610,6 +1277,219 @@ mod tests {
         assert_eq!(manager.current().unwrap().local_index, 100);
     }
 
+    #[test]
+    fn test_rekey_and_reject_after_time() {
+        let session = Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint());
+        let start = session.created_at;
+        let just_before = |limit: Duration| start + limit - Duration::from_millis(1);
+
+        assert!(!session.needs_rekey_at(just_before(REKEY_AFTER_TIME)));
+        assert!(session.needs_rekey_at(start + REKEY_AFTER_TIME));
+        assert!(!session.is_expired_at(start + REKEY_AFTER_TIME));
+
+        assert!(!session.is_expired_at(just_before(REJECT_AFTER_TIME)));
+        assert!(session.is_expired_at(start + REJECT_AFTER_TIME));
+    }
+
+    #[test]
+    fn test_passive_keepalive_after_unanswered_data() {
+        let mut session = Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint());
+        assert_eq!(session.passive_keepalive_deadline(), None);
+
+        // A received keepalive needs no answer
+        session.mark_received();
+        assert_eq!(session.passive_keepalive_deadline(), None);
+
+        session.last_sent -= Duration::from_millis(1);
+        session.mark_data_received();
+        let received = session.last_received;
+        assert_eq!(session.passive_keepalive_deadline(), Some(received + KEEPALIVE_TIMEOUT));
+        assert!(!session.needs_passive_keepalive_at(received + KEEPALIVE_TIMEOUT - Duration::from_millis(1)));
+        assert!(session.needs_passive_keepalive_at(received + KEEPALIVE_TIMEOUT));
+
+        // Anything we send back (data or the keepalive itself) answers it
+        session.last_sent = received + Duration::from_millis(1);
+        assert_eq!(session.passive_keepalive_deadline(), None);
+    }
+
+    #[test]
+    fn test_rekey_and_reject_after_messages() {
+        use crate::error::{MinnowVpnError, ProtocolError};
+        use crate::protocol::transport::{REJECT_AFTER_MESSAGES, REKEY_AFTER_MESSAGES};
+
+        let mut session = Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint());
+        let now = session.created_at;
+
+        session.transport.sending_counter = REKEY_AFTER_MESSAGES - 1;
+        assert!(!session.needs_rekey_at(now));
+        session.transport.encrypt(200, b"packet").unwrap();
+        assert!(session.needs_rekey_at(now));
+        assert!(!session.is_expired_at(now));
+
+        // The last allowed counter still encrypts; after that sending is refused
+        session.transport.sending_counter = REJECT_AFTER_MESSAGES - 1;
+        session.transport.encrypt(200, b"packet").unwrap();
+        assert!(matches!(
+            session.transport.encrypt(200, b"packet"),
+            Err(MinnowVpnError::Protocol(ProtocolError::CounterExhausted))
+        ));
+        assert_eq!(session.transport.sending_counter, REJECT_AFTER_MESSAGES);
+
+        // The session stays up to receive until a new handshake replaces it
+        assert!(!session.is_expired_at(now));
+        let mut manager = SessionManager::new();
+        manager.establish_session(session);
+        assert!(manager.current().is_some());
+        assert!(manager.needs_rekey_at(now));
+    }
+
+    #[test]
+    fn test_manager_rekeys_after_first_handshake() {
+        let mut manager = SessionManager::new();
+        manager.start_handshake(100);
+        manager.establish_session(Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        let start = manager.current().unwrap().created_at;
+
+        assert!(!manager.needs_rekey_at(start));
+        assert!(manager.needs_rekey_at(start + REKEY_AFTER_TIME));
+
+        // Not while a rekey is in flight, but again once it timed out
+        manager.current_mut().unwrap().created_at = Instant::now().checked_sub(REKEY_AFTER_TIME).unwrap();
+        manager.start_handshake(101);
+        let now = Instant::now();
+        assert!(!manager.needs_rekey_at(now));
+        assert!(manager.needs_rekey_at(now + REKEY_TIMEOUT));
+    }
+
+    #[test]
+    fn test_session_table_evicts_lru_idle_peer() {
+        let mut table = SessionTable::new(2);
+        table.peer(&[1u8; 32]);
+        table.peer(&[2u8; 32]).start_handshake(7);
+        table.peer(&[1u8; 32]);
+
+        // Peer 2 is least recently used but mid-handshake, so peer 1 goes
+        table.peer(&[3u8; 32]);
+        assert_eq!(table.len(), 2);
+        assert!(table.get(&[1u8; 32]).is_none());
+        assert_eq!(table.get(&[2u8; 32]).unwrap().pending_sender_index(), Some(7));
+
+        // With nothing idle the plain LRU peer goes
+        table.peer(&[3u8; 32]).start_handshake(8);
+        table.peer(&[4u8; 32]);
+        assert!(table.get(&[2u8; 32]).is_none());
+        assert!(table.get(&[3u8; 32]).is_some());
+
+        table.clear();
+        assert!(table.is_empty());
+        assert!(table.index_to_peer.is_empty());
+    }
+
+    #[test]
+    fn test_session_table_find_by_index() {
+        let mut table = SessionTable::new(4);
+        table.establish_session(&[1u8; 32], Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        table.establish_session(&[2u8; 32], Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+
+        let (key, session) = table.find_by_index(101).unwrap();
+        assert_eq!(key, [2u8; 32]);
+        assert_eq!(session.remote_index, 201);
+        assert_eq!(table.find_by_index(100).unwrap().0, [1u8; 32]);
+        assert!(table.find_by_index(102).is_none());
+
+        // A rekey keeps the old index as the previous session; the one before goes
+        table.establish_session(&[1u8; 32], Session::new(102, 202, [5u8; 32], [6u8; 32], test_endpoint()));
+        table.establish_session(&[1u8; 32], Session::new(103, 203, [7u8; 32], [8u8; 32], test_endpoint()));
+        assert!(table.find_by_index(100).is_none());
+        assert_eq!(table.find_by_index(102).unwrap().0, [1u8; 32]);
+        assert_eq!(table.find_by_index(103).unwrap().0, [1u8; 32]);
+        assert_eq!(table.index_to_peer.len(), 3);
+
+        table.clear_peer(&[1u8; 32]);
+        assert!(table.find_by_index(103).is_none());
+        table.remove(&[2u8; 32]);
+        assert!(table.find_by_index(101).is_none());
+        assert!(table.index_to_peer.is_empty());
+    }
+
+    #[test]
+    fn test_previous_session_decrypts_after_rekey() {
+        let mut manager = SessionManager::new();
+
+        manager.establish_session(Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+
+        // Peer's view of the old session (keys swapped)
+        let mut peer_old = TransportState::new([2u8; 32], [1u8; 32]);
+        let in_flight = peer_old.encrypt(100, b"late packet").unwrap();
+
+        // Rekey completes
+        manager.establish_session(Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert_eq!(manager.current().unwrap().local_index, 101);
+
+        // In-flight packet on the old keys still decrypts during the overlap
+        let previous = manager.find_by_index(100).unwrap();
+        assert_eq!(previous.transport.decrypt(&in_flight).unwrap(), b"late packet");
+
+        // Once the overlap window has passed, the previous session is dropped
+        manager.previous_retired_at = Instant::now().checked_sub(REJECT_AFTER_TIME);
+        assert!(manager.find_by_index(100).is_none());
+        assert!(manager.find_by_index(101).is_some());
+    }
+
+    #[test]
+    fn test_retired_session_still_expires() {
+        let mut manager = SessionManager::new();
+
+        // Old session close to its own REJECT_AFTER_TIME when the rekey completes
+        let mut old = Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint());
+        old.created_at = Instant::now().checked_sub(REJECT_AFTER_TIME - Duration::from_secs(1)).unwrap();
+        manager.establish_session(old);
+        manager.establish_session(Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert!(manager.find_by_index(100).is_some());
+
+        // Its keys reach REJECT_AFTER_TIME well inside the overlap window
+        manager.previous_session.as_mut().unwrap().created_at =
+            Instant::now().checked_sub(REJECT_AFTER_TIME).unwrap();
+        assert!(manager.find_by_index(100).is_none());
+        assert!(manager.previous_session.is_none());
+    }
+
+    #[test]
+    fn test_three_sessions_decrypt_interleaved_packets() {
+        let mut manager = SessionManager::new();
+        manager.establish_session(Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        manager.establish_next(Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert_eq!(manager.current().unwrap().local_index, 100);
+
+        // The peer's old and new keys, with packets on both in flight
+        let mut peer_old = TransportState::new([2u8; 32], [1u8; 32]);
+        let mut peer_new = TransportState::new([4u8; 32], [3u8; 32]);
+        let packets = [
+            (100, peer_old.encrypt(100, b"old 1").unwrap()),
+            (101, peer_new.encrypt(101, b"new 1").unwrap()),
+            (100, peer_old.encrypt(100, b"old 2").unwrap()),
+            (101, peer_new.encrypt(101, b"new 2").unwrap()),
+        ];
+
+        let mut received = Vec::new();
+        for (index, packet) in &packets {
+            let session = manager.find_by_index(*index).unwrap();
+            received.push(session.transport.decrypt(packet).unwrap());
+            manager.confirm_next(*index);
+        }
+        assert_eq!(received, [&b"old 1"[..], b"new 1", b"old 2", b"new 2"]);
+
+        // The first packet on the new keys promoted them
+        assert_eq!(manager.current().unwrap().local_index, 101);
+        assert!(!manager.confirm_next(101));
+        assert!(manager.find_by_index(100).is_some());
+
+        // Without a current session there is nothing to wait for
+        let mut fresh = SessionManager::new();
+        fresh.establish_next(Session::new(102, 202, [5u8; 32], [6u8; 32], test_endpoint()));
+        assert_eq!(fresh.current().unwrap().local_index, 102);
+    }
+
     #[test]
     fn test_session_manager_rekey() {
         let mut manager = SessionManager::new();
@@ -648,6 +1528,48 @@ mod tests {
         assert_eq!(manager.handshake_state(), HandshakeState::None);
     }
 
+    #[test]
+    fn test_traffic_stats_inner_outer() {
+        let stats = TrafficStats::new();
+
+        stats.add_sent(132);
+        stats.add_inner_sent(100);
+        stats.add_received(64);
+        stats.add_inner_received(32);
+
+        assert_eq!(stats.get_sent(), 132);
+        assert_eq!(stats.get_inner_sent(), 100);
+        assert_eq!(stats.get_received(), 64);
+        assert_eq!(stats.get_inner_received(), 32);
+
+        stats.reset();
+        assert_eq!(stats.get_sent(), 0);
+        assert_eq!(stats.get_inner_sent(), 0);
+        assert_eq!(stats.get_received(), 0);
+        assert_eq!(stats.get_inner_received(), 0);
+    }
+
+    #[test]
+    fn test_traffic_stats_last_handshake() {
+        let stats = TrafficStats::new();
+        assert_eq!(stats.last_handshake_ms(), None);
+
+        assert_eq!(stats.current_session_age(), None);
+        stats.mark_handshake();
+        assert!(stats.last_handshake_ms().is_some());
+        assert_eq!(stats.current_session_age(), Some(0));
+        assert_eq!(stats.reconnect_count(), 0);
+
+        // Rekeys and reconnects both count
+        stats.mark_handshake();
+        stats.mark_handshake();
+        assert_eq!(stats.reconnect_count(), 2);
+
+        stats.reset();
+        assert_eq!(stats.last_handshake_ms(), None);
+        assert_eq!(stats.reconnect_count(), 0);
+    }
+
     #[test]
     fn test_generate_sender_index() {
         let idx1 = generate_sender_index();
@@ -699,6 +1621,17 @@ mod tests {
         assert!(manager.get_peer(&peer2_key).is_some());
     }
 
+    #[test]
+    fn test_peer_manager_sorted_by_public_key() {
+        let mut manager = PeerManager::new();
+        for key in [[9u8; 32], [0u8; 32], [200u8; 32], [3u8; 32]] {
+            manager.add_peer(key, None, vec![]);
+        }
+
+        let keys: Vec<[u8; 32]> = manager.sorted().iter().map(|p| p.public_key).collect();
+        assert_eq!(keys, vec![[0u8; 32], [3u8; 32], [9u8; 32], [200u8; 32]]);
+    }
+
     #[test]
     fn test_peer_manager_session_lookup() {
         let mut manager = PeerManager::new();
@@ -713,7 +1646,222 @@ mod tests {
         // Should be able to find by index
         let peer = manager.find_by_index(100);
         assert!(peer.is_some());
-        assert_eq!(peer.unwrap().public_key, peer_key);
+        assert_eq!(lock_peer(&peer.unwrap()).public_key, peer_key);
+    }
+
+    #[test]
+    fn test_peer_manager_lookup_with_many_peers() {
+        const PEERS: u32 = 10_000;
+        let mut manager = PeerManager::new();
+        for n in 0..PEERS {
+            let mut key = [0u8; 32];
+            key[..4].copy_from_slice(&n.to_le_bytes());
+            manager.add_peer(key, None, vec![]);
+            let index = manager.unused_index();
+            manager.establish_session(&key, Session::new(index, n, [1u8; 32], [2u8; 32], test_endpoint()));
+        }
+
+        let indexes: Vec<(u32, u32)> = manager
+            .iter()
+            .map(|peer| (peer.current_session().unwrap().local_index, peer.current_session().unwrap().remote_index))
+            .collect();
+        assert_eq!(indexes.len(), PEERS as usize);
+        for (index, n) in indexes {
+            let peer = manager.find_by_index(index).unwrap();
+            assert_eq!(lock_peer(&peer).public_key[..4], n.to_le_bytes());
+        }
+        let unknown = (0..).find(|i| !manager.index_to_peer.contains_key(i)).unwrap();
+        assert!(manager.find_by_index(unknown).is_none());
+
+        // Taken indexes are skipped
+        let taken = manager.iter().next().unwrap().current_session().unwrap().local_index;
+        let free = (0..).find(|i| !manager.index_to_peer.contains_key(i)).unwrap();
+        let mut draws = [taken, taken, free].into_iter();
+        assert_eq!(manager.unused_index_from(|| draws.next().unwrap()), free);
+    }
+
+    #[test]
+    fn test_peer_manager_clear_sessions() {
+        let mut manager = PeerManager::new();
+        let peer_key = [1u8; 32];
+        manager.add_peer(peer_key, None, vec![]);
+        manager.establish_session(&peer_key, Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        manager.establish_session(&peer_key, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+
+        manager.clear_sessions();
+        assert!(manager.has_peer(&peer_key));
+        assert_eq!(manager.connected_count(), 0);
+        assert!(manager.find_by_index(100).is_none());
+        assert!(manager.find_by_index(101).is_none());
+    }
+
+    #[test]
+    fn test_peer_roam_to_respects_lock() {
+        let other: SocketAddr = "5.6.7.8:4000".parse().unwrap();
+        let mut peer = PeerState::new([1u8; 32], None, vec![]);
+
+        // Unlocked peers follow the latest source
+        assert!(peer.roam_to(test_endpoint()));
+        assert!(peer.roam_to(other));
+        assert_eq!(peer.endpoint, Some(other));
+
+        // Locked peers keep their endpoint
+        peer.lock_endpoint = true;
+        assert!(!peer.roam_to(test_endpoint()));
+        assert_eq!(peer.endpoint, Some(other));
+        assert!(peer.roam_to(other));
+
+        // ...but still learn the first one
+        let mut fresh = PeerState::new([2u8; 32], None, vec![]);
+        fresh.lock_endpoint = true;
+        assert!(fresh.roam_to(other));
+        assert_eq!(fresh.endpoint, Some(other));
+    }
+
+    #[test]
+    fn test_peer_metrics_label() {
+        let mut peer = PeerState::new([1u8; 32], None, vec![]);
+        assert_eq!(peer.metrics_label(), "AQEBAQEB");
+
+        peer.name = Some("alice-laptop".to_string());
+        assert_eq!(peer.metrics_label(), "alice-laptop");
+    }
+
+    #[test]
+    fn test_peer_manager_next_session_until_confirmed() {
+        let mut manager = PeerManager::new();
+        let peer_key = [1u8; 32];
+        manager.add_peer(peer_key, None, vec![]);
+
+        // Nothing to keep sending on: the first session is used right away
+        manager.establish_next(&peer_key, Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        assert_eq!(manager.get_peer(&peer_key).unwrap().current_session().unwrap().local_index, 100);
+
+        // A rekey we answered waits for the client's first packet on it
+        manager.establish_next(&peer_key, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert_eq!(manager.get_peer(&peer_key).unwrap().current_session().unwrap().local_index, 100);
+        let mut client_new = TransportState::new([4u8; 32], [3u8; 32]);
+        let confirming = client_new.encrypt(101, b"first").unwrap();
+
+        let handle = manager.find_by_index(101).unwrap();
+        let mut peer = lock_peer(&handle);
+        let session = peer.find_session_by_index(101).unwrap();
+        assert_eq!(session.transport.decrypt(&confirming).unwrap(), b"first");
+        assert!(peer.confirm_next(101).is_none());
+        assert_eq!(peer.current_session().unwrap().local_index, 101);
+        assert_eq!(peer.previous_session.as_ref().unwrap().local_index, 100);
+        drop(peer);
+
+        // The next confirmed rekey pushes the oldest session out
+        manager.establish_next(&peer_key, Session::new(102, 202, [5u8; 32], [6u8; 32], test_endpoint()));
+        let dropped = manager.get_peer(&peer_key).unwrap().confirm_next(102).unwrap();
+        assert_eq!(dropped.local_index, 100);
+        manager.unregister_session_index(dropped.local_index);
+        assert!(manager.find_by_index(100).is_none());
+        assert_eq!(lock_peer(&manager.find_by_index(102).unwrap()).current_session().unwrap().local_index, 102);
+    }
+
+    #[test]
+    fn test_peer_handshake_time_is_when_it_happened() {
+        let mut manager = PeerManager::new();
+        let peer_key = [1u8; 32];
+        manager.add_peer(peer_key, None, vec![]);
+        assert_eq!(manager.get_peer(&peer_key).unwrap().last_handshake_at, None);
+
+        let before = SystemTime::now();
+        manager.establish_next(&peer_key, Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        let first = manager.get_peer(&peer_key).unwrap().last_handshake_at.unwrap();
+        assert!(first >= before && first <= SystemTime::now());
+
+        // Reading it later doesn't move it; the next handshake does
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(manager.get_peer(&peer_key).unwrap().last_handshake_at, Some(first));
+        manager.establish_next(&peer_key, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert!(manager.get_peer(&peer_key).unwrap().last_handshake_at.unwrap() > first);
+    }
+
+    #[test]
+    fn test_peer_manager_expires_idle_sessions() {
+        let mut manager = PeerManager::new();
+        let (idle, active) = ([1u8; 32], [2u8; 32]);
+        manager.add_peer(idle, None, vec![]);
+        manager.add_peer(active, None, vec![]);
+        manager.add_peer([3u8; 32], None, vec![]);
+        manager.establish_session(&idle, Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        manager.establish_session(&active, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        let start = Instant::now();
+
+        // Only the peer that kept sending survives the timeout
+        let later = start + DEAD_PEER_TIMEOUT;
+        manager.get_peer(&active).unwrap().session.as_mut().unwrap().last_received = later - Duration::from_secs(5);
+        assert!(manager.expire_idle_sessions(later - Duration::from_secs(1)).is_empty());
+        assert_eq!(manager.expire_idle_sessions(later + Duration::from_secs(1)), vec![idle]);
+
+        assert!(manager.get_peer(&idle).unwrap().session.is_none());
+        assert!(manager.find_by_index(100).is_none());
+        assert!(manager.find_by_index(101).is_some());
+        assert!(manager.has_peer(&idle));
+
+        // Nothing left to time out for that peer
+        assert!(!manager.expire_idle_sessions(later + DEAD_PEER_TIMEOUT).contains(&idle));
+    }
+
+    #[test]
+    fn test_peer_manager_back_to_back_handshakes() {
+        let mut manager = PeerManager::new();
+        let peer_key = [1u8; 32];
+        manager.add_peer(peer_key, None, vec![]);
+
+        // First handshake, and a packet the client sends just before rekeying
+        manager.establish_session(&peer_key, Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+        let mut client_old = TransportState::new([2u8; 32], [1u8; 32]);
+        let in_flight = client_old.encrypt(100, b"late packet").unwrap();
+
+        // Client rekeys: the old session still resolves and decrypts
+        manager.establish_session(&peer_key, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        let handle = manager.find_by_index(100).unwrap();
+        let mut peer = lock_peer(&handle);
+        let session = peer.find_session_by_index(100).unwrap();
+        assert_eq!(session.transport.decrypt(&in_flight).unwrap(), b"late packet");
+        drop(peer);
+
+        // Another rekey right away pushes the first session out for good
+        manager.establish_session(&peer_key, Session::new(102, 202, [5u8; 32], [6u8; 32], test_endpoint()));
+        assert!(manager.find_by_index(100).is_none());
+        assert!(lock_peer(&manager.find_by_index(101).unwrap()).find_session_by_index(101).is_some());
+        assert_eq!(lock_peer(&manager.find_by_index(102).unwrap()).current_session().unwrap().local_index, 102);
+
+        // After the overlap window the previous session and its index are cleaned up
+        assert_eq!(manager.expire_previous_sessions(), 0);
+        manager.get_peer(&peer_key).unwrap().previous_retired_at =
+            Instant::now().checked_sub(REJECT_AFTER_TIME);
+        assert_eq!(manager.expire_previous_sessions(), 1);
+        assert!(manager.find_by_index(101).is_none());
+        assert!(manager.find_by_index(102).is_some());
+    }
+
+    #[test]
+    fn test_peer_manager_retired_session_still_expires() {
+        let mut manager = PeerManager::new();
+        let peer_key = [1u8; 32];
+        manager.add_peer(peer_key, None, vec![]);
+
+        // Client rekeys when the first session is close to its own expiry
+        let mut old = Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint());
+        old.created_at = Instant::now().checked_sub(REJECT_AFTER_TIME - Duration::from_secs(1)).unwrap();
+        manager.establish_session(&peer_key, old);
+        manager.establish_session(&peer_key, Session::new(101, 201, [3u8; 32], [4u8; 32], test_endpoint()));
+        assert!(lock_peer(&manager.find_by_index(100).unwrap()).find_session_by_index(100).is_some());
+
+        // Its keys expire while the overlap window is still open
+        let mut peer = manager.get_peer(&peer_key).unwrap();
+        peer.previous_session.as_mut().unwrap().created_at =
+            Instant::now().checked_sub(REJECT_AFTER_TIME).unwrap();
+        assert!(peer.find_session_by_index(100).is_none());
+        drop(peer);
+        assert_eq!(manager.expire_previous_sessions(), 1);
+        assert!(manager.find_by_index(100).is_none());
+        assert!(manager.find_by_index(101).is_some());
     }
 
     #[test]
@@ -727,16 +1875,89 @@ mod tests {
         manager.add_peer(peer2_key, None, vec!["192.168.1.0/24".parse().unwrap()]);
 
         // Route to correct peer
-        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(10, 0, 0, 5));
+        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(10, 0, 0, 5).into());
         assert!(peer.is_some());
-        assert_eq!(peer.unwrap().public_key, peer1_key);
+        assert_eq!(lock_peer(&peer.unwrap()).public_key, peer1_key);
 
-        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(192, 168, 1, 100));
+        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(192, 168, 1, 100).into());
         assert!(peer.is_some());
-        assert_eq!(peer.unwrap().public_key, peer2_key);
+        assert_eq!(lock_peer(&peer.unwrap()).public_key, peer2_key);
 
         // No route
-        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(172, 16, 0, 1));
+        let peer = manager.find_by_allowed_ip(Ipv4Addr::new(172, 16, 0, 1).into());
         assert!(peer.is_none());
+
+        // IPv6 inner traffic routes the same way
+        manager.add_peer([3u8; 32], None, vec!["fd00::3/128".parse().unwrap()]);
+        let peer = manager.find_by_allowed_ip("fd00::3".parse().unwrap());
+        assert_eq!(lock_peer(&peer.unwrap()).public_key, [3u8; 32]);
+    }
+
+    #[test]
+    fn test_peer_lookup_while_another_peer_is_locked() {
+        let mut manager = PeerManager::new();
+        manager.add_peer([1u8; 32], None, vec!["10.0.0.1/32".parse().unwrap()]);
+        manager.add_peer([2u8; 32], None, vec!["10.0.0.2/32".parse().unwrap()]);
+        manager.establish_session(&[2u8; 32], Session::new(100, 200, [1u8; 32], [2u8; 32], test_endpoint()));
+
+        // A busy peer doesn't hold up lookups or the other peer's state
+        let busy = manager.get_peer(&[1u8; 32]).unwrap();
+        let other = manager.find_by_index(100).unwrap();
+        assert!(other.try_lock().is_ok());
+        let routed = manager.find_by_allowed_ip(Ipv4Addr::new(10, 0, 0, 1).into()).unwrap();
+        assert!(routed.try_lock().is_err());
+        drop(busy);
+        assert_eq!(lock_peer(&routed).public_key, [1u8; 32]);
+    }
+
+    #[test]
+    fn test_overlapping_allowed_ips_use_longest_prefix() {
+        let mut manager = PeerManager::new();
+        manager.add_peer([3u8; 32], None, vec!["10.0.0.0/8".parse().unwrap()]);
+        manager.add_peer([2u8; 32], None, vec!["10.0.0.0/24".parse().unwrap()]);
+        manager.add_peer([1u8; 32], None, vec!["10.0.0.0/24".parse().unwrap(), "0.0.0.0/0".parse().unwrap()]);
+
+        // Equal /24 prefixes: the lowest public key wins, every time
+        let ip = Ipv4Addr::new(10, 0, 0, 5);
+        assert_eq!(lock_peer(&manager.find_by_allowed_ip(ip.into()).unwrap()).public_key, [1u8; 32]);
+
+        let candidates: Vec<_> = manager
+            .route_candidates(IpAddr::V4(ip))
+            .into_iter()
+            .map(|(peer, net)| (peer.public_key[0], net.to_string()))
+            .collect();
+        assert_eq!(
+            candidates,
+            vec![(1, "10.0.0.0/24".to_string()), (2, "10.0.0.0/24".to_string()), (3, "10.0.0.0/8".to_string())]
+        );
+
+        // Outside 10/8 only the default route matches
+        let ip = Ipv4Addr::new(192, 0, 2, 1);
+        assert_eq!(lock_peer(&manager.find_by_allowed_ip(ip.into()).unwrap()).public_key, [1u8; 32]);
+        assert_eq!(manager.route_candidates(IpAddr::V4(ip)).len(), 1);
+    }
+
+    #[test]
+    fn test_peer_manager_broadcast_peers() {
+        let mut manager = PeerManager::new();
+
+        manager.add_peer([1u8; 32], None, vec!["10.0.0.2/32".parse().unwrap()]);
+        manager.add_peer([2u8; 32], None, vec!["10.0.0.3/32".parse().unwrap()]);
+        manager.add_peer([3u8; 32], None, vec!["192.168.1.0/24".parse().unwrap()]);
+
+        let lan: Ipv4Net = "10.0.0.0/24".parse().unwrap();
+
+        // Limited broadcast goes to every peer inside the VPN subnet
+        let mut peers = manager.find_all_for_broadcast(Ipv4Addr::BROADCAST, Some(lan));
+        peers.sort();
+        assert_eq!(peers, vec![[1u8; 32], [2u8; 32]]);
+
+        // Without a subnet there is nothing to replicate to
+        assert!(manager.find_all_for_broadcast(Ipv4Addr::BROADCAST, None).is_empty());
+
+        // A peer explicitly routing the destination takes precedence
+        manager.add_peer([4u8; 32], None, vec!["224.0.0.0/4".parse().unwrap()]);
+        let peers = manager.find_all_for_broadcast(Ipv4Addr::new(224, 0, 0, 251), Some(lan));
+        assert_eq!(peers, vec![[4u8; 32]]);
     }
 }
@@ -1,15 +1,27 @@
 //! Transport layer encryption for WireGuard
 //!
 //! Handles encryption and decryption of IP packets using ChaCha20-Poly1305.
+//!
+//! The `_into` variants write into a caller's buffer, so the per-packet path
+//! doesn't allocate; the `Vec`-returning ones wrap them.
+
+use zeroize::Zeroize;
 
 use crate::crypto::aead;
 use crate::error::{CryptoError, ProtocolError, MinnowVpnError};
 use crate::protocol::messages::TransportHeader;
 
+/// Initiate a rekey after sending this many messages
+/// WireGuard spec: REKEY_AFTER_MESSAGES = 2^60
+pub const REKEY_AFTER_MESSAGES: u64 = 1 << 60;
+
 /// Maximum counter value before requiring rekey
 /// WireGuard spec: REJECT_AFTER_MESSAGES = 2^64 - 2^13 - 1
 pub const REJECT_AFTER_MESSAGES: u64 = u64::MAX - 8192;
 
+/// Bytes a transport message adds to its plaintext (header and tag)
+pub const TRANSPORT_OVERHEAD: usize = TransportHeader::SIZE + aead::TAG_LEN;
+
 /// Encrypt an IP packet for transport
 ///
 /// # Arguments
@@ -26,20 +38,37 @@ pub fn encrypt_packet(
     receiver_index: u32,
     plaintext: &[u8],
 ) -> Result<Vec<u8>, MinnowVpnError> {
+    let mut message = vec![0u8; plaintext.len() + TRANSPORT_OVERHEAD];
+    let len = encrypt_packet_into(key, counter, receiver_index, plaintext, &mut message)?;
+    message.truncate(len);
+    Ok(message)
+}
+
+/// [`encrypt_packet`] into `out`, which must hold [`TRANSPORT_OVERHEAD`] more
+/// bytes than the plaintext
+///
+/// Returns the length of the transport message.
+pub fn encrypt_packet_into(
+    key: &[u8; 32],
+    counter: u64,
+    receiver_index: u32,
+    plaintext: &[u8],
+    out: &mut [u8],
+) -> Result<usize, MinnowVpnError> {
     if counter >= REJECT_AFTER_MESSAGES {
-        return Err(ProtocolError::SessionExpired.into());
+        return Err(ProtocolError::CounterExhausted.into());
     }
+    check_room(out, plaintext.len() + TRANSPORT_OVERHEAD)?;
+
+    out[..TransportHeader::SIZE].copy_from_slice(&TransportHeader::header_bytes(receiver_index, counter));
 
     // Encrypt with ChaCha20-Poly1305
-    // No additional authenticated data (AAD) for transport packets
-    let ciphertext = aead::encrypt(key, counter, plaintext, &[])?;
-
-    // Build complete transport message
-    Ok(TransportHeader::build_message(
-        receiver_index,
-        counter,
-        &ciphertext,
-    ))
+    // The spec uses empty associated data: the counter is bound as the nonce and
+    // the receiver index only selects the key, so tampering with either fails the
+    // tag check. Adding AAD here would break interop with every WireGuard peer.
+    let len = aead::encrypt_into(key, counter, plaintext, &[], &mut out[TransportHeader::SIZE..])?;
+
+    Ok(TransportHeader::SIZE + len)
 }
 
 /// Decrypt a transport packet
@@ -54,6 +83,21 @@ pub fn decrypt_packet(
     key: &[u8; 32],
     packet: &[u8],
 ) -> Result<(u64, Vec<u8>), MinnowVpnError> {
+    let mut plaintext = vec![0u8; packet.len().saturating_sub(TRANSPORT_OVERHEAD)];
+    let (counter, len) = decrypt_packet_into(key, packet, &mut plaintext)?;
+    plaintext.truncate(len);
+    Ok((counter, plaintext))
+}
+
+/// [`decrypt_packet`] into `out`, which must hold the packet minus
+/// [`TRANSPORT_OVERHEAD`] bytes
+///
+/// Returns the counter and the length of the decrypted IP packet.
+pub fn decrypt_packet_into(
+    key: &[u8; 32],
+    packet: &[u8],
+    out: &mut [u8],
+) -> Result<(u64, usize), MinnowVpnError> {
     if packet.len() < TransportHeader::MIN_SIZE {
         return Err(ProtocolError::InvalidMessageLength {
             expected: TransportHeader::MIN_SIZE,
@@ -70,26 +114,41 @@ pub fn decrypt_packet(
     }
 
     // Decrypt with ChaCha20-Poly1305
-    let plaintext = aead::decrypt(key, header.counter, ciphertext, &[])?;
+    let len = aead::decrypt_into(key, header.counter, ciphertext, &[], out)?;
 
-    Ok((header.counter, plaintext))
+    Ok((header.counter, len))
+}
+
+/// Fail with `BufferTooSmall` unless `out` holds `needed` bytes
+fn check_room(out: &[u8], needed: usize) -> Result<(), MinnowVpnError> {
+    if out.len() < needed {
+        return Err(CryptoError::BufferTooSmall { needed, got: out.len() }.into());
+    }
+    Ok(())
 }
 
 /// Anti-replay window for tracking received packet counters
 ///
-/// Uses a sliding window bitmap to efficiently track which counters
-/// have been seen, preventing replay attacks.
+/// The sliding window from the WireGuard paper (RFC 6479 style): a ring of
+/// 64-bit words where bit `c % BITMAP_BITS` marks counter `c`. Counters above
+/// the highest seen are accepted and slide the window forward; counters within
+/// [`WINDOW_SIZE`] of the highest are accepted once; older counters and those
+/// past [`REJECT_AFTER_MESSAGES`] are rejected.
 #[derive(Debug, Clone)]
 pub struct ReplayWindow {
-    /// Highest counter value seen
-    highest: u64,
-    /// Bitmap for tracking recent counters
-    /// Bit N represents (highest - N) for N in 0..WINDOW_SIZE
-    bitmap: u128,
+    /// One past the highest counter accepted (0 = nothing received yet)
+    next: u64,
+    /// Ring of seen-bits covering the window
+    bitmap: [u64; BITMAP_WORDS],
 }
 
+/// Words in the replay bitmap (2048 bits)
+const BITMAP_WORDS: usize = 32;
+
 /// Size of the anti-replay window in packets
-const WINDOW_SIZE: u64 = 128;
+///
+/// One word short of the bitmap, so the word being slid into is always clear.
+const WINDOW_SIZE: u64 = (BITMAP_WORDS as u64 - 1) * 64;
 
 impl Default for ReplayWindow {
     fn default() -> Self {
@@ -101,73 +160,52 @@ impl ReplayWindow {
     /// Create a new replay window
     pub fn new() -> Self {
         Self {
-            highest: 0,
-            bitmap: 0,
+            next: 0,
+            bitmap: [0; BITMAP_WORDS],
         }
     }
 
     /// Check if a counter is valid (not a replay) and update window
     ///
-    /// Returns true if the counter is valid (first time seen and within window)
+    /// Returns true if the counter is valid (first time seen and within window).
+    /// Only call this for packets that authenticated, or a forged counter could
+    /// slide the window past genuine traffic.
     pub fn check_and_update(&mut self, counter: u64) -> bool {
-        // Counter 0 is special - always accept the first packet
-        if self.highest == 0 && self.bitmap == 0 {
-            self.highest = counter;
-            self.bitmap = 1;
-            return true;
+        if !self.would_accept(counter) {
+            return false;
         }
 
-        if counter > self.highest {
-            // New highest - shift window
-            let shift = counter - self.highest;
-            if shift >= WINDOW_SIZE {
-                // Counter is way ahead, reset window
-                self.bitmap = 1;
-            } else {
-                // Shift bitmap and mark new counter
-                self.bitmap = (self.bitmap << shift) | 1;
-            }
-            self.highest = counter;
-            true
-        } else {
-            // Counter is at or behind highest
-            let diff = self.highest - counter;
-
-            if diff >= WINDOW_SIZE {
-                // Too old, outside window
-                false
-            } else {
-                // Check if already seen
-                let bit = 1u128 << diff;
-                if self.bitmap & bit != 0 {
-                    // Already seen - replay!
-                    false
-                } else {
-                    // Mark as seen
-                    self.bitmap |= bit;
-                    true
+        let word = counter / 64;
+        if counter >= self.next {
+            // Clear the words the window slides into (all of them on a big jump)
+            if self.next > 0 {
+                let current = (self.next - 1) / 64;
+                let stale = (word - current).min(BITMAP_WORDS as u64);
+                for i in 1..=stale {
+                    self.bitmap[((current + i) % BITMAP_WORDS as u64) as usize] = 0;
                 }
             }
+            self.next = counter + 1;
         }
+
+        self.bitmap[(word % BITMAP_WORDS as u64) as usize] |= 1 << (counter % 64);
+        true
     }
 
     /// Check if a counter would be valid without updating the window
     pub fn would_accept(&self, counter: u64) -> bool {
-        if self.highest == 0 && self.bitmap == 0 {
+        if counter >= REJECT_AFTER_MESSAGES {
+            return false;
+        }
+        if counter >= self.next {
             return true;
         }
-
-        if counter > self.highest {
-            true
-        } else {
-            let diff = self.highest - counter;
-            if diff >= WINDOW_SIZE {
-                false
-            } else {
-                let bit = 1u128 << diff;
-                self.bitmap & bit == 0
-            }
+        if counter + WINDOW_SIZE < self.next {
+            // Too old, outside window
+            return false;
         }
+        let word = self.bitmap[((counter / 64) % BITMAP_WORDS as u64) as usize];
+        word & (1 << (counter % 64)) == 0
     }
 }
 
@@ -184,6 +222,20 @@ pub struct TransportState {
     pub replay_window: ReplayWindow,
 }
 
+/// Session keys are scrubbed when the session is dropped
+impl Zeroize for TransportState {
+    fn zeroize(&mut self) {
+        self.sending_key.zeroize();
+        self.receiving_key.zeroize();
+    }
+}
+
+impl Drop for TransportState {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl TransportState {
     /// Create a new transport state from handshake result
     pub fn new(sending_key: [u8; 32], receiving_key: [u8; 32]) -> Self {
@@ -196,27 +248,70 @@ impl TransportState {
     }
 
     /// Encrypt a packet and increment counter
+    ///
+    /// Fails with `CounterExhausted` once REJECT_AFTER_MESSAGES have been sent;
+    /// the counter never wraps into a reused nonce, and a new handshake is needed.
     pub fn encrypt(&mut self, receiver_index: u32, plaintext: &[u8]) -> Result<Vec<u8>, MinnowVpnError> {
+        let mut message = vec![0u8; plaintext.len() + TRANSPORT_OVERHEAD];
+        let len = self.encrypt_into(receiver_index, plaintext, &mut message)?;
+        message.truncate(len);
+        Ok(message)
+    }
+
+    /// [`TransportState::encrypt`] into `out`, returning the message length
+    ///
+    /// A buffer that is too small is rejected before a counter is used up.
+    pub fn encrypt_into(
+        &mut self,
+        receiver_index: u32,
+        plaintext: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, MinnowVpnError> {
+        if self.is_exhausted() {
+            return Err(ProtocolError::CounterExhausted.into());
+        }
+        check_room(out, plaintext.len() + TRANSPORT_OVERHEAD)?;
         let counter = self.sending_counter;
         self.sending_counter += 1;
-        encrypt_packet(&self.sending_key, counter, receiver_index, plaintext)
+        encrypt_packet_into(&self.sending_key, counter, receiver_index, plaintext, out)
     }
 
     /// Decrypt a packet and check for replay
+    ///
+    /// Replays are dropped before decryption; the window only moves once the
+    /// packet has authenticated.
     pub fn decrypt(&mut self, packet: &[u8]) -> Result<Vec<u8>, MinnowVpnError> {
-        let (counter, plaintext) = decrypt_packet(&self.receiving_key, packet)?;
+        let mut plaintext = vec![0u8; packet.len().saturating_sub(TRANSPORT_OVERHEAD)];
+        let len = self.decrypt_into(packet, &mut plaintext)?;
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+
+    /// [`TransportState::decrypt`] into `out`, returning the IP packet length
+    pub fn decrypt_into(&mut self, packet: &[u8], out: &mut [u8]) -> Result<usize, MinnowVpnError> {
+        if let Ok(header) = TransportHeader::from_bytes(packet) {
+            if !self.replay_window.would_accept(header.counter) {
+                return Err(ProtocolError::ReplayDetected { counter: header.counter }.into());
+            }
+        }
+
+        let (counter, len) = decrypt_packet_into(&self.receiving_key, packet, out)?;
 
         if !self.replay_window.check_and_update(counter) {
             return Err(ProtocolError::ReplayDetected { counter }.into());
         }
 
-        Ok(plaintext)
+        Ok(len)
     }
 
     /// Check if this transport state needs rekeying based on counter
     pub fn needs_rekey_by_counter(&self) -> bool {
-        // Rekey well before hitting the limit
-        self.sending_counter >= REJECT_AFTER_MESSAGES - 1000
+        self.sending_counter >= REKEY_AFTER_MESSAGES
+    }
+
+    /// Whether the sending counter hit REJECT_AFTER_MESSAGES (no more packets on these keys)
+    pub fn is_exhausted(&self) -> bool {
+        self.sending_counter >= REJECT_AFTER_MESSAGES
     }
 }
 
@@ -242,6 +337,64 @@ mod tests {
         assert_eq!(decrypted, plaintext);
     }
 
+    #[test]
+    fn test_zeroize_scrubs_session_keys() {
+        let mut state = TransportState::new([1u8; 32], [2u8; 32]);
+        state.zeroize();
+        assert_eq!(state.sending_key, [0u8; 32]);
+        assert_eq!(state.receiving_key, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_tampered_header_fails_decryption() {
+        let key = [42u8; 32];
+        let encrypted = encrypt_packet(&key, 7, 12345, b"payload").unwrap();
+
+        // Counter (bytes 8..16) is the AEAD nonce
+        let mut tampered = encrypted.clone();
+        tampered[8] ^= 0x01;
+        assert!(matches!(
+            decrypt_packet(&key, &tampered),
+            Err(MinnowVpnError::Crypto(CryptoError::Decryption))
+        ));
+
+        // Ciphertext and tag are authenticated
+        let mut tampered = encrypted.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x80;
+        assert!(decrypt_packet(&key, &tampered).is_err());
+
+        // A rewritten receiver index selects another session, whose key fails
+        let mut other_session = TransportState::new([1u8; 32], [9u8; 32]);
+        let mut tampered = encrypted.clone();
+        tampered[4..8].copy_from_slice(&54321u32.to_le_bytes());
+        assert!(other_session.decrypt(&tampered).is_err());
+
+        assert_eq!(decrypt_packet(&key, &encrypted).unwrap().1, b"payload");
+    }
+
+    #[test]
+    fn test_encrypt_refuses_exhausted_counter() {
+        let mut state = TransportState::new([1u8; 32], [2u8; 32]);
+        state.sending_counter = REJECT_AFTER_MESSAGES - 1;
+
+        let last = state.encrypt(7, b"last").unwrap();
+        assert_eq!(TransportHeader::from_bytes(&last).unwrap().counter, REJECT_AFTER_MESSAGES - 1);
+        assert!(state.is_exhausted());
+
+        for _ in 0..2 {
+            assert!(matches!(
+                state.encrypt(7, b"one more"),
+                Err(MinnowVpnError::Protocol(ProtocolError::CounterExhausted))
+            ));
+        }
+        assert_eq!(state.sending_counter, REJECT_AFTER_MESSAGES);
+        assert!(matches!(
+            encrypt_packet(&[1u8; 32], u64::MAX, 7, b"wrapped"),
+            Err(MinnowVpnError::Protocol(ProtocolError::CounterExhausted))
+        ));
+    }
+
     #[test]
     fn test_counter_increments() {
         let key = [42u8; 32];
@@ -303,15 +456,87 @@ mod tests {
     fn test_replay_window_outside_window() {
         let mut window = ReplayWindow::new();
 
-        // Start at counter 200
-        assert!(window.check_and_update(200));
+        // Start at counter 5000
+        assert!(window.check_and_update(5000));
 
         // Counter way in the past should be rejected
         assert!(!window.check_and_update(0));
-        assert!(!window.check_and_update(50));
+        assert!(!window.check_and_update(5000 - WINDOW_SIZE));
 
-        // Counter just inside window should be accepted
-        assert!(window.check_and_update(200 - WINDOW_SIZE + 1));
+        // Counters just inside window should be accepted
+        assert!(window.check_and_update(5000 - WINDOW_SIZE + 1));
+        assert!(window.check_and_update(4999));
+    }
+
+    #[test]
+    fn test_replay_window_advancement() {
+        let mut window = ReplayWindow::new();
+        for counter in 0..100 {
+            assert!(window.check_and_update(counter));
+        }
+
+        // Jump a full bitmap ahead: counter 2053 lands in the word that held 0..64
+        assert!(window.check_and_update(2053));
+        assert!(!window.check_and_update(5));
+        assert!(!window.check_and_update(2053));
+
+        // Reused bits were cleared, so unseen counters in that word are new
+        assert!(window.check_and_update(2051));
+        assert!(window.check_and_update(2000));
+        assert!(!window.check_and_update(2000));
+
+        // A jump beyond the window forgets everything before it
+        assert!(window.check_and_update(1_000_000));
+        assert!(!window.check_and_update(2052));
+        assert!(window.check_and_update(1_000_000 - 1));
+    }
+
+    #[test]
+    fn test_replay_window_rejects_exhausted_counters() {
+        let mut window = ReplayWindow::new();
+        assert!(window.check_and_update(REJECT_AFTER_MESSAGES - 1));
+        assert!(!window.check_and_update(REJECT_AFTER_MESSAGES));
+        assert!(!window.check_and_update(u64::MAX));
+    }
+
+    #[test]
+    fn test_into_buffers() {
+        let mut send_state = TransportState::new([1u8; 32], [2u8; 32]);
+        let mut recv_state = TransportState::new([2u8; 32], [1u8; 32]);
+        let mut message = [0u8; 64];
+        let mut plaintext = [0u8; 64];
+
+        // Too small: refused without using up a counter
+        assert!(matches!(
+            send_state.encrypt_into(100, b"packet", &mut message[..TRANSPORT_OVERHEAD + 5]),
+            Err(MinnowVpnError::Crypto(CryptoError::BufferTooSmall { needed: 38, got: 37 }))
+        ));
+        assert_eq!(send_state.sending_counter, 0);
+
+        let len = send_state.encrypt_into(100, b"packet", &mut message).unwrap();
+        assert_eq!(len, 6 + TRANSPORT_OVERHEAD);
+        assert_eq!(&message[..len], encrypt_packet(&[1u8; 32], 0, 100, b"packet").unwrap().as_slice());
+
+        let n = recv_state.decrypt_into(&message[..len], &mut plaintext).unwrap();
+        assert_eq!(&plaintext[..n], b"packet");
+        assert!(recv_state.decrypt_into(&message[..len], &mut plaintext).is_err());
+    }
+
+    #[test]
+    fn test_forged_counter_does_not_move_window() {
+        let mut recv_state = TransportState::new([2u8; 32], [1u8; 32]);
+        let genuine = encrypt_packet(&[1u8; 32], 10, 100, b"real").unwrap();
+
+        // Wrong key, far-ahead counter: fails authentication, window untouched
+        let forged = encrypt_packet(&[7u8; 32], 1_000_000, 100, b"fake").unwrap();
+        assert!(recv_state.decrypt(&forged).is_err());
+        assert_eq!(recv_state.decrypt(&genuine).unwrap(), b"real");
+
+        // The replay is refused before decryption
+        assert!(matches!(
+            recv_state.decrypt(&genuine),
+            Err(MinnowVpnError::Protocol(ProtocolError::ReplayDetected { counter: 10 }))
+        ));
     }
 
     #[test]
@@ -6,31 +6,46 @@
 //! - Managing multiple peer sessions
 //! - Routing packets between TUN and UDP based on AllowedIPs
 
-use std::net::{Ipv4Addr, SocketAddr};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use tokio::net::UdpSocket;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, RwLock};
 use tokio::time::{interval, Interval};
 
-use crate::config::WireGuardConfig;
+use crate::config::{RouteTable, WireGuardConfig};
 use crate::crypto::x25519;
 use crate::error::{ConfigError, NetworkError, ProtocolError, MinnowVpnError};
 use crate::protocol::{
-    verify_initiation_mac1, HandshakeInitiation, MessageType, PeerManager, ResponderHandshake,
+    verify_initiation_mac1, CookieChecker, HandshakeInitiation, MessageType, PeerManager, ResponderHandshake,
     Session, TrafficStats, TransportHeader,
 };
 use crate::protocol::messages::get_message_type;
-use crate::protocol::session::generate_sender_index;
-use crate::tunnel::{RouteManager, TunDevice};
+use crate::protocol::session::{lock_peer, PeerState, SharedPeer};
+use crate::protocol::transport::TRANSPORT_OVERHEAD;
+use crate::tunnel::batch::{self, Offload, RecvBatch, SendBatch};
+use crate::tunnel::pktinfo::{self, ReplySources};
+use crate::tunnel::pmtu;
+use crate::tunnel::{describe_ip_packet, set_socket_fwmark, RouteManager, TunDevice};
 
-use ipnet::IpNet;
+use ipnet::{IpNet, Ipv4Net};
 
 /// Buffer size for packets
 const BUFFER_SIZE: usize = 65535;
 
+/// Default handshake initiations per second accepted from one source address
+pub const DEFAULT_HANDSHAKE_RATE_LIMIT: u32 = 20;
+
+/// A /24 (IPv4) or /64 (IPv6) may hold several clients, so it gets this many times the rate
+const PREFIX_RATE_MULTIPLIER: u32 = 8;
+
+/// Rate limiter entries tracked; initiations from new sources are dropped
+/// beyond this until the next prune
+const MAX_RATE_LIMIT_ENTRIES: usize = 65536;
+
 // ============================================================================
 // Server Mode IPC Types
 // ============================================================================
@@ -43,9 +58,31 @@ pub enum PeerUpdate {
         public_key: [u8; 32],
         psk: Option<[u8; 32]>,
         allowed_ips: Vec<IpNet>,
+        /// Disable roaming for this peer
+        lock_endpoint: bool,
+        /// Human-readable name for listings and metrics
+        name: Option<String>,
     },
     /// Remove a peer (terminates active session)
     Remove { public_key: [u8; 32] },
+    /// Stop accepting new sessions ahead of a `stop` (maintenance)
+    Drain { mode: DrainMode },
+}
+
+/// Which handshake initiations a draining server still answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainMode {
+    /// Rekeys from peers that have a live session; new sessions are refused
+    AllowRekeys,
+    /// None: existing sessions carry on until they expire
+    RejectRekeys,
+}
+
+impl DrainMode {
+    /// Whether an initiation from a peer is refused (`has_session`: it has a live session)
+    pub fn refuses(self, has_session: bool) -> bool {
+        self == DrainMode::RejectRekeys || !has_session
+    }
 }
 
 /// Events emitted by server for daemon notifications
@@ -83,6 +120,14 @@ pub struct WireGuardServer {
     static_public: [u8; 32],
     /// UDP socket bound to ListenPort
     socket: UdpSocket,
+    /// UDP offloads enabled on the socket (GSO is turned off if the NIC rejects it)
+    offload: Offload,
+    /// Local address each peer endpoint last reached us on (empty without pktinfo)
+    reply_sources: ReplySources,
+    /// Cookie replies to initiations while under load (DoS protection)
+    cookies: CookieChecker,
+    /// Per-source token buckets for handshake initiations
+    handshake_limiter: HandshakeLimiter,
     /// TUN device for IP traffic
     tun: TunDevice,
     /// Route manager
@@ -93,18 +138,25 @@ pub struct WireGuardServer {
 
     // === Daemon mode fields (optional, for IPC control) ===
     /// Shared peer manager reference for daemon access (when in daemon mode)
-    shared_peers: Option<Arc<Mutex<PeerManager>>>,
+    ///
+    /// The event loop takes the write lock; API queries share the read lock.
+    shared_peers: Option<Arc<RwLock<PeerManager>>>,
     /// Channel to receive peer updates from daemon
     peer_update_rx: Option<mpsc::Receiver<PeerUpdate>>,
     /// Channel to send peer events to daemon
     peer_event_tx: Option<mpsc::Sender<PeerEvent>>,
     /// Aggregate traffic statistics (shared with daemon)
     traffic_stats: Option<Arc<TrafficStats>>,
+    /// Set by the daemon's `drain`: established sessions keep working, new ones are refused
+    drain: Option<DrainMode>,
 }
 
 impl WireGuardServer {
     /// Create a new WireGuard server
     pub async fn new(config: WireGuardConfig) -> Result<Self, MinnowVpnError> {
+        // Standalone mode has no way to add peers later
+        config.validate_server(false)?;
+
         // Clean up any stale routes from crashed previous sessions
         RouteManager::cleanup_stale_routes();
 
@@ -115,38 +167,55 @@ impl WireGuardServer {
             })
         })?;
 
-        // Parse our interface address
-        let our_address = config.interface.address.first().ok_or_else(|| {
-            MinnowVpnError::Config(ConfigError::MissingField {
+        // At least one interface address is required
+        if config.interface.address.is_empty() {
+            return Err(MinnowVpnError::Config(ConfigError::MissingField {
                 field: "Address".to_string(),
-            })
-        })?;
+            }));
+        }
 
-        // Create TUN device
+        // Create TUN device with every configured address
         let tun = TunDevice::create(
-            our_address.addr(),
-            our_address.prefix_len(),
+            &config.interface.addresses(),
             config.interface.mtu.unwrap_or(1420),
+            config.interface.tun_name.as_deref(),
         )
         .await?;
 
         // Create route manager
-        let routes = RouteManager::new(tun.name().to_string());
+        let table = config.interface.table.unwrap_or(RouteTable::Auto);
+        let routes = RouteManager::with_table(tun.name().to_string(), table);
 
-        // Bind UDP socket to ListenPort
-        let bind_addr = format!("0.0.0.0:{}", listen_port);
-        let socket = UdpSocket::bind(&bind_addr).await.map_err(|e| {
+        // Bind UDP socket to ListenPort (on BindAddress, if set)
+        let bind_ip = config.interface.bind_address.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+        let bind_addr = SocketAddr::new(bind_ip, listen_port);
+        let socket = UdpSocket::bind(bind_addr).await.map_err(|e| {
             NetworkError::BindFailed {
-                addr: bind_addr.clone(),
+                addr: bind_addr.to_string(),
                 reason: e.to_string(),
             }
         })?;
 
+        if let Some(mark) = config.interface.fwmark {
+            set_socket_fwmark(&socket, mark)?;
+        }
+
+        // Every read goes through RecvBatch, so GRO is safe here
+        let offload = batch::configure_socket(&socket, true);
+
+        // Reply from the address each peer sent to, for multi-homed hosts
+        if pktinfo::enable(&socket) {
+            tracing::debug!("Replies are sent from the address each peer reached");
+        }
+
         tracing::info!("Server listening on UDP port {}", listen_port);
 
         // Compute our public key from private key
         let static_private = config.interface.private_key;
         let static_public = x25519::public_key(&static_private);
+        let handshake_limiter = HandshakeLimiter::new(
+            config.interface.handshake_rate_limit.unwrap_or(DEFAULT_HANDSHAKE_RATE_LIMIT),
+        );
 
         // Initialize peer manager from config
         let mut peers = PeerManager::new();
@@ -156,6 +225,9 @@ impl WireGuardServer {
                 peer_config.preshared_key,
                 peer_config.allowed_ips.clone(),
             );
+            if let Some(mut peer) = peers.get_peer(&peer_config.public_key) {
+                peer.lock_endpoint = peer_config.lock_endpoint;
+            }
             tracing::info!(
                 "Added peer: {} with AllowedIPs: {:?}",
                 BASE64.encode(&peer_config.public_key[..8]),
@@ -168,6 +240,10 @@ impl WireGuardServer {
             static_private,
             static_public,
             socket,
+            offload,
+            reply_sources: ReplySources::new(),
+            cookies: CookieChecker::new(&static_public),
+            handshake_limiter,
             tun,
             routes,
             peers,
@@ -176,6 +252,7 @@ impl WireGuardServer {
             peer_update_rx: None,
             peer_event_tx: None,
             traffic_stats: None,
+            drain: None,
         })
     }
 
@@ -188,11 +265,17 @@ impl WireGuardServer {
     /// - Traffic statistics shared with daemon
     pub async fn new_with_channels(
         config: WireGuardConfig,
-        shared_peers: Arc<Mutex<PeerManager>>,
+        shared_peers: Arc<RwLock<PeerManager>>,
         peer_update_rx: mpsc::Receiver<PeerUpdate>,
         peer_event_tx: mpsc::Sender<PeerEvent>,
         traffic_stats: Arc<TrafficStats>,
     ) -> Result<Self, MinnowVpnError> {
+        // Peers can be added at runtime, so an empty bootstrap config is fine
+        config.validate_server(true)?;
+        if config.peers.is_empty() {
+            tracing::info!("Starting with no peers; waiting for peers to be added");
+        }
+
         // Clean up any stale routes from crashed previous sessions
         RouteManager::cleanup_stale_routes();
 
@@ -203,38 +286,55 @@ impl WireGuardServer {
             })
         })?;
 
-        // Parse our interface address
-        let our_address = config.interface.address.first().ok_or_else(|| {
-            MinnowVpnError::Config(ConfigError::MissingField {
+        // At least one interface address is required
+        if config.interface.address.is_empty() {
+            return Err(MinnowVpnError::Config(ConfigError::MissingField {
                 field: "Address".to_string(),
-            })
-        })?;
+            }));
+        }
 
-        // Create TUN device
+        // Create TUN device with every configured address
         let tun = TunDevice::create(
-            our_address.addr(),
-            our_address.prefix_len(),
+            &config.interface.addresses(),
             config.interface.mtu.unwrap_or(1420),
+            config.interface.tun_name.as_deref(),
         )
         .await?;
 
         // Create route manager
-        let routes = RouteManager::new(tun.name().to_string());
+        let table = config.interface.table.unwrap_or(RouteTable::Auto);
+        let routes = RouteManager::with_table(tun.name().to_string(), table);
 
-        // Bind UDP socket to ListenPort
-        let bind_addr = format!("0.0.0.0:{}", listen_port);
-        let socket = UdpSocket::bind(&bind_addr).await.map_err(|e| {
+        // Bind UDP socket to ListenPort (on BindAddress, if set)
+        let bind_ip = config.interface.bind_address.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+        let bind_addr = SocketAddr::new(bind_ip, listen_port);
+        let socket = UdpSocket::bind(bind_addr).await.map_err(|e| {
             NetworkError::BindFailed {
-                addr: bind_addr.clone(),
+                addr: bind_addr.to_string(),
                 reason: e.to_string(),
             }
         })?;
 
+        if let Some(mark) = config.interface.fwmark {
+            set_socket_fwmark(&socket, mark)?;
+        }
+
+        // Every read goes through RecvBatch, so GRO is safe here
+        let offload = batch::configure_socket(&socket, true);
+
+        // Reply from the address each peer sent to, for multi-homed hosts
+        if pktinfo::enable(&socket) {
+            tracing::debug!("Replies are sent from the address each peer reached");
+        }
+
         tracing::info!("Server listening on UDP port {}", listen_port);
 
         // Compute our public key from private key
         let static_private = config.interface.private_key;
         let static_public = x25519::public_key(&static_private);
+        let handshake_limiter = HandshakeLimiter::new(
+            config.interface.handshake_rate_limit.unwrap_or(DEFAULT_HANDSHAKE_RATE_LIMIT),
+        );
 
         // In daemon mode, peers are managed via the shared PeerManager
         // The shared_peers already contains the peers from config
@@ -245,6 +345,10 @@ impl WireGuardServer {
             static_private,
             static_public,
             socket,
+            offload,
+            reply_sources: ReplySources::new(),
+            cookies: CookieChecker::new(&static_public),
+            handshake_limiter,
             tun,
             routes,
             peers,
@@ -252,6 +356,7 @@ impl WireGuardServer {
             peer_update_rx: Some(peer_update_rx),
             peer_event_tx: Some(peer_event_tx),
             traffic_stats: Some(traffic_stats),
+            drain: None,
         })
     }
 
@@ -265,6 +370,16 @@ impl WireGuardServer {
         self.config.interface.address.first().map(|a| a.to_string())
     }
 
+    /// MTU of the TUN device: the largest inner packet the tunnel carries
+    fn tunnel_mtu(&self) -> u16 {
+        self.config.interface.mtu.unwrap_or(1420)
+    }
+
+    /// Our tunnel address, the source of ICMP errors we generate
+    fn router_address(&self) -> Option<Ipv4Addr> {
+        self.config.interface.ipv4_address().map(|a| a.addr())
+    }
+
     /// Run the server (main event loop)
     pub async fn run(&mut self) -> Result<(), MinnowVpnError> {
         // Set up routes for peers' allowed IPs
@@ -278,10 +393,8 @@ impl WireGuardServer {
     async fn setup_routes(&mut self) -> Result<(), MinnowVpnError> {
         for peer in &self.config.peers {
             for network in &peer.allowed_ips {
-                if let ipnet::IpNet::V4(v4net) = network {
-                    if let Err(e) = self.routes.add_route(*v4net).await {
-                        tracing::warn!("Failed to add route for {}: {}", network, e);
-                    }
+                if let Err(e) = self.routes.add_route(*network).await {
+                    tracing::warn!("Failed to add route for {}: {}", network, e);
                 }
             }
         }
@@ -291,10 +404,16 @@ impl WireGuardServer {
     /// Main event loop
     async fn event_loop(&mut self) -> Result<(), MinnowVpnError> {
         let mut tun_buf = [0u8; BUFFER_SIZE];
-        let mut udp_buf = [0u8; BUFFER_SIZE];
+        // Transport data is decrypted here, not into a new buffer per packet
+        let mut plaintext_buf = [0u8; BUFFER_SIZE];
+        let mut udp_batch = RecvBatch::new();
+        // Outgoing datagrams are encrypted into buffers kept between batches
+        let mut outgoing = SendBatch::new();
 
         // Rekey check interval (every 10 seconds)
         let mut rekey_check: Interval = interval(Duration::from_secs(10));
+        // Passive keepalives are due KEEPALIVE_TIMEOUT after unanswered data
+        let mut keepalive_check: Interval = interval(Duration::from_secs(1));
 
         tracing::info!("Server event loop started");
 
@@ -306,11 +425,7 @@ impl WireGuardServer {
                     // Read from TUN -> find peer -> encrypt -> send via UDP
                     result = self.tun.read(&mut tun_buf) => {
                         match result {
-                            Ok(len) => {
-                                if let Err(e) = self.handle_tun_packet(&tun_buf[..len]).await {
-                                    tracing::trace!("Error handling TUN packet: {}", e);
-                                }
-                            }
+                            Ok(len) => self.handle_tun_packets(&mut tun_buf, len, &mut outgoing).await,
                             Err(e) => {
                                 tracing::error!("TUN read error: {}", e);
                             }
@@ -318,11 +433,15 @@ impl WireGuardServer {
                     }
 
                     // Read from UDP -> dispatch by message type
-                    result = self.socket.recv_from(&mut udp_buf) => {
+                    result = udp_batch.recv(&self.socket) => {
                         match result {
-                            Ok((len, from)) => {
-                                if let Err(e) = self.handle_udp_packet(&udp_buf[..len], from).await {
-                                    tracing::trace!("Error handling UDP packet: {}", e);
+                            Ok(count) => {
+                                for i in 0..count {
+                                    let (packet, from) = udp_batch.get(i);
+                                    let local = udp_batch.local(i);
+                                    if let Err(e) = self.handle_udp_packet(packet, from, local, &mut plaintext_buf).await {
+                                        tracing::trace!("Error handling UDP packet: {}", e);
+                                    }
                                 }
                             }
                             Err(e) => {
@@ -334,8 +453,8 @@ impl WireGuardServer {
                     // Handle peer updates from daemon (daemon mode only)
                     update = rx.recv() => {
                         match update {
-                            Some(PeerUpdate::Add { public_key, psk, allowed_ips }) => {
-                                if let Err(e) = self.handle_add_peer(public_key, psk, allowed_ips).await {
+                            Some(PeerUpdate::Add { public_key, psk, allowed_ips, lock_endpoint, name }) => {
+                                if let Err(e) = self.handle_add_peer(public_key, psk, allowed_ips, lock_endpoint, name).await {
                                     tracing::error!("Failed to add peer: {}", e);
                                 }
                             }
@@ -344,6 +463,10 @@ impl WireGuardServer {
                                     tracing::error!("Failed to remove peer: {}", e);
                                 }
                             }
+                            Some(PeerUpdate::Drain { mode }) => {
+                                tracing::info!("Draining ({:?}): refusing new sessions", mode);
+                                self.drain = Some(mode);
+                            }
                             None => {
                                 // Channel closed, daemon shutting down
                                 tracing::info!("Peer update channel closed, shutting down");
@@ -354,8 +477,17 @@ impl WireGuardServer {
 
                     // Periodic rekey check for all peers
                     _ = rekey_check.tick() => {
-                        // Server doesn't initiate rekeys - it responds to client rekeys
-                        // But we could clean up expired sessions here if needed
+                        // Server doesn't initiate rekeys - it responds to client rekeys.
+                        // Retire previous sessions once their overlap window is over,
+                        // and drop the sessions of peers that went away.
+                        self.expire_previous_sessions().await;
+                        self.expire_idle_peers().await;
+                    }
+
+                    // Answer data the peers sent with a keepalive if we had nothing to send
+                    _ = keepalive_check.tick() => {
+                        self.send_passive_keepalives(&mut outgoing).await;
+                        self.handshake_limiter.prune(Instant::now());
                     }
                 }
             } else {
@@ -364,11 +496,7 @@ impl WireGuardServer {
                     // Read from TUN -> find peer -> encrypt -> send via UDP
                     result = self.tun.read(&mut tun_buf) => {
                         match result {
-                            Ok(len) => {
-                                if let Err(e) = self.handle_tun_packet(&tun_buf[..len]).await {
-                                    tracing::trace!("Error handling TUN packet: {}", e);
-                                }
-                            }
+                            Ok(len) => self.handle_tun_packets(&mut tun_buf, len, &mut outgoing).await,
                             Err(e) => {
                                 tracing::error!("TUN read error: {}", e);
                             }
@@ -376,11 +504,15 @@ impl WireGuardServer {
                     }
 
                     // Read from UDP -> dispatch by message type
-                    result = self.socket.recv_from(&mut udp_buf) => {
+                    result = udp_batch.recv(&self.socket) => {
                         match result {
-                            Ok((len, from)) => {
-                                if let Err(e) = self.handle_udp_packet(&udp_buf[..len], from).await {
-                                    tracing::trace!("Error handling UDP packet: {}", e);
+                            Ok(count) => {
+                                for i in 0..count {
+                                    let (packet, from) = udp_batch.get(i);
+                                    let local = udp_batch.local(i);
+                                    if let Err(e) = self.handle_udp_packet(packet, from, local, &mut plaintext_buf).await {
+                                        tracing::trace!("Error handling UDP packet: {}", e);
+                                    }
                                 }
                             }
                             Err(e) => {
@@ -391,8 +523,17 @@ impl WireGuardServer {
 
                     // Periodic rekey check for all peers
                     _ = rekey_check.tick() => {
-                        // Server doesn't initiate rekeys - it responds to client rekeys
-                        // But we could clean up expired sessions here if needed
+                        // Server doesn't initiate rekeys - it responds to client rekeys.
+                        // Retire previous sessions once their overlap window is over,
+                        // and drop the sessions of peers that went away.
+                        self.expire_previous_sessions().await;
+                        self.expire_idle_peers().await;
+                    }
+
+                    // Answer data the peers sent with a keepalive if we had nothing to send
+                    _ = keepalive_check.tick() => {
+                        self.send_passive_keepalives(&mut outgoing).await;
+                        self.handshake_limiter.prune(Instant::now());
                     }
                 }
             }
@@ -402,10 +543,16 @@ impl WireGuardServer {
     }
 
     /// Handle incoming UDP packet
+    ///
+    /// `local` is the address the packet was sent to, when pktinfo reports it.
+    /// It is remembered as the reply source for `from` once the packet has
+    /// authenticated, so unverified senders can't grow the table.
     async fn handle_udp_packet(
         &mut self,
         packet: &[u8],
         from: SocketAddr,
+        local: Option<IpAddr>,
+        plaintext_buf: &mut [u8],
     ) -> Result<(), MinnowVpnError> {
         if packet.is_empty() {
             return Ok(());
@@ -415,54 +562,83 @@ impl WireGuardServer {
 
         match msg_type {
             MessageType::HandshakeInitiation => {
-                self.handle_handshake_initiation(packet, from).await
+                // Before any crypto work: scanners outside HandshakeSources cost nothing
+                if !self.config.interface.accepts_handshake_from(from.ip()) {
+                    tracing::trace!("Dropping handshake initiation from {} (outside HandshakeSources)", from);
+                    return Ok(());
+                }
+                if !self.handshake_limiter.allow(from.ip(), Instant::now()) {
+                    tracing::trace!("Dropping handshake initiation from {} (rate limited)", from);
+                    return Ok(());
+                }
+                verify_initiation_mac1(packet, &self.static_public)?;
+
+                // Under load, the sender must prove its address (MAC2) before any DH work
+                if let Some(reply) = self.cookies.check_initiation(packet, from)? {
+                    tracing::debug!("Under load, sending cookie reply to {}", from);
+                    pktinfo::send_from(&self.socket, &reply.to_bytes(), from, local).await.map_err(|e| {
+                        NetworkError::SendFailed {
+                            reason: e.to_string(),
+                        }
+                    })?;
+                    return Ok(());
+                }
+                self.handle_handshake_initiation(packet, from, local).await?
             }
-            MessageType::TransportData => self.handle_transport_packet(packet, from).await,
+            MessageType::TransportData => self.handle_transport_packet(packet, from, plaintext_buf).await?,
             // Server doesn't process HandshakeResponse or CookieReply
             // (those are for clients)
-            _ => Ok(()),
+            _ => return Ok(()),
         }
+
+        if let Some(local) = local {
+            self.reply_sources.insert(from, local);
+        }
+        Ok(())
     }
 
-    /// Process handshake initiation from a peer
+    /// Process handshake initiation from a peer (MAC1 already verified)
     async fn handle_handshake_initiation(
         &mut self,
         packet: &[u8],
         from: SocketAddr,
+        local: Option<IpAddr>,
     ) -> Result<(), MinnowVpnError> {
         // 1. Parse initiation
         let initiation = HandshakeInitiation::from_bytes(packet)?;
 
-        // 2. Verify MAC1 using our public key
-        verify_initiation_mac1(packet, &self.static_public)?;
+        // 2. MAC1 (and MAC2 under load) were checked by handle_udp_packet
 
         // 3. Create responder handshake and process initiation
-        let sender_index = generate_sender_index();
+        // (with an index no other session uses, so transport lookups stay unambiguous)
+        let sender_index = match self.shared_peers {
+            Some(ref shared) => shared.read().await.unused_index(),
+            None => self.peers.unused_index(),
+        };
         let mut responder = ResponderHandshake::new(self.static_private, sender_index);
 
         // 4. Process initiation to get peer's public key
         let peer_public = responder.process_initiation(&initiation)?;
+        let drain = self.drain;
 
         // 5-11: Handle peer lookup and session establishment
         // This differs based on whether we're in daemon mode or standalone
         if let Some(ref shared) = self.shared_peers {
-            // Daemon mode: use shared peer manager
-            let mut peers = shared.lock().await;
-
-            let peer = peers.get_peer_mut(&peer_public).ok_or_else(|| {
-                tracing::warn!("Unknown peer: {}", BASE64.encode(&peer_public[..8]));
-                ProtocolError::InvalidSenderIndex {
-                    index: initiation.sender_index,
-                }
-            })?;
-
-            let psk = peer.psk;
+            // Daemon mode: use shared peer manager (read-locked for the lookup)
+            let peer = configured_peer(&*shared.read().await, &peer_public)?;
+            let psk = {
+                let mut peer = lock_peer(&peer);
+                refuse_while_draining(drain, &peer, &peer_public)?;
+                refuse_locked_endpoint(&peer, from, &peer_public)?;
+                refuse_stale_initiation(&mut peer, &responder.initiator_timestamp, &peer_public)?;
+                peer.psk
+            };
 
             // Create response
             let (response, result) = responder.create_response(psk, None)?;
 
             // Send response
-            self.socket.send_to(&response.to_bytes(), from).await.map_err(|e| {
+            pktinfo::send_from(&self.socket, &response.to_bytes(), from, local).await.map_err(|e| {
                 NetworkError::SendFailed {
                     reason: e.to_string(),
                 }
@@ -483,33 +659,29 @@ impl WireGuardServer {
                 from,
             );
 
-            peers.establish_session(&peer_public, session);
-
-            if let Some(peer) = peers.get_peer_mut(&peer_public) {
-                peer.endpoint = Some(from);
-            }
-
-            // Release the lock before sending event
-            drop(peers);
+            // Sent on once the client's first packet shows it got our response
+            // (registering its index takes the write lock)
+            shared.write().await.establish_next(&peer_public, session);
+            lock_peer(&peer).roam_to(from);
 
             // Send peer connected event (daemon mode)
             self.send_peer_connected_event(peer_public, from).await;
         } else {
             // Standalone mode: use local peer manager
-            let peer = self.peers.get_peer_mut(&peer_public).ok_or_else(|| {
-                tracing::warn!("Unknown peer: {}", BASE64.encode(&peer_public[..8]));
-                ProtocolError::InvalidSenderIndex {
-                    index: initiation.sender_index,
-                }
-            })?;
-
-            let psk = peer.psk;
+            let peer = configured_peer(&self.peers, &peer_public)?;
+            let psk = {
+                let mut peer = lock_peer(&peer);
+                refuse_while_draining(drain, &peer, &peer_public)?;
+                refuse_locked_endpoint(&peer, from, &peer_public)?;
+                refuse_stale_initiation(&mut peer, &responder.initiator_timestamp, &peer_public)?;
+                peer.psk
+            };
 
             // Create response
             let (response, result) = responder.create_response(psk, None)?;
 
             // Send response
-            self.socket.send_to(&response.to_bytes(), from).await.map_err(|e| {
+            pktinfo::send_from(&self.socket, &response.to_bytes(), from, local).await.map_err(|e| {
                 NetworkError::SendFailed {
                     reason: e.to_string(),
                 }
@@ -530,11 +702,9 @@ impl WireGuardServer {
                 from,
             );
 
-            self.peers.establish_session(&peer_public, session);
-
-            if let Some(peer) = self.peers.get_peer_mut(&peer_public) {
-                peer.endpoint = Some(from);
-            }
+            // Sent on once the client's first packet shows it got our response
+            self.peers.establish_next(&peer_public, session);
+            lock_peer(&peer).roam_to(from);
         }
 
         tracing::info!("Session established with peer {}", BASE64.encode(&peer_public[..8]));
@@ -542,155 +712,286 @@ impl WireGuardServer {
         Ok(())
     }
 
-    /// Handle transport data from a peer
+    /// Handle transport data from a peer, decrypting it into `plaintext_buf`
     async fn handle_transport_packet(
         &mut self,
         packet: &[u8],
         from: SocketAddr,
+        plaintext_buf: &mut [u8],
     ) -> Result<(), MinnowVpnError> {
         let header = TransportHeader::from_bytes(packet)?;
+        let (mtu, router) = (self.tunnel_mtu(), self.router_address());
+        let mut bounce_buf = [0u8; pmtu::FRAG_NEEDED_MAX_LEN + TRANSPORT_OVERHEAD];
 
         if let Some(ref shared) = self.shared_peers {
-            // Daemon mode: use shared peer manager
-            let mut peers = shared.lock().await;
-
-            let peer = peers.find_by_index(header.receiver_index).ok_or(
+            // Daemon mode: find the peer under a read lock, then lock only the peer
+            let handle = shared.read().await.find_by_index(header.receiver_index).ok_or(
                 ProtocolError::InvalidSenderIndex {
                     index: header.receiver_index,
                 },
             )?;
 
-            let session = peer
-                .find_session_by_index(header.receiver_index)
-                .ok_or(ProtocolError::NoSession)?;
-
-            let plaintext = session.transport.decrypt(packet)?;
-            session.mark_received();
-
-            // Update traffic stats
-            peer.traffic_stats.add_received(packet.len() as u64);
+            // The peer lock is released before taking the write lock or writing to TUN
+            let (len, bounce, dropped) = {
+                let mut peer = lock_peer(&handle);
+                let (len, dropped) = receive_transport(&mut peer, &header, packet, from, plaintext_buf)?;
+                let bounce = bounce_oversized(&mut peer, &plaintext_buf[..len], router, mtu, &mut bounce_buf);
 
-            // Update aggregate traffic stats
-            if let Some(ref stats) = self.traffic_stats {
-                stats.add_received(packet.len() as u64);
-            }
+                // Update aggregate traffic stats
+                if let Some(ref stats) = self.traffic_stats {
+                    stats.add_received(packet.len() as u64);
+                    stats.add_inner_received(len as u64);
+                }
+                (len, bounce, dropped)
+            };
 
-            // Update endpoint if changed (roaming)
-            if peer.endpoint != Some(from) {
-                tracing::info!("Peer endpoint changed to {}", from);
-                peer.endpoint = Some(from);
+            if let Some(index) = dropped {
+                shared.write().await.unregister_session_index(index);
             }
 
-            // Release lock before writing to TUN
-            drop(peers);
-
-            // Write decrypted IP packet to TUN
-            if !plaintext.is_empty() {
-                self.tun.write(&plaintext).await?;
-            }
+            self.deliver_to_tun(&plaintext_buf[..len], bounce.map(|n| &bounce_buf[..n]), from).await?;
         } else {
             // Standalone mode: use local peer manager
-            let peer = self.peers.find_by_index(header.receiver_index).ok_or(
+            let handle = self.peers.find_by_index(header.receiver_index).ok_or(
                 ProtocolError::InvalidSenderIndex {
                     index: header.receiver_index,
                 },
             )?;
 
-            let session = peer
-                .find_session_by_index(header.receiver_index)
-                .ok_or(ProtocolError::NoSession)?;
+            let (len, bounce, dropped) = {
+                let mut peer = lock_peer(&handle);
+                let (len, dropped) = receive_transport(&mut peer, &header, packet, from, plaintext_buf)?;
+                let bounce = bounce_oversized(&mut peer, &plaintext_buf[..len], router, mtu, &mut bounce_buf);
+                (len, bounce, dropped)
+            };
 
-            let plaintext = session.transport.decrypt(packet)?;
-            session.mark_received();
+            if let Some(index) = dropped {
+                self.peers.unregister_session_index(index);
+            }
 
-            // Update traffic stats
-            peer.traffic_stats.add_received(packet.len() as u64);
+            self.deliver_to_tun(&plaintext_buf[..len], bounce.map(|n| &bounce_buf[..n]), from).await?;
+        }
 
-            // Update endpoint if changed (roaming)
-            if peer.endpoint != Some(from) {
-                tracing::info!("Peer endpoint changed to {}", from);
-                peer.endpoint = Some(from);
-            }
+        Ok(())
+    }
 
-            // Write decrypted IP packet to TUN
-            if !plaintext.is_empty() {
-                self.tun.write(&plaintext).await?;
+    /// Write a decrypted packet to TUN, unless it is a DF packet over the tunnel MTU
+    ///
+    /// An oversized packet is dropped and `bounce`, the encrypted ICMP
+    /// "fragmentation needed" from [`bounce_oversized`], goes back to the peer.
+    async fn deliver_to_tun(
+        &mut self,
+        plaintext: &[u8],
+        bounce: Option<&[u8]>,
+        from: SocketAddr,
+    ) -> Result<(), MinnowVpnError> {
+        if pmtu::exceeds_mtu(plaintext, self.tunnel_mtu()) {
+            tracing::trace!(
+                "Dropping DF packet over tunnel MTU {}: {}",
+                self.tunnel_mtu(),
+                describe_ip_packet(plaintext)
+            );
+            if let Some(bounce) = bounce {
+                let local = self.reply_sources.get(&from).copied();
+                pktinfo::send_from(&self.socket, bounce, from, local).await?;
             }
+            return Ok(());
         }
 
+        // An empty packet is a keepalive
+        if !plaintext.is_empty() {
+            tracing::trace!("Tunnel -> TUN: {}", describe_ip_packet(plaintext));
+            self.tun.write(plaintext).await?;
+        }
         Ok(())
     }
 
-    /// Handle outgoing packet from TUN (needs routing to correct peer)
-    async fn handle_tun_packet(&mut self, packet: &[u8]) -> Result<(), MinnowVpnError> {
+    /// Encrypt a packet read from TUN plus any already queued behind it, then
+    /// send them all in one UDP batch
+    ///
+    /// Without the `udp-batch` feature the batch size is 1 and this is the
+    /// plain one-packet-per-syscall path.
+    async fn handle_tun_packets(&mut self, buf: &mut [u8], first_len: usize, outgoing: &mut SendBatch) {
+        outgoing.clear();
+        let mut len = first_len;
+
+        for n in 0..batch::BATCH_SIZE {
+            if n > 0 {
+                match self.tun.try_read(buf) {
+                    Ok(Some(next)) => len = next,
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::error!("TUN read error: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            if let Err(e) = self.encrypt_tun_packet(&buf[..len], outgoing).await {
+                tracing::trace!("Error handling TUN packet: {}", e);
+            }
+        }
+
+        if let Err(e) =
+            batch::send_batch_from(&self.socket, &mut self.offload, outgoing.packets(), &self.reply_sources).await
+        {
+            tracing::trace!("UDP send error: {}", e);
+        }
+    }
+
+    /// Encrypt an outgoing packet from TUN for the peer(s) it routes to,
+    /// adding the datagrams to `outgoing`
+    async fn encrypt_tun_packet(
+        &mut self,
+        packet: &[u8],
+        outgoing: &mut SendBatch,
+    ) -> Result<(), MinnowVpnError> {
+        tracing::trace!("TUN -> tunnel: {}", describe_ip_packet(packet));
+
+        // Never fragment: a DF packet that doesn't fit is answered with ICMP
+        let mtu = self.tunnel_mtu();
+        if pmtu::exceeds_mtu(packet, mtu) {
+            tracing::trace!("Dropping DF packet over tunnel MTU {}", mtu);
+            if let Some(icmp) = self.router_address().and_then(|router| pmtu::frag_needed(packet, router, mtu)) {
+                self.tun.write(&icmp).await?;
+            }
+            return Ok(());
+        }
+
         // Parse destination IP from packet
-        let dest_ip = parse_ipv4_dest(packet)?;
+        let dest_ip = parse_ip_dest(packet)?;
+
+        // Broadcast/multicast match no single peer; optionally replicate instead of dropping
+        if let (true, IpAddr::V4(dest_v4)) = (self.config.interface.replicate_broadcast, dest_ip) {
+            let lan = self.config.interface.ipv4_address().map(|a| a.trunc());
+            if is_broadcast_or_multicast(dest_v4, lan) {
+                self.replicate_tun_packet(packet, dest_v4, lan, outgoing).await;
+                return Ok(());
+            }
+        }
 
         if let Some(ref shared) = self.shared_peers {
-            // Daemon mode: use shared peer manager
-            let mut peers = shared.lock().await;
-
-            let peer = peers.find_by_allowed_ip_mut(dest_ip).ok_or_else(|| {
+            // Daemon mode: route under a read lock, then lock only the peer
+            let handle = shared.read().await.find_by_allowed_ip(dest_ip).ok_or_else(|| {
                 tracing::trace!("No route to {}", dest_ip);
                 NetworkError::NoEndpoint
             })?;
+            let mut peer = lock_peer(&handle);
 
             let endpoint = peer.endpoint.ok_or(NetworkError::NoEndpoint)?;
-
-            let session = peer.current_session_mut().ok_or(ProtocolError::NoSession)?;
-
-            let remote_index = session.remote_index;
-            let encrypted = session.transport.encrypt(remote_index, packet)?;
-            session.mark_sent();
-
-            // Update traffic stats
-            peer.traffic_stats.add_sent(encrypted.len() as u64);
-
-            // Update aggregate traffic stats
-            if let Some(ref stats) = self.traffic_stats {
-                stats.add_sent(encrypted.len() as u64);
-            }
-
-            // Release lock before sending
-            drop(peers);
-
-            self.socket.send_to(&encrypted, endpoint).await.map_err(|e| {
-                NetworkError::SendFailed {
-                    reason: e.to_string(),
-                }
-            })?;
+            seal(&mut peer, packet, endpoint, self.traffic_stats.as_deref(), outgoing)?;
         } else {
             // Standalone mode: use local peer manager
-            let peer = self.peers.find_by_allowed_ip_mut(dest_ip).ok_or_else(|| {
+            let handle = self.peers.find_by_allowed_ip(dest_ip).ok_or_else(|| {
                 tracing::trace!("No route to {}", dest_ip);
                 NetworkError::NoEndpoint
             })?;
+            let mut peer = lock_peer(&handle);
 
             let endpoint = peer.endpoint.ok_or(NetworkError::NoEndpoint)?;
+            seal(&mut peer, packet, endpoint, None, outgoing)?;
+        }
 
-            let session = peer.current_session_mut().ok_or(ProtocolError::NoSession)?;
+        Ok(())
+    }
 
-            let remote_index = session.remote_index;
-            let encrypted = session.transport.encrypt(remote_index, packet)?;
-            session.mark_sent();
+    /// Encrypt a copy of a broadcast/multicast TUN packet for every matching
+    /// peer into `outgoing`
+    ///
+    /// See `PeerManager::find_all_for_broadcast` for which peers receive a copy.
+    /// Peers without an endpoint or session are skipped.
+    async fn replicate_tun_packet(
+        &mut self,
+        packet: &[u8],
+        dest_ip: Ipv4Addr,
+        lan: Option<Ipv4Net>,
+        outgoing: &mut SendBatch,
+    ) {
+        let copies = if let Some(ref shared) = self.shared_peers {
+            let peers = shared.read().await;
+            encrypt_for_broadcast(&peers, packet, dest_ip, lan, self.traffic_stats.as_deref(), outgoing)
+        } else {
+            encrypt_for_broadcast(&self.peers, packet, dest_ip, lan, self.traffic_stats.as_deref(), outgoing)
+        };
 
-            // Update traffic stats
-            peer.traffic_stats.add_sent(encrypted.len() as u64);
+        tracing::trace!("Replicating packet to {} to {} peer(s)", dest_ip, copies);
+    }
 
-            self.socket.send_to(&encrypted, endpoint).await.map_err(|e| {
-                NetworkError::SendFailed {
-                    reason: e.to_string(),
-                }
-            })?;
+    /// Drop previous sessions (kept after a client rekey) past their overlap window
+    ///
+    /// Also forgets reply sources for endpoints no peer uses any more.
+    async fn expire_previous_sessions(&mut self) {
+        let reply_sources = &mut self.reply_sources;
+        let mut expire = |peers: &mut PeerManager| {
+            if !reply_sources.is_empty() {
+                let endpoints: HashSet<SocketAddr> = peers.iter().filter_map(|p| p.endpoint).collect();
+                reply_sources.retain(|endpoint, _| endpoints.contains(endpoint));
+            }
+            peers.expire_previous_sessions()
+        };
+        let expired = if let Some(ref shared) = self.shared_peers {
+            expire(&mut *shared.write().await)
+        } else {
+            expire(&mut self.peers)
+        };
+
+        if expired > 0 {
+            tracing::debug!("Retired {} previous session(s) after rekey", expired);
         }
+    }
 
-        Ok(())
+    /// Tear down the sessions of peers silent for DEAD_PEER_TIMEOUT and report them
+    async fn expire_idle_peers(&mut self) {
+        let now = Instant::now();
+        let events = match self.shared_peers {
+            Some(ref shared) => idle_peer_events(&mut *shared.write().await, now),
+            None => idle_peer_events(&mut self.peers, now),
+        };
+
+        if let Some(ref tx) = self.peer_event_tx {
+            for event in events {
+                let _ = tx.send(event).await;
+            }
+        }
     }
 
-    /// Clean up routes on shutdown
+    /// Send a keepalive to every peer whose data went unanswered for KEEPALIVE_TIMEOUT
+    async fn send_passive_keepalives(&mut self, outgoing: &mut SendBatch) {
+        let now = Instant::now();
+        outgoing.clear();
+        match self.shared_peers {
+            Some(ref shared) => passive_keepalives(&*shared.read().await, now, self.traffic_stats.as_deref(), outgoing),
+            None => passive_keepalives(&self.peers, now, self.traffic_stats.as_deref(), outgoing),
+        }
+        if outgoing.is_empty() {
+            return;
+        }
+
+        tracing::trace!("Sending {} passive keepalive(s)", outgoing.len());
+        if let Err(e) =
+            batch::send_batch_from(&self.socket, &mut self.offload, outgoing.packets(), &self.reply_sources).await
+        {
+            tracing::trace!("UDP send error: {}", e);
+        }
+    }
+
+    /// Shut down after the event loop has stopped (the `run` future was dropped)
+    ///
+    /// Same order as the client: sessions, then routes, then the TUN. See
+    /// [`crate::client::WireGuardClient::cleanup`] for why.
     pub async fn cleanup(&mut self) -> Result<(), MinnowVpnError> {
+        match self.shared_peers {
+            Some(ref shared) => shared.write().await.clear_sessions(),
+            None => self.peers.clear_sessions(),
+        }
+
         tracing::info!("Server cleaning up routes...");
-        self.routes.cleanup().await?;
+        let routes = self.routes.cleanup().await;
+
+        if let Err(e) = self.tun.close() {
+            tracing::debug!("Could not bring TUN {} down: {}", self.tun.name(), e);
+        }
+        routes?;
         tracing::info!("Server cleanup complete");
         Ok(())
     }
@@ -705,24 +1006,32 @@ impl WireGuardServer {
         public_key: [u8; 32],
         psk: Option<[u8; 32]>,
         allowed_ips: Vec<IpNet>,
+        lock_endpoint: bool,
+        name: Option<String>,
     ) -> Result<(), MinnowVpnError> {
         tracing::info!("Adding peer dynamically: {}", BASE64.encode(&public_key[..8]));
 
         // Add routes for the new peer's allowed IPs
         for network in &allowed_ips {
-            if let ipnet::IpNet::V4(v4net) = network {
-                if let Err(e) = self.routes.add_route(*v4net).await {
-                    tracing::warn!("Failed to add route for {}: {}", network, e);
-                }
+            if let Err(e) = self.routes.add_route(*network).await {
+                tracing::warn!("Failed to add route for {}: {}", network, e);
             }
         }
 
         // Add peer to the appropriate PeerManager
         if let Some(ref shared) = self.shared_peers {
-            let mut peers = shared.lock().await;
+            let mut peers = shared.write().await;
             peers.add_peer(public_key, psk, allowed_ips.clone());
+            if let Some(mut peer) = peers.get_peer(&public_key) {
+                peer.lock_endpoint = lock_endpoint;
+                peer.name = name;
+            };
         } else {
             self.peers.add_peer(public_key, psk, allowed_ips.clone());
+            if let Some(mut peer) = self.peers.get_peer(&public_key) {
+                peer.lock_endpoint = lock_endpoint;
+                peer.name = name;
+            }
         }
 
         // Send notification
@@ -742,21 +1051,22 @@ impl WireGuardServer {
         tracing::info!("Removing peer: {}", BASE64.encode(&public_key[..8]));
 
         let removed = if let Some(ref shared) = self.shared_peers {
-            let mut peers = shared.lock().await;
+            let mut peers = shared.write().await;
             peers.remove_peer(&public_key)
         } else {
             self.peers.remove_peer(&public_key)
         };
 
         if let Some(peer) = removed {
-            let was_connected = peer.session.is_some();
+            let (was_connected, allowed_ips) = {
+                let peer = lock_peer(&peer);
+                (peer.session.is_some(), peer.allowed_ips.clone())
+            };
 
             // Remove routes for this peer's allowed IPs
-            for network in &peer.allowed_ips {
-                if let ipnet::IpNet::V4(v4net) = network {
-                    if let Err(e) = self.routes.remove_route(*v4net).await {
-                        tracing::warn!("Failed to remove route for {}: {}", network, e);
-                    }
+            for network in &allowed_ips {
+                if let Err(e) = self.routes.remove_route(*network).await {
+                    tracing::warn!("Failed to remove route for {}: {}", network, e);
                 }
             }
 
@@ -791,6 +1101,370 @@ impl WireGuardServer {
     }
 }
 
+/// Check whether a destination is broadcast or multicast
+///
+/// Covers limited broadcast, multicast, and the directed broadcast of the VPN subnet.
+fn is_broadcast_or_multicast(dest: Ipv4Addr, lan: Option<Ipv4Net>) -> bool {
+    dest.is_broadcast()
+        || dest.is_multicast()
+        || lan.is_some_and(|lan| lan.prefix_len() < 31 && dest == lan.broadcast())
+}
+
+/// Encrypt `packet` on `peer`'s current session into a new datagram to `to`
+///
+/// Counts it in the peer's and the aggregate `traffic_stats`. Keepalives
+/// (empty packets) count towards the outer bytes only.
+fn seal(
+    peer: &mut PeerState,
+    packet: &[u8],
+    to: SocketAddr,
+    traffic_stats: Option<&TrafficStats>,
+    outgoing: &mut SendBatch,
+) -> Result<usize, MinnowVpnError> {
+    let session = peer.current_session_mut().ok_or(ProtocolError::NoSession)?;
+    let remote_index = session.remote_index;
+    let len = outgoing.push_with(packet.len() + TRANSPORT_OVERHEAD, to, |buf| {
+        session.transport.encrypt_into(remote_index, packet, buf)
+    })?;
+    session.mark_sent();
+
+    peer.traffic_stats.add_sent(len as u64);
+    peer.traffic_stats.add_inner_sent(packet.len() as u64);
+    if let Some(stats) = traffic_stats {
+        stats.add_sent(len as u64);
+        stats.add_inner_sent(packet.len() as u64);
+    }
+    Ok(len)
+}
+
+/// Encrypt a copy of `packet` for each broadcast recipient into `outgoing`,
+/// returning how many were added
+fn encrypt_for_broadcast(
+    peers: &PeerManager,
+    packet: &[u8],
+    dest_ip: Ipv4Addr,
+    lan: Option<Ipv4Net>,
+    traffic_stats: Option<&TrafficStats>,
+    outgoing: &mut SendBatch,
+) -> usize {
+    let mut copies = 0;
+
+    for public_key in peers.find_all_for_broadcast(dest_ip, lan) {
+        let Some(mut peer) = peers.get_peer(&public_key) else {
+            continue;
+        };
+        let Some(endpoint) = peer.endpoint else {
+            continue;
+        };
+        if peer.current_session().is_none() {
+            continue;
+        }
+
+        match seal(&mut peer, packet, endpoint, traffic_stats, outgoing) {
+            Ok(_) => copies += 1,
+            Err(e) => tracing::debug!("Failed to encrypt broadcast copy: {}", e),
+        }
+    }
+
+    copies
+}
+
+/// Encrypt a keepalive into `outgoing` for each peer that owes one at `now`
+fn passive_keepalives(
+    peers: &PeerManager,
+    now: Instant,
+    traffic_stats: Option<&TrafficStats>,
+    outgoing: &mut SendBatch,
+) {
+    for mut peer in peers.iter() {
+        let Some(endpoint) = peer.endpoint else {
+            continue;
+        };
+        let Some(session) = peer.current_session() else {
+            continue;
+        };
+        if !session.needs_passive_keepalive_at(now) {
+            continue;
+        }
+
+        if let Err(e) = seal(&mut peer, &[], endpoint, traffic_stats, outgoing) {
+            tracing::debug!("Failed to encrypt keepalive: {}", e);
+        }
+    }
+}
+
+/// Close the sessions of peers silent at `now`, returning their disconnect events
+fn idle_peer_events(peers: &mut PeerManager, now: Instant) -> Vec<PeerEvent> {
+    peers
+        .expire_idle_sessions(now)
+        .into_iter()
+        .map(|public_key| {
+            tracing::info!("Peer {} timed out, session closed", BASE64.encode(&public_key[..8]));
+            PeerEvent::Disconnected {
+                public_key,
+                reason: "timeout".to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Look up the peer an initiation came from; its key must be configured
+fn configured_peer(peers: &PeerManager, peer_public: &[u8; 32]) -> Result<SharedPeer, ProtocolError> {
+    peers.peer(peer_public).ok_or_else(|| {
+        let public_key_prefix = BASE64.encode(&peer_public[..8]);
+        tracing::warn!("Unknown peer: {}", public_key_prefix);
+        ProtocolError::UnknownPeer { public_key_prefix }
+    })
+}
+
+/// Refuse an initiation that a draining server no longer answers
+fn refuse_while_draining(
+    drain: Option<DrainMode>,
+    peer: &PeerState,
+    peer_public: &[u8; 32],
+) -> Result<(), ProtocolError> {
+    if drain.is_some_and(|mode| mode.refuses(peer.has_session())) {
+        let public_key_prefix = BASE64.encode(&peer_public[..8]);
+        tracing::debug!("Draining: refused handshake from {}", public_key_prefix);
+        return Err(ProtocolError::ServerDraining { public_key_prefix });
+    }
+    Ok(())
+}
+
+/// Refuse an initiation for a peer locked to a different endpoint
+///
+/// Checked before the timestamp so a refused initiation doesn't count as the
+/// peer's latest one.
+fn refuse_locked_endpoint(
+    peer: &PeerState,
+    from: SocketAddr,
+    peer_public: &[u8; 32],
+) -> Result<(), ProtocolError> {
+    if peer.lock_endpoint && peer.endpoint.is_some_and(|endpoint| endpoint != from) {
+        let public_key_prefix = BASE64.encode(&peer_public[..8]);
+        tracing::debug!("Endpoint locked: refused handshake from {} for {}", from, public_key_prefix);
+        return Err(ProtocolError::EndpointLocked { public_key_prefix, from: from.to_string() });
+    }
+    Ok(())
+}
+
+/// Reject a replayed initiation: its timestamp must be newer than the peer's last
+///
+/// Without this a captured initiation could be resent to force handshakes and
+/// reset the peer's session. The timestamp is recorded when it is accepted.
+fn refuse_stale_initiation(
+    peer: &mut PeerState,
+    timestamp: &[u8; 12],
+    peer_public: &[u8; 32],
+) -> Result<(), ProtocolError> {
+    if !peer.validate_timestamp(timestamp) {
+        let public_key_prefix = BASE64.encode(&peer_public[..8]);
+        tracing::debug!("Dropping replayed handshake initiation from {}", public_key_prefix);
+        return Err(ProtocolError::StaleTimestamp { public_key_prefix });
+    }
+    Ok(())
+}
+
+/// Token bucket allowing `rate` events per second, with bursts of up to `rate`
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(rate: u32, now: Instant) -> Self {
+        Self { tokens: rate as f64, updated: now }
+    }
+
+    /// Refill for the time since the last call, then take a token if there is one
+    fn take(&mut self, rate: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the bucket has been idle long enough to be full again
+    fn is_idle(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.updated) >= Duration::from_secs(1)
+    }
+}
+
+/// Rate limit on handshake initiations, checked before any crypto work
+///
+/// Each source address gets `rate` initiations per second, and its /24 (IPv4)
+/// or /64 (IPv6) a multiple of that, so a flood that rotates addresses within
+/// one network is held back too. Ports are ignored: they are free to change.
+///
+/// Idle entries are pruned once a second by the event loop, never on the
+/// packet path. While the table is full, sources it doesn't know are dropped.
+#[derive(Debug)]
+struct HandshakeLimiter {
+    rate: u32,
+    addresses: HashMap<IpAddr, TokenBucket>,
+    prefixes: HashMap<IpNet, TokenBucket>,
+}
+
+impl HandshakeLimiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            addresses: HashMap::new(),
+            prefixes: HashMap::new(),
+        }
+    }
+
+    /// Whether an initiation from `ip` may be processed (consumes a token if so)
+    fn allow(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let rate = self.rate;
+        if !bucket(&mut self.addresses, ip, rate, now).is_some_and(|address| address.take(rate, now)) {
+            return false;
+        }
+
+        let prefix_len = if ip.is_ipv4() { 24 } else { 64 };
+        let prefix = IpNet::new(ip, prefix_len).map(|net| net.trunc()).unwrap_or_else(|_| IpNet::from(ip));
+        let prefix_rate = rate.saturating_mul(PREFIX_RATE_MULTIPLIER);
+        bucket(&mut self.prefixes, prefix, prefix_rate, now).is_some_and(|prefix| prefix.take(prefix_rate, now))
+    }
+
+    /// Forget sources whose buckets have refilled (they'd start full anyway)
+    fn prune(&mut self, now: Instant) {
+        self.addresses.retain(|_, bucket| !bucket.is_idle(now));
+        self.prefixes.retain(|_, bucket| !bucket.is_idle(now));
+    }
+}
+
+/// The bucket for `key`, created full if there is room (None if the table is full)
+fn bucket<K: std::hash::Hash + Eq>(
+    buckets: &mut HashMap<K, TokenBucket>,
+    key: K,
+    rate: u32,
+    now: Instant,
+) -> Option<&mut TokenBucket> {
+    let full = buckets.len() >= MAX_RATE_LIMIT_ENTRIES;
+    match buckets.entry(key) {
+        Entry::Occupied(entry) => Some(entry.into_mut()),
+        Entry::Vacant(_) if full => None,
+        Entry::Vacant(entry) => Some(entry.insert(TokenBucket::full(rate, now))),
+    }
+}
+
+/// Promote `peer`'s next session if a packet on `index` just authenticated
+///
+/// Returns the local index of the previous session this pushes out, which
+/// the caller unregisters.
+fn confirm_next(peer: &mut PeerState, index: u32) -> Option<u32> {
+    if peer.next_session.as_ref().map(|s| s.local_index) != Some(index) {
+        return None;
+    }
+    tracing::debug!("Peer {} confirmed the new session", BASE64.encode(&peer.public_key[..8]));
+    peer.confirm_next(index).map(|dropped| dropped.local_index)
+}
+
+/// Decrypt a transport packet from `peer` into `plaintext_buf` and update its state
+///
+/// Marks the session, switches to a confirmed next session, counts the
+/// traffic and follows roaming. Returns the plaintext length and the local
+/// index of a session the switch pushed out, which the caller unregisters.
+fn receive_transport(
+    peer: &mut PeerState,
+    header: &TransportHeader,
+    packet: &[u8],
+    from: SocketAddr,
+    plaintext_buf: &mut [u8],
+) -> Result<(usize, Option<u32>), MinnowVpnError> {
+    let session = peer
+        .find_session_by_index(header.receiver_index)
+        .ok_or(ProtocolError::NoSession)?;
+
+    let len = session.transport.decrypt_into(packet, plaintext_buf)?;
+    if len == 0 {
+        session.mark_received();
+    } else {
+        session.mark_data_received();
+    }
+
+    // First packet on the keys of a rekey we answered: switch to them
+    let dropped = confirm_next(peer, header.receiver_index);
+
+    // Update traffic stats
+    peer.traffic_stats.add_received(packet.len() as u64);
+    peer.traffic_stats.add_inner_received(len as u64);
+
+    // Update endpoint if changed (roaming), unless the peer is locked
+    if peer.endpoint != Some(from) {
+        if peer.roam_to(from) {
+            tracing::info!("Peer endpoint changed to {}", from);
+        } else {
+            tracing::warn!(
+                "Peer {} is locked to {:?}; ignoring new source {}",
+                BASE64.encode(&peer.public_key[..8]),
+                peer.endpoint,
+                from
+            );
+        }
+    }
+
+    Ok((len, dropped))
+}
+
+/// Encrypt an ICMP "fragmentation needed" into `out` for a DF packet from
+/// `peer` that exceeds the tunnel MTU, returning its length
+///
+/// None if the packet fits, gets no ICMP, or the peer has no session to
+/// answer on. `out` needs [`pmtu::FRAG_NEEDED_MAX_LEN`] plus
+/// [`TRANSPORT_OVERHEAD`] bytes.
+fn bounce_oversized(
+    peer: &mut PeerState,
+    plaintext: &[u8],
+    router: Option<Ipv4Addr>,
+    mtu: u16,
+    out: &mut [u8],
+) -> Option<usize> {
+    if !pmtu::exceeds_mtu(plaintext, mtu) {
+        return None;
+    }
+    let icmp = pmtu::frag_needed(plaintext, router?, mtu)?;
+    let session = peer.current_session_mut()?;
+    let len = session.transport.encrypt_into(session.remote_index, &icmp, out).ok()?;
+    session.mark_sent();
+    Some(len)
+}
+
+/// Parse the destination address of an IPv4 or IPv6 packet
+pub(crate) fn parse_ip_dest(packet: &[u8]) -> Result<IpAddr, MinnowVpnError> {
+    match packet.first().map(|b| b >> 4) {
+        Some(6) => parse_ipv6_dest(packet).map(IpAddr::V6),
+        _ => parse_ipv4_dest(packet).map(IpAddr::V4),
+    }
+}
+
+/// Parse destination IPv6 address from an IP packet
+fn parse_ipv6_dest(packet: &[u8]) -> Result<Ipv6Addr, MinnowVpnError> {
+    if packet.len() < 40 {
+        return Err(ProtocolError::InvalidMessageLength {
+            expected: 40,
+            got: packet.len(),
+        }
+        .into());
+    }
+
+    let version = packet[0] >> 4;
+    if version != 6 {
+        return Err(ProtocolError::InvalidMessageType { msg_type: version }.into());
+    }
+
+    // IPv6 destination is bytes 24-39
+    let dest: [u8; 16] = packet[24..40].try_into().expect("16-byte slice");
+    Ok(Ipv6Addr::from(dest))
+}
+
 /// Parse destination IPv4 address from an IP packet
 fn parse_ipv4_dest(packet: &[u8]) -> Result<Ipv4Addr, MinnowVpnError> {
     if packet.len() < 20 {
@@ -815,6 +1489,234 @@ fn parse_ipv4_dest(packet: &[u8]) -> Result<Ipv4Addr, MinnowVpnError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_drain_modes() {
+        assert!(DrainMode::AllowRekeys.refuses(false));
+        assert!(!DrainMode::AllowRekeys.refuses(true));
+        assert!(DrainMode::RejectRekeys.refuses(false));
+        assert!(DrainMode::RejectRekeys.refuses(true));
+    }
+
+    /// One peer (10.0.0.7/32) with a session from 192.0.2.7: local index 100,
+    /// sending key `[1; 32]`, receiving key `[2; 32]`
+    fn peers_with_session() -> (PeerManager, [u8; 32], SocketAddr) {
+        let peer_key = [7u8; 32];
+        let endpoint: SocketAddr = "192.0.2.7:51820".parse().unwrap();
+        let mut peers = PeerManager::new();
+        peers.add_peer(peer_key, None, vec!["10.0.0.7/32".parse().unwrap()]);
+        peers.establish_session(&peer_key, Session::new(100, 200, [1u8; 32], [2u8; 32], endpoint));
+        (peers, peer_key, endpoint)
+    }
+
+    #[test]
+    fn test_bounce_oversized_encrypts_into_buffer() {
+        use crate::protocol::TransportState;
+
+        let (peers, peer_key, _) = peers_with_session();
+        let mut peer = peers.get_peer(&peer_key).unwrap();
+
+        // 1500-byte UDP packet with DF set, from the peer's side of the tunnel
+        let mut packet = vec![0u8; 1500];
+        packet[..20].copy_from_slice(&[
+            0x45, 0, 0x05, 0xdc, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 7, 10, 0, 0, 1,
+        ]);
+        let router = Some(Ipv4Addr::new(10, 0, 0, 1));
+        let mut out = [0u8; pmtu::FRAG_NEEDED_MAX_LEN + TRANSPORT_OVERHEAD];
+
+        assert_eq!(bounce_oversized(&mut peer, &packet[..1400], router, 1420, &mut out), None);
+        let len = bounce_oversized(&mut peer, &packet, router, 1420, &mut out).unwrap();
+        let icmp = TransportState::new([2u8; 32], [1u8; 32]).decrypt(&out[..len]).unwrap();
+        assert_eq!(icmp, pmtu::frag_needed(&packet, Ipv4Addr::new(10, 0, 0, 1), 1420).unwrap());
+    }
+
+    #[test]
+    fn test_passive_keepalive_answers_data_once() {
+        use crate::protocol::session::KEEPALIVE_TIMEOUT;
+
+        let (peers, peer_key, endpoint) = peers_with_session();
+        let mut peer = peers.get_peer(&peer_key).unwrap();
+        peer.endpoint = Some(endpoint);
+
+        let session = peer.current_session_mut().unwrap();
+        session.last_sent -= Duration::from_millis(1);
+        session.mark_data_received();
+        let due = session.last_received + KEEPALIVE_TIMEOUT;
+        drop(peer);
+
+        let mut outgoing = SendBatch::new();
+        passive_keepalives(&peers, due - Duration::from_millis(1), None, &mut outgoing);
+        assert!(outgoing.is_empty());
+        passive_keepalives(&peers, due, None, &mut outgoing);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing.packets()[0].1, endpoint);
+        assert_eq!(outgoing.packets()[0].0.len(), TRANSPORT_OVERHEAD);
+        assert_eq!(peers.get_peer(&peer_key).unwrap().traffic_stats.get_inner_sent(), 0);
+
+        // Answered now: nothing more until new data arrives
+        outgoing.clear();
+        passive_keepalives(&peers, due + KEEPALIVE_TIMEOUT, None, &mut outgoing);
+        assert!(outgoing.is_empty());
+    }
+
+    #[test]
+    fn test_silent_peer_times_out_with_event() {
+        use crate::protocol::session::DEAD_PEER_TIMEOUT;
+
+        let (mut peers, peer_key, _) = peers_with_session();
+        assert_eq!(peers.connected_count(), 1);
+
+        let now = Instant::now();
+        assert!(idle_peer_events(&mut peers, now).is_empty());
+
+        let events = idle_peer_events(&mut peers, now + DEAD_PEER_TIMEOUT);
+        assert!(matches!(
+            events.as_slice(),
+            [PeerEvent::Disconnected { public_key, reason }] if *public_key == peer_key && reason == "timeout"
+        ));
+        assert_eq!(peers.connected_count(), 0);
+        assert!(peers.find_by_index(100).is_none());
+
+        // Reported once
+        assert!(idle_peer_events(&mut peers, now + DEAD_PEER_TIMEOUT * 2).is_empty());
+    }
+
+    #[test]
+    fn test_handshake_limiter_drops_bursts() {
+        let mut limiter = HandshakeLimiter::new(5);
+        let now = Instant::now();
+        let source: IpAddr = "192.0.2.7".parse().unwrap();
+
+        // A burst gets `rate` through, the excess is dropped
+        let allowed = (0..20).filter(|_| limiter.allow(source, now)).count();
+        assert_eq!(allowed, 5);
+
+        // Tokens come back over time, `rate` per second
+        assert!(limiter.allow(source, now + Duration::from_millis(200)));
+        assert!(!limiter.allow(source, now + Duration::from_millis(200)));
+        let later = now + Duration::from_millis(1200);
+        assert_eq!((0..20).filter(|_| limiter.allow(source, later)).count(), 5);
+
+        // Idle sources are forgotten
+        limiter.prune(later + Duration::from_secs(1));
+        assert!(limiter.addresses.is_empty());
+        assert!(limiter.prefixes.is_empty());
+
+        // Rotating addresses within the /24 gets exactly the prefix budget (8 x 5)
+        let rotated = (1..=100u8)
+            .filter(|i| limiter.allow(IpAddr::from([192, 0, 2, *i]), now))
+            .count();
+        assert_eq!(rotated, 40);
+        assert!(limiter.allow("198.51.100.1".parse().unwrap(), now));
+    }
+
+    #[test]
+    fn test_handshake_limiter_full_table_drops_new_sources() {
+        let mut limiter = HandshakeLimiter::new(5);
+        let now = Instant::now();
+        for i in 0..MAX_RATE_LIMIT_ENTRIES as u32 {
+            // One address in each of 65536 /24s
+            assert!(limiter.allow(IpAddr::from((0x0a00_0000 + (i << 8)).to_be_bytes()), now));
+        }
+        assert_eq!(limiter.addresses.len(), MAX_RATE_LIMIT_ENTRIES);
+
+        // Known sources keep their budget, unknown ones wait for the prune
+        let newcomer: IpAddr = "198.51.100.1".parse().unwrap();
+        assert!(limiter.allow("10.0.1.0".parse().unwrap(), now));
+        assert!(!limiter.allow(newcomer, now));
+        assert_eq!(limiter.addresses.len(), MAX_RATE_LIMIT_ENTRIES);
+
+        limiter.prune(now + Duration::from_secs(1));
+        assert!(limiter.allow(newcomer, now + Duration::from_secs(1)));
+    }
+
+    /// The server's side of an initiation from `client_private` stamped `timestamp`:
+    /// the responder (holding the decrypted timestamp) and the initiator's public key
+    fn receive_initiation(
+        server_private: [u8; 32],
+        client_private: [u8; 32],
+        timestamp: tai64::Tai64N,
+    ) -> (ResponderHandshake, [u8; 32]) {
+        use crate::protocol::InitiatorHandshake;
+
+        let initiation = InitiatorHandshake::new(client_private, x25519::public_key(&server_private), None, 1)
+            .create_initiation_at(None, timestamp)
+            .unwrap();
+        let mut responder = ResponderHandshake::new(server_private, 2);
+        let peer_public = responder.process_initiation(&initiation).unwrap();
+        (responder, peer_public)
+    }
+
+    #[test]
+    fn test_replayed_initiation_is_rejected() {
+        use tai64::Tai64N;
+
+        let server_private = x25519::generate_private_key();
+        let (client_private, client_public) = x25519::generate_keypair();
+        let mut peer = PeerState::new(client_public, None, Vec::new());
+        let sent = Tai64N::UNIX_EPOCH;
+        let receive = |peer: &mut PeerState, timestamp| {
+            let (responder, peer_public) = receive_initiation(server_private, client_private, timestamp);
+            refuse_stale_initiation(peer, &responder.initiator_timestamp, &peer_public)
+        };
+
+        assert!(receive(&mut peer, sent).is_ok());
+        assert!(matches!(receive(&mut peer, sent), Err(ProtocolError::StaleTimestamp { .. })));
+
+        // A fresh initiation from the same peer is still accepted, an older one is not
+        assert!(receive(&mut peer, Tai64N(sent.0, 2)).is_ok());
+        assert!(matches!(receive(&mut peer, Tai64N(sent.0, 1)), Err(ProtocolError::StaleTimestamp { .. })));
+    }
+
+    #[test]
+    fn test_initiation_from_unconfigured_key_is_unknown_peer() {
+        let server_private = x25519::generate_private_key();
+        let (client_private, client_public) = x25519::generate_keypair();
+        let (stranger_private, stranger_public) = x25519::generate_keypair();
+        let mut peers = PeerManager::new();
+        peers.add_peer(client_public, None, Vec::new());
+
+        // The initiation itself is valid; only the key is not configured
+        let (_, peer_public) = receive_initiation(server_private, stranger_private, tai64::Tai64N::now());
+        assert_eq!(peer_public, stranger_public);
+
+        let expected = BASE64.encode(&stranger_public[..8]);
+        assert!(matches!(
+            configured_peer(&peers, &peer_public),
+            Err(ProtocolError::UnknownPeer { public_key_prefix }) if public_key_prefix == expected
+        ));
+
+        let (_, peer_public) = receive_initiation(server_private, client_private, tai64::Tai64N::now());
+        assert_eq!(lock_peer(&configured_peer(&peers, &peer_public).unwrap()).public_key, client_public);
+    }
+
+    #[test]
+    fn test_locked_peer_refuses_initiation_from_elsewhere() {
+        let server_private = x25519::generate_private_key();
+        let (client_private, client_public) = x25519::generate_keypair();
+        let endpoint: SocketAddr = "192.0.2.7:51820".parse().unwrap();
+        let elsewhere: SocketAddr = "198.51.100.9:40000".parse().unwrap();
+        let mut peer = PeerState::new(client_public, None, Vec::new());
+        peer.endpoint = Some(endpoint);
+        peer.lock_endpoint = true;
+
+        let (responder, peer_public) = receive_initiation(server_private, client_private, tai64::Tai64N::now());
+
+        assert!(matches!(
+            refuse_locked_endpoint(&peer, elsewhere, &peer_public),
+            Err(ProtocolError::EndpointLocked { from, .. }) if from == elsewhere.to_string()
+        ));
+        assert!(refuse_locked_endpoint(&peer, endpoint, &peer_public).is_ok());
+
+        // The refused initiation did not use up its timestamp
+        assert!(refuse_stale_initiation(&mut peer, &responder.initiator_timestamp, &peer_public).is_ok());
+
+        // Unlocked peers follow the initiation to its new source
+        peer.lock_endpoint = false;
+        assert!(refuse_locked_endpoint(&peer, elsewhere, &peer_public).is_ok());
+        assert!(peer.roam_to(elsewhere));
+        assert_eq!(peer.endpoint, Some(elsewhere));
+    }
+
     #[test]
     fn test_parse_ipv4_dest() {
         // Minimal valid IPv4 header with destination 192.168.1.100
@@ -829,6 +1731,29 @@ mod tests {
         assert_eq!(dest, Ipv4Addr::new(192, 168, 1, 100));
     }
 
+    #[test]
+    fn test_is_broadcast_or_multicast() {
+        let lan: Ipv4Net = "10.0.0.0/24".parse().unwrap();
+
+        assert!(is_broadcast_or_multicast(Ipv4Addr::BROADCAST, None));
+        assert!(is_broadcast_or_multicast(Ipv4Addr::new(224, 0, 0, 251), None));
+        assert!(is_broadcast_or_multicast(Ipv4Addr::new(10, 0, 0, 255), Some(lan)));
+        assert!(!is_broadcast_or_multicast(Ipv4Addr::new(10, 0, 0, 255), None));
+        assert!(!is_broadcast_or_multicast(Ipv4Addr::new(10, 0, 0, 5), Some(lan)));
+    }
+
+    #[test]
+    fn test_parse_ip_dest_ipv6() {
+        let mut packet = [0u8; 40];
+        packet[0] = 0x60;
+        packet[24] = 0xfd;
+        packet[39] = 2;
+
+        let dest = parse_ip_dest(&packet).unwrap();
+        assert_eq!(dest, "fd00::2".parse::<IpAddr>().unwrap());
+        assert!(parse_ip_dest(&packet[..30]).is_err());
+    }
+
     #[test]
     fn test_parse_ipv4_dest_too_short() {
         let packet = [0u8; 10];
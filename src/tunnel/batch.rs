@@ -0,0 +1,682 @@
+//! Batched UDP I/O for the transport socket
+//!
+//! With the `udp-batch` feature on Linux, datagrams are received with
+//! `recvmmsg` and sent with `sendmmsg`, and UDP GSO/GRO are used where the
+//! kernel supports them. Everywhere else the same API falls back to one
+//! `recv_from`/`send_to` per packet.
+//!
+//! When [`pktinfo::enable`] is on for the socket, each datagram also records
+//! the local address it arrived on, and sends can name the source address per
+//! destination (see [`send_batch_from`]).
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+
+use super::pktinfo::{self, ReplySources};
+
+/// Maximum datagrams moved per syscall
+#[cfg(all(target_os = "linux", feature = "udp-batch"))]
+pub const BATCH_SIZE: usize = 32;
+
+/// Maximum datagrams moved per syscall (batching disabled)
+#[cfg(not(all(target_os = "linux", feature = "udp-batch")))]
+pub const BATCH_SIZE: usize = 1;
+
+/// Receive buffer size (large enough for a GRO-coalesced datagram)
+const RECV_BUFFER_SIZE: usize = 65535;
+
+/// Offloads enabled on a socket by [`configure_socket`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Offload {
+    /// UDP_SEGMENT: runs of equal-sized datagrams to one peer go out as one buffer
+    pub gso: bool,
+    /// UDP_GRO: the kernel may coalesce datagrams from one peer into one buffer
+    pub gro: bool,
+}
+
+/// Enable the UDP offloads the kernel supports on `socket`
+///
+/// Only pass `allow_gro` when every read on the socket goes through
+/// [`RecvBatch`], since a plain `recv_from` cannot split coalesced datagrams.
+pub fn configure_socket(socket: &UdpSocket, allow_gro: bool) -> Offload {
+    #[cfg(all(target_os = "linux", feature = "udp-batch"))]
+    {
+        let offload = sys::configure(socket, allow_gro);
+        tracing::debug!(
+            "UDP batching enabled (batch {}, GSO: {}, GRO: {})",
+            BATCH_SIZE,
+            offload.gso,
+            offload.gro
+        );
+        offload
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "udp-batch")))]
+    {
+        let _ = (socket, allow_gro);
+        Offload::default()
+    }
+}
+
+/// Location of one received datagram inside a [`RecvBatch`]
+#[derive(Debug, Clone, Copy)]
+struct Received {
+    buf: usize,
+    start: usize,
+    end: usize,
+    from: SocketAddr,
+    local: Option<IpAddr>,
+}
+
+/// Reusable buffers for receiving up to [`BATCH_SIZE`] datagrams per syscall
+pub struct RecvBatch {
+    bufs: Vec<Vec<u8>>,
+    received: Vec<Received>,
+}
+
+impl RecvBatch {
+    /// Allocate receive buffers for one batch
+    pub fn new() -> Self {
+        Self {
+            bufs: vec![vec![0u8; RECV_BUFFER_SIZE]; BATCH_SIZE],
+            received: Vec::with_capacity(BATCH_SIZE),
+        }
+    }
+
+    /// Wait for at least one datagram, then take as many as are already queued
+    ///
+    /// Returns the number of datagrams, with GRO-coalesced buffers split back
+    /// into the original datagrams. Cancel-safe: nothing is consumed until the
+    /// socket is readable. A pending socket error (e.g. ICMP port unreachable
+    /// on a connected socket) is returned instead of waiting for data.
+    pub async fn recv(&mut self, socket: &UdpSocket) -> io::Result<usize> {
+        self.received.clear();
+
+        #[cfg(all(target_os = "linux", feature = "udp-batch"))]
+        {
+            let (bufs, received) = (&mut self.bufs, &mut self.received);
+            socket
+                .async_io(Interest::READABLE | Interest::ERROR, || {
+                    sys::recv_mmsg(socket, bufs, received)
+                })
+                .await?;
+        }
+
+        #[cfg(all(target_os = "linux", not(feature = "udp-batch")))]
+        {
+            let buf = &mut self.bufs[0];
+            let (len, from, local) = socket
+                .async_io(Interest::READABLE | Interest::ERROR, || {
+                    pktinfo::sys::try_recv_from(socket, buf)
+                })
+                .await?;
+            push_segments(&mut self.received, 0, len, len, from, local);
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let (len, from) = recv_from(socket, &mut self.bufs[0]).await?;
+            push_segments(&mut self.received, 0, len, len, from, None);
+        }
+
+        Ok(self.received.len())
+    }
+
+    /// Datagram `index` (and its source) from the last [`RecvBatch::recv`]
+    pub fn get(&self, index: usize) -> (&[u8], SocketAddr) {
+        let r = self.received[index];
+        (&self.bufs[r.buf][r.start..r.end], r.from)
+    }
+
+    /// Local address datagram `index` was sent to (None without pktinfo)
+    pub fn local(&self, index: usize) -> Option<IpAddr> {
+        self.received[index].local
+    }
+}
+
+impl Default for RecvBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reusable buffers for the datagrams of one [`send_batch`]
+///
+/// Each slot keeps its allocation between batches, so encrypting into a
+/// batch stops allocating once the slots have grown to the packet sizes.
+pub struct SendBatch {
+    packets: Vec<(Vec<u8>, SocketAddr)>,
+    len: usize,
+}
+
+impl SendBatch {
+    /// Empty batch; slots are allocated as datagrams are added
+    pub fn new() -> Self {
+        Self {
+            packets: Vec::with_capacity(BATCH_SIZE),
+            len: 0,
+        }
+    }
+
+    /// Drop the datagrams of the last batch, keeping their buffers
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Add a datagram to `to`, written by `fill` into a buffer of `size` bytes
+    ///
+    /// `fill` returns how many bytes it wrote. On error nothing is added.
+    pub fn push_with<E>(
+        &mut self,
+        size: usize,
+        to: SocketAddr,
+        fill: impl FnOnce(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, E> {
+        if self.len == self.packets.len() {
+            self.packets.push((Vec::new(), to));
+        }
+        let (buf, dest) = &mut self.packets[self.len];
+        buf.resize(size, 0);
+        let len = fill(buf)?;
+        buf.truncate(len);
+        *dest = to;
+        self.len += 1;
+        Ok(len)
+    }
+
+    /// Datagrams added since the last [`SendBatch::clear`]
+    pub fn packets(&self) -> &[(Vec<u8>, SocketAddr)] {
+        &self.packets[..self.len]
+    }
+
+    /// Number of datagrams in the batch
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if nothing was added since the last [`SendBatch::clear`]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for SendBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `recv_from` that also wakes up for a pending socket error
+///
+/// Tokio only wakes readers on readable events, but an ICMP error queued on a
+/// connected socket raises EPOLLERR alone, so a plain `recv_from` would sleep
+/// until the next datagram instead of reporting ECONNREFUSED.
+pub async fn recv_from(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+    socket
+        .async_io(Interest::READABLE | Interest::ERROR, || {
+            if let Some(e) = socket.take_error()? {
+                return Err(e);
+            }
+            socket.try_recv_from(buf)
+        })
+        .await
+}
+
+/// Record the datagrams in `len` bytes of buffer `buf`, `segment` bytes each
+///
+/// Without GRO `segment == len` and this records a single datagram; with GRO
+/// every datagram but the last is exactly `segment` bytes.
+fn push_segments(
+    received: &mut Vec<Received>,
+    buf: usize,
+    len: usize,
+    segment: usize,
+    from: SocketAddr,
+    local: Option<IpAddr>,
+) {
+    let segment = segment.max(1);
+    let mut start = 0;
+    while start < len {
+        let end = (start + segment).min(len);
+        received.push(Received { buf, start, end, from, local });
+        start = end;
+    }
+}
+
+/// Send every datagram, batching where the platform allows
+///
+/// A datagram that fails to send does not stop the rest; the last error is
+/// returned. If the NIC rejects GSO, it is disabled in `offload` and the
+/// affected datagrams are resent one by one.
+pub async fn send_batch(
+    socket: &UdpSocket,
+    offload: &mut Offload,
+    packets: &[(Vec<u8>, SocketAddr)],
+) -> io::Result<()> {
+    send_batch_from(socket, offload, packets, &ReplySources::new()).await
+}
+
+/// [`send_batch`], sending to each destination in `sources` from the local
+/// address recorded for it
+pub async fn send_batch_from(
+    socket: &UdpSocket,
+    offload: &mut Offload,
+    packets: &[(Vec<u8>, SocketAddr)],
+    sources: &ReplySources,
+) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "udp-batch"))]
+    if packets.len() > 1 {
+        return sys::send_all(socket, offload, packets, sources).await;
+    }
+
+    let _ = &offload;
+    send_each(socket, packets, sources).await
+}
+
+/// Single-packet send path
+async fn send_each(
+    socket: &UdpSocket,
+    packets: &[(Vec<u8>, SocketAddr)],
+    sources: &ReplySources,
+) -> io::Result<()> {
+    let mut result = Ok(());
+    for (packet, to) in packets {
+        if let Err(e) = pktinfo::send_from(socket, packet, *to, sources.get(to).copied()).await {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+#[cfg(all(target_os = "linux", feature = "udp-batch"))]
+mod sys {
+    use std::borrow::Cow;
+    use std::io;
+    use std::mem::{size_of, zeroed};
+    use std::os::fd::AsRawFd;
+
+    use std::net::IpAddr;
+
+    use socket2::SockAddr;
+    use tokio::io::Interest;
+    use tokio::net::UdpSocket;
+
+    use super::pktinfo::{self, ReplySources};
+    use super::{push_segments, send_each, Offload, Received, BATCH_SIZE};
+
+    /// Most segments the kernel accepts in one GSO send
+    const MAX_GSO_SEGMENTS: usize = 64;
+
+    /// Largest GSO buffer (stays under the 64 KiB IP datagram limit)
+    const MAX_GSO_BYTES: usize = 65000;
+
+    /// Control message space for one `int`/`u16` option
+    const OPTION_SPACE: usize = 24;
+
+    /// Room for a GRO/GSO option plus a pktinfo message, aligned for `cmsghdr`
+    const CMSG_BUF_SIZE: usize = OPTION_SPACE + pktinfo::sys::CMSG_SPACE;
+
+    #[repr(C, align(8))]
+    #[derive(Clone, Copy)]
+    struct CmsgBuf([u8; CMSG_BUF_SIZE]);
+
+    /// One `sendmmsg` entry: a datagram, or a GSO run of equal-sized datagrams
+    struct Message<'a> {
+        data: Cow<'a, [u8]>,
+        to: SockAddr,
+        segment: Option<u16>,
+        from: Option<IpAddr>,
+        packets: &'a [(Vec<u8>, std::net::SocketAddr)],
+    }
+
+    pub(super) fn configure(socket: &UdpSocket, allow_gro: bool) -> Offload {
+        let fd = socket.as_raw_fd();
+
+        // getsockopt(UDP_SEGMENT) only succeeds on kernels with UDP GSO (4.18+)
+        let mut size: libc::c_int = 0;
+        let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+        let gso = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_UDP,
+                libc::UDP_SEGMENT,
+                &mut size as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        } == 0;
+
+        let gro = allow_gro && {
+            let on: libc::c_int = 1;
+            let result = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_UDP,
+                    libc::UDP_GRO,
+                    &on as *const libc::c_int as *const libc::c_void,
+                    size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            result == 0
+        };
+
+        Offload { gso, gro }
+    }
+
+    pub(super) fn recv_mmsg(
+        socket: &UdpSocket,
+        bufs: &mut [Vec<u8>],
+        received: &mut Vec<Received>,
+    ) -> io::Result<()> {
+        let count = bufs.len().min(BATCH_SIZE);
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { zeroed() };
+        let mut names: [libc::sockaddr_storage; BATCH_SIZE] = unsafe { zeroed() };
+        let mut cmsgs = [CmsgBuf([0u8; CMSG_BUF_SIZE]); BATCH_SIZE];
+        let mut hdrs: [libc::mmsghdr; BATCH_SIZE] = unsafe { zeroed() };
+
+        for i in 0..count {
+            iovecs[i] = libc::iovec {
+                iov_base: bufs[i].as_mut_ptr() as *mut libc::c_void,
+                iov_len: bufs[i].len(),
+            };
+            let hdr = &mut hdrs[i].msg_hdr;
+            hdr.msg_name = &mut names[i] as *mut libc::sockaddr_storage as *mut libc::c_void;
+            hdr.msg_namelen = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            hdr.msg_iov = &mut iovecs[i];
+            hdr.msg_iovlen = 1;
+            hdr.msg_control = cmsgs[i].0.as_mut_ptr() as *mut libc::c_void;
+            hdr.msg_controllen = size_of::<CmsgBuf>() as _;
+        }
+
+        let ret = unsafe {
+            libc::recvmmsg(
+                socket.as_raw_fd(),
+                hdrs.as_mut_ptr(),
+                count as libc::c_uint,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        for (i, hdr) in hdrs.iter().enumerate().take(ret as usize) {
+            let len = hdr.msg_len as usize;
+            let from = unsafe { SockAddr::new(names[i], hdr.msg_hdr.msg_namelen) };
+            let Some(from) = from.as_socket() else {
+                continue;
+            };
+            let segment = gro_segment_size(&hdr.msg_hdr).unwrap_or(len);
+            let local = pktinfo::sys::local_addr(&hdr.msg_hdr);
+            push_segments(received, i, len, segment, from, local);
+        }
+
+        Ok(())
+    }
+
+    /// Segment size the kernel reports for a GRO-coalesced buffer
+    fn gro_segment_size(hdr: &libc::msghdr) -> Option<usize> {
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(hdr);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == libc::UDP_GRO {
+                    let size = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                    return usize::try_from(size).ok().filter(|s| *s > 0);
+                }
+                cmsg = libc::CMSG_NXTHDR(hdr, cmsg);
+            }
+        }
+        None
+    }
+
+    pub(super) async fn send_all(
+        socket: &UdpSocket,
+        offload: &mut Offload,
+        packets: &[(Vec<u8>, std::net::SocketAddr)],
+        sources: &ReplySources,
+    ) -> io::Result<()> {
+        let messages = build_messages(packets, offload.gso, sources);
+        let mut result = Ok(());
+        let mut pos = 0;
+
+        while pos < messages.len() {
+            let chunk = &messages[pos..];
+            match socket
+                .async_io(Interest::WRITABLE, || send_mmsg(socket, chunk))
+                .await
+            {
+                Ok(sent) => pos += sent.max(1),
+                Err(e) => {
+                    // sendmmsg only fails outright on the first message of the chunk
+                    let failed = &messages[pos];
+                    if failed.segment.is_some() && e.raw_os_error() == Some(libc::EIO) {
+                        tracing::warn!("UDP GSO rejected by the network device, disabling: {}", e);
+                        offload.gso = false;
+                        let rest: Vec<_> = messages[pos..].iter().flat_map(|m| m.packets.iter().cloned()).collect();
+                        return send_each(socket, &rest, sources).await;
+                    }
+                    if failed.from.is_some() && pktinfo::sys::source_unusable(&e) {
+                        // The learned source address is gone; let send_each fall back
+                        if let Err(e) = send_each(socket, failed.packets, sources).await {
+                            result = Err(e);
+                        }
+                        pos += 1;
+                        continue;
+                    }
+                    result = Err(e);
+                    pos += 1;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Group consecutive datagrams into `sendmmsg` entries
+    ///
+    /// With GSO, a run to the same destination where every datagram but the
+    /// last has the same length becomes a single entry.
+    fn build_messages<'a>(
+        packets: &'a [(Vec<u8>, std::net::SocketAddr)],
+        gso: bool,
+        sources: &ReplySources,
+    ) -> Vec<Message<'a>> {
+        let mut messages = Vec::with_capacity(packets.len());
+        let mut i = 0;
+
+        while i < packets.len() {
+            let (first, to) = &packets[i];
+            let mut end = i + 1;
+            let mut total = first.len();
+
+            if gso {
+                while end < packets.len()
+                    && end - i < MAX_GSO_SEGMENTS
+                    && packets[end].1 == *to
+                    && packets[end].0.len() <= first.len()
+                    && packets[end - 1].0.len() == first.len()
+                    && total + packets[end].0.len() <= MAX_GSO_BYTES
+                {
+                    total += packets[end].0.len();
+                    end += 1;
+                }
+            }
+
+            let run = &packets[i..end];
+            let (data, segment) = if run.len() == 1 {
+                (Cow::Borrowed(first.as_slice()), None)
+            } else {
+                let data: Vec<u8> = run.iter().flat_map(|(p, _)| p.iter().copied()).collect();
+                (Cow::Owned(data), Some(first.len() as u16))
+            };
+
+            messages.push(Message {
+                data,
+                to: SockAddr::from(*to),
+                segment,
+                from: sources.get(to).copied(),
+                packets: run,
+            });
+            i = end;
+        }
+
+        messages
+    }
+
+    fn send_mmsg(socket: &UdpSocket, messages: &[Message<'_>]) -> io::Result<usize> {
+        let count = messages.len().min(BATCH_SIZE);
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { zeroed() };
+        let mut cmsgs = [CmsgBuf([0u8; CMSG_BUF_SIZE]); BATCH_SIZE];
+        let mut hdrs: [libc::mmsghdr; BATCH_SIZE] = unsafe { zeroed() };
+
+        for (i, message) in messages.iter().take(count).enumerate() {
+            iovecs[i] = libc::iovec {
+                iov_base: message.data.as_ptr() as *mut libc::c_void,
+                iov_len: message.data.len(),
+            };
+            let hdr = &mut hdrs[i].msg_hdr;
+            hdr.msg_name = message.to.as_ptr() as *mut libc::c_void;
+            hdr.msg_namelen = message.to.len();
+            hdr.msg_iov = &mut iovecs[i];
+            hdr.msg_iovlen = 1;
+
+            if message.segment.is_some() || message.from.is_some() {
+                hdr.msg_control = cmsgs[i].0.as_mut_ptr() as *mut libc::c_void;
+            }
+            if let Some(segment) = message.segment {
+                hdr.msg_controllen = unsafe { libc::CMSG_SPACE(size_of::<u16>() as u32) } as _;
+                unsafe {
+                    let cmsg = libc::CMSG_FIRSTHDR(hdr);
+                    (*cmsg).cmsg_level = libc::SOL_UDP;
+                    (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<u16>() as u32) as _;
+                    std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment);
+                }
+            }
+            if let Some(from) = message.from {
+                pktinfo::sys::write_cmsg(hdr, from);
+            }
+        }
+
+        let ret = unsafe {
+            libc::sendmmsg(socket.as_raw_fd(), hdrs.as_mut_ptr(), count as libc::c_uint, 0)
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_segments_splits_gro_buffer() {
+        let from: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+        let mut received = Vec::new();
+
+        // No GRO: one datagram
+        push_segments(&mut received, 0, 148, 148, from, None);
+        assert_eq!(received.len(), 1);
+        assert_eq!((received[0].start, received[0].end), (0, 148));
+
+        // GRO: three 100-byte datagrams and a short tail
+        received.clear();
+        push_segments(&mut received, 2, 340, 100, from, None);
+        let spans: Vec<_> = received.iter().map(|r| (r.buf, r.start, r.end)).collect();
+        assert_eq!(spans, vec![(2, 0, 100), (2, 100, 200), (2, 200, 300), (2, 300, 340)]);
+    }
+
+    #[test]
+    fn test_send_batch_reuses_buffers() {
+        let to: SocketAddr = "127.0.0.1:51820".parse().unwrap();
+        let write = |data: &'static [u8]| {
+            move |buf: &mut [u8]| -> Result<usize, ()> {
+                buf[..data.len()].copy_from_slice(data);
+                Ok(data.len())
+            }
+        };
+        let mut batch = SendBatch::new();
+        batch.push_with(64, to, write(b"abc")).unwrap();
+        assert!(batch.push_with(64, to, |_| Err(())).is_err());
+        assert_eq!(batch.packets(), &[(b"abc".to_vec(), to)]);
+
+        // A new batch writes into the same allocation
+        let slot = batch.packets()[0].0.as_ptr();
+        batch.clear();
+        assert!(batch.is_empty());
+        batch.push_with(32, to, write(b"de")).unwrap();
+        assert_eq!(batch.packets(), &[(b"de".to_vec(), to)]);
+        assert_eq!(batch.packets()[0].0.as_ptr(), slot);
+    }
+
+    #[tokio::test]
+    async fn test_batch_round_trip() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        configure_socket(&receiver, true);
+        let mut offload = configure_socket(&sender, false);
+        let to = receiver.local_addr().unwrap();
+
+        // Equal-sized run (a GSO candidate) followed by a short datagram
+        let mut packets: Vec<(Vec<u8>, SocketAddr)> =
+            (0..5u8).map(|i| (vec![i; 200], to)).collect();
+        packets.push((vec![9u8; 32], to));
+
+        send_batch(&sender, &mut offload, &packets).await.unwrap();
+
+        let mut batch = RecvBatch::new();
+        let mut got = Vec::new();
+        while got.len() < packets.len() {
+            let count = tokio::time::timeout(std::time::Duration::from_secs(2), batch.recv(&receiver))
+                .await
+                .expect("timed out waiting for datagrams")
+                .unwrap();
+            for i in 0..count {
+                let (data, from) = batch.get(i);
+                assert_eq!(from, sender.local_addr().unwrap());
+                got.push(data.to_vec());
+            }
+        }
+
+        let sent: Vec<_> = packets.into_iter().map(|(p, _)| p).collect();
+        assert_eq!(got, sent);
+    }
+
+    #[tokio::test]
+    async fn test_burst_is_fully_drained() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        configure_socket(&receiver, true);
+        let to = receiver.local_addr().unwrap();
+
+        // Several batches' worth, all queued before the first read
+        let burst = 4 * BATCH_SIZE + 3;
+        let sent: Vec<Vec<u8>> = (0..burst).map(|i| (i as u32).to_be_bytes().repeat(1 + i % 8)).collect();
+        for packet in &sent {
+            sender.send_to(packet, to).await.unwrap();
+        }
+
+        let mut batch = RecvBatch::new();
+        let mut got = Vec::new();
+        let mut wakeups = 0;
+        while got.len() < burst {
+            let count = tokio::time::timeout(std::time::Duration::from_secs(2), batch.recv(&receiver))
+                .await
+                .expect("timed out waiting for datagrams")
+                .unwrap();
+            wakeups += 1;
+            got.extend((0..count).map(|i| batch.get(i).0.to_vec()));
+        }
+
+        assert_eq!(got, sent);
+        if BATCH_SIZE > 1 {
+            assert!(wakeups < burst, "{} datagrams took {} reads", burst, wakeups);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Pointing the system resolver at the tunnel's DNS servers
+//!
+//! The `DNS` servers from `[Interface]` are installed for the TUN interface
+//! when the client connects and removed again on cleanup. Which tool does the
+//! work depends on the platform:
+//!
+//! - Linux: `resolvectl` when systemd-resolved runs, `resolvconf` otherwise
+//! - macOS: `scutil`, as a resolver for the tunnel's own network service
+//! - Windows: `netsh` on the tunnel adapter
+//!
+//! The backend is recorded in the route state file, so settings left behind
+//! by a crash are undone on the next start (see [`revert_blocking`]).
+
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command as StdCommand, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::error::{MinnowVpnError, TunnelError};
+
+/// How DNS servers were installed for the interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsBackend {
+    /// Per-link servers in systemd-resolved, routing every domain (`~.`)
+    Resolvectl,
+    /// An exclusive `resolvconf` record named after the interface
+    Resolvconf,
+    /// A `State:/Network/Service/.../DNS` key in the macOS dynamic store
+    Scutil,
+    /// Static servers on the Windows adapter
+    Netsh,
+}
+
+/// One command to run, with optional input on stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DnsCommand {
+    program: &'static str,
+    args: Vec<String>,
+    stdin: Option<String>,
+}
+
+impl DnsCommand {
+    fn new(program: &'static str, args: &[&str]) -> Self {
+        Self {
+            program,
+            args: args.iter().map(|a| a.to_string()).collect(),
+            stdin: None,
+        }
+    }
+
+    fn with_stdin(mut self, stdin: String) -> Self {
+        self.stdin = Some(stdin);
+        self
+    }
+}
+
+/// The backend for this system (None where none is supported)
+pub fn detect_backend() -> Option<DnsBackend> {
+    #[cfg(target_os = "linux")]
+    {
+        if std::path::Path::new("/run/systemd/resolve").is_dir() {
+            return Some(DnsBackend::Resolvectl);
+        }
+        Some(DnsBackend::Resolvconf)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Some(DnsBackend::Scutil)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        Some(DnsBackend::Netsh)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+/// Install `servers` as the resolvers for `interface`
+///
+/// Returns the backend used, which [`revert`] needs to undo it.
+pub async fn apply(interface: &str, servers: &[IpAddr]) -> Result<DnsBackend, MinnowVpnError> {
+    let backend = detect_backend().ok_or_else(|| TunnelError::UnsupportedPlatform {
+        platform: format!("DNS configuration on {}", std::env::consts::OS),
+    })?;
+
+    for command in apply_commands(backend, interface, servers) {
+        if let Err(reason) = run(&command).await {
+            // Don't leave a half-applied configuration behind
+            for undo in revert_commands(backend, interface) {
+                let _ = run(&undo).await;
+            }
+            return Err(TunnelError::DnsSetupFailed { reason }.into());
+        }
+    }
+    Ok(backend)
+}
+
+/// Remove the resolvers [`apply`] installed for `interface`
+pub async fn revert(interface: &str, backend: DnsBackend) -> Result<(), MinnowVpnError> {
+    let mut result = Ok(());
+    for command in revert_commands(backend, interface) {
+        if let Err(reason) = run(&command).await {
+            result = Err(TunnelError::DnsSetupFailed { reason }.into());
+        }
+    }
+    result
+}
+
+/// [`revert`] for crash recovery at startup, before the async runtime is needed
+pub fn revert_blocking(interface: &str, backend: DnsBackend) -> bool {
+    let mut ok = true;
+    for command in revert_commands(backend, interface) {
+        ok &= run_blocking(&command).is_ok();
+    }
+    ok
+}
+
+/// Commands that install `servers` for `interface`
+fn apply_commands(backend: DnsBackend, interface: &str, servers: &[IpAddr]) -> Vec<DnsCommand> {
+    let addresses: Vec<String> = servers.iter().map(|s| s.to_string()).collect();
+
+    match backend {
+        DnsBackend::Resolvectl => {
+            let mut dns = vec!["dns", interface];
+            dns.extend(addresses.iter().map(String::as_str));
+            vec![
+                DnsCommand::new("resolvectl", &dns),
+                DnsCommand::new("resolvectl", &["domain", interface, "~."]),
+            ]
+        }
+        DnsBackend::Resolvconf => {
+            let records: String = addresses.iter().map(|a| format!("nameserver {}\n", a)).collect();
+            vec![DnsCommand::new("resolvconf", &["-a", interface, "-m", "0", "-x"]).with_stdin(records)]
+        }
+        DnsBackend::Scutil => {
+            let script = format!(
+                "d.init\nd.add ServerAddresses * {}\nd.add SupplementalMatchDomains * \"\"\nset {}\nquit\n",
+                addresses.join(" "),
+                scutil_key(interface)
+            );
+            vec![DnsCommand::new("scutil", &[]).with_stdin(script)]
+        }
+        DnsBackend::Netsh => {
+            let mut commands = Vec::new();
+            for family in ["ipv4", "ipv6"] {
+                let name = format!("name={}", interface);
+                let family_servers = addresses
+                    .iter()
+                    .zip(servers)
+                    .filter(|(_, ip)| ip.is_ipv6() == (family == "ipv6"))
+                    .map(|(a, _)| a);
+                for (i, address) in family_servers.enumerate() {
+                    let command = if i == 0 {
+                        vec!["interface", family, "set", "dnsservers", &name, "static", address, "primary", "validate=no"]
+                    } else {
+                        vec!["interface", family, "add", "dnsservers", &name, address, "validate=no"]
+                    };
+                    commands.push(DnsCommand::new("netsh", &command));
+                }
+            }
+            commands
+        }
+    }
+}
+
+/// Commands that remove what [`apply_commands`] installed
+fn revert_commands(backend: DnsBackend, interface: &str) -> Vec<DnsCommand> {
+    match backend {
+        DnsBackend::Resolvectl => vec![DnsCommand::new("resolvectl", &["revert", interface])],
+        DnsBackend::Resolvconf => vec![DnsCommand::new("resolvconf", &["-d", interface])],
+        DnsBackend::Scutil => {
+            vec![DnsCommand::new("scutil", &[]).with_stdin(format!("remove {}\nquit\n", scutil_key(interface)))]
+        }
+        DnsBackend::Netsh => ["ipv4", "ipv6"]
+            .iter()
+            .map(|family| {
+                let name = format!("name={}", interface);
+                DnsCommand::new("netsh", &["interface", family, "set", "dnsservers", &name, "source=dhcp"])
+            })
+            .collect(),
+    }
+}
+
+/// Dynamic store key holding the tunnel's resolver on macOS
+fn scutil_key(interface: &str) -> String {
+    format!("State:/Network/Service/MinnowVPN-{}/DNS", interface)
+}
+
+/// Run a command, returning a description of the failure
+async fn run(command: &DnsCommand) -> Result<(), String> {
+    let mut child = Command::new(command.program)
+        .args(&command.args)
+        .stdin(if command.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", command.program, e))?;
+
+    if let (Some(input), Some(mut stdin)) = (&command.stdin, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("{}: {}", command.program, e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("{}: {}", command.program, e))?;
+    check(command, output.status, &output.stderr)
+}
+
+/// Blocking [`run`]
+fn run_blocking(command: &DnsCommand) -> Result<(), String> {
+    let mut child = StdCommand::new(command.program)
+        .args(&command.args)
+        .stdin(if command.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", command.program, e))?;
+
+    if let (Some(input), Some(mut stdin)) = (&command.stdin, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("{}: {}", command.program, e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{}: {}", command.program, e))?;
+    check(command, output.status, &output.stderr)
+}
+
+fn check(command: &DnsCommand, status: std::process::ExitStatus, stderr: &[u8]) -> Result<(), String> {
+    if status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(stderr);
+    Err(format!("{} {} exited with {}: {}", command.program, command.args.join(" "), status, stderr.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers() -> Vec<IpAddr> {
+        vec!["10.0.0.1".parse().unwrap(), "fd00::1".parse().unwrap(), "1.1.1.1".parse().unwrap()]
+    }
+
+    #[test]
+    fn test_linux_commands() {
+        let commands = apply_commands(DnsBackend::Resolvectl, "tun0", &servers());
+        assert_eq!(commands[0].args, ["dns", "tun0", "10.0.0.1", "fd00::1", "1.1.1.1"]);
+        assert_eq!(commands[1].args, ["domain", "tun0", "~."]);
+        assert_eq!(revert_commands(DnsBackend::Resolvectl, "tun0")[0].args, ["revert", "tun0"]);
+
+        let commands = apply_commands(DnsBackend::Resolvconf, "tun0", &servers());
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].stdin.as_deref(),
+            Some("nameserver 10.0.0.1\nnameserver fd00::1\nnameserver 1.1.1.1\n")
+        );
+        assert_eq!(revert_commands(DnsBackend::Resolvconf, "tun0")[0].args, ["-d", "tun0"]);
+    }
+
+    #[test]
+    fn test_macos_and_windows_commands() {
+        let commands = apply_commands(DnsBackend::Scutil, "utun5", &servers());
+        let script = commands[0].stdin.as_deref().unwrap();
+        assert!(script.contains("d.add ServerAddresses * 10.0.0.1 fd00::1 1.1.1.1\n"));
+        assert!(script.contains("set State:/Network/Service/MinnowVPN-utun5/DNS\n"));
+        let revert = revert_commands(DnsBackend::Scutil, "utun5");
+        assert_eq!(revert[0].stdin.as_deref(), Some("remove State:/Network/Service/MinnowVPN-utun5/DNS\nquit\n"));
+
+        // One primary server per family, the rest appended in order
+        let commands = apply_commands(DnsBackend::Netsh, "MinnowVPN", &servers());
+        let lines: Vec<String> = commands.iter().map(|c| c.args.join(" ")).collect();
+        assert_eq!(
+            lines,
+            [
+                "interface ipv4 set dnsservers name=MinnowVPN static 10.0.0.1 primary validate=no",
+                "interface ipv4 add dnsservers name=MinnowVPN 1.1.1.1 validate=no",
+                "interface ipv6 set dnsservers name=MinnowVPN static fd00::1 primary validate=no",
+            ]
+        );
+        assert_eq!(revert_commands(DnsBackend::Netsh, "MinnowVPN").len(), 2);
+    }
+
+    #[test]
+    fn test_backend_in_state_file() {
+        assert_eq!(serde_json::to_string(&DnsBackend::Resolvectl).unwrap(), "\"resolvectl\"");
+        let parsed: DnsBackend = serde_json::from_str("\"netsh\"").unwrap();
+        assert_eq!(parsed, DnsBackend::Netsh);
+    }
+}
@@ -3,18 +3,28 @@
 //! Provides cross-platform TUN device support using the tun-rs crate.
 //! Supports macOS (utun), Linux (/dev/net/tun), and Windows (Wintun).
 
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr};
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::process::Command as StdCommand;
 
-use ipnet::Ipv4Net;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 use tun_rs::{AsyncDevice, DeviceBuilder};
 
+use crate::config::RouteTable;
 use crate::error::{MinnowVpnError, TunnelError};
 
+use self::dns::DnsBackend;
+
+pub mod batch;
+pub mod dns;
+pub mod netns;
+pub mod netsim;
+pub mod pktinfo;
+pub mod pmtu;
+
 /// Persistent state for route cleanup after crashes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteState {
@@ -29,6 +39,15 @@ pub struct RouteState {
     /// Default gateway (for endpoint bypass cleanup)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_gateway: Option<String>,
+    /// Firewall mark used for the policy routing rule (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<u32>,
+    /// Numbered routing table the routes are in (None = main table; Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<u32>,
+    /// How the DNS servers were installed for the interface (None = untouched)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsBackend>,
     /// Routes added through the tunnel (CIDR notation)
     pub routes: Vec<String>,
     /// Timestamp when state was saved
@@ -56,7 +75,7 @@ fn save_route_state(state: &RouteState) -> Result<(), std::io::Error> {
     let path = get_state_file_path();
     let json = serde_json::to_string_pretty(state)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-    std::fs::write(&path, json)?;
+    crate::config::write_atomic(&path, json.as_bytes(), None)?;
     tracing::debug!("Saved route state to {:?}", path);
     Ok(())
 }
@@ -126,6 +145,126 @@ fn interface_exists(interface: &str) -> bool {
     }
 }
 
+/// A tracked route and whether it is still installed on the system
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedRoute {
+    /// Network in CIDR notation
+    pub network: String,
+    /// Present in the system routing table (None = could not check on this platform)
+    pub present: Option<bool>,
+}
+
+/// Debug view of what MinnowVPN believes it installed, checked against the system
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteReport {
+    /// Route state file contents (None when no routes are tracked)
+    pub state: Option<RouteState>,
+    /// Whether the tracked TUN interface still exists
+    pub interface_exists: bool,
+    /// Routes through the tunnel
+    pub routes: Vec<TrackedRoute>,
+    /// Endpoint bypass route via the physical default gateway
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_bypass: Option<TrackedRoute>,
+}
+
+/// Report tracked TUN/route state (from the route state file) and check it is still live
+///
+/// The state file is rewritten by [`RouteManager`] on every change, so it mirrors
+/// the routes of the running client or server. Runs external commands; call from
+/// a blocking context.
+pub fn route_report() -> RouteReport {
+    build_route_report(load_route_state())
+}
+
+/// Build a [`RouteReport`] from a loaded route state
+fn build_route_report(state: Option<RouteState>) -> RouteReport {
+    let Some(state) = state else {
+        return RouteReport {
+            state: None,
+            interface_exists: false,
+            routes: Vec::new(),
+            endpoint_bypass: None,
+        };
+    };
+
+    let routes = state
+        .routes
+        .iter()
+        .map(|network| TrackedRoute {
+            network: network.clone(),
+            present: route_exists(network, Some(&state.interface), state.table),
+        })
+        .collect();
+
+    let endpoint_bypass = state.endpoint_bypass.as_ref().map(|endpoint| {
+        let network = bypass_network(endpoint);
+        let present = route_exists(&network, None, None);
+        TrackedRoute { network, present }
+    });
+
+    RouteReport {
+        interface_exists: interface_exists(&state.interface),
+        routes,
+        endpoint_bypass,
+        state: Some(state),
+    }
+}
+
+/// Check if an exact route is in the system routing table (optionally via `interface`)
+///
+/// Returns None when the check is not supported or the route tool could not run.
+fn route_exists(network: &str, interface: Option<&str>, table: Option<u32>) -> Option<bool> {
+    let is_v6 = network.contains(':');
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut args = Vec::new();
+        if is_v6 {
+            args.push("-6");
+        }
+        args.extend(["route", "show", "exact", network]);
+        if let Some(interface) = interface {
+            args.extend(["dev", interface]);
+        }
+        let table = table.map(|t| t.to_string());
+        if let Some(table) = &table {
+            args.extend(["table", table.as_str()]);
+        }
+        let output = StdCommand::new("ip").args(&args).output().ok()?;
+        if !output.status.success() {
+            return Some(false);
+        }
+        Some(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = table;
+        let family = if is_v6 { "-inet6" } else { "-inet" };
+        let output = StdCommand::new("route")
+            .args(["-n", "get", family, network])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return Some(false);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Some(match interface {
+            Some(interface) => stdout
+                .lines()
+                .any(|l| l.trim() == format!("interface: {}", interface)),
+            None => true,
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = (network, interface, table, is_v6);
+        None
+    }
+}
+
 /// Clean up routes from a previous crashed session using the state file.
 /// This is the safe replacement for the old netstat-parsing approach.
 pub fn cleanup_from_state_file() {
@@ -163,7 +302,7 @@ pub fn cleanup_from_state_file() {
 
     // Clean up regular routes
     for route in &state.routes {
-        if cleanup_single_route(route, &state.interface, state.interface_index) {
+        if cleanup_single_route(route, &state.interface, state.interface_index, state.table) {
             cleaned += 1;
         } else {
             failed += 1;
@@ -179,6 +318,20 @@ pub fn cleanup_from_state_file() {
         }
     }
 
+    // Clean up fwmark policy rule if present
+    if let Some(mark) = state.fwmark {
+        if cleanup_fwmark_rule(mark) {
+            tracing::debug!("Cleaned up fwmark rule for mark {:#x}", mark);
+        }
+    }
+
+    // Give the system its own resolvers back
+    if let Some(backend) = state.dns {
+        if dns::revert_blocking(&state.interface, backend) {
+            tracing::info!("Restored DNS settings changed for {}", state.interface);
+        }
+    }
+
     // Delete the state file after cleanup
     delete_route_state();
 
@@ -190,12 +343,13 @@ pub fn cleanup_from_state_file() {
 }
 
 /// Clean up a single route (platform-specific)
-fn cleanup_single_route(route: &str, interface: &str, _interface_index: Option<u32>) -> bool {
+fn cleanup_single_route(route: &str, interface: &str, _interface_index: Option<u32>, _table: Option<u32>) -> bool {
     #[cfg(target_os = "macos")]
     {
         // Use -interface to target the specific route
+        let family = if route.contains(':') { "-inet6" } else { "-inet" };
         let result = StdCommand::new("route")
-            .args(["-n", "delete", "-net", route, "-interface", interface])
+            .args(["-n", "delete", family, "-net", route, "-interface", interface])
             .output();
 
         match result {
@@ -206,7 +360,7 @@ fn cleanup_single_route(route: &str, interface: &str, _interface_index: Option<u
                 } else {
                     // Try without -interface as fallback (route might have been cleaned by system)
                     let _ = StdCommand::new("route")
-                        .args(["-n", "delete", "-net", route])
+                        .args(["-n", "delete", family, "-net", route])
                         .output();
                     false
                 }
@@ -220,9 +374,13 @@ fn cleanup_single_route(route: &str, interface: &str, _interface_index: Option<u
 
     #[cfg(target_os = "linux")]
     {
-        let result = StdCommand::new("ip")
-            .args(["route", "del", route, "dev", interface])
-            .output();
+        let family = if route.contains(':') { "-6" } else { "-4" };
+        let mut args = vec![family, "route", "del", route, "dev", interface];
+        let table = _table.map(|t| t.to_string());
+        if let Some(table) = &table {
+            args.extend(["table", table.as_str()]);
+        }
+        let result = StdCommand::new("ip").args(&args).output();
 
         match result {
             Ok(output) => {
@@ -286,33 +444,83 @@ fn cleanup_single_route(route: &str, interface: &str, _interface_index: Option<u
     }
 }
 
+/// Host route (/32 or /128) for a persisted endpoint bypass address
+fn bypass_network(endpoint: &str) -> String {
+    match endpoint.parse::<IpAddr>() {
+        Ok(ip) => IpNet::from(ip).to_string(),
+        Err(_) => format!("{}/32", endpoint),
+    }
+}
+
 /// Clean up the endpoint bypass route
+///
+/// The saved gateway is the IPv4 default gateway, so IPv6 bypass routes are
+/// deleted by destination only.
 fn cleanup_endpoint_bypass(endpoint: &str, gateway: &str) -> bool {
+    let is_v6 = endpoint.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv6());
+
     #[cfg(target_os = "macos")]
     {
-        let result = StdCommand::new("route")
-            .args(["-n", "delete", "-host", endpoint, gateway])
-            .output();
+        let args: Vec<&str> = if is_v6 {
+            vec!["-n", "delete", "-inet6", "-host", endpoint]
+        } else {
+            vec!["-n", "delete", "-host", endpoint, gateway]
+        };
+        let result = StdCommand::new("route").args(&args).output();
         result.map(|o| o.status.success()).unwrap_or(false)
     }
 
     #[cfg(target_os = "linux")]
     {
-        let result = StdCommand::new("ip")
-            .args(["route", "del", &format!("{}/32", endpoint), "via", gateway])
-            .output();
+        let network = bypass_network(endpoint);
+        let mut args = vec!["route", "del", network.as_str()];
+        if !is_v6 {
+            args.extend(["via", gateway]);
+        }
+        let result = StdCommand::new("ip").args(&args).output();
         result.map(|o| o.status.success()).unwrap_or(false)
     }
 
     #[cfg(target_os = "windows")]
     {
-        let result = StdCommand::new("route")
-            .args(["delete", endpoint, "mask", "255.255.255.255", gateway])
-            .output();
+        let network = bypass_network(endpoint);
+        let args: Vec<&str> = if is_v6 {
+            vec!["delete", network.as_str()]
+        } else {
+            vec!["delete", endpoint, "mask", "255.255.255.255", gateway]
+        };
+        let result = StdCommand::new("route").args(&args).output();
         result.map(|o| o.status.success()).unwrap_or(false)
     }
 }
 
+/// Clean up the fwmark policy rules and their routing table
+fn cleanup_fwmark_rule(mark: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let mut ok = true;
+        for family in ["-4", "-6"] {
+            for args in fwmark_rule_args(RouteAction::Delete, family, mark) {
+                let removed = StdCommand::new("ip").args(&args).output();
+                // IPv6 rules are best effort when installed, so don't count them
+                if family == "-4" {
+                    ok &= removed.map(|o| o.status.success()).unwrap_or(false);
+                }
+            }
+            let _ = StdCommand::new("ip")
+                .args([family, "route", "flush", "table", &mark.to_string()])
+                .output();
+        }
+        ok
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = mark;
+        false
+    }
+}
+
 /// Get the current default gateway (used for state file)
 fn get_default_gateway() -> Option<String> {
     #[cfg(target_os = "macos")]
@@ -374,6 +582,90 @@ fn get_interface_index(interface: &str) -> Option<u32> {
         .ok()
 }
 
+/// Set `SO_MARK` on a UDP socket so its packets can be matched by a policy rule
+///
+/// Only supported on Linux; on other platforms this logs a warning and does nothing.
+pub fn set_socket_fwmark(socket: &tokio::net::UdpSocket, mark: u32) -> Result<(), MinnowVpnError> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_MARK,
+                &mark as *const u32 as *const libc::c_void,
+                std::mem::size_of::<u32>() as libc::socklen_t,
+            )
+        };
+
+        if result != 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::PermissionDenied {
+                return Err(TunnelError::InsufficientPrivileges {
+                    message: format!("Setting FwMark requires CAP_NET_ADMIN: {}", e),
+                }.into());
+            }
+            return Err(TunnelError::Io(e).into());
+        }
+
+        tracing::debug!("Set SO_MARK {:#x} on UDP socket", mark);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = socket;
+        tracing::warn!("FwMark {:#x} is only supported on Linux - ignoring", mark);
+    }
+
+    Ok(())
+}
+
+/// Summarize an IP packet's header for trace logging (never includes payload)
+///
+/// Produces e.g. `IPv4 TCP(6) 10.0.0.2 -> 1.1.1.1 len=60`.
+pub fn describe_ip_packet(packet: &[u8]) -> String {
+    match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            let protocol = packet[9];
+            let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+            let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+            format!(
+                "IPv4 {}({}) {} -> {} len={}",
+                ip_protocol_name(protocol), protocol, src, dst, packet.len()
+            )
+        }
+        Some(6) if packet.len() >= 40 => {
+            let next_header = packet[6];
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&packet[8..24]);
+            dst.copy_from_slice(&packet[24..40]);
+            format!(
+                "IPv6 {}({}) {} -> {} len={}",
+                ip_protocol_name(next_header),
+                next_header,
+                std::net::Ipv6Addr::from(src),
+                std::net::Ipv6Addr::from(dst),
+                packet.len()
+            )
+        }
+        _ => format!("non-IP packet len={}", packet.len()),
+    }
+}
+
+/// Well-known name for an IP protocol number
+fn ip_protocol_name(protocol: u8) -> &'static str {
+    match protocol {
+        1 => "ICMP",
+        6 => "TCP",
+        17 => "UDP",
+        58 => "ICMPv6",
+        _ => "proto",
+    }
+}
+
 /// Async TUN device wrapper
 pub struct TunDevice {
     /// The underlying async TUN device
@@ -383,24 +675,41 @@ pub struct TunDevice {
 }
 
 impl TunDevice {
-    /// Create a new TUN device with the given configuration
+    /// Create a new TUN device with the given addresses
+    ///
+    /// The device is built first and every address is then assigned with
+    /// [`TunDevice::add_address`], so an address conflict surfaces as
+    /// `AddressAssignmentFailed` rather than a generic `CreateFailed`.
+    /// `name` requests a specific device name; `None` lets the OS pick one
+    /// (on Windows the adapter is called "MinnowVPN").
     pub async fn create(
-        address: Ipv4Addr,
-        prefix_len: u8,
+        addresses: &[IpNet],
         mtu: u16,
+        name: Option<&str>,
     ) -> Result<Self, MinnowVpnError> {
-        // Check for required privileges first
+        // A config built in code skips the parser's check; refuse before touching the system
+        crate::config::check_mtu(mtu)?;
+
+        // Check for required privileges
         check_privileges()?;
 
-        let builder = DeviceBuilder::new();
+        if addresses.is_empty() {
+            return Err(TunnelError::CreateFailed {
+                reason: "No interface address configured".to_string(),
+            }.into());
+        }
+
+        let mut builder = DeviceBuilder::new();
 
         #[cfg(target_os = "windows")]
-        {
-            builder = builder.name("MinnowVPN");
+        let name = name.or(Some("MinnowVPN"));
+
+        if let Some(name) = name {
+            tracing::info!("Requesting TUN device name: {}", name);
+            builder = builder.name(name);
         }
 
         let device = builder
-            .ipv4(address, prefix_len, None)
             .mtu(mtu)
             .build_async()
             .map_err(|e| TunnelError::CreateFailed {
@@ -413,12 +722,34 @@ impl TunDevice {
                 reason: format!("Failed to get device name: {}", e),
             })?;
 
-        tracing::info!("Created TUN device: {} with address {}/{}", name, address, prefix_len);
+        tracing::info!("Created TUN device: {}", name);
 
-        Ok(Self {
+        let tun = Self {
             device,
             name,
-        })
+        };
+
+        for address in addresses {
+            tun.add_address(*address)?;
+        }
+
+        Ok(tun)
+    }
+
+    /// Assign an address (IPv4 or IPv6) to the device
+    pub fn add_address(&self, address: IpNet) -> Result<(), MinnowVpnError> {
+        let result = match address {
+            IpNet::V4(v4) => self.device.deref().add_address_v4(v4.addr(), v4.prefix_len()),
+            IpNet::V6(v6) => self.device.deref().add_address_v6(v6.addr(), v6.prefix_len()),
+        };
+
+        result.map_err(|e| TunnelError::AddressAssignmentFailed {
+            address: address.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        tracing::info!("Added address {} to {}", address, self.name);
+        Ok(())
     }
 
     /// Get the device name
@@ -436,6 +767,17 @@ impl TunDevice {
             }.into())
     }
 
+    /// Read a packet only if one is already queued (never waits)
+    pub fn try_read(&self, buf: &mut [u8]) -> Result<Option<usize>, MinnowVpnError> {
+        match self.device.try_recv(buf) {
+            Ok(len) => Ok(Some(len)),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(TunnelError::ReadFailed {
+                reason: e.to_string(),
+            }.into()),
+        }
+    }
+
     /// Write a packet to the TUN device
     pub async fn write(&self, packet: &[u8]) -> Result<usize, MinnowVpnError> {
         self.device
@@ -445,6 +787,16 @@ impl TunDevice {
                 reason: e.to_string(),
             }.into())
     }
+
+    /// Bring the interface down so the OS stops handing it packets
+    ///
+    /// The file descriptor itself is closed when the device is dropped.
+    pub fn close(&self) -> Result<(), MinnowVpnError> {
+        self.device
+            .deref()
+            .enabled(false)
+            .map_err(|e| TunnelError::Io(e).into())
+    }
 }
 
 /// Check for required privileges to create TUN devices
@@ -525,10 +877,16 @@ fn is_elevated_windows() -> bool {
 pub struct RouteManager {
     /// Device name for routing
     device_name: String,
-    /// Routes that have been added
-    added_routes: Vec<Ipv4Net>,
+    /// Table the routes go to (`Off` makes every route call a no-op)
+    table: RouteTable,
+    /// Routes that have been added (IPv4 and IPv6)
+    added_routes: Vec<IpNet>,
     /// Endpoint bypass route (needs separate cleanup)
-    endpoint_bypass: Option<Ipv4Addr>,
+    endpoint_bypass: Option<IpAddr>,
+    /// Firewall mark with installed policy rules (needs separate cleanup)
+    fwmark: Option<u32>,
+    /// DNS servers installed for the interface (needs separate cleanup)
+    dns: Option<DnsBackend>,
     /// Default gateway (for state file)
     default_gateway: Option<String>,
     /// Interface index (Windows only)
@@ -539,16 +897,34 @@ pub struct RouteManager {
 impl RouteManager {
     /// Create a new route manager
     pub fn new(device_name: String) -> Self {
-        // Capture default gateway at creation time
-        let default_gateway = get_default_gateway();
+        Self::with_table(device_name, RouteTable::Auto)
+    }
+
+    /// Create a route manager whose routes go to `table`
+    ///
+    /// With [`RouteTable::Off`] no routes, bypass routes or policy rules are
+    /// installed; DNS is still applied.
+    pub fn with_table(device_name: String, table: RouteTable) -> Self {
+        // Capture default gateway at creation time (never needed without routes)
+        let default_gateway = match table {
+            RouteTable::Off => None,
+            _ => get_default_gateway(),
+        };
+
+        if matches!(table, RouteTable::Id(_)) && !cfg!(target_os = "linux") {
+            tracing::warn!("Table = {} is only supported on Linux; using the main table", table);
+        }
 
         #[cfg(target_os = "windows")]
         let interface_index = get_interface_index(&device_name);
 
         Self {
             device_name,
+            table,
             added_routes: Vec::new(),
             endpoint_bypass: None,
+            fwmark: None,
+            dns: None,
             default_gateway,
             #[cfg(target_os = "windows")]
             interface_index,
@@ -573,6 +949,9 @@ impl RouteManager {
             interface_index: None,
             endpoint_bypass: self.endpoint_bypass.map(|ip| ip.to_string()),
             default_gateway: self.default_gateway.clone(),
+            fwmark: self.fwmark,
+            table: self.table_id(),
+            dns: self.dns,
             routes: self.added_routes.iter().map(|r| r.to_string()).collect(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -587,7 +966,11 @@ impl RouteManager {
 
     /// Add a bypass route for the VPN endpoint to go through the default gateway
     /// This prevents a routing loop where encrypted packets would be re-routed through the tunnel
-    pub async fn add_endpoint_bypass(&mut self, endpoint: Ipv4Addr) -> Result<(), MinnowVpnError> {
+    pub async fn add_endpoint_bypass(&mut self, endpoint: IpAddr) -> Result<(), MinnowVpnError> {
+        if self.table == RouteTable::Off {
+            tracing::debug!("Table = off: not adding endpoint bypass route for {}", endpoint);
+            return Ok(());
+        }
         add_endpoint_bypass_platform(endpoint).await?;
         self.endpoint_bypass = Some(endpoint);
         self.save_state();
@@ -595,27 +978,75 @@ impl RouteManager {
         Ok(())
     }
 
-    /// Add a route for the given network
-    pub async fn add_route(&mut self, network: Ipv4Net) -> Result<(), MinnowVpnError> {
-        add_route_platform(&self.device_name, &network).await?;
-        self.added_routes.push(network);
+    /// Install policy rules so packets carrying `mark` skip the tunnel routes
+    ///
+    /// Like wg-quick, routes added afterwards go to a dedicated table (numbered
+    /// after the mark) that only unmarked packets consult. Our own encrypted UDP
+    /// (see [`set_socket_fwmark`]) is marked, so it keeps using the main table and
+    /// whatever default route it has, even after roaming to a new gateway. This
+    /// avoids a routing loop without per-endpoint bypass routes.
+    ///
+    /// With a numbered `Table` the caller manages policy routing, so nothing is installed.
+    pub async fn add_fwmark_rule(&mut self, mark: u32) -> Result<(), MinnowVpnError> {
+        if self.table != RouteTable::Auto {
+            tracing::debug!("Table = {}: not adding fwmark rule for mark {:#x}", self.table, mark);
+            return Ok(());
+        }
+        add_fwmark_rule_platform(mark).await?;
+        self.fwmark = Some(mark);
+        self.save_state();
+        tracing::info!("Added fwmark rules: packets without mark {:#x} use table {}", mark, mark);
+        Ok(())
+    }
+
+    /// Use `servers` as the system's resolvers while the tunnel is up
+    ///
+    /// Undone by [`RouteManager::cleanup`], or after a crash by the next start.
+    pub async fn set_dns(&mut self, servers: &[IpAddr]) -> Result<(), MinnowVpnError> {
+        let backend = dns::apply(&self.device_name, servers).await?;
+        self.dns = Some(backend);
         self.save_state();
-        tracing::info!("Added route: {} via {}", network, self.device_name);
+        tracing::info!("Set DNS servers for {} ({:?})", self.device_name, backend);
+        Ok(())
+    }
+
+    /// Add a route for the given network
+    ///
+    /// A default route is installed as its two halves (see [`split_default_route`]).
+    pub async fn add_route(&mut self, network: IpNet) -> Result<(), MinnowVpnError> {
+        if self.table == RouteTable::Off {
+            tracing::debug!("Table = off: not adding route for {}", network);
+            return Ok(());
+        }
+        for part in split_default_route(network) {
+            if self.added_routes.contains(&part) {
+                continue;
+            }
+            add_route_platform(&self.device_name, &part, self.table_id()).await?;
+            self.added_routes.push(part);
+            self.save_state();
+            tracing::info!("Added route: {} via {}", part, self.device_name);
+        }
         Ok(())
     }
 
     /// Remove a single route (for dynamic peer removal)
-    pub async fn remove_route(&mut self, network: Ipv4Net) -> Result<(), MinnowVpnError> {
-        if let Err(e) = remove_route_platform(&self.device_name, &network).await {
-            tracing::warn!("Failed to remove route {}: {}", network, e);
-            return Err(e);
+    pub async fn remove_route(&mut self, network: IpNet) -> Result<(), MinnowVpnError> {
+        if self.table == RouteTable::Off {
+            return Ok(());
         }
+        for part in split_default_route(network) {
+            if let Err(e) = remove_route_platform(&self.device_name, &part, self.table_id()).await {
+                tracing::warn!("Failed to remove route {}: {}", part, e);
+                return Err(e);
+            }
 
-        // Remove from tracked routes
-        self.added_routes.retain(|r| r != &network);
-        self.save_state();
+            // Remove from tracked routes
+            self.added_routes.retain(|r| r != &part);
+            self.save_state();
 
-        tracing::info!("Removed route: {} from {}", network, self.device_name);
+            tracing::info!("Removed route: {} from {}", part, self.device_name);
+        }
         Ok(())
     }
 
@@ -632,8 +1063,17 @@ impl RouteManager {
             }
         }
 
+        if let Some(backend) = self.dns.take() {
+            if let Err(e) = dns::revert(&self.device_name, backend).await {
+                tracing::warn!("Failed to restore DNS settings: {}", e);
+            } else {
+                tracing::debug!("Restored DNS settings for {}", self.device_name);
+            }
+        }
+
+        let table = self.table_id();
         for network in self.added_routes.drain(..) {
-            if let Err(e) = remove_route_platform(&self.device_name, &network).await {
+            if let Err(e) = remove_route_platform(&self.device_name, &network, table).await {
                 tracing::warn!("Failed to remove route {}: {}", network, e);
                 errors.push((network, e));
             } else {
@@ -641,6 +1081,15 @@ impl RouteManager {
             }
         }
 
+        // After the routes: flushing the mark's table would remove them first
+        if let Some(mark) = self.fwmark.take() {
+            if let Err(e) = remove_fwmark_rule_platform(mark).await {
+                tracing::warn!("Failed to remove fwmark rule: {}", e);
+            } else {
+                tracing::debug!("Removed fwmark rule for mark {:#x}", mark);
+            }
+        }
+
         // Delete state file on clean exit
         delete_route_state();
 
@@ -653,36 +1102,105 @@ impl RouteManager {
     }
 
     /// Get the list of added routes
-    pub fn routes(&self) -> &[Ipv4Net] {
+    pub fn routes(&self) -> &[IpNet] {
         &self.added_routes
     }
+
+    /// Numbered table for route commands (None = main table)
+    ///
+    /// Once fwmark rules are installed, routes go to the table named after the mark.
+    fn table_id(&self) -> Option<u32> {
+        match self.table {
+            RouteTable::Id(id) => Some(id),
+            RouteTable::Auto => self.fwmark,
+            RouteTable::Off => None,
+        }
+    }
 }
 
-/// Platform-specific route addition
-async fn add_route_platform(device: &str, network: &Ipv4Net) -> Result<(), MinnowVpnError> {
+/// The routes that stand in for `network` in the routing table
+///
+/// Like wg-quick, a default route (`0.0.0.0/0`, `::/0`) becomes its two /1
+/// halves. They win over the system default by being more specific, so the
+/// original default route is never replaced and is intact after cleanup.
+fn split_default_route(network: IpNet) -> Vec<IpNet> {
+    if network.prefix_len() == 0 {
+        network.subnets(1).map(|halves| halves.collect()).unwrap_or_default()
+    } else {
+        vec![network]
+    }
+}
+
+/// Route table operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouteAction {
+    Add,
+    Delete,
+}
+
+/// Build the route command (program and arguments) for a network on macOS/Linux
+///
+/// IPv6 networks get the family flag (`-inet6` / `-6`) so they go to the v6 table.
+/// A numbered `table` is only honored on Linux.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn route_command(
+    action: RouteAction,
+    device: &str,
+    network: &IpNet,
+    table: Option<u32>,
+) -> (&'static str, Vec<String>) {
+    let network_str = network.to_string();
+    let is_v6 = matches!(network, IpNet::V6(_));
+
     #[cfg(target_os = "macos")]
     {
-        let status = Command::new("route")
-            .args(["-n", "add", "-net", &network.to_string(), "-interface", device])
-            .status()
-            .await
-            .map_err(|e| TunnelError::RouteSetupFailed {
-                network: network.to_string(),
-                reason: e.to_string(),
-            })?;
-
-        if !status.success() {
-            return Err(TunnelError::RouteSetupFailed {
-                network: network.to_string(),
-                reason: format!("route command exited with {}", status),
-            }.into());
+        let _ = table;
+        let mut args = vec!["-n".to_string()];
+        args.push(match action {
+            RouteAction::Add => "add",
+            RouteAction::Delete => "delete",
+        }.to_string());
+        if is_v6 {
+            args.push("-inet6".to_string());
+        }
+        args.push("-net".to_string());
+        args.push(network_str);
+        if action == RouteAction::Add {
+            args.push("-interface".to_string());
+            args.push(device.to_string());
         }
+        ("route", args)
     }
 
     #[cfg(target_os = "linux")]
     {
-        let status = Command::new("ip")
-            .args(["route", "add", &network.to_string(), "dev", device])
+        let mut args = Vec::new();
+        if is_v6 {
+            args.push("-6".to_string());
+        }
+        args.push("route".to_string());
+        args.push(match action {
+            RouteAction::Add => "add",
+            RouteAction::Delete => "del",
+        }.to_string());
+        args.push(network_str);
+        args.push("dev".to_string());
+        args.push(device.to_string());
+        if let Some(table) = table {
+            args.push("table".to_string());
+            args.push(table.to_string());
+        }
+        ("ip", args)
+    }
+}
+
+/// Platform-specific route addition
+async fn add_route_platform(device: &str, network: &IpNet, table: Option<u32>) -> Result<(), MinnowVpnError> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let (program, args) = route_command(RouteAction::Add, device, network, table);
+        let status = Command::new(program)
+            .args(&args)
             .status()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -693,13 +1211,14 @@ async fn add_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Minno
         if !status.success() {
             return Err(TunnelError::RouteSetupFailed {
                 network: network.to_string(),
-                reason: format!("ip route command exited with {}", status),
+                reason: format!("{} command exited with {}", program, status),
             }.into());
         }
     }
 
     #[cfg(target_os = "windows")]
     {
+        let _ = table;
         // Get interface index
         let output = Command::new("powershell")
             .args(["-Command", &format!(
@@ -717,9 +1236,10 @@ async fn add_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Minno
             .trim()
             .to_string();
 
+        let family = if matches!(network, IpNet::V6(_)) { "ipv6" } else { "ip" };
         let status = Command::new("netsh")
             .args([
-                "interface", "ip", "add", "route",
+                "interface", family, "add", "route",
                 &network.to_string(),
                 &if_index,
             ])
@@ -742,31 +1262,12 @@ async fn add_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Minno
 }
 
 /// Platform-specific route removal
-async fn remove_route_platform(device: &str, network: &Ipv4Net) -> Result<(), MinnowVpnError> {
-    #[cfg(target_os = "macos")]
-    {
-        let _ = device; // Device not needed for macOS route removal
-        let status = Command::new("route")
-            .args(["-n", "delete", "-net", &network.to_string()])
-            .status()
-            .await
-            .map_err(|e| TunnelError::RouteCleanupFailed {
-                network: network.to_string(),
-                reason: e.to_string(),
-            })?;
-
-        if !status.success() {
-            return Err(TunnelError::RouteCleanupFailed {
-                network: network.to_string(),
-                reason: format!("route command exited with {}", status),
-            }.into());
-        }
-    }
-
-    #[cfg(target_os = "linux")]
+async fn remove_route_platform(device: &str, network: &IpNet, table: Option<u32>) -> Result<(), MinnowVpnError> {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
     {
-        let status = Command::new("ip")
-            .args(["route", "del", &network.to_string(), "dev", device])
+        let (program, args) = route_command(RouteAction::Delete, device, network, table);
+        let status = Command::new(program)
+            .args(&args)
             .status()
             .await
             .map_err(|e| TunnelError::RouteCleanupFailed {
@@ -777,13 +1278,14 @@ async fn remove_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Mi
         if !status.success() {
             return Err(TunnelError::RouteCleanupFailed {
                 network: network.to_string(),
-                reason: format!("ip route command exited with {}", status),
+                reason: format!("{} command exited with {}", program, status),
             }.into());
         }
     }
 
     #[cfg(target_os = "windows")]
     {
+        let _ = table;
         let output = Command::new("powershell")
             .args(["-Command", &format!(
                 "(Get-NetAdapter -Name '{}').ifIndex",
@@ -800,9 +1302,10 @@ async fn remove_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Mi
             .trim()
             .to_string();
 
+        let family = if matches!(network, IpNet::V6(_)) { "ipv6" } else { "ip" };
         let status = Command::new("netsh")
             .args([
-                "interface", "ip", "delete", "route",
+                "interface", family, "delete", "route",
                 &network.to_string(),
                 &if_index,
             ])
@@ -825,14 +1328,20 @@ async fn remove_route_platform(device: &str, network: &Ipv4Net) -> Result<(), Mi
 }
 
 /// Add a route for the VPN endpoint to bypass the tunnel (go through default gateway)
-async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVpnError> {
+///
+/// Uses the default gateway of the endpoint's address family.
+async fn add_endpoint_bypass_platform(endpoint: IpAddr) -> Result<(), MinnowVpnError> {
     let endpoint_str = endpoint.to_string();
+    let is_v6 = endpoint.is_ipv6();
 
     #[cfg(target_os = "macos")]
     {
         // Get default gateway
+        let family: &[&str] = if is_v6 { &["-inet6"] } else { &[] };
         let output = Command::new("route")
-            .args(["-n", "get", "default"])
+            .args(["-n", "get"])
+            .args(family)
+            .arg("default")
             .output()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -853,7 +1362,9 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
 
         // Add specific route for endpoint through default gateway
         let status = Command::new("route")
-            .args(["-n", "add", "-host", &endpoint_str, &gateway])
+            .args(["-n", "add"])
+            .args(family)
+            .args(["-host", &endpoint_str, &gateway])
             .status()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -872,7 +1383,9 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
     #[cfg(target_os = "linux")]
     {
         // Get default gateway
+        let family: &[&str] = if is_v6 { &["-6"] } else { &[] };
         let output = Command::new("ip")
+            .args(family)
             .args(["route", "show", "default"])
             .output()
             .await
@@ -893,8 +1406,14 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
                 reason: "Could not parse default gateway".to_string(),
             })?;
 
+        let mut args = vec!["route", "add", endpoint_str.as_str(), "via", gateway.as_str()];
+        // IPv6 gateways are usually link-local and need the outgoing device
+        let device = output_str.split_whitespace().skip_while(|&s| s != "dev").nth(1);
+        if let (true, Some(device)) = (is_v6, device) {
+            args.extend(["dev", device]);
+        }
         let status = Command::new("ip")
-            .args(["route", "add", &endpoint_str, "via", &gateway])
+            .args(&args)
             .status()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -913,8 +1432,12 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
     #[cfg(target_os = "windows")]
     {
         // Get default gateway from route table
+        let prefix = if is_v6 { "::/0" } else { "0.0.0.0/0" };
         let output = Command::new("powershell")
-            .args(["-Command", "Get-NetRoute -DestinationPrefix '0.0.0.0/0' | Select-Object -First 1 -ExpandProperty NextHop"])
+            .args(["-Command", &format!(
+                "Get-NetRoute -DestinationPrefix '{}' | Select-Object -First 1 -ExpandProperty NextHop",
+                prefix
+            )])
             .output()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -924,8 +1447,14 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
 
         let gateway = String::from_utf8_lossy(&output.stdout).trim().to_string();
 
+        let host = IpNet::from(endpoint).to_string();
+        let args: Vec<&str> = if is_v6 {
+            vec!["add", host.as_str(), gateway.as_str()]
+        } else {
+            vec!["add", endpoint_str.as_str(), "mask", "255.255.255.255", gateway.as_str()]
+        };
         let status = Command::new("route")
-            .args(["add", &endpoint_str, "mask", "255.255.255.255", &gateway])
+            .args(&args)
             .status()
             .await
             .map_err(|e| TunnelError::RouteSetupFailed {
@@ -945,13 +1474,16 @@ async fn add_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVp
 }
 
 /// Remove the VPN endpoint bypass route
-async fn remove_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), MinnowVpnError> {
+async fn remove_endpoint_bypass_platform(endpoint: IpAddr) -> Result<(), MinnowVpnError> {
     let endpoint_str = endpoint.to_string();
 
     #[cfg(target_os = "macos")]
     {
+        let family: &[&str] = if endpoint.is_ipv6() { &["-inet6"] } else { &[] };
         let _ = Command::new("route")
-            .args(["-n", "delete", "-host", &endpoint_str])
+            .args(["-n", "delete"])
+            .args(family)
+            .args(["-host", &endpoint_str])
             .status()
             .await;
     }
@@ -967,7 +1499,7 @@ async fn remove_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), Minno
     #[cfg(target_os = "windows")]
     {
         let _ = Command::new("route")
-            .args(["delete", &endpoint_str])
+            .args(["delete", &IpNet::from(endpoint).to_string()])
             .status()
             .await;
     }
@@ -975,12 +1507,121 @@ async fn remove_endpoint_bypass_platform(endpoint: Ipv4Addr) -> Result<(), Minno
     Ok(())
 }
 
+/// `ip` arguments for the fwmark policy rules of one address family (`-4`/`-6`)
+///
+/// Packets without `mark` look up the mark's table, where the tunnel routes
+/// are. The second rule checks the main table first but ignores its default
+/// route, so more specific main routes (the local network) still win.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn fwmark_rule_args(action: RouteAction, family: &'static str, mark: u32) -> [Vec<String>; 2] {
+    let action = match action {
+        RouteAction::Add => "add",
+        RouteAction::Delete => "del",
+    };
+    let mark = mark.to_string();
+    [
+        [family, "rule", action, "not", "fwmark", &mark, "table", &mark]
+            .map(String::from)
+            .to_vec(),
+        [family, "rule", action, "table", "main", "suppress_prefixlength", "0"]
+            .map(String::from)
+            .to_vec(),
+    ]
+}
+
+/// Install the fwmark policy rules
+///
+/// IPv4 rules are required; IPv6 ones are best effort, since IPv6 may be disabled.
+async fn add_fwmark_rule_platform(mark: u32) -> Result<(), MinnowVpnError> {
+    #[cfg(target_os = "linux")]
+    {
+        for family in ["-4", "-6"] {
+            for args in fwmark_rule_args(RouteAction::Add, family, mark) {
+                let status = Command::new("ip")
+                    .args(&args)
+                    .status()
+                    .await
+                    .map_err(|e| TunnelError::RouteSetupFailed {
+                        network: format!("fwmark {}", mark),
+                        reason: e.to_string(),
+                    })?;
+
+                if status.success() {
+                    continue;
+                }
+                if family == "-6" {
+                    tracing::debug!("IPv6 fwmark rule not installed: ip {} exited with {}", args.join(" "), status);
+                    break;
+                }
+                let _ = remove_fwmark_rule_platform(mark).await;
+                return Err(TunnelError::RouteSetupFailed {
+                    network: format!("fwmark {}", mark),
+                    reason: format!("ip rule command exited with {}", status),
+                }.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = mark;
+        Err(TunnelError::UnsupportedPlatform {
+            platform: format!("fwmark routing on {}", std::env::consts::OS),
+        }.into())
+    }
+}
+
+/// Remove the fwmark policy rules and flush the mark's routing table
+async fn remove_fwmark_rule_platform(mark: u32) -> Result<(), MinnowVpnError> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut failed = None;
+        for family in ["-4", "-6"] {
+            for args in fwmark_rule_args(RouteAction::Delete, family, mark) {
+                let status = Command::new("ip")
+                    .args(&args)
+                    .status()
+                    .await
+                    .map_err(|e| TunnelError::RouteCleanupFailed {
+                        network: format!("fwmark {}", mark),
+                        reason: e.to_string(),
+                    })?;
+                if !status.success() && family == "-4" {
+                    failed = Some(status);
+                }
+            }
+
+            let _ = Command::new("ip")
+                .args([family, "route", "flush", "table", &mark.to_string()])
+                .status()
+                .await;
+        }
+
+        if let Some(status) = failed {
+            return Err(TunnelError::RouteCleanupFailed {
+                network: format!("fwmark {}", mark),
+                reason: format!("ip rule command exited with {}", status),
+            }.into());
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = mark;
+    }
+
+    Ok(())
+}
+
 // Old netstat-parsing cleanup functions have been removed.
 // Route cleanup now uses the persistent state file approach via cleanup_from_state_file().
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::WireGuardConfig;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -991,6 +1632,9 @@ mod tests {
             interface_index: None,
             endpoint_bypass: Some("203.0.113.1".to_string()),
             default_gateway: Some("192.168.1.1".to_string()),
+            fwmark: None,
+            table: None,
+            dns: None,
             routes: vec![
                 "10.13.13.0/24".to_string(),
                 "10.10.10.0/24".to_string(),
@@ -1019,6 +1663,9 @@ mod tests {
             interface_index: None,
             endpoint_bypass: None,
             default_gateway: None,
+            fwmark: None,
+            table: None,
+            dns: None,
             routes: vec!["10.0.0.0/8".to_string()],
             timestamp: "0".to_string(),
         };
@@ -1028,6 +1675,7 @@ mod tests {
         assert!(!json.contains("endpoint_bypass"));
         assert!(!json.contains("default_gateway"));
         assert!(!json.contains("interface_index"));
+        assert!(!json.contains("\"dns\""));
 
         // Should still deserialize correctly
         let parsed: RouteState = serde_json::from_str(&json).unwrap();
@@ -1041,6 +1689,9 @@ mod tests {
             interface_index: Some(12),
             endpoint_bypass: Some("10.0.0.1".to_string()),
             default_gateway: Some("192.168.0.1".to_string()),
+            fwmark: None,
+            table: None,
+            dns: None,
             routes: vec!["0.0.0.0/0".to_string()],
             timestamp: "9999999999".to_string(),
         };
@@ -1062,6 +1713,9 @@ mod tests {
             interface_index: None,
             endpoint_bypass: Some("1.2.3.4".to_string()),
             default_gateway: Some("192.168.1.1".to_string()),
+            fwmark: None,
+            table: None,
+            dns: Some(DnsBackend::Resolvconf),
             routes: vec!["10.0.0.0/8".to_string(), "172.16.0.0/12".to_string()],
             timestamp: "1706600000".to_string(),
         };
@@ -1078,6 +1732,100 @@ mod tests {
         assert_eq!(loaded.interface, "utun99");
         assert_eq!(loaded.routes.len(), 2);
         assert_eq!(loaded.endpoint_bypass, Some("1.2.3.4".to_string()));
+        assert_eq!(loaded.dns, Some(DnsBackend::Resolvconf));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    fn test_route_command_ipv6() {
+        let v6: IpNet = "fd00:13::/64".parse().unwrap();
+        let (_, args) = route_command(RouteAction::Add, "tun0", &v6, None);
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(args, ["-6", "route", "add", "fd00:13::/64", "dev", "tun0"]);
+        #[cfg(target_os = "macos")]
+        assert_eq!(args, ["-n", "add", "-inet6", "-net", "fd00:13::/64", "-interface", "tun0"]);
+
+        // IPv4 routes have no family flag
+        let v4: IpNet = "10.13.13.0/24".parse().unwrap();
+        let (_, args) = route_command(RouteAction::Delete, "tun0", &v4, None);
+        assert!(!args.iter().any(|a| a == "-6" || a == "-inet6"));
+        assert!(args.contains(&"10.13.13.0/24".to_string()));
+    }
+
+    #[test]
+    fn test_split_default_route() {
+        let parts = |s: &str| -> Vec<String> {
+            split_default_route(s.parse().unwrap()).iter().map(|n| n.to_string()).collect()
+        };
+        assert_eq!(parts("0.0.0.0/0"), ["0.0.0.0/1", "128.0.0.0/1"]);
+        assert_eq!(parts("::/0"), ["::/1", "8000::/1"]);
+        assert_eq!(parts("10.0.0.0/8"), ["10.0.0.0/8"]);
+        assert_eq!(parts("0.0.0.0/1"), ["0.0.0.0/1"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_route_command_numbered_table() {
+        let v4: IpNet = "10.13.13.0/24".parse().unwrap();
+        let (_, args) = route_command(RouteAction::Add, "tun0", &v4, Some(1234));
+        assert_eq!(args, ["route", "add", "10.13.13.0/24", "dev", "tun0", "table", "1234"]);
+    }
+
+    #[test]
+    fn test_fwmark_rule_args() {
+        let [exclude, suppress] = fwmark_rule_args(RouteAction::Add, "-4", 51820);
+        assert_eq!(exclude, ["-4", "rule", "add", "not", "fwmark", "51820", "table", "51820"]);
+        assert_eq!(suppress, ["-4", "rule", "add", "table", "main", "suppress_prefixlength", "0"]);
+
+        let [exclude, _] = fwmark_rule_args(RouteAction::Delete, "-6", 51820);
+        assert_eq!(exclude[..3], ["-6", "rule", "del"]);
+    }
+
+    #[tokio::test]
+    async fn test_table_off_adds_no_routes() {
+        let config = WireGuardConfig::parse(
+            "[Interface]\n\
+            PrivateKey = UOvtcWdILFwjb1UnsnK+a9lcqYvNTmtPv+fvqIVOz3w=\n\
+            Address = 10.0.0.2/24\n\
+            Table = off\n\
+            [Peer]\n\
+            PublicKey = YgkBjKXER5YarD8STsvMFURw/5nhCLIFOJ5uKWrrMW4=\n\
+            Endpoint = 192.0.2.1:51820\n\
+            AllowedIPs = 0.0.0.0/0\n",
+        )
+        .unwrap();
+        let table = config.interface.table.unwrap_or(RouteTable::Auto);
+        assert_eq!(table, RouteTable::Off);
+
+        let mut routes = RouteManager::with_table("minnow-test0".to_string(), table);
+        routes.add_fwmark_rule(51820).await.unwrap();
+        routes.add_endpoint_bypass("192.0.2.1".parse().unwrap()).await.unwrap();
+        for network in &config.peers[0].allowed_ips {
+            routes.add_route(*network).await.unwrap();
+        }
+        assert!(routes.routes().is_empty());
+        assert_eq!(routes.endpoint_bypass, None);
+        assert_eq!(routes.fwmark, None);
+    }
+
+    #[test]
+    fn test_describe_ip_packet() {
+        let mut v4 = [0u8; 28];
+        v4[0] = 0x45;
+        v4[9] = 17;
+        v4[12..16].copy_from_slice(&[10, 0, 0, 2]);
+        v4[16..20].copy_from_slice(&[1, 1, 1, 1]);
+        assert_eq!(describe_ip_packet(&v4), "IPv4 UDP(17) 10.0.0.2 -> 1.1.1.1 len=28");
+
+        let mut v6 = [0u8; 40];
+        v6[0] = 0x60;
+        v6[6] = 58;
+        v6[23] = 1;
+        v6[39] = 2;
+        assert_eq!(describe_ip_packet(&v6), "IPv6 ICMPv6(58) ::1 -> ::2 len=40");
+
+        assert_eq!(describe_ip_packet(&[0x45, 0, 0]), "non-IP packet len=3");
     }
 
     #[test]
@@ -1096,4 +1844,46 @@ mod tests {
         #[cfg(target_os = "linux")]
         assert!(interface_exists("lo"));
     }
+
+    #[test]
+    fn test_route_report() {
+        let report = build_route_report(None);
+        assert!(report.state.is_none());
+        assert!(report.routes.is_empty());
+
+        let state = RouteState {
+            interface: "mvpn-test-missing0".to_string(),
+            interface_index: None,
+            endpoint_bypass: Some("192.0.2.1".to_string()),
+            default_gateway: None,
+            fwmark: None,
+            table: None,
+            dns: None,
+            routes: vec!["198.51.100.0/24".to_string(), "2001:db8:ffff::/48".to_string()],
+            timestamp: "0".to_string(),
+        };
+        let report = build_route_report(Some(state));
+        assert!(!report.interface_exists);
+        assert_eq!(report.routes.len(), 2);
+        assert_eq!(report.routes[1].network, "2001:db8:ffff::/48");
+        // Routes on a missing interface can never be reported as present
+        assert!(report.routes.iter().all(|r| r.present != Some(true)));
+        assert_eq!(report.endpoint_bypass.unwrap().network, "192.0.2.1/32");
+    }
+
+    #[test]
+    fn test_bypass_network_family() {
+        assert_eq!(bypass_network("192.0.2.1"), "192.0.2.1/32");
+        assert_eq!(bypass_network("2001:db8::1"), "2001:db8::1/128");
+    }
+
+    #[tokio::test]
+    async fn test_create_rejects_tiny_mtu_before_touching_the_system() {
+        let addresses = ["10.0.0.2/24".parse().unwrap()];
+        let result = TunDevice::create(&addresses, 20, None).await;
+        assert!(matches!(
+            result,
+            Err(MinnowVpnError::Config(crate::error::ConfigError::InvalidMtu { mtu: 20, .. }))
+        ));
+    }
 }
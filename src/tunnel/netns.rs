@@ -0,0 +1,96 @@
+//! Running the tunnel inside a named network namespace (Linux)
+//!
+//! Namespaces are the ones managed by `ip netns`, i.e. bind mounts under
+//! `/var/run/netns`. A missing namespace is created with `ip netns add`.
+//!
+//! `setns` only moves the calling thread, and threads inherit the namespace
+//! of the thread that spawns them. [`enter`] must therefore run before the
+//! async runtime starts, so every worker thread (and every `ip` command they
+//! spawn) creates the TUN, binds sockets and adds routes inside the namespace.
+
+use crate::error::{MinnowVpnError, TunnelError};
+
+/// Directory where `ip netns` keeps named namespaces
+pub const NETNS_DIR: &str = "/var/run/netns";
+
+/// Move the current thread into network namespace `name`, creating it if needed
+#[cfg(target_os = "linux")]
+pub fn enter(name: &str) -> Result<(), MinnowVpnError> {
+    use std::fs::File;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+    use std::process::Command;
+
+    let failed = |reason: String| TunnelError::NetnsFailed {
+        name: name.to_string(),
+        reason,
+    };
+
+    if !is_valid_name(name) {
+        return Err(failed("not a valid namespace name".to_string()).into());
+    }
+
+    let path = Path::new(NETNS_DIR).join(name);
+    if !path.exists() {
+        tracing::info!("Creating network namespace {}", name);
+        let output = Command::new("ip")
+            .args(["netns", "add", name])
+            .output()
+            .map_err(|e| failed(format!("could not run ip netns add: {}", e)))?;
+        if !output.status.success() {
+            return Err(failed(String::from_utf8_lossy(&output.stderr).trim().to_string()).into());
+        }
+    }
+
+    let ns = File::open(&path).map_err(|e| failed(format!("{}: {}", path.display(), e)))?;
+    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() == Some(libc::EPERM) {
+            return Err(TunnelError::InsufficientPrivileges {
+                message: format!("entering network namespace {} requires CAP_SYS_ADMIN", name),
+            }
+            .into());
+        }
+        return Err(failed(e.to_string()).into());
+    }
+
+    tracing::info!("Running in network namespace {}", name);
+    Ok(())
+}
+
+/// Network namespaces are Linux-only
+#[cfg(not(target_os = "linux"))]
+pub fn enter(name: &str) -> Result<(), MinnowVpnError> {
+    Err(TunnelError::NetnsFailed {
+        name: name.to_string(),
+        reason: "network namespaces are only supported on Linux".to_string(),
+    }
+    .into())
+}
+
+/// A name `ip netns` accepts: one path component, no `/` or `.`/`..`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_names() {
+        assert!(is_valid_name("vpn"));
+        assert!(is_valid_name("vpn-1.test"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name(".."));
+        assert!(!is_valid_name("../etc"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_invalid_name_is_rejected_before_touching_the_system() {
+        let err = enter("a/b").unwrap_err();
+        assert!(matches!(err, MinnowVpnError::Tunnel(TunnelError::NetnsFailed { .. })));
+    }
+}
@@ -0,0 +1,174 @@
+//! Lossy-link simulator for resilience testing
+//!
+//! Set `MINNOWVPN_NETSIM` to make the client drop, duplicate and delay its UDP
+//! traffic, e.g. `drop=0.1,dup=0.05,delay=50,jitter=20` (probabilities, and
+//! milliseconds for delay/jitter). Outgoing datagrams can be dropped, duplicated
+//! and delayed; incoming ones can be dropped.
+//!
+//! Debug builds only: in release builds [`NetSim::from_env`] always returns
+//! `None`, so the simulator is inert.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::net::UdpSocket;
+
+/// Environment variable holding the simulator settings
+pub const NETSIM_ENV: &str = "MINNOWVPN_NETSIM";
+
+/// Simulated link impairments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetSim {
+    /// Probability of dropping a datagram (each direction)
+    pub drop: f64,
+    /// Probability of sending an outgoing datagram twice
+    pub duplicate: f64,
+    /// Fixed extra latency for outgoing datagrams
+    pub delay: Duration,
+    /// Random extra latency, uniform in `0..=jitter`
+    pub jitter: Duration,
+}
+
+impl NetSim {
+    /// Read settings from `MINNOWVPN_NETSIM` (None if unset, invalid, or a release build)
+    pub fn from_env() -> Option<Self> {
+        if !cfg!(debug_assertions) {
+            return None;
+        }
+
+        let spec = std::env::var(NETSIM_ENV).ok()?;
+        match Self::parse(&spec) {
+            Ok(sim) => {
+                tracing::warn!("Network simulator enabled: {:?}", sim);
+                Some(sim)
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring {}: {}", NETSIM_ENV, e);
+                None
+            }
+        }
+    }
+
+    /// Parse `key=value` pairs separated by commas (`drop`, `dup`, `delay`, `jitter`)
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut sim = Self::default();
+
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got '{}'", part))?;
+            let value = value.trim();
+            match key.trim() {
+                "drop" => sim.drop = parse_probability(value)?,
+                "dup" => sim.duplicate = parse_probability(value)?,
+                "delay" => sim.delay = parse_millis(value)?,
+                "jitter" => sim.jitter = parse_millis(value)?,
+                other => return Err(format!("unknown setting '{}'", other)),
+            }
+        }
+
+        Ok(sim)
+    }
+
+    /// Whether an incoming datagram should be discarded
+    pub fn drop_incoming(&self) -> bool {
+        rand::thread_rng().gen_bool(self.drop)
+    }
+
+    /// Latency for each copy of an outgoing datagram (empty = dropped)
+    fn outgoing_delays(&self) -> Vec<Duration> {
+        let mut rng = rand::thread_rng();
+        if rng.gen_bool(self.drop) {
+            return Vec::new();
+        }
+
+        let copies = if rng.gen_bool(self.duplicate) { 2 } else { 1 };
+        (0..copies)
+            .map(|_| {
+                let jitter = rng.gen_range(0..=self.jitter.as_millis() as u64);
+                self.delay + Duration::from_millis(jitter)
+            })
+            .collect()
+    }
+
+    /// Send through the simulated link
+    ///
+    /// Undelayed copies go out right away so send errors still reach the caller;
+    /// delayed ones are sent later from a duplicate of the socket. A dropped
+    /// datagram reports success, like a real lossy link.
+    pub async fn send_to(&self, socket: &UdpSocket, data: &[u8], to: SocketAddr) -> io::Result<usize> {
+        for delay in self.outgoing_delays() {
+            if delay.is_zero() {
+                socket.send_to(data, to).await?;
+                continue;
+            }
+
+            let delayed = socket2::SockRef::from(socket).try_clone()?;
+            let data = data.to_vec();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = delayed.send_to(&data, &to.into());
+            });
+        }
+        Ok(data.len())
+    }
+}
+
+fn parse_probability(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0.0..=1.0).contains(p))
+        .ok_or_else(|| format!("'{}' is not a probability between 0 and 1", value))
+}
+
+fn parse_millis(value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("'{}' is not a number of milliseconds", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let sim = NetSim::parse("drop=0.1, dup=0.05,delay=50,jitter=20").unwrap();
+        assert_eq!(sim.drop, 0.1);
+        assert_eq!(sim.duplicate, 0.05);
+        assert_eq!(sim.delay, Duration::from_millis(50));
+        assert_eq!(sim.jitter, Duration::from_millis(20));
+
+        assert_eq!(NetSim::parse("").unwrap(), NetSim::default());
+        assert!(NetSim::parse("drop=1.5").is_err());
+        assert!(NetSim::parse("loss=0.1").is_err());
+        assert!(NetSim::parse("delay").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drop_and_duplicate() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let to = receiver.local_addr().unwrap();
+        let mut buf = [0u8; 16];
+
+        let lossy = NetSim { drop: 1.0, ..NetSim::default() };
+        assert_eq!(lossy.send_to(&sender, b"lost", to).await.unwrap(), 4);
+        assert!(lossy.drop_incoming());
+
+        let doubled = NetSim { duplicate: 1.0, delay: Duration::from_millis(10), ..NetSim::default() };
+        doubled.send_to(&sender, b"twice", to).await.unwrap();
+        for _ in 0..2 {
+            let (len, _) = tokio::time::timeout(Duration::from_secs(2), receiver.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(&buf[..len], b"twice");
+        }
+        assert!(receiver.try_recv_from(&mut buf).is_err());
+    }
+}
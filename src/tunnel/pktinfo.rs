@@ -0,0 +1,263 @@
+//! Reply source addresses for servers bound to a wildcard address
+//!
+//! A socket bound to `0.0.0.0` leaves the source address of each reply to the
+//! routing table, which on a multi-homed host is not necessarily the address
+//! the peer sent to; the peer (or a NAT in front of it) then drops the reply.
+//! On Linux `IP_PKTINFO`/`IPV6_PKTINFO` report the local address a datagram
+//! arrived on, and replies are sent from that address again. Elsewhere
+//! [`enable`] returns false and replies use the kernel's choice.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use tokio::net::UdpSocket;
+
+/// Local address to reply from, per peer endpoint
+pub type ReplySources = HashMap<SocketAddr, IpAddr>;
+
+/// Ask the kernel to report the local address of every received datagram
+///
+/// Returns false (nothing to do) when the socket is bound to a specific
+/// address, or when the platform has no `IP_PKTINFO`.
+pub fn enable(socket: &UdpSocket) -> bool {
+    match socket.local_addr() {
+        Ok(addr) if addr.ip().is_unspecified() => sys::enable(socket, addr.is_ipv6()),
+        _ => false,
+    }
+}
+
+/// Send `data` to `to`, from the local address `from` when one is given
+///
+/// If the local address has gone away since it was learned, the datagram is
+/// sent again with the kernel's choice of source.
+pub async fn send_from(
+    socket: &UdpSocket,
+    data: &[u8],
+    to: SocketAddr,
+    from: Option<IpAddr>,
+) -> io::Result<usize> {
+    if let Some(from) = from {
+        match sys::send_from(socket, data, to, from).await {
+            Err(e) if sys::source_unusable(&e) => {
+                tracing::debug!("Reply source {} unusable ({}), letting the kernel pick", from, e);
+            }
+            result => return result,
+        }
+    }
+    socket.send_to(data, to).await
+}
+
+#[cfg(target_os = "linux")]
+pub(super) mod sys {
+    use std::io;
+    use std::mem::{size_of, zeroed};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::os::fd::AsRawFd;
+
+    use socket2::SockAddr;
+    use tokio::io::Interest;
+    use tokio::net::UdpSocket;
+
+    /// Control message space for the larger of the two pktinfo structs
+    pub const CMSG_SPACE: usize = 40;
+
+    /// Control buffer for a single pktinfo message, aligned for `cmsghdr`
+    #[repr(C, align(8))]
+    struct CmsgBuf([u8; CMSG_SPACE]);
+
+    pub fn enable(socket: &UdpSocket, ipv6: bool) -> bool {
+        let (level, name) = if ipv6 {
+            (libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO)
+        } else {
+            (libc::IPPROTO_IP, libc::IP_PKTINFO)
+        };
+        let on: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &on as *const libc::c_int as *const libc::c_void,
+                size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        result == 0
+    }
+
+    /// Local address from an `IP_PKTINFO`/`IPV6_PKTINFO` control message
+    pub fn local_addr(hdr: &libc::msghdr) -> Option<IpAddr> {
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(hdr);
+            while !cmsg.is_null() {
+                match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                    (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+                        let info = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::in_pktinfo);
+                        return Some(Ipv4Addr::from(u32::from_be(info.ipi_addr.s_addr)).into());
+                    }
+                    (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+                        let info = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::in6_pktinfo);
+                        return Some(Ipv6Addr::from(info.ipi6_addr.s6_addr).into());
+                    }
+                    _ => {}
+                }
+                cmsg = libc::CMSG_NXTHDR(hdr, cmsg);
+            }
+        }
+        None
+    }
+
+    /// Append a pktinfo control message selecting `from` as the source address
+    ///
+    /// `hdr.msg_control` must point at a buffer with at least [`CMSG_SPACE`]
+    /// bytes free after the current `msg_controllen`.
+    #[allow(clippy::unnecessary_cast)] // msg_controllen is not usize on every libc
+    pub fn write_cmsg(hdr: &mut libc::msghdr, from: IpAddr) {
+        unsafe {
+            let (level, kind, len) = match from {
+                IpAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_PKTINFO, size_of::<libc::in_pktinfo>()),
+                IpAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO, size_of::<libc::in6_pktinfo>()),
+            };
+            let cmsg = (hdr.msg_control as *mut u8).add(hdr.msg_controllen as usize) as *mut libc::cmsghdr;
+            hdr.msg_controllen = (hdr.msg_controllen as usize + libc::CMSG_SPACE(len as u32) as usize) as _;
+            (*cmsg).cmsg_level = level;
+            (*cmsg).cmsg_type = kind;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(len as u32) as _;
+
+            match from {
+                IpAddr::V4(ip) => {
+                    let mut info: libc::in_pktinfo = zeroed();
+                    info.ipi_spec_dst.s_addr = u32::from(ip).to_be();
+                    std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in_pktinfo, info);
+                }
+                IpAddr::V6(ip) => {
+                    let mut info: libc::in6_pktinfo = zeroed();
+                    info.ipi6_addr.s6_addr = ip.octets();
+                    std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in6_pktinfo, info);
+                }
+            }
+        }
+    }
+
+    /// Non-blocking `recvmsg` returning the datagram's source and local address
+    ///
+    /// Like `try_recv_from`, a pending socket error is returned first. Only the
+    /// single-packet receive path uses it; `udp-batch` reads with `recvmmsg`.
+    #[cfg(not(feature = "udp-batch"))]
+    pub fn try_recv_from(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<IpAddr>)> {
+        if let Some(e) = socket.take_error()? {
+            return Err(e);
+        }
+
+        let mut name: libc::sockaddr_storage = unsafe { zeroed() };
+        let mut control = CmsgBuf([0u8; CMSG_SPACE]);
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut hdr: libc::msghdr = unsafe { zeroed() };
+        hdr.msg_name = &mut name as *mut libc::sockaddr_storage as *mut libc::c_void;
+        hdr.msg_namelen = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        hdr.msg_iov = &mut iov;
+        hdr.msg_iovlen = 1;
+        hdr.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+        hdr.msg_controllen = CMSG_SPACE as _;
+
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut hdr, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let from = unsafe { SockAddr::new(name, hdr.msg_namelen) }
+            .as_socket()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "datagram without an IP source"))?;
+        Ok((len as usize, from, local_addr(&hdr)))
+    }
+
+    pub async fn send_from(socket: &UdpSocket, data: &[u8], to: SocketAddr, from: IpAddr) -> io::Result<usize> {
+        let to = SockAddr::from(to);
+        socket
+            .async_io(Interest::WRITABLE, || {
+                let mut control = CmsgBuf([0u8; CMSG_SPACE]);
+                let mut iov = libc::iovec {
+                    iov_base: data.as_ptr() as *mut libc::c_void,
+                    iov_len: data.len(),
+                };
+                let mut hdr: libc::msghdr = unsafe { zeroed() };
+                hdr.msg_name = to.as_ptr() as *mut libc::c_void;
+                hdr.msg_namelen = to.len();
+                hdr.msg_iov = &mut iov;
+                hdr.msg_iovlen = 1;
+                hdr.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+                write_cmsg(&mut hdr, from);
+
+                let sent = unsafe { libc::sendmsg(socket.as_raw_fd(), &hdr, 0) };
+                if sent < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(sent as usize)
+            })
+            .await
+    }
+
+    /// Whether a send failed because the requested source address is gone
+    pub fn source_unusable(e: &io::Error) -> bool {
+        matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::EADDRNOTAVAIL))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) mod sys {
+    use std::io;
+    use std::net::{IpAddr, SocketAddr};
+
+    use tokio::net::UdpSocket;
+
+    pub fn enable(_socket: &UdpSocket, _ipv6: bool) -> bool {
+        false
+    }
+
+    pub async fn send_from(socket: &UdpSocket, data: &[u8], to: SocketAddr, _from: IpAddr) -> io::Result<usize> {
+        socket.send_to(data, to).await
+    }
+
+    pub fn source_unusable(_e: &io::Error) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::tunnel::batch::RecvBatch;
+
+    #[tokio::test]
+    async fn test_reply_from_arrival_address() {
+        let server = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+        assert!(enable(&server));
+        let port = server.local_addr().unwrap().port();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        // 127.0.0.2 is on lo but never the kernel's default source for 127.0.0.1
+        client.send_to(b"ping", ("127.0.0.2", port)).await.unwrap();
+
+        let mut batch = RecvBatch::new();
+        batch.recv(&server).await.unwrap();
+        let (data, from) = batch.get(0);
+        assert_eq!(data, b"ping");
+        let local = batch.local(0);
+        assert_eq!(local, Some("127.0.0.2".parse().unwrap()));
+
+        send_from(&server, b"pong", from, local).await.unwrap();
+        let mut buf = [0u8; 16];
+        let (len, reply_from) = client.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"pong");
+        assert_eq!(reply_from, SocketAddr::new("127.0.0.2".parse().unwrap(), port));
+    }
+
+    #[tokio::test]
+    async fn test_bound_socket_needs_no_pktinfo() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        assert!(!enable(&socket));
+    }
+}
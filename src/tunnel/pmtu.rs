@@ -0,0 +1,155 @@
+//! Path MTU discovery through the tunnel (IPv4 "Don't Fragment")
+//!
+//! The tunnel never fragments. An IPv4 packet with DF set that is larger than
+//! the tunnel MTU is dropped, and the inner source gets an ICMP "fragmentation
+//! needed" (type 3, code 4) carrying the MTU, so it retries with smaller
+//! packets instead of blackholing. Packets without DF are passed on as before.
+
+use std::net::Ipv4Addr;
+
+/// IPv4 header flag: Don't Fragment
+const DF: u16 = 0x4000;
+/// IPv4 fragment offset mask
+const FRAGMENT_OFFSET: u16 = 0x1fff;
+/// ICMP type for "destination unreachable"
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+/// Destination unreachable code for "fragmentation needed and DF set"
+const ICMP_FRAG_NEEDED: u8 = 4;
+
+/// Longest reply from [`frag_needed`]: its own headers, the longest quoted
+/// IPv4 header and 8 bytes of payload
+pub const FRAG_NEEDED_MAX_LEN: usize = 20 + 8 + 60 + 8;
+
+/// Whether `packet` is IPv4 with DF set and longer than `mtu`
+pub fn exceeds_mtu(packet: &[u8], mtu: u16) -> bool {
+    packet.len() >= 20
+        && packet[0] >> 4 == 4
+        && u16::from_be_bytes([packet[6], packet[7]]) & DF != 0
+        && packet.len() > mtu as usize
+}
+
+/// ICMP "fragmentation needed" from `router` telling the sender of `packet` to use `mtu`
+///
+/// Returns None where RFC 1812 forbids an ICMP error: for non-initial
+/// fragments, for ICMP errors, and for packets whose source can't be replied to.
+pub fn frag_needed(packet: &[u8], router: Ipv4Addr, mtu: u16) -> Option<Vec<u8>> {
+    let header_len = ((packet.first()? & 0x0f) as usize) * 4;
+    if header_len < 20 || packet.len() < header_len {
+        return None;
+    }
+
+    let fragment_offset = u16::from_be_bytes([packet[6], packet[7]]) & FRAGMENT_OFFSET;
+    let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    if fragment_offset != 0
+        || source.is_unspecified()
+        || source.is_broadcast()
+        || source.is_multicast()
+        || is_icmp_error(packet, header_len)
+    {
+        return None;
+    }
+
+    // Original header plus the first 8 bytes of its payload (ports/ICMP id)
+    let quoted = &packet[..packet.len().min(header_len + 8)];
+    let total_len = 20 + 8 + quoted.len();
+
+    let mut reply = Vec::with_capacity(total_len);
+    reply.extend_from_slice(&[0x45, 0]);
+    reply.extend_from_slice(&(total_len as u16).to_be_bytes());
+    reply.extend_from_slice(&[0, 0, 0, 0]); // id, flags, fragment offset
+    reply.extend_from_slice(&[64, 1, 0, 0]); // TTL, protocol ICMP, checksum
+    reply.extend_from_slice(&router.octets());
+    reply.extend_from_slice(&source.octets());
+    let checksum = internet_checksum(&reply[..20]);
+    reply[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    reply.extend_from_slice(&[ICMP_DEST_UNREACHABLE, ICMP_FRAG_NEEDED, 0, 0, 0, 0]);
+    reply.extend_from_slice(&mtu.to_be_bytes());
+    reply.extend_from_slice(quoted);
+    let checksum = internet_checksum(&reply[20..]);
+    reply[22..24].copy_from_slice(&checksum.to_be_bytes());
+
+    Some(reply)
+}
+
+/// ICMP destination unreachable, source quench, redirect, time exceeded or parameter problem
+fn is_icmp_error(packet: &[u8], header_len: usize) -> bool {
+    packet[9] == 1 && packet.get(header_len).is_some_and(|t| matches!(t, 3 | 4 | 5 | 11 | 12))
+}
+
+/// RFC 1071 ones' complement checksum
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IPv4 UDP packet of `len` bytes from 10.0.0.2 to 192.0.2.1
+    fn udp_packet(len: usize, dont_fragment: bool) -> Vec<u8> {
+        let mut packet = vec![0u8; len];
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        if dont_fragment {
+            packet[6] = 0x40;
+        }
+        packet[8] = 64;
+        packet[9] = 17;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 2]);
+        packet[16..20].copy_from_slice(&[192, 0, 2, 1]);
+        packet[20..22].copy_from_slice(&5353u16.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_exceeds_mtu_needs_df() {
+        assert!(exceeds_mtu(&udp_packet(1500, true), 1420));
+        assert!(!exceeds_mtu(&udp_packet(1420, true), 1420));
+        assert!(!exceeds_mtu(&udp_packet(1500, false), 1420));
+
+        let mut v6 = vec![0u8; 1500];
+        v6[0] = 0x60;
+        assert!(!exceeds_mtu(&v6, 1420));
+    }
+
+    #[test]
+    fn test_frag_needed_message() {
+        let packet = udp_packet(1500, true);
+        let reply = frag_needed(&packet, Ipv4Addr::new(10, 0, 0, 1), 1420).unwrap();
+
+        assert_eq!(reply.len(), 20 + 8 + 28);
+        assert_eq!(&reply[12..16], &[10, 0, 0, 1]);
+        assert_eq!(&reply[16..20], &[10, 0, 0, 2]);
+        assert_eq!(internet_checksum(&reply[..20]), 0);
+        assert_eq!(internet_checksum(&reply[20..]), 0);
+        assert_eq!(&reply[20..22], &[3, 4]);
+        assert_eq!(u16::from_be_bytes([reply[26], reply[27]]), 1420);
+        assert_eq!(&reply[28..], &packet[..28]);
+    }
+
+    #[test]
+    fn test_no_icmp_about_icmp_errors_or_fragments() {
+        let router = Ipv4Addr::new(10, 0, 0, 1);
+
+        let mut icmp_error = udp_packet(1500, true);
+        icmp_error[9] = 1;
+        icmp_error[20] = 3;
+        assert!(frag_needed(&icmp_error, router, 1420).is_none());
+
+        // Echo requests still get one, so ping -M do works
+        icmp_error[20] = 8;
+        assert!(frag_needed(&icmp_error, router, 1420).is_some());
+
+        let mut fragment = udp_packet(1500, true);
+        fragment[7] = 0x10;
+        assert!(frag_needed(&fragment, router, 1420).is_none());
+    }
+}